@@ -18,16 +18,106 @@ pub fn expand_axum_crud(attr: TokenStream1, input: ItemStruct) -> TokenStream {
 
     // 3. Parseo de atributos configurables
     // Espera: #[axum_crud(path = "/units", new = "NewItem", params = "Params")]
+    // y opcionalmente los roles exigidos: read = "reader", write = "master"
+    // y scope = "project" para limitar las lecturas y escrituras a los miembros
+    // del proyecto
     let attr_str = attr.to_string();
 
     let route_path = extract_attr(&attr_str, "path").unwrap_or_else(|| "/".into());
     let new_type_name = extract_attr(&attr_str, "new").unwrap_or_else(|| "NewItem".into());
     let params_type_name = extract_attr(&attr_str, "params").unwrap_or_else(|| "Params".into());
+    let read_level = match level(&attr_str, "read", "reader") {
+        Ok(level) => level,
+        Err(message) => return Error::new_spanned(&input, message).to_compile_error(),
+    };
+    let write_level = match level(&attr_str, "write", "writer") {
+        Ok(level) => level,
+        Err(message) => return Error::new_spanned(&input, message).to_compile_error(),
+    };
+
+    let scoped = match extract_attr(&attr_str, "scope").as_deref() {
+        None => false,
+        Some("project") => true,
+        Some(scope) => {
+            return Error::new_spanned(&input, format!("#[axum_crud] scope desconocido: \"{}\"", scope))
+                .to_compile_error();
+        }
+    };
 
     // Convertimos strings en identificadores reales de Rust
     let new_item_ident = format_ident!("{}", new_type_name);
     let params_ident = format_ident!("{}", params_type_name);
 
+    // Lecturas limitadas a los proyectos del usuario: el modelo declara
+    // `const PROJECT` y su `Params` un campo `member_scope`
+    let read_args = if scoped {
+        quote! {
+            current: Option<axum::Extension<crate::http::middleware::CurrentUser>>,
+            axum::extract::Query(mut params): axum::extract::Query<#params_ident>,
+        }
+    } else {
+        quote! { axum::extract::Query(params): axum::extract::Query<#params_ident>, }
+    };
+    let read_scope = if scoped {
+        quote! {
+            params.member_scope = current.and_then(|axum::Extension(current)| current.member_scope());
+            if let (Some(id), Some(scope)) = (params.id, &params.member_scope) {
+                match crate::models::ProjectMember::can_read(&app_state.pool, #name::TABLE, #name::PROJECT, id, scope).await {
+                    Ok(true) => {}
+                    Ok(false) => return crate::models::CustomResponse::api(axum::http::StatusCode::NOT_FOUND, "No encontrado", crate::models::Data::None),
+                    Err(e) => return crate::models::CustomResponse::api(axum::http::StatusCode::BAD_REQUEST, &e.to_string(), crate::models::Data::None),
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let read_all = if scoped {
+        quote! {
+            match &params.member_scope {
+                Some(scope) => crate::models::ProjectMember::read_visible::<#name>(&app_state.pool, #name::TABLE, #name::PROJECT, scope).await,
+                None => #name::read_all(&app_state.pool).await,
+            }
+        }
+    } else {
+        quote! { #name::read_all(&app_state.pool).await }
+    };
+
+    // Escrituras limitadas a los proyectos en los que el rol del usuario llega
+    // al nivel de escritura: se comprueban la fila que se modifica o borra y
+    // el padre (`Scoped`) de la que se crea o modifica. Fuera del ámbito, 404.
+    // Al crear, el registro recibe el ámbito (`Scoped::set_member_scope`)
+    let write_args = if scoped {
+        quote! { current: Option<axum::Extension<crate::http::middleware::CurrentUser>>, }
+    } else {
+        quote! {}
+    };
+    let create_payload = if scoped { quote! { mut payload } } else { quote! { payload } };
+    let write_scope = |id: TokenStream, parent: TokenStream, then: TokenStream| {
+        if !scoped {
+            return quote! {};
+        }
+        quote! {
+            if let Some(scope) = current.and_then(|axum::Extension(current)| current.member_scope()) {
+                let scope = scope.with_level(crate::http::permissions::Level::#write_level);
+                match crate::models::ProjectMember::can_write(&app_state.pool, #name::TABLE, #name::PROJECT, #id, #parent, &scope).await {
+                    Ok(true) => {}
+                    Ok(false) => return crate::models::ApiResponse::new(axum::http::StatusCode::NOT_FOUND, "No encontrado", crate::models::Data::None),
+                    Err(e) => return crate::models::ApiResponse::from_error(&e),
+                }
+                #then
+            }
+        }
+    };
+    let parent = quote! { crate::models::Scoped::scope_parent(&payload) };
+    let create_scope = write_scope(
+        quote! { None },
+        parent.clone(),
+        quote! { crate::models::Scoped::set_member_scope(&mut payload, scope); },
+    );
+    let update_scope = write_scope(quote! { Some(payload.id) }, parent, quote! {});
+    let delete_scope = write_scope(quote! { Some(id) }, quote! { None }, quote! {});
+
     // 4. Generación de código
     quote! {
         #input
@@ -41,6 +131,12 @@ pub fn expand_axum_crud(attr: TokenStream1, input: ItemStruct) -> TokenStream {
 
         // El router se asocia al struct para mantener el orden
         impl #name {
+            /// Roles exigidos para leer y escribir el recurso.
+            pub const PERMISSIONS: crate::http::permissions::Permissions = crate::http::permissions::Permissions::new(
+                crate::http::permissions::Level::#read_level,
+                crate::http::permissions::Level::#write_level,
+            );
+
             pub fn router() -> axum::Router<std::sync::Arc<crate::models::AppState>> {
                 axum::Router::new()
                     .route("/", axum::routing::post(create))
@@ -54,9 +150,11 @@ pub fn expand_axum_crud(attr: TokenStream1, input: ItemStruct) -> TokenStream {
 
         pub async fn create(
             axum::extract::State(app_state): axum::extract::State<std::sync::Arc<crate::models::AppState>>,
-            axum::Json(payload): axum::Json<#new_item_ident>,
+            #write_args
+            axum::Json(#create_payload): axum::Json<#new_item_ident>,
         ) -> impl axum::response::IntoResponse {
             tracing::debug!("Creando {}: {:?}", stringify!(#name), payload);
+            #create_scope
             match #name::create(&app_state.pool, payload).await {
                 Ok(item) => crate::models::ApiResponse::new(
                     axum::http::StatusCode::CREATED,
@@ -65,29 +163,32 @@ pub fn expand_axum_crud(attr: TokenStream1, input: ItemStruct) -> TokenStream {
                 ),
                 Err(e) => {
                     tracing::error!("Error en create {}: {:?}", stringify!(#name), e);
-                    crate::models::ApiResponse::new(axum::http::StatusCode::BAD_REQUEST, &e.to_string(), crate::models::Data::None)
+                    crate::models::ApiResponse::from_error(&e)
                 }
             }
         }
 
         pub async fn update(
             axum::extract::State(app_state): axum::extract::State<std::sync::Arc<crate::models::AppState>>,
+            #write_args
             axum::Json(payload): axum::Json<#name>,
         ) -> impl axum::response::IntoResponse {
+            #update_scope
             match #name::update(&app_state.pool, payload).await {
                 Ok(updated) => crate::models::ApiResponse::new(
                     axum::http::StatusCode::OK,
                     &format!("{} actualizado", stringify!(#name)),
                     crate::models::Data::Some(serde_json::to_value(updated).unwrap()),
                 ),
-                Err(e) => crate::models::ApiResponse::new(axum::http::StatusCode::BAD_REQUEST, &e.to_string(), crate::models::Data::None),
+                Err(e) => crate::models::ApiResponse::from_error(&e),
             }
         }
 
         pub async fn read(
             axum::extract::State(app_state): axum::extract::State<std::sync::Arc<crate::models::AppState>>,
-            axum::extract::Query(params): axum::extract::Query<#params_ident>,
+            #read_args
         ) -> impl axum::response::IntoResponse {
+            #read_scope
             // 1. Búsqueda por ID
             if let Some(id) = params.id {
                 return match #name::read_by_id(&app_state.pool, id).await {
@@ -114,7 +215,7 @@ pub fn expand_axum_crud(attr: TokenStream1, input: ItemStruct) -> TokenStream {
             }
 
             // 3. Fallback: Todos
-            match #name::read_all(&app_state.pool).await {
+            match #read_all {
                 Ok(items) => crate::models::CustomResponse::api(axum::http::StatusCode::OK, "Lista completa", crate::models::Data::Some(serde_json::to_value(items).unwrap())),
                 Err(e) => crate::models::CustomResponse::api(axum::http::StatusCode::BAD_REQUEST, &e.to_string(), crate::models::Data::None),
             }
@@ -122,11 +223,13 @@ pub fn expand_axum_crud(attr: TokenStream1, input: ItemStruct) -> TokenStream {
 
         pub async fn delete(
             axum::extract::State(app_state): axum::extract::State<std::sync::Arc<crate::models::AppState>>,
+            #write_args
             axum::extract::Query(params): axum::extract::Query<#params_ident>,
         ) -> impl axum::response::IntoResponse {
             let Some(id) = params.id else {
                 return crate::models::ApiResponse::new(axum::http::StatusCode::BAD_REQUEST, "ID requerido", crate::models::Data::None);
             };
+            #delete_scope
 
             match #name::delete(&app_state.pool, id).await {
                 Ok(item) => crate::models::ApiResponse::new(
@@ -134,16 +237,29 @@ pub fn expand_axum_crud(attr: TokenStream1, input: ItemStruct) -> TokenStream {
                     "Eliminado",
                     crate::models::Data::Some(serde_json::to_value(item).unwrap())
                 ),
-                Err(e) => crate::models::ApiResponse::new(axum::http::StatusCode::BAD_REQUEST, &e.to_string(), crate::models::Data::None),
+                Err(e) => crate::models::ApiResponse::from_error(&e),
             }
         }
     }
 }
 
+/// Variante de `Level` para el rol `key` (ej: "master" -> `Master`)
+fn level(attr: &str, key: &str, default: &str) -> Result<proc_macro2::Ident, String> {
+    let role = extract_attr(attr, key).unwrap_or_else(|| default.into());
+    let variant = match role.as_str() {
+        "reader" => "Reader",
+        "writer" => "Writer",
+        "master" => "Master",
+        "admin" => "Admin",
+        _ => return Err(format!("#[axum_crud] rol desconocido en {}: \"{}\"", key, role)),
+    };
+    Ok(format_ident!("{}", variant))
+}
+
 /// Extrae valores de atributos tipo llave="valor"
 fn extract_attr(attr: &str, key: &str) -> Option<String> {
     attr.split(',')
-        .find(|s| s.contains(key))
+        .find(|s| s.split('=').next().is_some_and(|k| k.trim() == key))
         .and_then(|s| s.split('=').next_back()) // <--- Cambiado .last() por .next_back()
         .map(|s| {
             s.trim()
@@ -0,0 +1,8 @@
+ALTER TABLE prices ADD CONSTRAINT prices_code_key UNIQUE (code);
+
+ALTER TABLE units ALTER COLUMN params DROP DEFAULT;
+ALTER TABLE units DROP COLUMN IF EXISTS description;
+ALTER TABLE units ALTER COLUMN symbol TYPE VARCHAR(4);
+ALTER TABLE units ADD CONSTRAINT units_symbol_key UNIQUE (symbol);
+ALTER TABLE units RENAME COLUMN name TO unit;
+ALTER TABLE units ADD CONSTRAINT units_unit_key UNIQUE (unit);
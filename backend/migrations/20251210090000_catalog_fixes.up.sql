@@ -0,0 +1,10 @@
+-- Align 'units' with the Unit model
+ALTER TABLE units RENAME COLUMN unit TO name;
+ALTER TABLE units DROP CONSTRAINT IF EXISTS units_unit_key;
+ALTER TABLE units DROP CONSTRAINT IF EXISTS units_symbol_key;
+ALTER TABLE units ALTER COLUMN symbol TYPE VARCHAR(30);
+ALTER TABLE units ADD COLUMN description TEXT;
+ALTER TABLE units ALTER COLUMN params SET DEFAULT '[]'::jsonb;
+
+-- A price code is unique inside its version, not across versions
+ALTER TABLE prices DROP CONSTRAINT IF EXISTS prices_code_key;
@@ -0,0 +1,5 @@
+ALTER TABLE budgets
+    DROP COLUMN IF EXISTS custom_rates,
+    DROP COLUMN IF EXISTS vat,
+    DROP COLUMN IF EXISTS industrial_profit,
+    DROP COLUMN IF EXISTS general_expenses;
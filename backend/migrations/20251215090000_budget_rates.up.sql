@@ -0,0 +1,9 @@
+-- Porcentajes que llevan del PEM al PEC y al total con IVA
+ALTER TABLE budgets
+    ADD COLUMN general_expenses NUMERIC(5, 2) NOT NULL DEFAULT 13.00,  -- GG sobre el PEM
+    ADD COLUMN industrial_profit NUMERIC(5, 2) NOT NULL DEFAULT 6.00,  -- BI sobre el PEM
+    ADD COLUMN vat NUMERIC(5, 2) NOT NULL DEFAULT 21.00,               -- IVA sobre el PEC
+    ADD COLUMN custom_rates JSONB NOT NULL DEFAULT '[]'::jsonb,        -- [{"name", "percentage"}] sobre el PEM
+    ADD CONSTRAINT budgets_general_expenses_check CHECK (general_expenses BETWEEN 0 AND 100),
+    ADD CONSTRAINT budgets_industrial_profit_check CHECK (industrial_profit BETWEEN 0 AND 100),
+    ADD CONSTRAINT budgets_vat_check CHECK (vat BETWEEN 0 AND 100);
@@ -0,0 +1,2 @@
+DROP TRIGGER IF EXISTS set_updated_at_project_members ON project_members;
+DROP TABLE IF EXISTS project_members;
@@ -0,0 +1,18 @@
+-- Miembros de un proyecto: solo ellos (y los administradores) ven el proyecto,
+-- sus presupuestos, elementos y mediciones
+CREATE TABLE project_members (
+    id SERIAL PRIMARY KEY,
+    project_id INTEGER NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Rol dentro del proyecto
+    role_id INTEGER NOT NULL REFERENCES roles(id),
+    -- Audit Fields
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT (NOW() AT TIME ZONE 'UTC'),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT (NOW() AT TIME ZONE 'UTC'),
+    UNIQUE (project_id, user_id)
+);
+CREATE INDEX project_members_user_id_idx ON project_members (user_id);
+
+CREATE TRIGGER set_updated_at_project_members
+BEFORE UPDATE ON project_members
+FOR EACH ROW EXECUTE PROCEDURE update_updated_at_column();
@@ -0,0 +1,2 @@
+DROP TRIGGER IF EXISTS set_updated_at_sessions ON sessions;
+DROP TABLE IF EXISTS sessions;
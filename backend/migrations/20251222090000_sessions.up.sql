@@ -0,0 +1,20 @@
+-- Sesiones abiertas con /auth/login. Del refresh token solo se guarda su hash
+-- SHA-256; el access token (JWT) lleva el id de la sesión en el claim `sid`
+CREATE TABLE sessions (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    refresh_token_hash CHAR(64) NOT NULL UNIQUE,
+    -- Dispositivo desde el que se abrió la sesión
+    user_agent TEXT,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    last_used_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT (NOW() AT TIME ZONE 'UTC'),
+    revoked_at TIMESTAMP WITH TIME ZONE,
+    -- Audit Fields
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT (NOW() AT TIME ZONE 'UTC'),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT (NOW() AT TIME ZONE 'UTC')
+);
+CREATE INDEX sessions_user_id_idx ON sessions (user_id);
+
+CREATE TRIGGER set_updated_at_sessions
+BEFORE UPDATE ON sessions
+FOR EACH ROW EXECUTE PROCEDURE update_updated_at_column();
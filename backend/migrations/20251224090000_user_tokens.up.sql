@@ -0,0 +1,17 @@
+-- Verificación del email de los usuarios
+ALTER TABLE users ADD COLUMN email_verified_at TIMESTAMP WITH TIME ZONE;
+
+-- Tokens de un solo uso enviados por email: recuperación de contraseña y
+-- verificación del email. Solo se guarda su hash SHA-256
+CREATE TYPE user_token_purpose_enum AS ENUM ('reset', 'verify');
+
+CREATE TABLE user_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    purpose user_token_purpose_enum NOT NULL,
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT (NOW() AT TIME ZONE 'UTC')
+);
+CREATE INDEX user_tokens_user_id_idx ON user_tokens (user_id, purpose);
@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS audit_entries;
+DROP TYPE IF EXISTS audit_action_enum;
+DROP TABLE IF EXISTS login_throttles;
+DROP TYPE IF EXISTS login_scope_enum;
@@ -0,0 +1,30 @@
+-- Intentos fallidos de login por cuenta (email) y por IP. Tras varios fallos
+-- seguidos se bloquea temporalmente, con un bloqueo que se duplica a cada
+-- nuevo fallo
+CREATE TYPE login_scope_enum AS ENUM ('account', 'ip');
+
+CREATE TABLE login_throttles (
+    scope login_scope_enum NOT NULL,
+    -- Email en minúsculas o dirección IP
+    key VARCHAR(255) NOT NULL,
+    failures INTEGER NOT NULL DEFAULT 0,
+    last_failure_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT (NOW() AT TIME ZONE 'UTC'),
+    locked_until TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (scope, key)
+);
+
+-- Registro de auditoría de los eventos de seguridad
+CREATE TYPE audit_action_enum AS ENUM ('account_locked', 'ip_locked', 'account_unlocked');
+
+CREATE TABLE audit_entries (
+    id SERIAL PRIMARY KEY,
+    action audit_action_enum NOT NULL,
+    -- Usuario afectado, si lo hay
+    user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    -- Usuario que realizó la acción, si no fue el sistema
+    actor_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    ip VARCHAR(45),
+    detail TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT (NOW() AT TIME ZONE 'UTC')
+);
+CREATE INDEX audit_entries_user_id_idx ON audit_entries (user_id);
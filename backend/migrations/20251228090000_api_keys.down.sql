@@ -0,0 +1,3 @@
+DROP TRIGGER IF EXISTS set_updated_at_api_keys ON api_keys;
+DROP TABLE IF EXISTS api_keys;
+DROP TYPE IF EXISTS api_key_scope_enum;
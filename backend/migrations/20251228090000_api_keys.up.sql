@@ -0,0 +1,27 @@
+-- API keys para integraciones (ERP, scripts). Actúan en nombre de un usuario,
+-- con sus permisos limitados por el alcance de la key. Solo se guarda el hash
+-- SHA-256; el prefijo identifica la key en los listados
+CREATE TYPE api_key_scope_enum AS ENUM ('read', 'write');
+
+CREATE TABLE api_keys (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    prefix VARCHAR(16) NOT NULL,
+    key_hash CHAR(64) NOT NULL UNIQUE,
+    scope api_key_scope_enum NOT NULL DEFAULT 'read',
+    -- Proyectos a los que se limita; NULL para todos los del usuario
+    project_ids INTEGER[],
+    expires_at TIMESTAMP WITH TIME ZONE,
+    last_used_at TIMESTAMP WITH TIME ZONE,
+    revoked_at TIMESTAMP WITH TIME ZONE,
+    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    -- Audit Fields
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT (NOW() AT TIME ZONE 'UTC'),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT (NOW() AT TIME ZONE 'UTC')
+);
+CREATE INDEX api_keys_user_id_idx ON api_keys (user_id);
+
+CREATE TRIGGER set_updated_at_api_keys
+BEFORE UPDATE ON api_keys
+FOR EACH ROW EXECUTE PROCEDURE update_updated_at_column();
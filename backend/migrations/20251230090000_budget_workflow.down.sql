@@ -0,0 +1,6 @@
+DROP TRIGGER IF EXISTS prevent_locked_measurement_changes ON measurements;
+DROP TRIGGER IF EXISTS prevent_locked_element_changes ON elements;
+DROP FUNCTION IF EXISTS prevent_locked_measurement_changes();
+DROP FUNCTION IF EXISTS prevent_locked_element_changes();
+DROP FUNCTION IF EXISTS check_budget_editable(INTEGER);
+DROP TABLE IF EXISTS budget_status_changes;
@@ -0,0 +1,64 @@
+-- Historial de los cambios de estado de los presupuestos (enviar, aprobar,
+-- rechazar, archivar). El rechazo lleva el motivo en `comment`
+CREATE TABLE budget_status_changes (
+    id SERIAL PRIMARY KEY,
+    budget_id INTEGER NOT NULL REFERENCES budgets(id) ON DELETE CASCADE,
+    from_status budget_status_enum NOT NULL,
+    to_status budget_status_enum NOT NULL,
+    user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    comment TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT (NOW() AT TIME ZONE 'UTC')
+);
+CREATE INDEX budget_status_changes_budget_id_idx ON budget_status_changes (budget_id);
+
+-- Los elementos y mediciones de un presupuesto enviado, aprobado o archivado
+-- no se pueden modificar. Se comprueba en la base de datos para que ninguna
+-- escritura (API, importación BC3...) se lo salte. El código de error
+-- 55000 (object_not_in_prerequisite_state) se devuelve como un 409. El
+-- estado se lee con FOR SHARE: un cambio de estado en curso (FOR UPDATE)
+-- espera a que terminen las escrituras y estas, a que se confirme el cambio
+CREATE FUNCTION check_budget_editable(budget INTEGER) RETURNS VOID AS $$
+DECLARE
+    current_status budget_status_enum;
+BEGIN
+    SELECT status INTO current_status FROM budgets WHERE id = budget FOR SHARE;
+    IF current_status IN ('submitted', 'approved', 'archived') THEN
+        RAISE EXCEPTION 'Budget % is %: its elements and measurements cannot be modified', budget, current_status
+            USING ERRCODE = 'object_not_in_prerequisite_state';
+    END IF;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE FUNCTION prevent_locked_element_changes() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        PERFORM check_budget_editable(OLD.budget_id);
+    END IF;
+    IF TG_OP <> 'DELETE' THEN
+        PERFORM check_budget_editable(NEW.budget_id);
+        RETURN NEW;
+    END IF;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE FUNCTION prevent_locked_measurement_changes() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        PERFORM check_budget_editable((SELECT budget_id FROM elements WHERE id = OLD.element_id));
+    END IF;
+    IF TG_OP <> 'DELETE' THEN
+        PERFORM check_budget_editable((SELECT budget_id FROM elements WHERE id = NEW.element_id));
+        RETURN NEW;
+    END IF;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER prevent_locked_element_changes
+BEFORE INSERT OR UPDATE OR DELETE ON elements
+FOR EACH ROW EXECUTE PROCEDURE prevent_locked_element_changes();
+
+CREATE TRIGGER prevent_locked_measurement_changes
+BEFORE INSERT OR UPDATE OR DELETE ON measurements
+FOR EACH ROW EXECUTE PROCEDURE prevent_locked_measurement_changes();
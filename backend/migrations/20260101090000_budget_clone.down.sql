@@ -0,0 +1,2 @@
+ALTER TABLE elements DROP CONSTRAINT IF EXISTS elements_budget_id_code_key;
+ALTER TABLE elements ADD CONSTRAINT elements_code_key UNIQUE (code);
@@ -0,0 +1,4 @@
+-- Un código de elemento es único dentro de su presupuesto, no entre
+-- presupuestos: las versiones copiadas de un presupuesto conservan sus códigos
+ALTER TABLE elements DROP CONSTRAINT IF EXISTS elements_code_key;
+ALTER TABLE elements ADD CONSTRAINT elements_budget_id_code_key UNIQUE (budget_id, code);
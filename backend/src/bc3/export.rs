@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use sqlx::{postgres::PgPool, types::BigDecimal};
+use tracing::debug;
+
+use super::{
+    parser::{
+        Bc3Document,
+        Component,
+        Concept,
+        ConceptKind,
+        Decomposition,
+        Header,
+        MeasurementLine,
+        MeasurementRecord,
+    },
+    Bc3Error,
+};
+use crate::{
+    models::{
+        Budget,
+        Element,
+        ElementType,
+        Measurement,
+    },
+    pricing::Catalog,
+};
+
+const FORMAT: &str = "FIEBDC-3/2016";
+const PROGRAM: &str = "Presu";
+const CHARSET: &str = "ANSI";
+const SUMMARY_LENGTH: usize = 80;
+// Sin clasificar
+const CATEGORY: &str = "0";
+
+/// Construye el documento BC3 de un presupuesto: el concepto raíz (`##`), sus
+/// capítulos (`#`), los precios usados en las mediciones con sus
+/// descomposiciones y las líneas de medición. Devuelve `None` si el
+/// presupuesto no existe.
+///
+/// La raíz y los capítulos se guardan en el documento con su sufijo, para que
+/// no se mezclen con un precio del mismo código.
+pub async fn export_budget(pool: &PgPool, budget_id: i32) -> Result<Option<Bc3Document>, Bc3Error> {
+    let Some(budget) = Budget::read_by_id(pool, budget_id).await? else {
+        return Ok(None);
+    };
+    let elements = Element::read_by_budget(pool, budget_id).await?;
+    let measurements = Measurement::read_by_budget(pool, budget_id).await?;
+
+    // Precios usados y, recursivamente, sus componentes
+    let price_ids: Vec<i32> = measurements.iter().map(|m| m.price_id).collect();
+    let catalog = Catalog::load(pool, &price_ids).await?;
+    let unit_prices = catalog.unit_prices()?;
+    let prices = catalog.prices();
+    debug!(
+        "Exporting budget {} to BC3: {} elements, {} measurements, {} prices",
+        budget.code,
+        elements.len(),
+        measurements.len(),
+        prices.len()
+    );
+
+    let mut document = Bc3Document {
+        header: Some(Header {
+            owner: PROGRAM.to_string(),
+            format: FORMAT.to_string(),
+            date: chrono::Utc::now().format("%d%m%Y").to_string(),
+            program: PROGRAM.to_string(),
+            label: budget.name.clone(),
+            charset: CHARSET.to_string(),
+        }),
+        ..Default::default()
+    };
+    let root = format!("{}##", budget.code);
+    document.concepts.push(Concept {
+        code: root.clone(),
+        kind: ConceptKind::Root,
+        unit: String::new(),
+        summary: budget.name.clone(),
+        price: None,
+        category: Some(CATEGORY.to_string()),
+    });
+    document.decompositions.push(Decomposition { parent: root.clone(), components: Vec::new() });
+
+    // Capítulos
+    let by_id: HashMap<i32, &Element> = elements.iter().map(|e| (e.id, e)).collect();
+    let container = |element: &Element| -> String {
+        let mut parent = element.parent_id;
+        while let Some(id) = parent {
+            match by_id.get(&id) {
+                Some(e) if e.element_type == ElementType::Chapter => return chapter_code(e),
+                Some(e) => parent = e.parent_id,
+                None => break,
+            }
+        }
+        root.clone()
+    };
+    for chapter in elements.iter().filter(|e| e.element_type == ElementType::Chapter) {
+        let code = chapter_code(chapter);
+        document.concepts.push(Concept {
+            code: code.clone(),
+            kind: ConceptKind::Chapter,
+            unit: String::new(),
+            summary: chapter.description.as_deref().map(summary).unwrap_or_else(|| chapter.budget_code.clone()),
+            price: None,
+            category: Some(CATEGORY.to_string()),
+        });
+        document.decompositions.push(Decomposition { parent: code.clone(), components: Vec::new() });
+        let parent = container(chapter);
+        push_component(&mut document, &parent, &code, BigDecimal::from(1));
+    }
+
+    // Mediciones, agrupadas por capítulo y precio
+    for measurement in &measurements {
+        let (Some(element), Some(price)) = (by_id.get(&measurement.element_id), catalog.price(measurement.price_id)) else {
+            continue;
+        };
+        let parent = if element.element_type == ElementType::Chapter {
+            chapter_code(element)
+        } else {
+            container(element)
+        };
+        push_component(&mut document, &parent, &price.code, measurement.measured_quantity.clone());
+        let line = MeasurementLine {
+            comment: measurement
+                .measurement_text
+                .clone()
+                .or_else(|| element.description.clone())
+                .unwrap_or_else(|| element.budget_code.clone()),
+            units: Some(measurement.measured_quantity.clone()),
+            ..Default::default()
+        };
+        match document
+            .measurements
+            .iter_mut()
+            .find(|m| m.parent.as_deref() == Some(parent.as_str()) && m.code == price.code)
+        {
+            Some(record) => {
+                record.total = Some(record.total.clone().unwrap_or_default() + &measurement.measured_quantity);
+                record.lines.push(line);
+            }
+            None => document.measurements.push(MeasurementRecord {
+                parent: Some(parent),
+                code: price.code.clone(),
+                total: Some(measurement.measured_quantity.clone()),
+                lines: vec![line],
+            }),
+        }
+    }
+
+    // Precios y descomposiciones
+    for price in &prices {
+        let summary = summary(&price.description);
+        if summary != price.description {
+            document.texts.insert(price.code.clone(), price.description.clone());
+        }
+        document.concepts.push(Concept {
+            code: price.code.clone(),
+            kind: ConceptKind::Item,
+            unit: catalog.unit(price).map(|u| u.symbol.clone()).unwrap_or_default(),
+            summary,
+            price: unit_prices.get(&price.id).cloned(),
+            category: Some(CATEGORY.to_string()),
+        });
+        for descomposition in catalog.components(price.id) {
+            let Some(component) = catalog.price(descomposition.component_price_id) else {
+                continue;
+            };
+            push_component(&mut document, &price.code, &component.code, catalog.quantity(descomposition)?);
+        }
+    }
+
+    // Importe de la raíz y de los capítulos
+    let chapters: Vec<String> = document
+        .concepts
+        .iter()
+        .filter(|c| c.kind != ConceptKind::Item)
+        .map(|c| c.code.clone())
+        .collect();
+    for code in chapters {
+        let amount = amount(&document, &code, 0).with_scale_round(2, bigdecimal::RoundingMode::HalfUp);
+        if let Some(concept) = document.concepts.iter_mut().find(|c| c.code == code) {
+            concept.price = Some(amount);
+        }
+    }
+    Ok(Some(document))
+}
+
+/// Código del capítulo en el documento, con el sufijo `#`.
+fn chapter_code(chapter: &Element) -> String {
+    format!("{}#", chapter.budget_code)
+}
+
+/// Añade un componente a la descomposición de `parent`, sumando la cantidad si ya existe.
+fn push_component(document: &mut Bc3Document, parent: &str, code: &str, quantity: BigDecimal) {
+    let index = match document.decompositions.iter().position(|d| d.parent == parent) {
+        Some(index) => index,
+        None => {
+            document.decompositions.push(Decomposition { parent: parent.to_string(), components: Vec::new() });
+            document.decompositions.len() - 1
+        }
+    };
+    let components = &mut document.decompositions[index].components;
+    match components.iter_mut().find(|c| c.code == code) {
+        Some(component) => component.performance += quantity,
+        None => components.push(Component {
+            code: code.to_string(),
+            factor: BigDecimal::from(1),
+            performance: quantity,
+        }),
+    }
+}
+
+/// Suma de cantidad × precio de los componentes de un capítulo.
+fn amount(document: &Bc3Document, code: &str, depth: usize) -> BigDecimal {
+    let Some(decomposition) = document.decomposition(code) else {
+        return BigDecimal::from(0);
+    };
+    if depth > document.concepts.len() {
+        return BigDecimal::from(0);
+    }
+    decomposition
+        .components
+        .iter()
+        .map(|c| {
+            let price = match document.concept(&c.code) {
+                Some(concept) if concept.kind == ConceptKind::Item => concept.price.clone().unwrap_or_default(),
+                Some(_) => amount(document, &c.code, depth + 1),
+                None => BigDecimal::from(0),
+            };
+            c.quantity() * price
+        })
+        .sum()
+}
+
+/// Primera línea de la descripción, recortada a la longitud habitual del resumen.
+fn summary(description: &str) -> String {
+    description
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .take(SUMMARY_LENGTH)
+        .collect()
+}
@@ -0,0 +1,207 @@
+use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use sqlx::{
+    postgres::PgPool,
+    types::BigDecimal,
+    Postgres,
+    Transaction,
+};
+use tracing::{debug, info};
+
+use super::{
+    parser::{Bc3Document, Bc3Issue, ConceptKind},
+    Bc3Error,
+};
+use crate::models::{
+    descomposition::{CalculationMode, DescompositionError},
+    price::PriceType,
+    Descomposition,
+    NewDescomposition,
+    NewPrice,
+    NewUnit,
+    NewVersion,
+    Price,
+    Unit,
+    Version,
+};
+
+// Límites impuestos por las columnas de la base de datos
+const MAX_CODE_LENGTH: usize = 50;
+const MAX_SYMBOL_LENGTH: usize = 30;
+const MAX_PRICE: i64 = 100_000_000; // NUMERIC(10, 2)
+const MAX_QUANTITY: i64 = 1_000_000; // NUMERIC(10, 4)
+const DEFAULT_SYMBOL: &str = "ud";
+const DEFAULT_FORMULA: &str = "a";
+
+/// Resultado de la importación de un catálogo.
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub version: Version,
+    pub units_created: usize,
+    pub prices_created: usize,
+    pub descompositions_created: usize,
+    pub skipped: Vec<Bc3Issue>,
+}
+
+/// Crea una nueva `Version` con las unidades, precios y descomposiciones del
+/// fichero. Todo se hace en una única transacción: si falla una inserción no
+/// queda nada a medias. Los registros que no se pueden importar se devuelven
+/// en el informe.
+pub async fn import_catalog(pool: &PgPool, name: &str, document: &Bc3Document) -> Result<ImportReport, Bc3Error> {
+    if document.concepts.is_empty() {
+        return Err(Bc3Error::Empty);
+    }
+    info!("Importing BC3 catalog '{}' ({} concepts)", name, document.concepts.len());
+    let mut tx = pool.begin().await?;
+    let version = Version::create(&mut *tx, NewVersion { name: name.to_string() }).await?;
+    let mut report = ImportReport {
+        version,
+        units_created: 0,
+        prices_created: 0,
+        descompositions_created: 0,
+        skipped: document.issues.clone(),
+    };
+
+    let decomposed: HashSet<&str> = document
+        .decompositions
+        .iter()
+        .filter(|d| !d.components.is_empty())
+        .map(|d| d.parent.as_str())
+        .collect();
+    let mut units: HashMap<String, i32> = HashMap::new();
+    let mut prices: HashMap<&str, i32> = HashMap::new();
+
+    for concept in &document.concepts {
+        if concept.kind != ConceptKind::Item {
+            report.skipped.push(Bc3Issue::new("C", Some(&concept.code), "Capítulo: no es un precio"));
+            continue;
+        }
+        if concept.code.chars().count() > MAX_CODE_LENGTH {
+            report.skipped.push(Bc3Issue::new("C", Some(&concept.code), "Código demasiado largo"));
+            continue;
+        }
+        let base_price = concept.price.clone().unwrap_or_default();
+        if base_price.abs() >= MAX_PRICE {
+            report.skipped.push(Bc3Issue::new("C", Some(&concept.code), "Precio fuera de rango"));
+            continue;
+        }
+        let symbol = if concept.unit.is_empty() { DEFAULT_SYMBOL } else { concept.unit.as_str() };
+        if symbol.chars().count() > MAX_SYMBOL_LENGTH {
+            report.skipped.push(Bc3Issue::new("C", Some(&concept.code), "Unidad demasiado larga"));
+            continue;
+        }
+        let unit_id = match units.get(symbol) {
+            Some(id) => *id,
+            None => {
+                let id = find_or_create_unit(&mut tx, symbol, &mut report).await?;
+                units.insert(symbol.to_string(), id);
+                id
+            }
+        };
+        let description = document
+            .texts
+            .get(&concept.code)
+            .filter(|t| !t.is_empty())
+            .cloned()
+            .unwrap_or_else(|| concept.summary.clone());
+        let price_type = if decomposed.contains(concept.code.as_str()) {
+            PriceType::Decomposed
+        } else {
+            PriceType::Base
+        };
+        let price = Price::create(&mut *tx, NewPrice {
+            version_id: report.version.id,
+            code: concept.code.clone(),
+            description,
+            base_price,
+            unit_id,
+            price_type,
+        })
+        .await?;
+        prices.insert(concept.code.as_str(), price.id);
+        report.prices_created += 1;
+    }
+
+    for decomposition in &document.decompositions {
+        let Some(parent_id) = prices.get(decomposition.parent.as_str()) else {
+            // La estructura de capítulos no forma parte de un catálogo
+            if document.concept(&decomposition.parent).is_none() {
+                report.skipped.push(Bc3Issue::new("D", Some(&decomposition.parent), "Concepto padre inexistente"));
+            }
+            continue;
+        };
+        // Un mismo componente puede repetirse: se suman sus cantidades
+        let mut quantities: Vec<(&str, BigDecimal)> = Vec::new();
+        for component in &decomposition.components {
+            match quantities.iter_mut().find(|(code, _)| *code == component.code) {
+                Some((_, quantity)) => *quantity += component.quantity(),
+                None => quantities.push((component.code.as_str(), component.quantity())),
+            }
+        }
+        for (code, quantity) in quantities {
+            let key = format!("{}\\{}", decomposition.parent, code);
+            if code == decomposition.parent {
+                report.skipped.push(Bc3Issue::new("D", Some(&key), "Un precio no puede ser componente de sí mismo"));
+                continue;
+            }
+            let Some(component_id) = prices.get(code) else {
+                report.skipped.push(Bc3Issue::new("D", Some(&key), "Componente inexistente"));
+                continue;
+            };
+            if quantity.abs() >= MAX_QUANTITY {
+                report.skipped.push(Bc3Issue::new("D", Some(&key), "Cantidad fuera de rango"));
+                continue;
+            }
+            let created = Descomposition::create_in(&mut tx, NewDescomposition {
+                parent_price_id: *parent_id,
+                component_price_id: *component_id,
+                calculation_mode: CalculationMode::Fixed,
+                fixed_quantity: Some(quantity),
+                params_json: None,
+            })
+            .await;
+            match created {
+                Ok(_) => report.descompositions_created += 1,
+                Err(DescompositionError::Database(e)) => return Err(e.into()),
+                // Los ciclos del fichero no se importan
+                Err(e) => report.skipped.push(Bc3Issue::new("D", Some(&key), &e.to_string())),
+            }
+        }
+    }
+
+    for measurement in &document.measurements {
+        report.skipped.push(Bc3Issue::new(
+            "M",
+            Some(&measurement.code),
+            "Las mediciones no forman parte de un catálogo de precios",
+        ));
+    }
+
+    tx.commit().await?;
+    debug!(
+        "BC3 import finished: {} prices, {} descompositions, {} skipped",
+        report.prices_created,
+        report.descompositions_created,
+        report.skipped.len()
+    );
+    Ok(report)
+}
+
+async fn find_or_create_unit(
+    tx: &mut Transaction<'_, Postgres>,
+    symbol: &str,
+    report: &mut ImportReport,
+) -> Result<i32, Bc3Error> {
+    if let Some(unit) = Unit::read_by_symbol(&mut **tx, symbol).await? {
+        return Ok(unit.id);
+    }
+    let unit = Unit::create(&mut **tx, NewUnit {
+        name: symbol.to_string(),
+        symbol: symbol.to_string(),
+        description: None,
+        formula: DEFAULT_FORMULA.to_string(),
+    })
+    .await?;
+    report.units_created += 1;
+    Ok(unit.id)
+}
@@ -0,0 +1,55 @@
+//! Intercambio de bases de precios y presupuestos en formato FIEBDC-3 (BC3).
+pub mod export;
+pub mod import;
+pub mod parser;
+pub mod writer;
+
+use std::fmt;
+
+use crate::pricing::PricingError;
+
+pub use export::export_budget;
+pub use import::{import_catalog, ImportReport};
+pub use parser::{
+    decode,
+    parse,
+    Bc3Document,
+    Bc3Issue,
+    Concept,
+    ConceptKind,
+};
+pub use writer::{encode, write};
+
+#[derive(Debug)]
+pub enum Bc3Error {
+    Empty,
+    Pricing(PricingError),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for Bc3Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "The file does not contain any BC3 concept"),
+            Self::Pricing(e) => write!(f, "{}", e),
+            Self::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Bc3Error {}
+
+impl From<PricingError> for Bc3Error {
+    fn from(e: PricingError) -> Self {
+        match e {
+            PricingError::Database(e) => Self::Database(e),
+            e => Self::Pricing(e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for Bc3Error {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Database(e)
+    }
+}
@@ -0,0 +1,414 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+};
+use encoding_rs::WINDOWS_1252;
+use serde::Serialize;
+use sqlx::types::BigDecimal;
+
+// =================================================================
+// 1. ESTRUCTURAS DE DATOS (STRUCTS)
+// =================================================================
+
+/// Cabecera del fichero (registro ~V).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Header {
+    pub owner: String,
+    pub format: String,
+    pub date: String,
+    pub program: String,
+    pub label: String,
+    pub charset: String,
+}
+
+/// Tipo de concepto según el sufijo de su código (`##` raíz, `#` capítulo).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ConceptKind {
+    Root,
+    Chapter,
+    Item,
+}
+
+/// Concepto del fichero (registro ~C). Al leer un fichero el código se guarda
+/// sin los sufijos `#`; al exportar, la raíz y los capítulos los conservan.
+#[derive(Debug, Clone, Serialize)]
+pub struct Concept {
+    pub code: String,
+    pub kind: ConceptKind,
+    pub unit: String,
+    pub summary: String,
+    pub price: Option<BigDecimal>,
+    pub category: Option<String>,
+}
+
+/// Componente de una descomposición: código hijo, factor y rendimiento.
+#[derive(Debug, Clone, Serialize)]
+pub struct Component {
+    pub code: String,
+    pub factor: BigDecimal,
+    pub performance: BigDecimal,
+}
+
+impl Component {
+    /// Cantidad efectiva del componente (factor × rendimiento).
+    pub fn quantity(&self) -> BigDecimal {
+        &self.factor * &self.performance
+    }
+}
+
+/// Descomposición de un concepto (registro ~D).
+#[derive(Debug, Clone, Serialize)]
+pub struct Decomposition {
+    pub parent: String,
+    pub components: Vec<Component>,
+}
+
+/// Línea de medición: tipo, comentario, unidades, longitud, latitud y altura.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MeasurementLine {
+    pub kind: Option<String>,
+    pub comment: String,
+    pub units: Option<BigDecimal>,
+    pub length: Option<BigDecimal>,
+    pub width: Option<BigDecimal>,
+    pub height: Option<BigDecimal>,
+}
+
+/// Mediciones de un concepto dentro de su padre (registro ~M).
+#[derive(Debug, Clone, Serialize)]
+pub struct MeasurementRecord {
+    pub parent: Option<String>,
+    pub code: String,
+    pub total: Option<BigDecimal>,
+    pub lines: Vec<MeasurementLine>,
+}
+
+/// Registro descartado o no válido, con el motivo.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Bc3Issue {
+    pub record: String,
+    pub code: Option<String>,
+    pub reason: String,
+}
+
+impl Bc3Issue {
+    pub fn new(record: &str, code: Option<&str>, reason: &str) -> Self {
+        Self {
+            record: format!("~{}", record),
+            code: code.map(|c| c.to_string()),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Contenido de un fichero BC3 ya interpretado.
+#[derive(Debug, Default)]
+pub struct Bc3Document {
+    pub header: Option<Header>,
+    pub concepts: Vec<Concept>,
+    pub decompositions: Vec<Decomposition>,
+    pub texts: HashMap<String, String>,
+    pub measurements: Vec<MeasurementRecord>,
+    pub issues: Vec<Bc3Issue>,
+}
+
+impl Bc3Document {
+    /// Busca primero el código tal cual y, si no está, sin los sufijos `#`.
+    pub fn concept(&self, code: &str) -> Option<&Concept> {
+        self.concepts.iter().find(|c| c.code == code).or_else(|| {
+            let code = code.trim_end_matches('#');
+            self.concepts.iter().find(|c| c.code == code)
+        })
+    }
+
+    pub fn decomposition(&self, code: &str) -> Option<&Decomposition> {
+        self.decompositions.iter().find(|d| d.parent == code).or_else(|| {
+            let code = code.trim_end_matches('#');
+            self.decompositions.iter().find(|d| d.parent == code)
+        })
+    }
+}
+
+// =================================================================
+// 2. LECTURA
+// =================================================================
+
+/// Decodifica el fichero: UTF-8 si es válido y, si no, ANSI (Windows-1252).
+pub fn decode(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.trim_start_matches('\u{feff}').to_string(),
+        Err(_) => WINDOWS_1252.decode(bytes).0.into_owned(),
+    }
+}
+
+/// Interpreta el texto de un fichero FIEBDC-3. Los registros que no se pueden
+/// interpretar se recogen en `issues` en lugar de abortar la lectura.
+pub fn parse(input: &str) -> Bc3Document {
+    let mut document = Bc3Document::default();
+    for raw in input.split('~').skip(1) {
+        let (tag, body) = raw.split_once('|').unwrap_or((raw, ""));
+        let tag = clean(tag);
+        let fields: Vec<&str> = body.split('|').collect();
+        match tag.as_str() {
+            "V" => document.header = Some(parse_header(&fields)),
+            "C" => parse_concept(&mut document, &fields),
+            "D" => parse_decomposition(&mut document, &fields),
+            "T" => parse_text(&mut document, &fields),
+            "M" => parse_measurement(&mut document, &fields),
+            "" => {}
+            other => document.issues.push(Bc3Issue::new(other, None, "Tipo de registro no soportado")),
+        }
+    }
+    document
+}
+
+fn parse_header(fields: &[&str]) -> Header {
+    Header {
+        owner: field(fields, 0),
+        format: first_subfield(&field(fields, 1)),
+        date: subfields(&field(fields, 1)).get(1).cloned().unwrap_or_default(),
+        program: field(fields, 2),
+        label: subfields(&field(fields, 3)).get(1).cloned().unwrap_or_default(),
+        charset: field(fields, 4),
+    }
+}
+
+fn parse_concept(document: &mut Bc3Document, fields: &[&str]) {
+    let raw_code = first_subfield(&field(fields, 0));
+    if raw_code.trim_end_matches('#').is_empty() {
+        document.issues.push(Bc3Issue::new("C", None, "Código vacío"));
+        return;
+    }
+    let kind = if raw_code.ends_with("##") {
+        ConceptKind::Root
+    } else if raw_code.ends_with('#') {
+        ConceptKind::Chapter
+    } else {
+        ConceptKind::Item
+    };
+    let code = raw_code.trim_end_matches('#').to_string();
+    let price = match parse_number(&first_subfield(&field(fields, 3))) {
+        Ok(price) => price,
+        Err(_) => {
+            document.issues.push(Bc3Issue::new("C", Some(&code), "Precio no válido"));
+            return;
+        }
+    };
+    let category = Some(field(fields, 5)).filter(|c| !c.is_empty());
+    let concept = Concept {
+        code,
+        kind,
+        unit: field(fields, 1),
+        summary: field(fields, 2),
+        price,
+        category,
+    };
+    match document.concepts.iter_mut().find(|c| c.code == concept.code) {
+        Some(existing) => {
+            document.issues.push(Bc3Issue::new(
+                "C",
+                Some(&concept.code),
+                "Concepto duplicado: se usa la última definición",
+            ));
+            *existing = concept;
+        }
+        None => document.concepts.push(concept),
+    }
+}
+
+fn parse_decomposition(document: &mut Bc3Document, fields: &[&str]) {
+    let parent = field(fields, 0).trim_end_matches('#').to_string();
+    if parent.is_empty() {
+        document.issues.push(Bc3Issue::new("D", None, "Código vacío"));
+        return;
+    }
+    let mut components = Vec::new();
+    for chunk in subfields(&field(fields, 1)).chunks(3) {
+        let code = chunk[0].trim_end_matches('#').to_string();
+        if code.is_empty() {
+            continue;
+        }
+        let factor = parse_number(chunk.get(1).map(String::as_str).unwrap_or(""));
+        let performance = parse_number(chunk.get(2).map(String::as_str).unwrap_or(""));
+        match (factor, performance) {
+            (Ok(factor), Ok(performance)) => components.push(Component {
+                code,
+                factor: factor.unwrap_or_else(|| BigDecimal::from(1)),
+                performance: performance.unwrap_or_else(|| BigDecimal::from(1)),
+            }),
+            _ => document.issues.push(Bc3Issue::new(
+                "D",
+                Some(&format!("{}\\{}", parent, code)),
+                "Factor o rendimiento no válido",
+            )),
+        }
+    }
+    match document.decompositions.iter_mut().find(|d| d.parent == parent) {
+        Some(existing) => existing.components.extend(components),
+        None => document.decompositions.push(Decomposition { parent, components }),
+    }
+}
+
+fn parse_text(document: &mut Bc3Document, fields: &[&str]) {
+    let code = field(fields, 0).trim_end_matches('#').to_string();
+    if code.is_empty() {
+        document.issues.push(Bc3Issue::new("T", None, "Código vacío"));
+        return;
+    }
+    let text = fields.get(1).copied().unwrap_or("").replace("\r\n", "\n");
+    document.texts.insert(code, text.trim().to_string());
+}
+
+fn parse_measurement(document: &mut Bc3Document, fields: &[&str]) {
+    let codes = subfields(&field(fields, 0));
+    let (parent, code) = match codes.as_slice() {
+        [code] => (None, code.clone()),
+        [parent, code, ..] => (Some(parent.trim_end_matches('#').to_string()).filter(|p| !p.is_empty()), code.clone()),
+        [] => (None, String::new()),
+    };
+    let code = code.trim_end_matches('#').to_string();
+    if code.is_empty() {
+        document.issues.push(Bc3Issue::new("M", None, "Código vacío"));
+        return;
+    }
+    let Ok(total) = parse_number(&field(fields, 2)) else {
+        document.issues.push(Bc3Issue::new("M", Some(&code), "Medición total no válida"));
+        return;
+    };
+    let mut lines = Vec::new();
+    for chunk in subfields(&field(fields, 3)).chunks(6) {
+        if chunk.iter().all(|s| s.is_empty()) {
+            continue;
+        }
+        let value = |index: usize| parse_number(chunk.get(index).map(String::as_str).unwrap_or(""));
+        match (value(2), value(3), value(4), value(5)) {
+            (Ok(units), Ok(length), Ok(width), Ok(height)) => lines.push(MeasurementLine {
+                kind: Some(chunk[0].clone()).filter(|k| !k.is_empty()),
+                comment: chunk.get(1).cloned().unwrap_or_default(),
+                units,
+                length,
+                width,
+                height,
+            }),
+            _ => document.issues.push(Bc3Issue::new("M", Some(&code), "Línea de medición no válida")),
+        }
+    }
+    document.measurements.push(MeasurementRecord { parent, code, total, lines });
+}
+
+// =================================================================
+// 3. UTILIDADES
+// =================================================================
+
+/// Elimina los saltos de línea (sólo significativos en los registros ~T) y los blancos.
+fn clean(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| *c != '\r' && *c != '\n')
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn field(fields: &[&str], index: usize) -> String {
+    fields.get(index).map(|f| clean(f)).unwrap_or_default()
+}
+
+fn subfields(value: &str) -> Vec<String> {
+    value.split('\\').map(|s| s.trim().to_string()).collect()
+}
+
+fn first_subfield(value: &str) -> String {
+    subfields(value)
+        .into_iter()
+        .find(|s| !s.is_empty())
+        .unwrap_or_default()
+}
+
+fn parse_number(value: &str) -> Result<Option<BigDecimal>, ()> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    BigDecimal::from_str(value).map(Some).map_err(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "~V|PRESU|FIEBDC-3/2016\\01012025|Presu|\\Catálogo de prueba|ANSI|\r\n\
+~C|CAT##||Catálogo|1234.50||0|\r\n\
+~C|01#||Movimiento de tierras|1234.50||0|\r\n\
+~C|E01|m3|Excavación|12.345||0|\r\n\
+~C|MO1\\MO-ALT|h|Peón|20.00||1|\r\n\
+~C|MT1|kg|Cemento|0.10||3|\r\n\
+~D|E01|MO1\\1\\0.5\\MT1\\\\2\\|\r\n\
+~T|E01|Excavación en zanja\r\nen terreno compacto|\r\n\
+~M|01\\E01|1|24|\\Zanja\\2\\3\\4\\1\\|\r\n\
+~K|\\2\\2\\3\\|\r\n";
+
+    #[test]
+    fn test_parse_header() {
+        let document = parse(SAMPLE);
+        let header = document.header.unwrap();
+        assert_eq!(header.owner, "PRESU");
+        assert_eq!(header.format, "FIEBDC-3/2016");
+        assert_eq!(header.date, "01012025");
+        assert_eq!(header.label, "Catálogo de prueba");
+        assert_eq!(header.charset, "ANSI");
+    }
+
+    #[test]
+    fn test_parse_concepts() {
+        let document = parse(SAMPLE);
+        assert_eq!(document.concepts.len(), 5);
+        assert_eq!(document.concept("CAT").unwrap().kind, ConceptKind::Root);
+        assert_eq!(document.concept("01#").unwrap().kind, ConceptKind::Chapter);
+        let item = document.concept("E01").unwrap();
+        assert_eq!(item.kind, ConceptKind::Item);
+        assert_eq!(item.unit, "m3");
+        assert_eq!(item.price, Some(BigDecimal::from_str("12.345").unwrap()));
+        assert_eq!(document.concept("MO1").unwrap().category, Some("1".to_string()));
+    }
+
+    #[test]
+    fn test_parse_decomposition() {
+        let document = parse(SAMPLE);
+        let decomposition = document.decomposition("E01").unwrap();
+        assert_eq!(decomposition.components.len(), 2);
+        assert_eq!(decomposition.components[0].quantity(), BigDecimal::from_str("0.5").unwrap());
+        // Factor vacío: se toma 1
+        assert_eq!(decomposition.components[1].quantity(), BigDecimal::from(2));
+    }
+
+    #[test]
+    fn test_parse_text_and_measurement() {
+        let document = parse(SAMPLE);
+        assert_eq!(document.texts["E01"], "Excavación en zanja\nen terreno compacto");
+        let measurement = &document.measurements[0];
+        assert_eq!(measurement.parent, Some("01".to_string()));
+        assert_eq!(measurement.code, "E01");
+        assert_eq!(measurement.total, Some(BigDecimal::from(24)));
+        assert_eq!(measurement.lines[0].comment, "Zanja");
+        assert_eq!(measurement.lines[0].units, Some(BigDecimal::from(2)));
+        assert_eq!(measurement.lines[0].width, Some(BigDecimal::from(4)));
+        assert_eq!(measurement.lines[0].height, Some(BigDecimal::from(1)));
+    }
+
+    #[test]
+    fn test_parse_issues() {
+        let document = parse("~C|X|ud|Malo|abc||0|\r\n~C||ud|Sin código|1||0|\r\n~K|\\2\\|\r\n");
+        assert!(document.concepts.is_empty());
+        assert_eq!(document.issues.len(), 3);
+        assert_eq!(document.issues[0], Bc3Issue::new("C", Some("X"), "Precio no válido"));
+        assert_eq!(document.issues[2].record, "~K");
+    }
+
+    #[test]
+    fn test_decode_ansi() {
+        let bytes = b"~C|A|m2|Ca\xf1o|1||0|";
+        assert_eq!(decode(bytes), "~C|A|m2|Caño|1||0|");
+        assert_eq!(decode("~C|A|m2|Caño|1||0|".as_bytes()), "~C|A|m2|Caño|1||0|");
+    }
+}
@@ -0,0 +1,217 @@
+use std::fmt::Write;
+use encoding_rs::WINDOWS_1252;
+use sqlx::types::BigDecimal;
+
+use super::parser::{Bc3Document, ConceptKind, MeasurementLine};
+
+const END_OF_RECORD: &str = "\r\n";
+
+/// Escribe el documento en formato FIEBDC-3. Es la operación inversa de
+/// `parser::parse`: cada concepto lleva el sufijo `##` (raíz) o `#` (capítulo)
+/// también cuando aparece en los registros ~D y ~M.
+pub fn write(document: &Bc3Document) -> String {
+    let mut out = String::new();
+    if let Some(header) = &document.header {
+        let _ = write!(
+            out,
+            "~V|{}|{}\\{}|{}|\\{}\\|{}|{}",
+            clean(&header.owner),
+            clean(&header.format),
+            clean(&header.date),
+            clean(&header.program),
+            clean(&header.label),
+            clean(&header.charset),
+            END_OF_RECORD
+        );
+    }
+    for concept in &document.concepts {
+        let _ = write!(
+            out,
+            "~C|{}|{}|{}|{}||{}|{}",
+            coded(document, &concept.code),
+            clean(&concept.unit),
+            clean(&concept.summary),
+            concept.price.as_ref().map(number).unwrap_or_default(),
+            concept.category.as_deref().map(clean).unwrap_or_default(),
+            END_OF_RECORD
+        );
+    }
+    for decomposition in &document.decompositions {
+        let components: String = decomposition
+            .components
+            .iter()
+            .map(|c| format!("{}\\{}\\{}\\", coded(document, &c.code), number(&c.factor), number(&c.performance)))
+            .collect();
+        let _ = write!(out, "~D|{}|{}|{}", coded(document, &decomposition.parent), components, END_OF_RECORD);
+    }
+    for concept in &document.concepts {
+        if let Some(text) = document.texts.get(&concept.code) {
+            let text = text.replace("\r\n", "\n").replace(['|', '~'], " ").replace(['\r', '\n'], "\r\n");
+            let _ = write!(out, "~T|{}|{}|{}", coded(document, &concept.code), text, END_OF_RECORD);
+        }
+    }
+    for measurement in &document.measurements {
+        let codes = match &measurement.parent {
+            Some(parent) => format!("{}\\{}", coded(document, parent), coded(document, &measurement.code)),
+            None => coded(document, &measurement.code),
+        };
+        let lines: String = measurement.lines.iter().map(line).collect();
+        let _ = write!(
+            out,
+            "~M|{}||{}|{}|{}",
+            codes,
+            measurement.total.as_ref().map(number).unwrap_or_default(),
+            lines,
+            END_OF_RECORD
+        );
+    }
+    out
+}
+
+/// Codifica el texto en ANSI (Windows-1252), el juego de caracteres declarado en ~V.
+pub fn encode(text: &str) -> Vec<u8> {
+    WINDOWS_1252.encode(text).0.into_owned()
+}
+
+fn line(line: &MeasurementLine) -> String {
+    let value = |v: &Option<BigDecimal>| v.as_ref().map(number).unwrap_or_default();
+    format!(
+        "{}\\{}\\{}\\{}\\{}\\{}\\",
+        line.kind.as_deref().map(clean).unwrap_or_default(),
+        clean(&line.comment),
+        value(&line.units),
+        value(&line.length),
+        value(&line.width),
+        value(&line.height)
+    )
+}
+
+/// Código con el sufijo que corresponde a su tipo de concepto.
+fn coded(document: &Bc3Document, code: &str) -> String {
+    let suffix = match document.concept(code).map(|c| c.kind) {
+        Some(ConceptKind::Root) => "##",
+        Some(ConceptKind::Chapter) => "#",
+        _ => "",
+    };
+    format!("{}{}", clean(code.trim_end_matches('#')), suffix)
+}
+
+fn number(value: &BigDecimal) -> String {
+    value.normalized().to_plain_string()
+}
+
+/// Elimina los separadores del formato y los saltos de línea.
+fn clean(value: &str) -> String {
+    value
+        .replace(['|', '\\', '~'], " ")
+        .replace(['\r', '\n'], " ")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bc3::parser::{parse, Component, Concept, Decomposition, Header, MeasurementRecord};
+    use std::str::FromStr;
+
+    fn document() -> Bc3Document {
+        let mut document = Bc3Document {
+            header: Some(Header {
+                owner: "Presu".to_string(),
+                format: "FIEBDC-3/2016".to_string(),
+                date: "01012025".to_string(),
+                program: "Presu".to_string(),
+                label: "Obra | nueva".to_string(),
+                charset: "ANSI".to_string(),
+            }),
+            ..Default::default()
+        };
+        let concept = |code: &str, kind, price: &str| Concept {
+            code: code.to_string(),
+            kind,
+            unit: if kind == ConceptKind::Item { "m2".to_string() } else { String::new() },
+            summary: format!("Concepto {}", code),
+            price: Some(BigDecimal::from_str(price).unwrap()),
+            category: Some("0".to_string()),
+        };
+        document.concepts.push(concept("OBRA", ConceptKind::Root, "150"));
+        document.concepts.push(concept("01", ConceptKind::Chapter, "150"));
+        document.concepts.push(concept("P1", ConceptKind::Item, "15.00"));
+        let component = |code: &str, performance: &str| Component {
+            code: code.to_string(),
+            factor: BigDecimal::from(1),
+            performance: BigDecimal::from_str(performance).unwrap(),
+        };
+        document.decompositions.push(Decomposition { parent: "OBRA".to_string(), components: vec![component("01", "1")] });
+        document.decompositions.push(Decomposition { parent: "01".to_string(), components: vec![component("P1", "10.000")] });
+        document.texts.insert("P1".to_string(), "Solado\r\ncerámico\nen\rsalón".to_string());
+        document.measurements.push(MeasurementRecord {
+            parent: Some("01".to_string()),
+            code: "P1".to_string(),
+            total: Some(BigDecimal::from(10)),
+            lines: vec![MeasurementLine {
+                comment: "Salón".to_string(),
+                units: Some(BigDecimal::from(10)),
+                ..Default::default()
+            }],
+        });
+        document
+    }
+
+    #[test]
+    fn test_write_records() {
+        let text = write(&document());
+        assert!(text.starts_with("~V|Presu|FIEBDC-3/2016\\01012025|Presu|\\Obra   nueva\\|ANSI|\r\n"));
+        assert!(text.contains("~C|OBRA##||Concepto OBRA|150||0|\r\n"));
+        assert!(text.contains("~C|01#||Concepto 01|150||0|\r\n"));
+        assert!(text.contains("~D|01#|P1\\1\\10\\|\r\n"));
+        assert!(text.contains("~T|P1|Solado\r\ncerámico\r\nen\r\nsalón|\r\n"));
+        assert!(text.contains("~M|01#\\P1||10|\\Salón\\10\\\\\\\\|\r\n"));
+    }
+
+    #[test]
+    fn test_chapter_and_price_with_the_same_code() {
+        let mut document = Bc3Document::default();
+        let concept = |code: &str, kind| Concept {
+            code: code.to_string(),
+            kind,
+            unit: String::new(),
+            summary: format!("Concepto {}", code),
+            price: None,
+            category: None,
+        };
+        document.concepts.push(concept("01#", ConceptKind::Chapter));
+        document.concepts.push(concept("01", ConceptKind::Item));
+        document.decompositions.push(Decomposition {
+            parent: "01#".to_string(),
+            components: vec![Component {
+                code: "01".to_string(),
+                factor: BigDecimal::from(1),
+                performance: BigDecimal::from(2),
+            }],
+        });
+        let text = write(&document);
+        assert!(text.contains("~C|01#||Concepto 01#||||\r\n"));
+        assert!(text.contains("~C|01||Concepto 01||||\r\n"));
+        assert!(text.contains("~D|01#|01\\1\\2\\|\r\n"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let original = document();
+        let parsed = parse(&write(&original));
+        assert!(parsed.issues.is_empty());
+        assert_eq!(parsed.concepts.len(), original.concepts.len());
+        assert_eq!(parsed.concept("OBRA").unwrap().kind, ConceptKind::Root);
+        assert_eq!(parsed.concept("P1").unwrap().price, Some(BigDecimal::from(15)));
+        assert_eq!(parsed.decomposition("01").unwrap().components[0].quantity(), BigDecimal::from(10));
+        assert_eq!(parsed.texts["P1"], "Solado\ncerámico\nen\nsalón");
+        assert_eq!(parsed.measurements[0].lines[0].units, Some(BigDecimal::from(10)));
+    }
+
+    #[test]
+    fn test_encode_ansi() {
+        assert_eq!(encode("Caño"), b"Ca\xf1o".to_vec());
+    }
+}
@@ -0,0 +1,121 @@
+//! Árbol de capítulos y líneas de un presupuesto con las cantidades y los
+//! importes acumulados en cada nodo, para pintarlo sin pedir los elementos
+//! nivel a nivel.
+//!
+//! La jerarquía y los acumulados salen de una sola consulta recursiva (ver
+//! `Element::read_tree`). Los importes se calculan como en el resumen con las
+//! opciones por defecto (ver `summary`), así que los subtotales cuadran.
+use std::collections::HashMap;
+use serde::Serialize;
+use sqlx::{postgres::PgPool, types::BigDecimal};
+use tracing::debug;
+
+use crate::{
+    models::{Budget, Element, ElementType, Measurement},
+    pricing::{Catalog, PricingError},
+    summary,
+};
+
+/// Precio medido en el nodo o en sus descendientes.
+#[derive(Debug, Clone, Serialize)]
+pub struct TreeItem {
+    pub price_id: i32,
+    pub code: String,
+    pub description: String,
+    pub unit: String,
+    pub quantity: BigDecimal,
+    pub unit_price: BigDecimal,
+    pub amount: BigDecimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TreeNode {
+    pub element_id: i32,
+    pub element_type: ElementType,
+    pub code: String,
+    pub description: Option<String>,
+    pub depth: i32,
+    pub items: Vec<TreeItem>,
+    pub amount: BigDecimal,
+    // Tiene hijos que no se devuelven por el límite de profundidad
+    pub truncated: bool,
+    pub children: Vec<TreeNode>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetTree {
+    pub budget_id: i32,
+    pub code: String,
+    pub name: String,
+    pub max_depth: Option<i32>,
+    // Presupuesto de ejecución material
+    pub amount: BigDecimal,
+    pub nodes: Vec<TreeNode>,
+}
+
+/// Árbol del presupuesto hasta `max_depth` (0 para solo los elementos raíz).
+/// Devuelve `None` si el presupuesto no existe.
+pub async fn tree(pool: &PgPool, budget_id: i32, max_depth: Option<i32>) -> Result<Option<BudgetTree>, PricingError> {
+    let Some(budget) = Budget::read_by_id(pool, budget_id).await? else {
+        return Ok(None);
+    };
+    let price_ids = Measurement::read_price_ids_by_budget(pool, budget_id).await?;
+    let catalog = Catalog::load(pool, &price_ids).await?;
+    let unit_prices = catalog.unit_prices()?;
+    let rows = Element::read_tree(pool, budget_id, max_depth, &unit_prices, summary::DEFAULT_DECIMALS as i32).await?;
+    debug!("Tree of budget {}: {} rows", budget.code, rows.len());
+
+    // Las filas de un elemento van seguidas y cada padre antes que sus hijos
+    let mut flat: Vec<(Option<i32>, TreeNode)> = Vec::new();
+    for row in rows {
+        if flat.last().is_none_or(|(_, node)| node.element_id != row.id) {
+            let parent = if row.depth == 0 { None } else { row.parent_id };
+            flat.push((parent, TreeNode {
+                element_id: row.id,
+                element_type: row.element_type,
+                code: row.budget_code,
+                description: row.description,
+                depth: row.depth,
+                items: Vec::new(),
+                amount: BigDecimal::from(0),
+                truncated: row.children > 0 && max_depth.is_some_and(|max| row.depth >= max),
+                children: Vec::new(),
+            }));
+        }
+        let (Some(price_id), Some(quantity), Some(amount)) = (row.price_id, row.quantity, row.amount) else {
+            continue;
+        };
+        let Some(price) = catalog.price(price_id) else {
+            return Err(PricingError::MissingPrice(price_id));
+        };
+        let (_, node) = flat.last_mut().expect("node pushed above");
+        node.amount += &amount;
+        node.items.push(TreeItem {
+            price_id,
+            code: price.code.clone(),
+            description: price.description.clone(),
+            unit: catalog.unit(price).map(|u| u.symbol.clone()).unwrap_or_default(),
+            quantity,
+            unit_price: unit_prices.get(&price_id).cloned().unwrap_or_default(),
+            amount,
+        });
+    }
+
+    // De abajo arriba, cada nodo recoge a sus hijos ya completos
+    let mut children: HashMap<Option<i32>, Vec<TreeNode>> = HashMap::new();
+    for (parent, mut node) in flat.into_iter().rev() {
+        node.children = children.remove(&Some(node.element_id)).unwrap_or_default();
+        node.children.reverse();
+        children.entry(parent).or_default().push(node);
+    }
+    let mut nodes = children.remove(&None).unwrap_or_default();
+    nodes.reverse();
+    Ok(Some(BudgetTree {
+        budget_id: budget.id,
+        code: budget.code,
+        name: budget.name,
+        max_depth,
+        amount: nodes.iter().map(|n| &n.amount).sum(),
+        nodes,
+    }))
+}
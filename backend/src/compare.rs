@@ -0,0 +1,408 @@
+//! Comparación de dos presupuestos, normalmente dos versiones del mismo
+//! proyecto: qué capítulos y líneas se han añadido, eliminado o modificado y
+//! cuánto cambian las cantidades, los precios y los importes.
+//!
+//! Los elementos se emparejan por su código en el presupuesto (`budget_code`)
+//! y, dentro de cada línea, los precios medidos por su código. Los importes
+//! son los del resumen de cada presupuesto (ver `summary`), así que las
+//! diferencias cuadran con lo que se imprime.
+use std::collections::HashMap;
+use serde::Serialize;
+use sqlx::{postgres::PgPool, types::BigDecimal};
+use tracing::debug;
+
+use crate::{
+    csv::CsvWriter,
+    models::ElementType,
+    pricing::PricingError,
+    summary::{self, BudgetSummary, SummaryItem, SummaryNode, SummaryOptions},
+};
+
+/// Qué le ha pasado a un elemento o a un precio entre los dos presupuestos.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Added,
+    Removed,
+    Modified,
+    Unchanged,
+}
+
+impl Change {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Modified => "modified",
+            Self::Unchanged => "unchanged",
+        }
+    }
+}
+
+/// Valor en cada presupuesto y la diferencia (`to - from`). Lo que no existe
+/// en uno de los dos cuenta como cero en la diferencia.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Delta {
+    pub from: Option<BigDecimal>,
+    pub to: Option<BigDecimal>,
+    pub delta: BigDecimal,
+}
+
+impl Delta {
+    fn new(from: Option<&BigDecimal>, to: Option<&BigDecimal>) -> Self {
+        let zero = BigDecimal::from(0);
+        Self {
+            delta: to.unwrap_or(&zero) - from.unwrap_or(&zero),
+            from: from.cloned(),
+            to: to.cloned(),
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.delta == 0
+    }
+}
+
+/// Precio medido en una línea.
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemComparison {
+    pub code: String,
+    pub description: String,
+    pub unit: String,
+    pub change: Change,
+    pub quantity: Delta,
+    pub unit_price: Delta,
+    pub amount: Delta,
+}
+
+/// Capítulo o línea. En los capítulos `items` está vacío y `amount` es el
+/// subtotal.
+#[derive(Debug, Clone, Serialize)]
+pub struct ElementComparison {
+    pub budget_code: String,
+    pub element_type: ElementType,
+    pub description: Option<String>,
+    pub change: Change,
+    pub items: Vec<ItemComparison>,
+    pub amount: Delta,
+}
+
+/// Presupuesto comparado.
+#[derive(Debug, Clone, Serialize)]
+pub struct ComparedBudget {
+    pub budget_id: i32,
+    pub code: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetComparison {
+    pub from: ComparedBudget,
+    pub to: ComparedBudget,
+    pub options: SummaryOptions,
+    pub chapters: Vec<ElementComparison>,
+    pub lines: Vec<ElementComparison>,
+    pub pem: Delta,
+    pub pec: Delta,
+    pub vat: Delta,
+    pub total: Delta,
+}
+
+/// Compara `from` con `to`. Devuelve `None` si alguno de los dos no existe.
+pub async fn compare(
+    pool: &PgPool,
+    from_id: i32,
+    to_id: i32,
+    options: SummaryOptions,
+) -> Result<Option<BudgetComparison>, PricingError> {
+    let Some(from) = summary::summarize(pool, from_id, options).await? else {
+        return Ok(None);
+    };
+    let Some(to) = summary::summarize(pool, to_id, options).await? else {
+        return Ok(None);
+    };
+    debug!("Comparing budget {} with {}", from.code, to.code);
+    Ok(Some(diff(&from, &to)))
+}
+
+/// Compara dos resúmenes. Los elementos siguen el orden de `to`, con los
+/// eliminados al final en el orden de `from`.
+pub fn diff(from: &BudgetSummary, to: &BudgetSummary) -> BudgetComparison {
+    let from_nodes: HashMap<&str, &SummaryNode> = from.nodes().into_iter().map(|(_, n)| (n.code.as_str(), n)).collect();
+    let to_nodes: HashMap<&str, &SummaryNode> = to.nodes().into_iter().map(|(_, n)| (n.code.as_str(), n)).collect();
+    let pairs = to
+        .nodes()
+        .into_iter()
+        .map(|(_, node)| (from_nodes.get(node.code.as_str()).copied(), Some(node)))
+        .chain(
+            from.nodes()
+                .into_iter()
+                .filter(|(_, node)| !to_nodes.contains_key(node.code.as_str()))
+                .map(|(_, node)| (Some(node), None)),
+        );
+
+    let (mut chapters, mut lines) = (Vec::new(), Vec::new());
+    for (from_node, to_node) in pairs {
+        let element = compare_nodes(from_node, to_node);
+        match element.element_type {
+            ElementType::Chapter => chapters.push(element),
+            ElementType::Line => lines.push(element),
+        }
+    }
+    BudgetComparison {
+        from: ComparedBudget { budget_id: from.budget_id, code: from.code.clone(), name: from.name.clone() },
+        to: ComparedBudget { budget_id: to.budget_id, code: to.code.clone(), name: to.name.clone() },
+        options: to.options,
+        chapters,
+        lines,
+        pem: Delta::new(Some(&from.pem), Some(&to.pem)),
+        pec: Delta::new(Some(&from.pec), Some(&to.pec)),
+        vat: Delta::new(Some(&from.vat.amount), Some(&to.vat.amount)),
+        total: Delta::new(Some(&from.total), Some(&to.total)),
+    }
+}
+
+/// Compara un elemento presente en al menos uno de los dos presupuestos.
+fn compare_nodes(from: Option<&SummaryNode>, to: Option<&SummaryNode>) -> ElementComparison {
+    let node = to.or(from).expect("element in one of the budgets");
+    let items = if node.element_type == ElementType::Line {
+        compare_items(from.map(|n| n.items.as_slice()).unwrap_or_default(), to.map(|n| n.items.as_slice()).unwrap_or_default())
+    } else {
+        Vec::new()
+    };
+    let amount = Delta::new(from.map(|n| &n.amount), to.map(|n| &n.amount));
+    let change = match (from, to) {
+        (None, _) => Change::Added,
+        (_, None) => Change::Removed,
+        (Some(from), Some(to)) => {
+            let modified = from.description != to.description
+                || from.element_type != to.element_type
+                || !amount.is_zero()
+                || items.iter().any(|item| item.change != Change::Unchanged);
+            if modified { Change::Modified } else { Change::Unchanged }
+        }
+    };
+    ElementComparison {
+        budget_code: node.code.clone(),
+        element_type: node.element_type,
+        description: node.description.clone(),
+        change,
+        items,
+        amount,
+    }
+}
+
+fn compare_items(from: &[SummaryItem], to: &[SummaryItem]) -> Vec<ItemComparison> {
+    let pairs = to
+        .iter()
+        .map(|item| (from.iter().find(|f| f.code == item.code), Some(item)))
+        .chain(
+            from.iter()
+                .filter(|item| !to.iter().any(|t| t.code == item.code))
+                .map(|item| (Some(item), None)),
+        );
+    pairs
+        .map(|(from, to)| {
+            let item = to.or(from).expect("item in one of the lines");
+            let quantity = Delta::new(from.map(|i| &i.quantity), to.map(|i| &i.quantity));
+            let unit_price = Delta::new(from.map(|i| &i.unit_price), to.map(|i| &i.unit_price));
+            let amount = Delta::new(from.map(|i| &i.amount), to.map(|i| &i.amount));
+            let change = match (from, to) {
+                (None, _) => Change::Added,
+                (_, None) => Change::Removed,
+                _ if quantity.is_zero() && unit_price.is_zero() && amount.is_zero() => Change::Unchanged,
+                _ => Change::Modified,
+            };
+            ItemComparison {
+                code: item.code.clone(),
+                description: item.description.clone(),
+                unit: item.unit.clone(),
+                change,
+                quantity,
+                unit_price,
+                amount,
+            }
+        })
+        .collect()
+}
+
+impl BudgetComparison {
+    /// Quita las líneas sin cambios.
+    pub fn retain_changed(&mut self) {
+        self.lines.retain(|line| line.change != Change::Unchanged);
+    }
+
+    /// Comparación en CSV: una fila por capítulo, una por cada precio medido
+    /// en las líneas (o una sola si la línea no tiene mediciones) y al final
+    /// los totales.
+    pub fn csv(&self) -> String {
+        let mut csv = CsvWriter::new();
+        csv.row([
+            "section",
+            "budget_code",
+            "description",
+            "change",
+            "price_code",
+            "unit",
+            "from_quantity",
+            "to_quantity",
+            "quantity_delta",
+            "from_unit_price",
+            "to_unit_price",
+            "unit_price_delta",
+            "from_amount",
+            "to_amount",
+            "amount_delta",
+        ]);
+        let empty = || vec![String::new(); 8];
+        for chapter in &self.chapters {
+            let mut row = element_head("chapter", chapter);
+            row.extend(empty());
+            row.extend(delta(&chapter.amount));
+            csv.row(row);
+        }
+        for line in &self.lines {
+            if line.items.is_empty() {
+                let mut row = element_head("line", line);
+                row.extend(empty());
+                row.extend(delta(&line.amount));
+                csv.row(row);
+            }
+            for item in &line.items {
+                let mut row = element_head("line", line);
+                row[3] = item.change.as_str().to_string();
+                row.extend([item.code.clone(), item.unit.clone()]);
+                row.extend(delta(&item.quantity));
+                row.extend(delta(&item.unit_price));
+                row.extend(delta(&item.amount));
+                csv.row(row);
+            }
+        }
+        let totals = [
+            ("PEM", &self.pem),
+            ("PEC", &self.pec),
+            (summary::VAT, &self.vat),
+            ("Total", &self.total),
+        ];
+        for (name, amount) in totals {
+            let change = if amount.is_zero() { Change::Unchanged } else { Change::Modified };
+            let mut row = vec!["total".to_string(), name.to_string(), String::new(), change.as_str().to_string()];
+            row.extend(empty());
+            row.extend(delta(amount));
+            csv.row(row);
+        }
+        csv.finish()
+    }
+}
+
+fn element_head(section: &str, element: &ElementComparison) -> Vec<String> {
+    vec![
+        section.to_string(),
+        element.budget_code.clone(),
+        element.description.clone().unwrap_or_default(),
+        element.change.as_str().to_string(),
+    ]
+}
+
+fn delta(delta: &Delta) -> [String; 3] {
+    let value = |value: &Option<BigDecimal>| value.as_ref().map(BigDecimal::to_plain_string).unwrap_or_default();
+    [value(&delta.from), value(&delta.to), delta.delta.to_plain_string()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn decimal(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    fn item(code: &str, quantity: &str, unit_price: &str) -> SummaryItem {
+        SummaryItem {
+            price_id: 1,
+            code: code.to_string(),
+            description: format!("Price {}", code),
+            unit: "m".to_string(),
+            amount: decimal(quantity) * decimal(unit_price),
+            quantity: decimal(quantity),
+            unit_price: decimal(unit_price),
+        }
+    }
+
+    fn line(code: &str, items: Vec<SummaryItem>) -> SummaryNode {
+        SummaryNode {
+            element_id: 1,
+            element_type: ElementType::Line,
+            code: code.to_string(),
+            description: None,
+            amount: items.iter().map(|i| &i.amount).sum(),
+            items,
+            children: Vec::new(),
+        }
+    }
+
+    fn budget(lines: Vec<SummaryNode>) -> BudgetSummary {
+        let pem: BigDecimal = lines.iter().map(|l| &l.amount).sum();
+        let options = SummaryOptions::default();
+        BudgetSummary {
+            budget_id: 1,
+            code: "B".to_string(),
+            name: "Budget".to_string(),
+            options,
+            chapters: vec![SummaryNode {
+                element_id: 1,
+                element_type: ElementType::Chapter,
+                code: "01".to_string(),
+                description: Some("Chapter".to_string()),
+                items: Vec::new(),
+                amount: pem.clone(),
+                children: lines,
+            }],
+            rates: Vec::new(),
+            pec: pem.clone(),
+            vat: options.rate(summary::VAT, &BigDecimal::from(0), &pem),
+            total: pem.clone(),
+            pem,
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let from = budget(vec![
+            line("01.01", vec![item("A", "2", "10")]),
+            line("01.02", vec![item("B", "1", "5")]),
+            line("01.03", vec![item("C", "1", "1")]),
+        ]);
+        let to = budget(vec![
+            line("01.01", vec![item("A", "3", "10"), item("D", "1", "2")]),
+            line("01.02", vec![item("B", "1", "5")]),
+            line("01.04", vec![item("C", "1", "1")]),
+        ]);
+        let comparison = diff(&from, &to);
+        let changes: Vec<(&str, Change)> = comparison.lines.iter().map(|l| (l.budget_code.as_str(), l.change)).collect();
+        assert_eq!(changes, vec![
+            ("01.01", Change::Modified),
+            ("01.02", Change::Unchanged),
+            ("01.04", Change::Added),
+            ("01.03", Change::Removed),
+        ]);
+        let modified = &comparison.lines[0];
+        assert_eq!(modified.items[0].change, Change::Modified);
+        assert_eq!(modified.items[0].quantity.delta, decimal("1"));
+        assert_eq!(modified.items[1].change, Change::Added);
+        assert_eq!(modified.amount.delta, decimal("12"));
+        assert_eq!(comparison.lines[3].amount, Delta { from: Some(decimal("1")), to: None, delta: decimal("-1") });
+        assert_eq!(comparison.chapters[0].change, Change::Modified);
+        assert_eq!(comparison.pem.delta, decimal("12"));
+
+        let mut comparison = comparison;
+        comparison.retain_changed();
+        assert_eq!(comparison.lines.len(), 3);
+        let csv = comparison.csv();
+        assert!(csv.starts_with("section,budget_code,description,change,price_code,unit,"));
+        assert!(csv.contains("\r\nline,01.01,,modified,A,m,2,3,1,10,10,0,20,30,10\r\n"));
+        assert!(csv.contains("\r\nline,01.03,,removed,C,m,1,,-1,1,,-1,1,,-1\r\n"));
+        assert!(csv.ends_with(",Total,,modified,,,,,,,,,26,38,12\r\n"));
+    }
+}
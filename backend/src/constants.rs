@@ -2,4 +2,5 @@
 pub const DEFAULT_PAGE: u32 = 1;
 pub const DEFAULT_LIMIT: u32 = 20;
 
-
+// Tamaño máximo de un fichero BC3 importado
+pub const MAX_BC3_SIZE: usize = 64 * 1024 * 1024;
@@ -0,0 +1,53 @@
+//! Escritura de ficheros CSV (RFC 4180): campos separados por comas, entre
+//! comillas cuando contienen comas, comillas o saltos de línea, y filas
+//! terminadas en CRLF.
+
+#[derive(Debug, Default)]
+pub struct CsvWriter {
+    out: String,
+}
+
+impl CsvWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn row<I, S>(&mut self, fields: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let fields: Vec<String> = fields.into_iter().map(|f| field(f.as_ref())).collect();
+        self.out.push_str(&fields.join(","));
+        self.out.push_str("\r\n");
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+fn field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv() {
+        let mut csv = CsvWriter::new();
+        csv.row(["code", "description", "price"]);
+        csv.row(["E01", "Solado \"gres\", con rodapié", "23.73"]);
+        csv.row(["E02", "Dos\nlíneas", ""]);
+        assert_eq!(
+            csv.finish(),
+            "code,description,price\r\nE01,\"Solado \"\"gres\"\", con rodapié\",23.73\r\nE02,\"Dos\nlíneas\",\r\n"
+        );
+    }
+}
@@ -0,0 +1,608 @@
+//! Evaluación de las fórmulas de medición de las unidades (`Unit.formula`).
+//!
+//! Admite números, variables, los operadores `+ - * / ^`, paréntesis, la
+//! constante `pi` y las funciones `min`, `max`, `round`, `abs`, `floor`,
+//! `ceil` y `sqrt`. El cálculo se hace con `BigDecimal`, así que `0.1 + 0.2`
+//! vale exactamente `0.3`.
+use std::{collections::HashMap, fmt, str::FromStr};
+use bigdecimal::{FromPrimitive, RoundingMode, ToPrimitive, Zero};
+use serde_json::Value;
+use sqlx::types::BigDecimal;
+
+const MAX_LENGTH: usize = 1024;
+const MAX_DEPTH: usize = 64;
+const MAX_EXPONENT: i64 = 1000;
+const MAX_DIGITS: i64 = 100;
+// Cifras de un valor, contando los ceros que implica su escala
+const MAX_SIZE: u64 = 4000;
+const PI: &str = "3.14159265358979323846264338327950288";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormulaError {
+    Empty,
+    TooLong,
+    TooDeep,
+    UnexpectedChar { position: usize, found: char },
+    UnexpectedToken { position: usize, found: String },
+    UnexpectedEnd,
+    UnknownFunction(String),
+    WrongArguments { function: String, found: usize },
+    ReservedName(String),
+    MissingVariable(String),
+    InvalidValue(String),
+    DivisionByZero,
+    InvalidOperation(String),
+}
+
+impl fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "The formula is empty"),
+            Self::TooLong => write!(f, "The formula is longer than {} characters", MAX_LENGTH),
+            Self::TooDeep => write!(f, "The formula is nested more than {} levels", MAX_DEPTH),
+            Self::UnexpectedChar { position, found } => {
+                write!(f, "Unexpected character '{}' at position {}", found, position)
+            }
+            Self::UnexpectedToken { position, found } => write!(f, "Unexpected '{}' at position {}", found, position),
+            Self::UnexpectedEnd => write!(f, "Unexpected end of formula"),
+            Self::UnknownFunction(name) => write!(f, "Unknown function '{}'", name),
+            Self::WrongArguments { function, found } => {
+                write!(f, "Wrong number of arguments for '{}': {}", function, found)
+            }
+            Self::ReservedName(name) => write!(f, "'{}' is a reserved name", name),
+            Self::MissingVariable(name) => write!(f, "Missing value for variable '{}'", name),
+            Self::InvalidValue(name) => write!(f, "The value of '{}' is not a number", name),
+            Self::DivisionByZero => write!(f, "Division by zero"),
+            Self::InvalidOperation(reason) => write!(f, "Invalid operation: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for FormulaError {}
+
+// =================================================================
+// 1. ANÁLISIS LÉXICO
+// =================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(BigDecimal),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(n) => write!(f, "{}", n),
+            Self::Ident(s) => write!(f, "{}", s),
+            Self::Plus => write!(f, "+"),
+            Self::Minus => write!(f, "-"),
+            Self::Star => write!(f, "*"),
+            Self::Slash => write!(f, "/"),
+            Self::Caret => write!(f, "^"),
+            Self::LParen => write!(f, "("),
+            Self::RParen => write!(f, ")"),
+            Self::Comma => write!(f, ","),
+        }
+    }
+}
+
+/// Divide la fórmula en tokens, cada uno con su posición (en caracteres, desde 1).
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, FormulaError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let position = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let number = BigDecimal::from_str(&text)
+                .map_err(|_| FormulaError::UnexpectedToken { position, found: text.clone() })?;
+            tokens.push((position, Token::Number(number)));
+            continue;
+        }
+        if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((position, Token::Ident(chars[start..i].iter().collect())));
+            continue;
+        }
+        let token = match c {
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' | '×' => Token::Star,
+            '/' => Token::Slash,
+            '^' => Token::Caret,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' | ';' => Token::Comma,
+            _ => return Err(FormulaError::UnexpectedChar { position, found: c }),
+        };
+        tokens.push((position, token));
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+// =================================================================
+// 2. ANÁLISIS SINTÁCTICO
+// =================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Min,
+    Max,
+    Round,
+    Abs,
+    Floor,
+    Ceil,
+    Sqrt,
+    Pi,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "min" => Some(Self::Min),
+            "max" => Some(Self::Max),
+            "round" => Some(Self::Round),
+            "abs" => Some(Self::Abs),
+            "floor" => Some(Self::Floor),
+            "ceil" => Some(Self::Ceil),
+            "sqrt" => Some(Self::Sqrt),
+            "pi" => Some(Self::Pi),
+            _ => None,
+        }
+    }
+
+    fn accepts(&self, arguments: usize) -> bool {
+        match self {
+            Self::Min | Self::Max => arguments >= 1,
+            Self::Round => arguments == 1 || arguments == 2,
+            Self::Pi => arguments == 0,
+            _ => arguments == 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(BigDecimal),
+    Variable(String),
+    Negate(Box<Expr>),
+    Binary(Box<Expr>, Token, Box<Expr>),
+    Call(Function, String, Vec<Expr>),
+}
+
+/// Analizador descendente recursivo:
+///
+/// ```text
+/// expr    := term (('+' | '-') term)*
+/// term    := unary (('*' | '/') unary)*
+/// unary   := ('+' | '-') unary | power
+/// power   := primary ('^' unary)?
+/// primary := number | name | name '(' args? ')' | '(' expr ')'
+/// ```
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(_, t)| t)
+    }
+
+    fn next(&mut self) -> Option<(usize, Token)> {
+        let token = self.tokens.get(self.index).cloned();
+        self.index += 1;
+        token
+    }
+
+    fn unexpected(&self) -> FormulaError {
+        match self.tokens.get(self.index) {
+            Some((position, token)) => FormulaError::UnexpectedToken { position: *position, found: token.to_string() },
+            None => FormulaError::UnexpectedEnd,
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), FormulaError> {
+        if self.peek() == Some(&expected) {
+            self.index += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), FormulaError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(FormulaError::TooDeep);
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self) -> Result<Expr, FormulaError> {
+        self.enter()?;
+        let mut left = self.term()?;
+        while let Some(op @ (Token::Plus | Token::Minus)) = self.peek().cloned() {
+            self.index += 1;
+            left = Expr::Binary(Box::new(left), op, Box::new(self.term()?));
+        }
+        self.depth -= 1;
+        Ok(left)
+    }
+
+    fn term(&mut self) -> Result<Expr, FormulaError> {
+        let mut left = self.unary()?;
+        while let Some(op @ (Token::Star | Token::Slash)) = self.peek().cloned() {
+            self.index += 1;
+            left = Expr::Binary(Box::new(left), op, Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, FormulaError> {
+        self.enter()?;
+        let expr = match self.peek() {
+            Some(Token::Minus) => {
+                self.index += 1;
+                Expr::Negate(Box::new(self.unary()?))
+            }
+            Some(Token::Plus) => {
+                self.index += 1;
+                self.unary()?
+            }
+            _ => self.power()?,
+        };
+        self.depth -= 1;
+        Ok(expr)
+    }
+
+    fn power(&mut self) -> Result<Expr, FormulaError> {
+        let base = self.primary()?;
+        if self.peek() == Some(&Token::Caret) {
+            self.index += 1;
+            return Ok(Expr::Binary(Box::new(base), Token::Caret, Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Expr, FormulaError> {
+        match self.next() {
+            Some((_, Token::Number(n))) => Ok(Expr::Number(n)),
+            Some((_, Token::LParen)) => {
+                let expr = self.expr()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Some((_, Token::Ident(name))) => {
+                let function = Function::from_name(&name);
+                if self.peek() == Some(&Token::LParen) {
+                    self.index += 1;
+                    let function = function.ok_or_else(|| FormulaError::UnknownFunction(name.clone()))?;
+                    let mut arguments = Vec::new();
+                    if self.peek() != Some(&Token::RParen) {
+                        arguments.push(self.expr()?);
+                        while self.peek() == Some(&Token::Comma) {
+                            self.index += 1;
+                            arguments.push(self.expr()?);
+                        }
+                    }
+                    self.expect(Token::RParen)?;
+                    if !function.accepts(arguments.len()) {
+                        return Err(FormulaError::WrongArguments { function: name, found: arguments.len() });
+                    }
+                    return Ok(Expr::Call(function, name, arguments));
+                }
+                match function {
+                    Some(Function::Pi) => Ok(Expr::Call(Function::Pi, name, Vec::new())),
+                    Some(_) => Err(FormulaError::ReservedName(name)),
+                    None => Ok(Expr::Variable(name)),
+                }
+            }
+            Some(_) => {
+                self.index -= 1;
+                Err(self.unexpected())
+            }
+            None => Err(FormulaError::UnexpectedEnd),
+        }
+    }
+}
+
+// =================================================================
+// 3. FÓRMULA
+// =================================================================
+
+/// Fórmula ya analizada, lista para evaluarse tantas veces como haga falta.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    expr: Expr,
+    variables: Vec<String>,
+}
+
+impl FromStr for Formula {
+    type Err = FormulaError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
+
+impl Formula {
+    /// Analiza la fórmula. Los errores indican la posición (en caracteres) del problema.
+    pub fn parse(source: &str) -> Result<Self, FormulaError> {
+        if source.chars().count() > MAX_LENGTH {
+            return Err(FormulaError::TooLong);
+        }
+        let tokens = tokenize(source)?;
+        if tokens.is_empty() {
+            return Err(FormulaError::Empty);
+        }
+        let mut parser = Parser { tokens, index: 0, depth: 0 };
+        let expr = parser.expr()?;
+        if parser.index < parser.tokens.len() {
+            return Err(parser.unexpected());
+        }
+        let mut variables = Vec::new();
+        collect_variables(&expr, &mut variables);
+        variables.sort();
+        variables.dedup();
+        Ok(Self { expr, variables })
+    }
+
+    /// Variables de la fórmula, ordenadas alfabéticamente y sin repetir.
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    /// Evalúa la fórmula con los valores indicados para sus variables.
+    pub fn evaluate(&self, values: &HashMap<String, BigDecimal>) -> Result<BigDecimal, FormulaError> {
+        evaluate(&self.expr, values)
+    }
+
+    /// Evalúa la fórmula con los valores de un objeto JSON (`{"a": 2, "b": "1.5"}`).
+    /// Se admiten números y cadenas numéricas.
+    pub fn evaluate_json(&self, params: &Value) -> Result<BigDecimal, FormulaError> {
+        let mut values = HashMap::new();
+        for name in &self.variables {
+            let value = match params.get(name) {
+                Some(Value::Number(n)) => BigDecimal::from_str(&n.to_string()).ok(),
+                Some(Value::String(s)) => BigDecimal::from_str(s.trim()).ok(),
+                Some(Value::Null) | None => return Err(FormulaError::MissingVariable(name.clone())),
+                Some(_) => None,
+            };
+            let value = value
+                .filter(|v| size(v) <= MAX_SIZE)
+                .ok_or_else(|| FormulaError::InvalidValue(name.clone()))?;
+            values.insert(name.clone(), value);
+        }
+        self.evaluate(&values)
+    }
+}
+
+fn collect_variables(expr: &Expr, variables: &mut Vec<String>) {
+    match expr {
+        Expr::Number(_) => {}
+        Expr::Variable(name) => variables.push(name.clone()),
+        Expr::Negate(inner) => collect_variables(inner, variables),
+        Expr::Binary(left, _, right) => {
+            collect_variables(left, variables);
+            collect_variables(right, variables);
+        }
+        Expr::Call(_, _, arguments) => arguments.iter().for_each(|a| collect_variables(a, variables)),
+    }
+}
+
+fn evaluate(expr: &Expr, values: &HashMap<String, BigDecimal>) -> Result<BigDecimal, FormulaError> {
+    match expr {
+        Expr::Number(n) => Ok(n.clone()),
+        Expr::Variable(name) => values.get(name).cloned().ok_or_else(|| FormulaError::MissingVariable(name.clone())),
+        Expr::Negate(inner) => Ok(-evaluate(inner, values)?),
+        Expr::Binary(left, op, right) => {
+            let left = evaluate(left, values)?;
+            let right = evaluate(right, values)?;
+            match op {
+                Token::Plus => bounded(left + right),
+                Token::Minus => bounded(left - right),
+                Token::Star => bounded(left * right),
+                Token::Slash if right.is_zero() => Err(FormulaError::DivisionByZero),
+                Token::Slash => Ok(left / right),
+                _ => power(&left, &right),
+            }
+        }
+        Expr::Call(function, _, arguments) => {
+            let arguments = arguments
+                .iter()
+                .map(|a| evaluate(a, values))
+                .collect::<Result<Vec<_>, _>>()?;
+            call(*function, arguments)
+        }
+    }
+}
+
+fn power(base: &BigDecimal, exponent: &BigDecimal) -> Result<BigDecimal, FormulaError> {
+    if exponent.is_integer() {
+        let exponent = exponent
+            .to_i64()
+            .filter(|e| e.abs() <= MAX_EXPONENT)
+            .ok_or_else(|| FormulaError::InvalidOperation(format!("exponent out of range ({})", exponent)))?;
+        if base.is_zero() && exponent < 0 {
+            return Err(FormulaError::DivisionByZero);
+        }
+        // Se descarta antes de calcularla una potencia que no cabría en el límite
+        if size(base).saturating_mul(exponent.unsigned_abs()) > MAX_SIZE {
+            return Err(too_large());
+        }
+        return bounded(base.powi(exponent));
+    }
+    // Exponente fraccionario: la raíz cuadrada se calcula con precisión decimal
+    if *exponent == BigDecimal::new(5.into(), 1) {
+        return base
+            .sqrt()
+            .ok_or_else(|| FormulaError::InvalidOperation(format!("square root of {}", base)));
+    }
+    // En el resto de casos se recurre a coma flotante
+    base.to_f64()
+        .zip(exponent.to_f64())
+        .map(|(b, e)| b.powf(e))
+        .and_then(BigDecimal::from_f64)
+        .ok_or_else(|| FormulaError::InvalidOperation(format!("{} ^ {}", base, exponent)))
+}
+
+/// Número de cifras de un valor, incluidos los ceros de su escala (`1e5`
+/// tiene 6 y `1e-5`, 6).
+fn size(value: &BigDecimal) -> u64 {
+    value.digits().saturating_add(value.fractional_digit_count().unsigned_abs())
+}
+
+fn too_large() -> FormulaError {
+    FormulaError::InvalidOperation(format!("result has more than {} digits", MAX_SIZE))
+}
+
+fn bounded(value: BigDecimal) -> Result<BigDecimal, FormulaError> {
+    if size(&value) > MAX_SIZE {
+        return Err(too_large());
+    }
+    Ok(value)
+}
+
+fn call(function: Function, arguments: Vec<BigDecimal>) -> Result<BigDecimal, FormulaError> {
+    let first = || arguments[0].clone();
+    match function {
+        Function::Pi => Ok(BigDecimal::from_str(PI).unwrap_or_default()),
+        Function::Min => Ok(arguments.iter().min().cloned().unwrap_or_default()),
+        Function::Max => Ok(arguments.iter().max().cloned().unwrap_or_default()),
+        Function::Abs => Ok(first().abs()),
+        Function::Floor => Ok(first().with_scale_round(0, RoundingMode::Floor)),
+        Function::Ceil => Ok(first().with_scale_round(0, RoundingMode::Ceiling)),
+        Function::Sqrt => first()
+            .sqrt()
+            .ok_or_else(|| FormulaError::InvalidOperation(format!("square root of {}", first()))),
+        Function::Round => {
+            let digits = match arguments.get(1) {
+                Some(d) if d.is_integer() => d.to_i64().filter(|d| d.abs() <= MAX_DIGITS),
+                Some(_) => None,
+                None => Some(0),
+            }
+            .ok_or_else(|| FormulaError::InvalidOperation("round digits must be a small integer".to_string()))?;
+            Ok(first().with_scale_round(digits, RoundingMode::HalfUp))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn eval(source: &str, values: &[(&str, &str)]) -> Result<BigDecimal, FormulaError> {
+        let values = values
+            .iter()
+            .map(|(k, v)| (k.to_string(), BigDecimal::from_str(v).unwrap()))
+            .collect();
+        Formula::parse(source)?.evaluate(&values)
+    }
+
+    fn decimal(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(eval("1 + 2 * 3", &[]).unwrap(), decimal("7"));
+        assert_eq!(eval("(1 + 2) * 3", &[]).unwrap(), decimal("9"));
+        assert_eq!(eval("2 ^ 3 ^ 2", &[]).unwrap(), decimal("512"));
+        assert_eq!(eval("-2 ^ 2", &[]).unwrap(), decimal("-4"));
+        assert_eq!(eval("2 ^ -1", &[]).unwrap(), decimal("0.5"));
+        assert_eq!(eval("10 - 4 - 3", &[]).unwrap(), decimal("3"));
+        assert_eq!(eval("0.1 + 0.2", &[]).unwrap(), decimal("0.3"));
+    }
+
+    #[test]
+    fn test_variables() {
+        let formula = Formula::parse("a * b + a").unwrap();
+        assert_eq!(formula.variables(), &["a".to_string(), "b".to_string()]);
+        assert_eq!(eval("a * b", &[("a", "2.5"), ("b", "4")]).unwrap(), decimal("10"));
+        assert_eq!(eval("a * b", &[("a", "2")]), Err(FormulaError::MissingVariable("b".to_string())));
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(eval("min(3, a, 5)", &[("a", "1")]).unwrap(), decimal("1"));
+        assert_eq!(eval("max(3, 7, 5)", &[]).unwrap(), decimal("7"));
+        assert_eq!(eval("round(2.345, 2)", &[]).unwrap(), decimal("2.35"));
+        assert_eq!(eval("round(2.5)", &[]).unwrap(), decimal("3"));
+        assert_eq!(eval("round(pi * r ^ 2, 4)", &[("r", "1")]).unwrap(), decimal("3.1416"));
+        assert_eq!(eval("round(pi() * 2, 2)", &[]).unwrap(), decimal("6.28"));
+        assert_eq!(eval("sqrt(16) + abs(-1)", &[]).unwrap(), decimal("5"));
+        assert_eq!(eval("floor(2.7) + ceil(2.1)", &[]).unwrap(), decimal("5"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Formula::parse("  "), Err(FormulaError::Empty));
+        assert_eq!(Formula::parse("a * # b"), Err(FormulaError::UnexpectedChar { position: 5, found: '#' }));
+        assert_eq!(
+            Formula::parse("a * * b"),
+            Err(FormulaError::UnexpectedToken { position: 5, found: "*".to_string() })
+        );
+        assert_eq!(Formula::parse("(a + b"), Err(FormulaError::UnexpectedEnd));
+        assert_eq!(Formula::parse("a b"), Err(FormulaError::UnexpectedToken { position: 3, found: "b".to_string() }));
+        assert_eq!(Formula::parse("foo(a)"), Err(FormulaError::UnknownFunction("foo".to_string())));
+        assert_eq!(
+            Formula::parse("round(1, 2, 3)"),
+            Err(FormulaError::WrongArguments { function: "round".to_string(), found: 3 })
+        );
+        assert_eq!(Formula::parse("max * 2"), Err(FormulaError::ReservedName("max".to_string())));
+        assert_eq!(Formula::parse(&"(".repeat(100)), Err(FormulaError::TooDeep));
+    }
+
+    #[test]
+    fn test_evaluation_errors() {
+        assert_eq!(eval("a / (b - 2)", &[("a", "1"), ("b", "2")]), Err(FormulaError::DivisionByZero));
+        assert!(matches!(eval("sqrt(-1)", &[]), Err(FormulaError::InvalidOperation(_))));
+        assert!(matches!(eval("2 ^ 100000", &[]), Err(FormulaError::InvalidOperation(_))));
+        assert!(matches!(eval("((9 ^ 1000) ^ 1000) ^ 1000", &[]), Err(FormulaError::InvalidOperation(_))));
+        let product = ["(9 ^ 1000)"; 5].join(" * ");
+        assert!(matches!(eval(&product, &[]), Err(FormulaError::InvalidOperation(_))));
+        assert!(matches!(eval("a + 1", &[("a", "1e-100000")]), Err(FormulaError::InvalidOperation(_))));
+        assert!(eval("9 ^ 1000", &[]).is_ok());
+    }
+
+    #[test]
+    fn test_evaluate_json() {
+        let formula = Formula::parse("a * b").unwrap();
+        assert_eq!(formula.evaluate_json(&json!({"a": 2, "b": "1.5"})).unwrap(), decimal("3"));
+        assert_eq!(formula.evaluate_json(&json!({"a": 2})), Err(FormulaError::MissingVariable("b".to_string())));
+        assert_eq!(
+            formula.evaluate_json(&json!({"a": 2, "b": "x"})),
+            Err(FormulaError::InvalidValue("b".to_string()))
+        );
+        assert_eq!(
+            formula.evaluate_json(&json!({"a": 2, "b": "1e-100000"})),
+            Err(FormulaError::InvalidValue("b".to_string()))
+        );
+    }
+}
@@ -1,29 +1,97 @@
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock};
 
 use axum::{
     body,
     extract::{
+        ConnectInfo,
         State,
         Path,
     },
-    http::{header, StatusCode},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
-    routing, Json, Router,
+    routing, Extension, Json, Router,
 };
-use bcrypt::verify;
-use tracing::{debug, error};
+use std::net::SocketAddr;
+use tracing::{debug, error, info, warn};
 
 use axum_extra::extract::cookie::{Cookie, SameSite};
 use jsonwebtoken::{encode, EncodingKey, Header};
+use serde::Deserialize;
 
-use crate::models::{ApiResponse, AppState, Data, TokenClaims, User, UserPass, NewUser, Role};
+use crate::{
+    http::{
+        middleware::{CurrentUser, TOKEN_COOKIE},
+        permissions::Level,
+    },
+    mailer::{self, Mail},
+    models::{
+        ApiResponse, AppState, Data, TokenClaims, User, UserPass, NewUser, Role, Session,
+        TokenPurpose, UserToken, AuditAction, AuditEntry, NewAuditEntry, LoginScope, LoginThrottle,
+        UtcTimestamp,
+    },
+};
+
+// Duración del access token (JWT); se renueva con el refresh token
+const ACCESS_TOKEN_MINUTES: i64 = 60;
+// URL del frontend para los enlaces de los correos si no se define `APP_URL`
+const DEFAULT_APP_URL: &str = "http://localhost:3000";
 
+/// Rutas públicas: no requieren token. `/setup` solo sirve mientras no haya
+/// ningún usuario.
 pub fn router() -> Router<Arc<AppState>> {
     Router::new()
+        .route("/setup", routing::get(read_setup).post(setup))
         .route("/login", routing::post(login))
+        .route("/refresh", routing::post(refresh))
         .route("/logout", routing::get(logout))
-        .route("/register", routing::post(register))
+        .route("/forgot", routing::post(forgot))
+        .route("/reset", routing::post(reset))
+        .route("/verify", routing::post(verify_email))
+}
+
+/// Rutas que requieren un usuario autenticado (ver `middleware::require_auth`).
+pub fn private_router() -> Router<Arc<AppState>> {
+    Router::new()
         .route("/role/{name}", routing::get(get_role))
+        .route("/me", routing::get(me))
+        .route("/sessions", routing::get(read_sessions))
+        .route("/sessions/{id}", routing::delete(revoke_session))
+        .route("/verify/request", routing::post(request_verification))
+}
+
+/// Rutas que además requieren ser administrador.
+pub fn admin_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/register", routing::post(register))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshToken {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPassword {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPassword {
+    pub token: String,
+    pub password: String,
+}
+
+// Primer usuario de la instalación; será administrador
+#[derive(Deserialize)]
+pub struct Setup {
+    pub username: String,
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmail {
+    pub token: String,
 }
 
 pub fn api_user_router() -> Router<Arc<AppState>> {
@@ -34,24 +102,172 @@ pub fn api_user_router() -> Router<Arc<AppState>> {
 
 type Result = std::result::Result<ApiResponse, ApiResponse>;
 
-pub async fn login(State(app_state): State<Arc<AppState>>, Json(user_pass): Json<UserPass>) -> Result {
-    //) -> Result<Json<serde_json::Value>,(StatusCode, Json<serde_json::Value>)>{
+/// Inicia sesión. Los fallos se cuentan por cuenta y por IP: al alcanzar
+/// `LoginScope::max_failures` se bloquea temporalmente, cada vez durante más
+/// tiempo (ver `LoginThrottle`), y se responde con un 429.
+pub async fn login(
+    State(app_state): State<Arc<AppState>>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+    Json(user_pass): Json<UserPass>,
+) -> std::result::Result<ApiResponse, Response> {
     tracing::info!("init login");
-    tracing::info!("User pass: {:?}", user_pass);
+    let ip = client_ip(&headers, connect_info.map(|Extension(ConnectInfo(addr))| addr), *TRUSTED_PROXIES);
+    let account = user_pass.email.trim().to_lowercase();
+    let throttles = [(LoginScope::Account, Some(account)), (LoginScope::Ip, ip.clone())];
+    for (scope, key) in &throttles {
+        let Some(key) = key else { continue };
+        let locked_until = LoginThrottle::locked_until(&app_state.pool, *scope, key)
+            .await
+            .map_err(|e| {
+                error!("Error reading login throttle: {}", e);
+                ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None).into_response()
+            })?;
+        if let Some(locked_until) = locked_until {
+            debug!("Login blocked for {} {} until {}", scope, key, locked_until);
+            return Err(too_many_attempts(locked_until));
+        }
+    }
     let user = User::read_by_email(&app_state.pool, user_pass.email)
         .await
         .map_err(|e| {
             let message = &format!("Error: {}", e);
-            ApiResponse::new(StatusCode::FORBIDDEN, message, Data::None)
-        })?
-        .ok_or_else(|| {
+            ApiResponse::new(StatusCode::FORBIDDEN, message, Data::None).into_response()
+        })?;
+    let valid = match &user {
+        Some(user) if user.is_active => user.verify_password(&user_pass.password).await,
+        _ => false,
+    };
+    let user = match user {
+        Some(user) if valid => user,
+        user => {
+            record_failures(&app_state, &throttles, user.as_ref(), ip.as_deref()).await;
             let message = "Invalid name or password";
-            ApiResponse::new(StatusCode::FORBIDDEN, message, Data::None)
+            return Err(ApiResponse::new(StatusCode::FORBIDDEN, message, Data::None).into_response());
+        }
+    };
+    if let Some(account) = &throttles[0].1
+        && let Err(e) = LoginThrottle::clear(&app_state.pool, LoginScope::Account, account).await
+    {
+        error!("Error clearing login failures of user {}: {}", user.id, e);
+    }
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+    let (session, refresh_token) = Session::create(&app_state.pool, user.id, user_agent)
+        .await
+        .map_err(|e| {
+            error!("Error creating session for user {}: {}", user.id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None).into_response()
         })?;
-    if !user.is_active || !verify(&user_pass.password, &user.hashed_password).unwrap() {
-        let message = "Invalid name or password";
-        return Err(ApiResponse::new(StatusCode::FORBIDDEN, message, Data::None));
+    tokens(&app_state, &user, &session, refresh_token)
+        .await
+        .map_err(IntoResponse::into_response)
+}
+
+// Proxies inversos de confianza delante del servidor, de `TRUST_FORWARDED_FOR`:
+// `true` para uno o el número de ellos. Se lee una sola vez
+static TRUSTED_PROXIES: LazyLock<usize> = LazyLock::new(|| {
+    match std::env::var("TRUST_FORWARDED_FOR").as_deref() {
+        Ok("true") => 1,
+        Ok(value) => value.parse().unwrap_or(0),
+        Err(_) => 0,
+    }
+});
+
+/// IP del cliente. Detrás de proxies inversos de confianza se toma de
+/// `X-Forwarded-For`: cada proxy añade al final la IP de la que recibe la
+/// petición, así que la del cliente es la que añadió el primero de ellos,
+/// contando desde la derecha. Lo anterior lo puede falsear el cliente.
+fn client_ip(headers: &HeaderMap, addr: Option<SocketAddr>, trusted_proxies: usize) -> Option<String> {
+    if trusted_proxies > 0
+        && let Some(forwarded) = headers.get("x-forwarded-for").and_then(|value| value.to_str().ok())
+    {
+        let ips: Vec<&str> = forwarded.split(',').map(str::trim).collect();
+        if let Some(ip) = ips.get(ips.len().saturating_sub(trusted_proxies)).filter(|ip| !ip.is_empty()) {
+            return Some(ip.to_string());
+        }
+    }
+    addr.map(|addr| addr.ip().to_string())
+}
+
+/// Respuesta a un login bloqueado, con la cabecera `Retry-After`.
+fn too_many_attempts(locked_until: UtcTimestamp) -> Response {
+    let seconds = (locked_until - chrono::Utc::now()).num_seconds().max(1);
+    let message = "Too many failed login attempts, try again later";
+    (
+        [(header::RETRY_AFTER, seconds.to_string())],
+        ApiResponse::new(StatusCode::TOO_MANY_REQUESTS, message, Data::None),
+    )
+        .into_response()
+}
+
+/// Cuenta un login fallido en cada contador y audita los bloqueos. Los
+/// errores solo se registran: el login ya ha fallado.
+async fn record_failures(
+    app_state: &AppState,
+    throttles: &[(LoginScope, Option<String>)],
+    user: Option<&User>,
+    ip: Option<&str>,
+) {
+    for (scope, key) in throttles {
+        let Some(key) = key else { continue };
+        let throttle = match LoginThrottle::record_failure(&app_state.pool, *scope, key).await {
+            Ok(throttle) => throttle,
+            Err(e) => {
+                error!("Error recording login failure for {} {}: {}", scope, key, e);
+                continue;
+            }
+        };
+        let Some(locked_until) = throttle.locked_until.filter(|_| throttle.failures >= scope.max_failures()) else {
+            continue;
+        };
+        warn!("Login locked for {} {} after {} failures", scope, key, throttle.failures);
+        let entry = NewAuditEntry {
+            action: match scope {
+                LoginScope::Account => AuditAction::AccountLocked,
+                LoginScope::Ip => AuditAction::IpLocked,
+            },
+            user_id: user.filter(|_| *scope == LoginScope::Account).map(|user| user.id),
+            actor_id: None,
+            ip: ip.map(|ip| ip.to_string()),
+            detail: format!("{} failed attempts on {}, locked until {}", throttle.failures, key, locked_until.to_rfc3339()),
+        };
+        if let Err(e) = AuditEntry::create(&app_state.pool, entry).await {
+            error!("Error auditing login lock for {} {}: {}", scope, key, e);
+        }
     }
+}
+
+/// Renueva el access token con un refresh token. El refresh token se rota: el
+/// recibido deja de valer y se devuelve uno nuevo.
+pub async fn refresh(State(app_state): State<Arc<AppState>>, Json(body): Json<RefreshToken>) -> Result {
+    let invalid = || ApiResponse::new(StatusCode::UNAUTHORIZED, "Invalid refresh token", Data::None);
+    let internal = |e: sqlx::Error| {
+        error!("Error refreshing session: {}", e);
+        ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+    };
+    let session = Session::read_by_token(&app_state.pool, &body.refresh_token)
+        .await
+        .map_err(internal)?
+        .ok_or_else(invalid)?;
+    let user = User::read_by_id(&app_state.pool, session.user_id)
+        .await
+        .map_err(internal)?
+        .filter(|user| user.is_active)
+        .ok_or_else(invalid)?;
+    // Solo una de varias peticiones con el mismo token consigue rotarlo
+    let (session, refresh_token) = Session::rotate(&app_state.pool, &body.refresh_token)
+        .await
+        .map_err(internal)?
+        .ok_or_else(invalid)?;
+    debug!("Session {} refreshed for user {}", session.id, user.id);
+    tokens(&app_state, &user, &session, refresh_token).await
+}
+
+/// Access token de la sesión junto con su refresh token.
+async fn tokens(app_state: &AppState, user: &User, session: &Session, refresh_token: String) -> Result {
     let role = Role::read_by_id(&app_state.pool, user.role_id)
         .await
         .map_err(|e| {
@@ -65,10 +281,11 @@ pub async fn login(State(app_state): State<Arc<AppState>>, Json(user_pass): Json
 
     let now = chrono::Utc::now();
     let iat = now.timestamp() as usize;
-    let exp = (now + chrono::Duration::minutes(60)).timestamp() as usize;
+    let exp = (now + chrono::Duration::minutes(ACCESS_TOKEN_MINUTES)).timestamp() as usize;
     let claims: TokenClaims = TokenClaims {
         sub: user.email.to_string(),
         role: role.name.to_string(),
+        sid: session.id,
         exp,
         iat,
     };
@@ -83,11 +300,68 @@ pub async fn login(State(app_state): State<Arc<AppState>>, Json(user_pass): Json
         ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &message, Data::None)
     })
     .map(|token| {
-        let value = serde_json::json!({"token": token});
+        let value = serde_json::json!({
+            "token": token,
+            "refresh_token": refresh_token,
+            "expires_in": ACCESS_TOKEN_MINUTES * 60,
+        });
         ApiResponse::new(StatusCode::OK, "Ok", Data::Some(value))
     })
 }
 
+/// Indica si falta crear el primer usuario (ver `setup`).
+pub async fn read_setup(State(app_state): State<Arc<AppState>>) -> Result {
+    let count = User::count_all(&app_state.pool).await.map_err(|e| {
+        error!("Error counting users: {}", e);
+        ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+    })?;
+    let value = serde_json::json!({ "required": count == 0 });
+    Ok(ApiResponse::new(StatusCode::OK, "Ok", Data::Some(value)))
+}
+
+/// Crea el primer usuario, como administrador, e inicia su sesión. Una vez
+/// que existe algún usuario responde con un 409: los demás los da de alta un
+/// administrador con `register`.
+pub async fn setup(
+    State(app_state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(body): Json<Setup>,
+) -> Result {
+    let internal = |e: sqlx::Error| {
+        error!("Error creating the first user: {}", e);
+        ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+    };
+    let role = Role::read_by_name(&app_state.pool, Level::Admin.name())
+        .await
+        .map_err(internal)?
+        .ok_or_else(|| ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Role not found", Data::None))?;
+    let item = NewUser {
+        username: body.username,
+        email: body.email,
+        password: body.password,
+        role_id: role.id,
+        is_active: true,
+    };
+    let user = match User::create_first(&app_state.pool, item).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(ApiResponse::new(StatusCode::CONFLICT, "The first user already exists", Data::None)),
+        Err(sqlx::Error::InvalidArgument(message)) => {
+            return Err(ApiResponse::new(StatusCode::BAD_REQUEST, &message, Data::None));
+        }
+        Err(e) => return Err(internal(e)),
+    };
+    info!("First user {} created", user.id);
+    if let Err(e) = send_token(&app_state, &user, TokenPurpose::Verify).await {
+        error!("Error sending verification mail to user {}: {}", user.id, e);
+    }
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+    let (session, refresh_token) = Session::create(&app_state.pool, user.id, user_agent).await.map_err(internal)?;
+    tokens(&app_state, &user, &session, refresh_token).await
+}
+
 pub async fn register(
     State(app_state): State<Arc<AppState>>,
     Json(user): Json<NewUser>,
@@ -96,6 +370,9 @@ pub async fn register(
     match User::create(&app_state.pool, user).await {
         Ok(user) => {
             debug!("User created: {:?}", user);
+            if let Err(e) = send_token(&app_state, &user, TokenPurpose::Verify).await {
+                error!("Error sending verification mail to user {}: {}", user.id, e);
+            }
             ApiResponse::new(StatusCode::CREATED, "User created", Data::Some(serde_json::to_value(user).unwrap()))
         },
         Err(e) => {
@@ -105,9 +382,122 @@ pub async fn register(
     }
 }
 
-pub async fn logout() -> impl IntoResponse {
+/// Envía un enlace para restablecer la contraseña si el email es de un
+/// usuario activo. La respuesta es siempre la misma para no revelar qué
+/// emails están registrados; el correo se envía en segundo plano para que
+/// tampoco lo revele el tiempo de respuesta.
+pub async fn forgot(State(app_state): State<Arc<AppState>>, Json(body): Json<ForgotPassword>) -> impl IntoResponse {
+    match User::read_by_email(&app_state.pool, body.email).await {
+        Ok(Some(user)) if user.is_active => {
+            let app_state = app_state.clone();
+            tokio::spawn(async move {
+                if let Err(e) = send_token(&app_state, &user, TokenPurpose::Reset).await {
+                    error!("Error sending reset mail to user {}: {}", user.id, e);
+                }
+            });
+        }
+        Ok(_) => debug!("Password reset requested for an unknown or inactive email"),
+        Err(e) => error!("Error reading user for password reset: {}", e),
+    }
+    ApiResponse::new(StatusCode::OK, "If the email is registered, a reset link has been sent", Data::None)
+}
+
+/// Cambia la contraseña con el token del enlace de `forgot`. El token solo
+/// vale una vez y se cierran todas las sesiones del usuario.
+pub async fn reset(State(app_state): State<Arc<AppState>>, Json(body): Json<ResetPassword>) -> Result {
+    // Se valida antes de gastar el token para poder reintentarlo
+    User::validate_password(&body.password)
+        .map_err(|e| ApiResponse::new(StatusCode::BAD_REQUEST, &e.to_string(), Data::None))?;
+    let user_token = consume(&app_state, &body.token, TokenPurpose::Reset).await?;
+    User::set_password(&app_state.pool, user_token.user_id, &body.password)
+        .await
+        .map_err(|e| {
+            error!("Error resetting password of user {}: {}", user_token.user_id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+        })?;
+    debug!("Password reset for user {}", user_token.user_id);
+    Ok(ApiResponse::new(StatusCode::OK, "Password changed", Data::None))
+}
+
+/// Marca como verificado el email con el token del enlace enviado al usuario.
+pub async fn verify_email(State(app_state): State<Arc<AppState>>, Json(body): Json<VerifyEmail>) -> Result {
+    let user_token = consume(&app_state, &body.token, TokenPurpose::Verify).await?;
+    let user = User::verify_email(&app_state.pool, user_token.user_id)
+        .await
+        .map_err(|e| {
+            error!("Error verifying email of user {}: {}", user_token.user_id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+        })?;
+    Ok(ApiResponse::new(StatusCode::OK, "Email verified", Data::Some(serde_json::to_value(user).unwrap())))
+}
+
+/// Vuelve a enviar el enlace de verificación al usuario autenticado.
+pub async fn request_verification(State(app_state): State<Arc<AppState>>, current: CurrentUser) -> Result {
+    if current.user.email_verified_at.is_some() {
+        return Err(ApiResponse::new(StatusCode::CONFLICT, "Email already verified", Data::None));
+    }
+    send_token(&app_state, &current.user, TokenPurpose::Verify)
+        .await
+        .map_err(|e| {
+            error!("Error sending verification mail to user {}: {}", current.user.id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Error sending verification mail", Data::None)
+        })?;
+    Ok(ApiResponse::new(StatusCode::OK, "Verification mail sent", Data::None))
+}
+
+/// Gasta un token de un solo uso; si no vale responde con un 400.
+async fn consume(app_state: &AppState, token: &str, purpose: TokenPurpose) -> std::result::Result<UserToken, ApiResponse> {
+    UserToken::consume(&app_state.pool, token, purpose)
+        .await
+        .map_err(|e| {
+            error!("Error consuming {} token: {}", purpose, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+        })?
+        .ok_or_else(|| ApiResponse::new(StatusCode::BAD_REQUEST, "Invalid or expired token", Data::None))
+}
+
+/// Emite un token para el usuario y le envía por correo el enlace que lo usa.
+async fn send_token(app_state: &AppState, user: &User, purpose: TokenPurpose) -> std::result::Result<(), String> {
+    let (user_token, token) = UserToken::create(&app_state.pool, user.id, purpose)
+        .await
+        .map_err(|e| e.to_string())?;
+    let app_url = std::env::var("APP_URL").unwrap_or(DEFAULT_APP_URL.to_string());
+    let hours = purpose.ttl().num_hours();
+    let (subject, body) = match purpose {
+        TokenPurpose::Reset => (
+            "Restablecer la contraseña",
+            format!(
+                "Hola {},\n\nPara elegir una contraseña nueva abre este enlace, válido durante {} horas:\n\n{}/reset-password?token={}\n\nSi no lo has pedido tú, ignora este correo.\n",
+                user.username, hours, app_url.trim_end_matches('/'), token
+            ),
+        ),
+        TokenPurpose::Verify => (
+            "Verifica tu email",
+            format!(
+                "Hola {},\n\nPara confirmar tu email abre este enlace, válido durante {} horas:\n\n{}/verify-email?token={}\n",
+                user.username, hours, app_url.trim_end_matches('/'), token
+            ),
+        ),
+    };
+    let mail = Mail { to: user.email.clone(), subject: subject.to_string(), body };
+    mailer::deliver(app_state.mailer.clone(), mail).await.map_err(|e| e.to_string())?;
+    debug!("Sent {} token {} to user {}", purpose, user_token.id, user.id);
+    Ok(())
+}
+
+/// Cierra la sesión del token recibido, si lo hay, y borra la cookie.
+pub async fn logout(
+    State(app_state): State<Arc<AppState>>,
+    current: std::result::Result<CurrentUser, ApiResponse>,
+) -> impl IntoResponse {
     debug!("Logout");
-    let cookie = Cookie::build(("token", ""))
+    if let Some((sid, current)) = current.ok().and_then(|current| Some((current.session_id()?, current))) {
+        match Session::revoke(&app_state.pool, sid, current.user.id).await {
+            Ok(_) => debug!("Session {} revoked", sid),
+            Err(e) => error!("Error revoking session {}: {}", sid, e),
+        }
+    }
+    let cookie = Cookie::build((TOKEN_COOKIE, ""))
         .path("/")
         .max_age(cookie::time::Duration::ZERO)
         .same_site(SameSite::Lax)
@@ -124,6 +514,52 @@ pub async fn logout() -> impl IntoResponse {
         .unwrap()
 }
 
+/// Devuelve el usuario autenticado.
+pub async fn me(current: CurrentUser) -> impl IntoResponse {
+    ApiResponse::new(
+        StatusCode::OK,
+        "Current user",
+        Data::Some(serde_json::to_value(current.user.as_ref()).unwrap()),
+    )
+}
+
+/// Sesiones activas (dispositivos) del usuario autenticado.
+pub async fn read_sessions(State(app_state): State<Arc<AppState>>, current: CurrentUser) -> impl IntoResponse {
+    match Session::read_active_by_user(&app_state.pool, current.user.id).await {
+        Ok(sessions) => {
+            let mut value = serde_json::to_value(sessions).unwrap();
+            // Marca la sesión de la petición
+            for session in value.as_array_mut().into_iter().flatten() {
+                session["current"] = serde_json::Value::Bool(current.session_id().is_some_and(|sid| session["id"] == sid));
+            }
+            ApiResponse::new(StatusCode::OK, "Sessions", Data::Some(value))
+        }
+        Err(e) => {
+            error!("Error reading sessions: {:?}", e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &format!("Error reading sessions: {}", e), Data::None)
+        }
+    }
+}
+
+/// Revoca una sesión del usuario autenticado (cierra la sesión en ese dispositivo).
+pub async fn revoke_session(
+    State(app_state): State<Arc<AppState>>,
+    current: CurrentUser,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    match Session::revoke(&app_state.pool, id, current.user.id).await {
+        Ok(Some(session)) => {
+            debug!("Session {} revoked by user {}", session.id, current.user.id);
+            ApiResponse::new(StatusCode::OK, "Session revoked", Data::Some(serde_json::to_value(session).unwrap()))
+        }
+        Ok(None) => ApiResponse::new(StatusCode::NOT_FOUND, "Session not found", Data::None),
+        Err(e) => {
+            error!("Error revoking session {}: {:?}", id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &format!("Error revoking session: {}", e), Data::None)
+        }
+    }
+}
+
 pub async fn read(
     State(app_state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
@@ -168,3 +604,21 @@ pub async fn get_role(
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_ip() {
+        let addr = Some(SocketAddr::from(([10, 0, 0, 1], 4000)));
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "1.1.1.1, 2.2.2.2, 3.3.3.3".parse().unwrap());
+        assert_eq!(client_ip(&headers, addr, 0).as_deref(), Some("10.0.0.1"));
+        // El cliente puede añadir entradas por la izquierda, no por la derecha
+        assert_eq!(client_ip(&headers, addr, 1).as_deref(), Some("3.3.3.3"));
+        assert_eq!(client_ip(&headers, addr, 2).as_deref(), Some("2.2.2.2"));
+        assert_eq!(client_ip(&headers, addr, 5).as_deref(), Some("1.1.1.1"));
+        assert_eq!(client_ip(&HeaderMap::new(), addr, 1).as_deref(), Some("10.0.0.1"));
+    }
+}
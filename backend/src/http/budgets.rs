@@ -0,0 +1,562 @@
+use axum::{
+    extract::{
+        rejection::RawPathParamsRejection,
+        Path,
+        Query,
+        RawPathParams,
+        Request,
+        State,
+    },
+    middleware::Next,
+    routing,
+    Extension,
+    Json,
+    Router,
+    response::{IntoResponse, Response},
+    http::{
+        header::{
+            CONTENT_DISPOSITION,
+            CONTENT_TYPE,
+        },
+        HeaderMap,
+        HeaderValue,
+        StatusCode,
+    },
+};
+use serde::Deserialize;
+use crate::{
+    bc3,
+    budget_tree,
+    compare,
+    http::{middleware::CurrentUser, permissions::Permissions},
+    pdf,
+    price_list,
+    models::{
+        Data,
+        ApiResponse,
+        AppState,
+        Budget,
+        BudgetStatus,
+        BudgetStatusChange,
+        CloneBudget,
+        CustomResponse,
+    },
+    pricing::PricingError,
+    summary::{self, Rounding, SummaryOptions},
+    version_migration,
+};
+use std::sync::Arc;
+use tracing::{debug, error};
+
+const BC3_CONTENT_TYPE: &str = "application/octet-stream";
+const PDF_CONTENT_TYPE: &str = "application/pdf";
+const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/compare", routing::get(compare_budgets))
+        .route("/{id}/bc3", routing::get(export_bc3))
+        .route("/{id}/pdf", routing::get(export_pdf))
+        .route("/{id}/price-list", routing::get(read_price_list))
+        .route("/{id}/price-list/{number}", routing::get(export_price_list))
+        .route("/{id}/summary", routing::get(read_summary))
+        .route("/{id}/tree", routing::get(read_tree))
+        .route("/{id}/submit", routing::post(submit))
+        .route("/{id}/history", routing::get(read_history))
+        .route("/{id}/clone", routing::post(clone_budget))
+        .route("/{id}/migrate-version", routing::get(preview_version_migration).post(migrate_version))
+}
+
+/// Acciones de revisión del flujo de aprobación, reservadas a los usuarios
+/// con rol `master` (ver `http::api_router`).
+pub fn review_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/{id}/approve", routing::post(approve))
+        .route("/{id}/reject", routing::post(reject))
+        .route("/{id}/archive", routing::post(archive))
+}
+
+/// Middleware que limita las rutas `/{id}/...` a los miembros del proyecto del
+/// presupuesto cuyo rol en el proyecto llega al nivel que exige el método.
+/// Para los demás el presupuesto no existe.
+pub async fn require_member(
+    State((app_state, permissions)): State<(Arc<AppState>, Permissions)>,
+    current: Option<Extension<CurrentUser>>,
+    params: Result<RawPathParams, RawPathParamsRejection>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let id = params
+        .ok()
+        .and_then(|params| params.iter().find(|(key, _)| *key == "id").and_then(|(_, value)| value.parse::<i32>().ok()));
+    let scope = current.and_then(|Extension(current)| current.member_scope());
+    let (Some(id), Some(scope)) = (id, scope) else {
+        return next.run(request).await;
+    };
+    let scope = scope.with_level(permissions.required(request.method()));
+    let project_id = match Budget::read_by_id(&app_state.pool, id).await {
+        Ok(Some(budget)) => budget.project_id,
+        Ok(None) => return next.run(request).await,
+        Err(e) => {
+            error!("Error reading budget {}: {}", id, e);
+            return ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None).into_response();
+        }
+    };
+    match scope.allows(&app_state.pool, project_id).await {
+        Ok(true) => next.run(request).await,
+        Ok(false) => {
+            debug!("Project {} is out of scope {:?}", project_id, scope);
+            ApiResponse::new(StatusCode::NOT_FOUND, "Budget not found", Data::None).into_response()
+        }
+        Err(e) => {
+            error!("Error reading members of project {}: {}", project_id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None).into_response()
+        }
+    }
+}
+
+/// Cabeceras de un fichero descargable. El nombre se limita a caracteres ASCII seguros.
+fn file_headers(content_type: &'static str, disposition: &str, filename: &str) -> HeaderMap {
+    let filename = filename.replace(|c: char| !c.is_ascii_alphanumeric() && !matches!(c, '-' | '_' | '.'), "_");
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    if let Ok(value) = HeaderValue::from_str(&format!("{}; filename=\"{}\"", disposition, filename)) {
+        headers.insert(CONTENT_DISPOSITION, value);
+    }
+    headers
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Pdf,
+    Csv,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CompareFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompareParams {
+    pub from: i32,
+    pub to: i32,
+    #[serde(default)]
+    pub format: CompareFormat,
+    // Incluir también las líneas sin cambios
+    #[serde(default)]
+    pub unchanged: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VersionMigrationParams {
+    pub version_id: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SummaryParams {
+    pub decimals: Option<i64>,
+    pub rounding: Option<Rounding>,
+}
+
+impl SummaryParams {
+    fn options(&self) -> Option<SummaryOptions> {
+        let defaults = SummaryOptions::default();
+        let decimals = self.decimals.unwrap_or(defaults.decimals);
+        (0..=summary::MAX_DECIMALS).contains(&decimals).then(|| SummaryOptions {
+            decimals,
+            rounding: self.rounding.unwrap_or(defaults.rounding),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TreeParams {
+    // Niveles que se devuelven por debajo de los elementos raíz
+    pub depth: Option<u32>,
+}
+
+/// Descarga el presupuesto como fichero FIEBDC-3 (BC3) codificado en ANSI.
+pub async fn export_bc3(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    debug!("Exporting budget {} to BC3", id);
+    match bc3::export_budget(&app_state.pool, id).await {
+        Ok(Some(document)) => {
+            let code = document.concepts.first().map(|c| c.code.trim_end_matches('#').to_string()).unwrap_or_default();
+            let headers = file_headers(BC3_CONTENT_TYPE, "attachment", &format!("{}.bc3", code));
+            CustomResponse::file(headers, bc3::encode(&bc3::write(&document)))
+        }
+        Ok(None) => ApiResponse::new(StatusCode::NOT_FOUND, "Budget not found", Data::None).into(),
+        Err(e) => {
+            error!("Error exporting budget {} to BC3: {}", id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None).into()
+        }
+    }
+}
+
+/// Documento imprimible del presupuesto en PDF.
+pub async fn export_pdf(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    debug!("Rendering budget {} as PDF", id);
+    let code = match Budget::read_by_id(&app_state.pool, id).await {
+        Ok(Some(budget)) => budget.code,
+        Ok(None) => return ApiResponse::new(StatusCode::NOT_FOUND, "Budget not found", Data::None).into(),
+        Err(e) => {
+            error!("Error reading budget {}: {}", id, e);
+            return ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None).into();
+        }
+    };
+    match pdf::render_budget(&app_state.pool, id).await {
+        Ok(Some(bytes)) => {
+            CustomResponse::pdf(file_headers(PDF_CONTENT_TYPE, "inline", &format!("{}.pdf", code)), bytes)
+        }
+        Ok(None) => ApiResponse::new(StatusCode::NOT_FOUND, "Budget not found", Data::None).into(),
+        Err(PricingError::Database(e)) => {
+            error!("Error rendering budget {} as PDF: {}", id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None).into()
+        }
+        Err(e) => ApiResponse::new(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string(), Data::None).into(),
+    }
+}
+
+/// Importe de cada línea, subtotales por capítulo y PEM del presupuesto.
+pub async fn read_summary(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Query(params): Query<SummaryParams>,
+) -> impl IntoResponse {
+    let Some(options) = params.options() else {
+        return ApiResponse::new(
+            StatusCode::BAD_REQUEST,
+            &format!("Decimals must be between 0 and {}", summary::MAX_DECIMALS),
+            Data::None,
+        );
+    };
+    debug!("Summarizing budget {} with {:?}", id, options);
+    match summary::summarize(&app_state.pool, id, options).await {
+        Ok(Some(summary)) => ApiResponse::new(
+            StatusCode::OK,
+            "Budget summary",
+            Data::Some(serde_json::to_value(summary).unwrap()),
+        ),
+        Ok(None) => ApiResponse::new(StatusCode::NOT_FOUND, "Budget not found", Data::None),
+        Err(PricingError::Database(e)) => {
+            error!("Error summarizing budget {}: {}", id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+        }
+        Err(e) => ApiResponse::new(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string(), Data::None),
+    }
+}
+
+/// Árbol de capítulos y líneas del presupuesto con las cantidades y los
+/// importes acumulados en cada nodo.
+pub async fn read_tree(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Query(params): Query<TreeParams>,
+) -> impl IntoResponse {
+    let max_depth = params.depth.map(|depth| i32::try_from(depth).unwrap_or(i32::MAX));
+    match budget_tree::tree(&app_state.pool, id, max_depth).await {
+        Ok(Some(tree)) => ApiResponse::new(
+            StatusCode::OK,
+            "Budget tree",
+            Data::Some(serde_json::to_value(tree).unwrap()),
+        ),
+        Ok(None) => ApiResponse::new(StatusCode::NOT_FOUND, "Budget not found", Data::None),
+        Err(PricingError::Database(e)) => {
+            error!("Error reading tree of budget {}: {}", id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+        }
+        Err(e) => ApiResponse::new(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string(), Data::None),
+    }
+}
+
+/// Cuadros de precios nº 1 y nº 2 del presupuesto.
+pub async fn read_price_list(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    debug!("Computing price list of budget {}", id);
+    match price_list::price_list(&app_state.pool, id).await {
+        Ok(Some(list)) => ApiResponse::new(
+            StatusCode::OK,
+            "Price list",
+            Data::Some(serde_json::to_value(list).unwrap()),
+        ),
+        Ok(None) => ApiResponse::new(StatusCode::NOT_FOUND, "Budget not found", Data::None),
+        Err(PricingError::Database(e)) => {
+            error!("Error computing price list of budget {}: {}", id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+        }
+        Err(e) => ApiResponse::new(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string(), Data::None),
+    }
+}
+
+/// Descarga el cuadro de precios nº 1 o nº 2 en PDF o CSV.
+pub async fn export_price_list(
+    State(app_state): State<Arc<AppState>>,
+    Path((id, number)): Path<(i32, u8)>,
+    Query(params): Query<ExportParams>,
+) -> impl IntoResponse {
+    if !matches!(number, 1 | 2) {
+        return ApiResponse::new(StatusCode::NOT_FOUND, "Price list not found", Data::None).into();
+    }
+    debug!("Exporting price list {} of budget {} as {:?}", number, id, params.format);
+    match price_list::price_list(&app_state.pool, id).await {
+        Ok(Some(list)) => {
+            let filename = format!("{}-cuadro-precios-{}", list.code, number);
+            match (params.format, number) {
+                (ExportFormat::Pdf, 1) => CustomResponse::pdf(
+                    file_headers(PDF_CONTENT_TYPE, "inline", &format!("{}.pdf", filename)),
+                    pdf::render_first(&list),
+                ),
+                (ExportFormat::Pdf, _) => CustomResponse::pdf(
+                    file_headers(PDF_CONTENT_TYPE, "inline", &format!("{}.pdf", filename)),
+                    pdf::render_second(&list),
+                ),
+                (ExportFormat::Csv, 1) => CustomResponse::file(
+                    file_headers(CSV_CONTENT_TYPE, "attachment", &format!("{}.csv", filename)),
+                    list.first_csv().into_bytes(),
+                ),
+                (ExportFormat::Csv, _) => CustomResponse::file(
+                    file_headers(CSV_CONTENT_TYPE, "attachment", &format!("{}.csv", filename)),
+                    list.second_csv().into_bytes(),
+                ),
+            }
+        }
+        Ok(None) => ApiResponse::new(StatusCode::NOT_FOUND, "Budget not found", Data::None).into(),
+        Err(PricingError::Database(e)) => {
+            error!("Error exporting price list of budget {}: {}", id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None).into()
+        }
+        Err(e) => ApiResponse::new(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string(), Data::None).into(),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TransitionRequest {
+    pub comment: Option<String>,
+}
+
+/// Cambia el estado del presupuesto y anota quién lo hizo. El cuerpo con el
+/// comentario es opcional salvo al rechazar.
+async fn transition(
+    app_state: &AppState,
+    current: Option<Extension<CurrentUser>>,
+    id: i32,
+    to: BudgetStatus,
+    request: Option<Json<TransitionRequest>>,
+) -> ApiResponse {
+    let user_id = current.map(|Extension(current)| current.user.id);
+    let Json(request) = request.unwrap_or_default();
+    match Budget::transition(&app_state.pool, id, to, user_id, request.comment).await {
+        Ok(budget) => ApiResponse::new(
+            StatusCode::OK,
+            &format!("Budget {}", to),
+            Data::Some(serde_json::to_value(budget).unwrap()),
+        ),
+        Err(e) => {
+            debug!("Budget {} cannot change to {}: {}", id, to, e);
+            ApiResponse::from_error(&e)
+        }
+    }
+}
+
+/// Envía el presupuesto a revisión: deja de poder modificarse.
+pub async fn submit(
+    State(app_state): State<Arc<AppState>>,
+    current: Option<Extension<CurrentUser>>,
+    Path(id): Path<i32>,
+    request: Option<Json<TransitionRequest>>,
+) -> impl IntoResponse {
+    transition(&app_state, current, id, BudgetStatus::Submitted, request).await
+}
+
+/// Aprueba un presupuesto enviado.
+pub async fn approve(
+    State(app_state): State<Arc<AppState>>,
+    current: Option<Extension<CurrentUser>>,
+    Path(id): Path<i32>,
+    request: Option<Json<TransitionRequest>>,
+) -> impl IntoResponse {
+    transition(&app_state, current, id, BudgetStatus::Approved, request).await
+}
+
+/// Rechaza un presupuesto enviado con el motivo en `comment`. Vuelve a
+/// poder modificarse.
+pub async fn reject(
+    State(app_state): State<Arc<AppState>>,
+    current: Option<Extension<CurrentUser>>,
+    Path(id): Path<i32>,
+    request: Option<Json<TransitionRequest>>,
+) -> impl IntoResponse {
+    transition(&app_state, current, id, BudgetStatus::Rejected, request).await
+}
+
+/// Archiva el presupuesto. Es definitivo.
+pub async fn archive(
+    State(app_state): State<Arc<AppState>>,
+    current: Option<Extension<CurrentUser>>,
+    Path(id): Path<i32>,
+    request: Option<Json<TransitionRequest>>,
+) -> impl IntoResponse {
+    transition(&app_state, current, id, BudgetStatus::Archived, request).await
+}
+
+/// Historial de estados del presupuesto, del más antiguo al más reciente.
+pub async fn read_history(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    match Budget::read_by_id(&app_state.pool, id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return ApiResponse::new(StatusCode::NOT_FOUND, "Budget not found", Data::None),
+        Err(e) => {
+            error!("Error reading budget {}: {}", id, e);
+            return ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None);
+        }
+    }
+    match BudgetStatusChange::read_by_budget(&app_state.pool, id).await {
+        Ok(changes) => ApiResponse::new(
+            StatusCode::OK,
+            "Budget history",
+            Data::Some(serde_json::to_value(changes).unwrap()),
+        ),
+        Err(e) => {
+            error!("Error reading history of budget {}: {}", id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+        }
+    }
+}
+
+/// Copia el presupuesto, con sus elementos y mediciones, como nueva versión
+/// del proyecto. El cuerpo con `code` y `name` es opcional.
+pub async fn clone_budget(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    request: Option<Json<CloneBudget>>,
+) -> impl IntoResponse {
+    let Json(item) = request.unwrap_or_default();
+    match Budget::clone_version(&app_state.pool, id, item).await {
+        Ok(budget) => ApiResponse::new(
+            StatusCode::CREATED,
+            "Budget cloned",
+            Data::Some(serde_json::to_value(budget).unwrap()),
+        ),
+        Err(e) => {
+            error!("Error cloning budget {}: {}", id, e);
+            ApiResponse::from_error(&e)
+        }
+    }
+}
+
+/// Diferencias entre dos presupuestos (`from` y `to`): capítulos, líneas
+/// añadidas, eliminadas o modificadas y totales, en JSON o CSV. Por defecto
+/// se omiten las líneas sin cambios.
+pub async fn compare_budgets(
+    State(app_state): State<Arc<AppState>>,
+    current: Option<Extension<CurrentUser>>,
+    Query(params): Query<CompareParams>,
+) -> impl IntoResponse {
+    // La ruta no lleva `{id}`: `require_member` no comprueba los presupuestos
+    if let Some(scope) = current.and_then(|Extension(current)| current.member_scope()) {
+        for id in [params.from, params.to] {
+            let allowed = match Budget::read_by_id(&app_state.pool, id).await {
+                Ok(Some(budget)) => scope.allows(&app_state.pool, budget.project_id).await,
+                Ok(None) => Ok(true),
+                Err(e) => Err(e),
+            };
+            match allowed {
+                Ok(true) => {}
+                Ok(false) => return ApiResponse::new(StatusCode::NOT_FOUND, "Budget not found", Data::None).into(),
+                Err(e) => {
+                    error!("Error checking access to budget {}: {}", id, e);
+                    return ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None).into();
+                }
+            }
+        }
+    }
+    debug!("Comparing budget {} with {} as {:?}", params.from, params.to, params.format);
+    match compare::compare(&app_state.pool, params.from, params.to, SummaryOptions::default()).await {
+        Ok(Some(mut comparison)) => {
+            if !params.unchanged {
+                comparison.retain_changed();
+            }
+            match params.format {
+                CompareFormat::Json => ApiResponse::new(
+                    StatusCode::OK,
+                    "Budget comparison",
+                    Data::Some(serde_json::to_value(comparison).unwrap()),
+                )
+                .into(),
+                CompareFormat::Csv => {
+                    let filename = format!("{}-{}.csv", comparison.from.code, comparison.to.code);
+                    CustomResponse::file(file_headers(CSV_CONTENT_TYPE, "attachment", &filename), comparison.csv().into_bytes())
+                }
+            }
+        }
+        Ok(None) => ApiResponse::new(StatusCode::NOT_FOUND, "Budget not found", Data::None).into(),
+        Err(PricingError::Database(e)) => {
+            error!("Error comparing budget {} with {}: {}", params.from, params.to, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None).into()
+        }
+        Err(e) => ApiResponse::new(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string(), Data::None).into(),
+    }
+}
+
+/// Previsualiza la migración del presupuesto a otra versión del catálogo:
+/// precios sin equivalente e impacto en los importes. No cambia nada.
+pub async fn preview_version_migration(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Query(params): Query<VersionMigrationParams>,
+) -> impl IntoResponse {
+    match version_migration::preview(&app_state.pool, id, params.version_id).await {
+        Ok(Some(migration)) => ApiResponse::new(
+            StatusCode::OK,
+            "Version migration preview",
+            Data::Some(serde_json::to_value(migration).unwrap()),
+        ),
+        Ok(None) => ApiResponse::new(StatusCode::NOT_FOUND, "Budget not found", Data::None),
+        Err(e) => {
+            debug!("Error previewing migration of budget {} to version {}: {}", id, params.version_id, e);
+            ApiResponse::from_error(&e)
+        }
+    }
+}
+
+/// Migra el presupuesto a otra versión del catálogo. Si algún precio no
+/// tiene equivalente responde con un 422 y la previsualización en `data`.
+pub async fn migrate_version(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(params): Json<VersionMigrationParams>,
+) -> impl IntoResponse {
+    match version_migration::apply(&app_state.pool, id, params.version_id).await {
+        Ok(Some(migration)) => ApiResponse::new(
+            StatusCode::OK,
+            "Budget migrated",
+            Data::Some(serde_json::to_value(migration).unwrap()),
+        ),
+        Ok(None) => ApiResponse::new(StatusCode::NOT_FOUND, "Budget not found", Data::None),
+        Err(e) => {
+            debug!("Error migrating budget {} to version {}: {}", id, params.version_id, e);
+            ApiResponse::from_error(&e)
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Autenticación de las peticiones con el JWT que emite `auth::login`, enviado
+//! como `Authorization: Bearer <token>` o en la cookie `token`, o con una API
+//! key (`Authorization: Bearer pk_...` o `X-Api-Key: pk_...`).
+use std::sync::Arc;
+use axum::{
+    extract::{FromRequestParts, Request},
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use axum_extra::extract::cookie::CookieJar;
+use jsonwebtoken::{decode, errors::ErrorKind, Algorithm, DecodingKey, Validation};
+use tracing::{debug, error};
+
+use crate::{
+    http::permissions::Level,
+    models::{ApiKey, ApiKeyScope, ApiResponse, AppState, Data, MemberScope, Role, Session, TokenClaims, User},
+};
+
+pub const TOKEN_COOKIE: &str = "token";
+pub const API_KEY_HEADER: &str = "x-api-key";
+const BEARER: &str = "bearer ";
+
+/// Usuario autenticado de la petición. Como extractor valida el token; tras
+/// `require_auth` lo toma de las extensiones de la petición.
+///
+/// El nivel se obtiene del rol actual del usuario en la base de datos y no del
+/// token, para que un cambio de rol tenga efecto sin esperar a que caduque.
+#[derive(Debug, Clone)]
+pub struct CurrentUser {
+    pub user: Arc<User>,
+    pub credential: Credential,
+    // Nivel del rol; `None` si no es uno de los sembrados
+    pub level: Option<Level>,
+}
+
+/// Con qué se ha autenticado la petición.
+#[derive(Debug, Clone)]
+pub enum Credential {
+    Token(TokenClaims),
+    ApiKey(Arc<ApiKey>),
+}
+
+impl FromRequestParts<Arc<AppState>> for CurrentUser {
+    type Rejection = ApiResponse;
+
+    async fn from_request_parts(parts: &mut Parts, app_state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        if let Some(current) = parts.extensions.get::<Self>() {
+            return Ok(current.clone());
+        }
+        let token = token(parts).ok_or_else(|| unauthorized("Missing token"))?;
+        let (user, credential) = if token.starts_with(ApiKey::KEY_PREFIX) {
+            authenticate_api_key(app_state, &token).await?
+        } else {
+            authenticate_token(app_state, &token).await?
+        };
+        let level = Role::read_by_id(&app_state.pool, user.role_id)
+            .await
+            .map_err(|e| internal_error(&user.email, e))?
+            .and_then(|role| role.name.parse().ok());
+        Ok(Self { user: Arc::new(user), credential, level })
+    }
+}
+
+/// Usuario de un JWT cuya sesión sigue abierta.
+async fn authenticate_token(app_state: &AppState, token: &str) -> Result<(User, Credential), ApiResponse> {
+    let claims = validate(token, &app_state.secret)?;
+    let user = User::read_by_email(&app_state.pool, claims.sub.clone())
+        .await
+        .map_err(|e| internal_error(&claims.sub, e))?
+        .filter(|user| user.is_active)
+        .ok_or_else(|| unauthorized("Invalid token"))?;
+    // La sesión del token puede haberse cerrado (logout, cambio de contraseña)
+    if !Session::is_active(&app_state.pool, claims.sid, user.id)
+        .await
+        .map_err(|e| internal_error(&claims.sub, e))?
+    {
+        return Err(unauthorized("Session revoked"));
+    }
+    Ok((user, Credential::Token(claims)))
+}
+
+/// Usuario de una API key activa. Anota su uso.
+async fn authenticate_api_key(app_state: &AppState, key: &str) -> Result<(User, Credential), ApiResponse> {
+    let api_key = ApiKey::read_by_key(&app_state.pool, key)
+        .await
+        .map_err(|e| internal_error("API key", e))?
+        .ok_or_else(|| unauthorized("Invalid API key"))?;
+    let user = User::read_by_id(&app_state.pool, api_key.user_id)
+        .await
+        .map_err(|e| internal_error("API key", e))?
+        .filter(|user| user.is_active)
+        .ok_or_else(|| unauthorized("Invalid API key"))?;
+    if let Err(e) = ApiKey::touch(&app_state.pool, api_key.id).await {
+        error!("Error updating last use of API key {}: {}", api_key.id, e);
+    }
+    Ok((user, Credential::ApiKey(Arc::new(api_key))))
+}
+
+impl CurrentUser {
+    /// Proyectos a los que se limitan las lecturas de proyectos y
+    /// presupuestos: los del usuario, salvo para los administradores, y solo
+    /// los de la API key si está limitada. `None` si puede verlo todo.
+    pub fn member_scope(&self) -> Option<MemberScope> {
+        let user_id = (self.level != Some(Level::Admin)).then_some(self.user.id);
+        let project_ids = match &self.credential {
+            Credential::ApiKey(api_key) => api_key.project_ids.clone(),
+            Credential::Token(_) => None,
+        };
+        (user_id.is_some() || project_ids.is_some()).then_some(MemberScope { user_id, project_ids, level: None })
+    }
+
+    /// Nivel con el que se autorizan las peticiones: el del rol, salvo con
+    /// una API key de solo lectura, que no da más que el de lector, o con una
+    /// limitada a unos proyectos, que no llega a las rutas de administración
+    /// (usuarios, API keys, miembros...).
+    pub fn effective_level(&self) -> Option<Level> {
+        match &self.credential {
+            Credential::ApiKey(api_key) if api_key.scope == ApiKeyScope::Read => {
+                self.level.map(|level| level.min(Level::Reader))
+            }
+            Credential::ApiKey(api_key) if api_key.project_ids.is_some() => {
+                self.level.map(|level| level.min(Level::Master))
+            }
+            _ => self.level,
+        }
+    }
+
+    /// Sesión del JWT; `None` con una API key.
+    pub fn session_id(&self) -> Option<i32> {
+        match &self.credential {
+            Credential::Token(claims) => Some(claims.sid),
+            Credential::ApiKey(_) => None,
+        }
+    }
+}
+
+/// Middleware que rechaza con un 401 las peticiones sin un token válido y deja
+/// el `CurrentUser` disponible para los handlers.
+pub async fn require_auth(current: CurrentUser, mut request: Request, next: Next) -> Response {
+    debug!("Authenticated {} ({:?})", current.user.email, current.level);
+    request.extensions_mut().insert(current);
+    next.run(request).await
+}
+
+/// Token de la cabecera `Authorization`, de `X-Api-Key` o, si no las hay, de
+/// la cookie.
+fn token(parts: &Parts) -> Option<String> {
+    if let Some(key) = parts.headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok()) {
+        return Some(key.trim().to_string()).filter(|key| !key.is_empty());
+    }
+    let header = parts.headers.get(AUTHORIZATION).and_then(|value| value.to_str().ok());
+    if let Some(value) = header {
+        return value
+            .get(..BEARER.len())
+            .filter(|scheme| scheme.eq_ignore_ascii_case(BEARER))
+            .map(|_| value[BEARER.len()..].trim().to_string())
+            .filter(|token| !token.is_empty());
+    }
+    CookieJar::from_headers(&parts.headers)
+        .get(TOKEN_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+/// Comprueba la firma y la caducidad del token y devuelve sus claims.
+pub fn validate(token: &str, secret: &str) -> Result<TokenClaims, ApiResponse> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.leeway = 0;
+    decode::<TokenClaims>(token, &DecodingKey::from_secret(secret.as_bytes()), &validation)
+        .map(|data| data.claims)
+        .map_err(|e| match e.kind() {
+            ErrorKind::ExpiredSignature => unauthorized("Token expired"),
+            _ => unauthorized("Invalid token"),
+        })
+}
+
+fn internal_error(email: &str, e: sqlx::Error) -> ApiResponse {
+    error!("Error reading user {}: {}", email, e);
+    ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+}
+
+fn unauthorized(message: &str) -> ApiResponse {
+    ApiResponse::new(StatusCode::UNAUTHORIZED, message, Data::None)
+}
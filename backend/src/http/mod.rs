@@ -1,9 +1,76 @@
-use axum::http::StatusCode;
-use crate::models::{ApiResponse, Data};
+use std::sync::Arc;
+use axum::{http::StatusCode, middleware::from_fn_with_state, Router};
+use permissions::{Level, Permissions};
+use crate::models::{
+    ApiResponse,
+    AppState,
+    Budget,
+    Data,
+    Descomposition,
+    Element,
+    Measurement,
+    Price,
+    Project,
+    ProjectMember,
+    Role,
+    Unit,
+    User,
+    Version,
+};
 
 pub mod health;
 pub mod auth;
 pub mod stats;
+pub mod budgets;
+pub mod prices;
+pub mod users;
+pub mod versions;
+pub mod middleware;
+pub mod permissions;
+
+/// Rutas de la API. Todas exigen un token salvo `/health` y las de
+/// `auth::router` (login, renovación, logout y recuperación de la contraseña),
+/// y cada recurso exige además el rol de sus `PERMISSIONS`.
+/// Proyectos, presupuestos, elementos y mediciones se limitan a los proyectos
+/// de los que el usuario es miembro, y las escrituras, a aquellos en los que su
+/// rol en el proyecto llega al nivel exigido (ver `ProjectMember`).
+pub fn api_router(app_state: Arc<AppState>) -> Router {
+    let protected = Router::new()
+        .nest("/budgets", member_guard(&app_state, Budget::router().merge(budgets::router()), Budget::PERMISSIONS)
+            .merge(member_guard(&app_state, budgets::review_router(), Permissions::new(Level::Writer, Level::Master))))
+        .nest("/descompositions", guard(Descomposition::router(), Descomposition::PERMISSIONS))
+        .nest("/elements", guard(Element::router(), Element::PERMISSIONS))
+        .nest("/measurements", guard(Measurement::router(), Measurement::PERMISSIONS))
+        .nest("/prices", guard(Price::router().merge(prices::router()), Price::PERMISSIONS))
+        .nest("/projects", guard(Project::router(), Project::PERMISSIONS))
+        .nest("/project-members", guard(ProjectMember::router(), ProjectMember::PERMISSIONS))
+        .nest("/roles", guard(Role::router(), Role::PERMISSIONS))
+        .nest("/units", guard(Unit::router(), Unit::PERMISSIONS))
+        .nest("/users", guard(User::router().merge(users::router()), User::PERMISSIONS))
+        .nest("/versions", guard(Version::router().merge(versions::router()), Version::PERMISSIONS))
+        .nest("/stats", guard(stats::router(), Permissions::new(Level::Reader, Level::Reader)))
+        .nest("/auth", guard(auth::private_router(), Permissions::new(Level::Reader, Level::Reader))
+            .merge(guard(auth::admin_router(), Permissions::new(Level::Admin, Level::Admin))))
+        .route_layer(from_fn_with_state(app_state.clone(), middleware::require_auth));
+    Router::new()
+        .merge(protected)
+        .nest("/health", health::router())
+        .nest("/auth", auth::router())
+        .fallback(fallback_404)
+        .with_state(app_state)
+}
+
+/// Aplica a todas las rutas de `router` los permisos indicados.
+fn guard(router: Router<Arc<AppState>>, permissions: Permissions) -> Router<Arc<AppState>> {
+    router.route_layer(from_fn_with_state(permissions, permissions::authorize))
+}
+
+/// Como `guard`, y además limita las rutas `/{id}/...` de presupuestos a los
+/// miembros del proyecto con el mismo nivel en él (ver `budgets::require_member`).
+fn member_guard(app_state: &Arc<AppState>, router: Router<Arc<AppState>>, permissions: Permissions) -> Router<Arc<AppState>> {
+    // Primero el rol del usuario (403) y después el del proyecto (404)
+    guard(router.route_layer(from_fn_with_state((app_state.clone(), permissions), budgets::require_member)), permissions)
+}
 
 pub async fn fallback_404() -> impl axum::response::IntoResponse {
     ApiResponse::new(
@@ -0,0 +1,133 @@
+//! Autorización por rol. Los roles sembrados por la migración de `roles` son
+//! jerárquicos: cada uno puede hacer lo mismo que los inferiores.
+//!
+//! - `reader`: solo lectura (`GET`).
+//! - `writer`: presupuestos, elementos y mediciones.
+//! - `master`: catálogos de precios (`Price`, `Descomposition`, `Unit`, `Version`)
+//!   y revisión de presupuestos (aprobar, rechazar y archivar).
+//! - `admin`: usuarios y roles.
+//!
+//! Cada recurso declara el nivel que exige para leer y para escribir con
+//! `#[axum_crud(read = "...", write = "...")]`; ver `http::api_router`.
+use std::str::FromStr;
+use axum::{
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use tracing::debug;
+
+use crate::{
+    http::middleware::CurrentUser,
+    models::{ApiResponse, Data},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Reader,
+    Writer,
+    Master,
+    Admin,
+}
+
+impl Level {
+    /// Nombre del rol sembrado con este nivel.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Reader => "reader",
+            Self::Writer => "writer",
+            Self::Master => "master",
+            Self::Admin => "admin",
+        }
+    }
+
+    /// Nombres de los roles sembrados que llegan a este nivel.
+    pub fn role_names(self) -> Vec<String> {
+        [Self::Reader, Self::Writer, Self::Master, Self::Admin]
+            .into_iter()
+            .filter(|level| *level >= self)
+            .map(|level| level.name().to_string())
+            .collect()
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "reader" => Ok(Self::Reader),
+            "writer" => Ok(Self::Writer),
+            "master" => Ok(Self::Master),
+            "admin" => Ok(Self::Admin),
+            _ => Err(format!("Unknown role: {}", name)),
+        }
+    }
+}
+
+/// Niveles exigidos para leer (`GET`/`HEAD`) y para el resto de métodos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permissions {
+    pub read: Level,
+    pub write: Level,
+}
+
+impl Permissions {
+    pub const fn new(read: Level, write: Level) -> Self {
+        Self { read, write }
+    }
+
+    /// Nivel exigido para un método HTTP.
+    pub fn required(&self, method: &Method) -> Level {
+        if method == Method::GET || method == Method::HEAD {
+            self.read
+        } else {
+            self.write
+        }
+    }
+}
+
+/// Middleware que rechaza con un 403 las peticiones de usuarios sin el nivel
+/// exigido. Debe ir detrás de `middleware::require_auth`.
+pub async fn authorize(State(permissions): State<Permissions>, request: Request, next: Next) -> Response {
+    let Some(current) = request.extensions().get::<CurrentUser>() else {
+        return ApiResponse::new(StatusCode::UNAUTHORIZED, "Missing token", Data::None).into_response();
+    };
+    let required = permissions.required(request.method());
+    let level = current.effective_level();
+    if level.is_none_or(|level| level < required) {
+        debug!(
+            "Forbidden {} {} to {} ({:?} < {:?})",
+            request.method(),
+            request.uri(),
+            current.user.email,
+            level,
+            required,
+        );
+        return ApiResponse::new(StatusCode::FORBIDDEN, "Insufficient permissions", Data::None).into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        assert_eq!("master".parse::<Level>(), Ok(Level::Master));
+        assert!("SYSTEM_ADMIN".parse::<Level>().is_err());
+        assert!(Level::Admin > Level::Master && Level::Master > Level::Writer && Level::Writer > Level::Reader);
+        assert_eq!(Level::Master.role_names(), vec!["master", "admin"]);
+        assert_eq!(Level::Writer.name().parse::<Level>(), Ok(Level::Writer));
+
+        let permissions = Permissions::new(Level::Reader, Level::Master);
+        assert_eq!(permissions.required(&Method::GET), Level::Reader);
+        assert_eq!(permissions.required(&Method::HEAD), Level::Reader);
+        assert_eq!(permissions.required(&Method::POST), Level::Master);
+        assert_eq!(permissions.required(&Method::DELETE), Level::Master);
+    }
+}
@@ -0,0 +1,76 @@
+use axum::{
+    extract::{
+        Path,
+        Query,
+        State,
+    },
+    routing,
+    Router,
+    response::IntoResponse,
+    http::StatusCode,
+};
+use crate::{
+    models::{
+        Data,
+        ApiResponse,
+        AppState,
+    },
+    price_history,
+    pricing::{self, PricingError},
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{debug, error};
+
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/history", routing::get(read_history))
+        .route("/{id}/breakdown", routing::get(read_breakdown))
+}
+
+/// Devuelve el precio calculado y el árbol completo de su descomposición.
+pub async fn read_breakdown(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    debug!("Computing breakdown of price {}", id);
+    match pricing::breakdown(&app_state.pool, id).await {
+        Ok(Some(breakdown)) => ApiResponse::new(
+            StatusCode::OK,
+            "Price breakdown",
+            Data::Some(serde_json::to_value(breakdown).unwrap()),
+        ),
+        Ok(None) => ApiResponse::new(StatusCode::NOT_FOUND, "Price not found", Data::None),
+        Err(PricingError::Database(e)) => {
+            error!("Error computing breakdown of price {}: {}", id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+        }
+        Err(e) => ApiResponse::new(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string(), Data::None),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryParams {
+    pub code: String,
+}
+
+/// Precio guardado y calculado de un código en cada versión del catálogo, de
+/// la más antigua a la más reciente.
+pub async fn read_history(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<HistoryParams>,
+) -> impl IntoResponse {
+    match price_history::history(&app_state.pool, &params.code).await {
+        Ok(Some(history)) => ApiResponse::new(
+            StatusCode::OK,
+            "Price history",
+            Data::Some(serde_json::to_value(history).unwrap()),
+        ),
+        Ok(None) => ApiResponse::new(StatusCode::NOT_FOUND, "Price not found", Data::None),
+        Err(PricingError::Database(e)) => {
+            error!("Error reading history of price {}: {}", params.code, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+        }
+        Err(e) => ApiResponse::new(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string(), Data::None),
+    }
+}
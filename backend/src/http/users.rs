@@ -0,0 +1,164 @@
+use axum::{
+    extract::{
+        Path,
+        State,
+    },
+    Json,
+    routing,
+    Router,
+    response::IntoResponse,
+    http::StatusCode,
+};
+use crate::{
+    http::middleware::CurrentUser,
+    models::{
+        Data,
+        ApiResponse,
+        ApiKey,
+        AppState,
+        AuditAction,
+        AuditEntry,
+        LoginScope,
+        LoginThrottle,
+        NewApiKey,
+        NewAuditEntry,
+        User,
+    },
+};
+use std::sync::Arc;
+use tracing::{debug, error};
+
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/{id}/unlock", routing::post(unlock))
+        .route("/{id}/audit", routing::get(read_audit))
+        .route("/{id}/api-keys", routing::get(read_api_keys).post(create_api_key))
+        .route("/{id}/api-keys/{key_id}", routing::delete(revoke_api_key))
+}
+
+/// Desbloquea la cuenta de un usuario bloqueada por logins fallidos y pone a
+/// cero su contador de fallos.
+pub async fn unlock(
+    State(app_state): State<Arc<AppState>>,
+    current: Option<axum::Extension<CurrentUser>>,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let user = match User::read_by_id(&app_state.pool, id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return ApiResponse::new(StatusCode::NOT_FOUND, "User not found", Data::None),
+        Err(e) => {
+            error!("Error reading user {}: {}", id, e);
+            return ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None);
+        }
+    };
+    let was_locked = match LoginThrottle::clear(&app_state.pool, LoginScope::Account, &user.email.to_lowercase()).await {
+        Ok(was_locked) => was_locked,
+        Err(e) => {
+            error!("Error unlocking user {}: {}", id, e);
+            return ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None);
+        }
+    };
+    let actor_id = current.map(|axum::Extension(current)| current.user.id);
+    let entry = NewAuditEntry {
+        action: AuditAction::AccountUnlocked,
+        user_id: Some(user.id),
+        actor_id,
+        ip: None,
+        detail: if was_locked { "Locked account unlocked" } else { "Failed attempts reset" }.to_string(),
+    };
+    if let Err(e) = AuditEntry::create(&app_state.pool, entry).await {
+        error!("Error auditing unlock of user {}: {}", id, e);
+    }
+    debug!("User {} unlocked by {:?} (was locked: {})", id, actor_id, was_locked);
+    ApiResponse::new(
+        StatusCode::OK,
+        "User unlocked",
+        Data::Some(serde_json::json!({ "user_id": user.id, "was_locked": was_locked })),
+    )
+}
+
+/// Registro de auditoría de un usuario, de lo más reciente a lo más antiguo.
+pub async fn read_audit(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    match AuditEntry::read_by_user(&app_state.pool, id).await {
+        Ok(entries) => ApiResponse::new(
+            StatusCode::OK,
+            "Audit entries",
+            Data::Some(serde_json::to_value(entries).unwrap()),
+        ),
+        Err(e) => {
+            error!("Error reading audit of user {}: {}", id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+        }
+    }
+}
+
+/// API keys del usuario, incluidas las revocadas. Nunca se devuelve la key.
+pub async fn read_api_keys(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    match ApiKey::read_by_user(&app_state.pool, id).await {
+        Ok(api_keys) => ApiResponse::new(
+            StatusCode::OK,
+            "API keys",
+            Data::Some(serde_json::to_value(api_keys).unwrap()),
+        ),
+        Err(e) => {
+            error!("Error reading API keys of user {}: {}", id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+        }
+    }
+}
+
+/// Crea una API key para el usuario. La key solo se devuelve en esta
+/// respuesta.
+pub async fn create_api_key(
+    State(app_state): State<Arc<AppState>>,
+    current: Option<axum::Extension<CurrentUser>>,
+    Path(id): Path<i32>,
+    Json(item): Json<NewApiKey>,
+) -> impl IntoResponse {
+    match User::read_by_id(&app_state.pool, id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return ApiResponse::new(StatusCode::NOT_FOUND, "User not found", Data::None),
+        Err(e) => {
+            error!("Error reading user {}: {}", id, e);
+            return ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None);
+        }
+    }
+    let created_by = current.map(|axum::Extension(current)| current.user.id);
+    match ApiKey::create(&app_state.pool, id, created_by, item).await {
+        Ok((api_key, key)) => {
+            debug!("API key {} created for user {} by {:?}", api_key.id, id, created_by);
+            let mut value = serde_json::to_value(api_key).unwrap();
+            value["key"] = serde_json::Value::String(key);
+            ApiResponse::new(StatusCode::CREATED, "API key created", Data::Some(value))
+        }
+        Err(sqlx::Error::InvalidArgument(message)) => ApiResponse::new(StatusCode::BAD_REQUEST, &message, Data::None),
+        Err(e) => {
+            error!("Error creating API key for user {}: {}", id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+        }
+    }
+}
+
+/// Revoca una API key del usuario: deja de valer inmediatamente.
+pub async fn revoke_api_key(
+    State(app_state): State<Arc<AppState>>,
+    Path((id, key_id)): Path<(i32, i32)>,
+) -> impl IntoResponse {
+    match ApiKey::revoke(&app_state.pool, key_id, id).await {
+        Ok(Some(api_key)) => {
+            debug!("API key {} of user {} revoked", key_id, id);
+            ApiResponse::new(StatusCode::OK, "API key revoked", Data::Some(serde_json::to_value(api_key).unwrap()))
+        }
+        Ok(None) => ApiResponse::new(StatusCode::NOT_FOUND, "API key not found", Data::None),
+        Err(e) => {
+            error!("Error revoking API key {}: {}", key_id, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use axum::{
+    body::Bytes,
+    extract::{
+        DefaultBodyLimit,
+        Path,
+        Query,
+        State,
+    },
+    Json,
+    routing,
+    Router,
+    response::IntoResponse,
+    http::StatusCode,
+};
+use serde::Deserialize;
+use crate::{
+    bc3,
+    constants::MAX_BC3_SIZE,
+    models::{
+        Data,
+        ApiResponse,
+        AppState,
+    },
+    price_history,
+    pricing::PricingError,
+    version_clone::{self, CloneVersion},
+};
+use std::sync::Arc;
+use tracing::{debug, error};
+
+const DEFAULT_IMPORT_NAME: &str = "BC3 import";
+
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/import", routing::post(import_bc3).layer(DefaultBodyLimit::max(MAX_BC3_SIZE)))
+        .route("/compare", routing::get(compare_versions))
+        .route("/{id}/clone", routing::post(clone_version))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportParams {
+    pub name: Option<String>,
+}
+
+/// Importa un fichero BC3 (enviado como cuerpo de la petición) en una nueva versión.
+pub async fn import_bc3(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<ImportParams>,
+    body: Bytes,
+) -> impl IntoResponse {
+    let document = bc3::parse(&bc3::decode(&body));
+    let name = params
+        .name
+        .filter(|n| !n.trim().is_empty())
+        .or_else(|| document.header.as_ref().map(|h| h.label.clone()).filter(|l| !l.is_empty()))
+        .unwrap_or_else(|| DEFAULT_IMPORT_NAME.to_string());
+    debug!("Importing BC3 into version '{}'", name);
+    match bc3::import_catalog(&app_state.pool, &name, &document).await {
+        Ok(report) => ApiResponse::new(
+            StatusCode::CREATED,
+            "Catalog imported",
+            Data::Some(serde_json::to_value(report).unwrap()),
+        ),
+        Err(bc3::Bc3Error::Empty) => {
+            ApiResponse::new(StatusCode::BAD_REQUEST, &bc3::Bc3Error::Empty.to_string(), Data::None)
+        }
+        Err(e) => {
+            error!("Error importing BC3: {}", e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+        }
+    }
+}
+
+/// Copia la versión, con sus precios y descomposiciones, en una nueva versión.
+/// Opcionalmente aplica un porcentaje a los precios base.
+pub async fn clone_version(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(item): Json<CloneVersion>,
+) -> impl IntoResponse {
+    match version_clone::clone_version(&app_state.pool, id, item).await {
+        Ok(Some(clone)) => ApiResponse::new(
+            StatusCode::CREATED,
+            "Version cloned",
+            Data::Some(serde_json::to_value(clone).unwrap()),
+        ),
+        Ok(None) => ApiResponse::new(StatusCode::NOT_FOUND, "Version not found", Data::None),
+        Err(e) => {
+            error!("Error cloning version {}: {}", id, e);
+            ApiResponse::from_error(&e)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompareParams {
+    pub from: i32,
+    pub to: i32,
+    // Número máximo de precios que cambian
+    pub limit: Option<usize>,
+}
+
+/// Informe de la versión `to` frente a `from`: precios con mayor cambio
+/// porcentual, precios nuevos y precios eliminados.
+pub async fn compare_versions(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<CompareParams>,
+) -> impl IntoResponse {
+    match price_history::compare_versions(&app_state.pool, params.from, params.to, params.limit).await {
+        Ok(Some(comparison)) => ApiResponse::new(
+            StatusCode::OK,
+            "Version comparison",
+            Data::Some(serde_json::to_value(comparison).unwrap()),
+        ),
+        Ok(None) => ApiResponse::new(StatusCode::NOT_FOUND, "Version not found", Data::None),
+        Err(PricingError::Database(e)) => {
+            error!("Error comparing versions {} and {}: {}", params.from, params.to, e);
+            ApiResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string(), Data::None)
+        }
+        Err(e) => ApiResponse::new(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string(), Data::None),
+    }
+}
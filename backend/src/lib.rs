@@ -1,3 +1,17 @@
 pub mod models;
 pub mod http;
-pub mod constants;
\ No newline at end of file
+pub mod constants;
+pub mod bc3;
+pub mod budget_tree;
+pub mod compare;
+pub mod csv;
+pub mod formula;
+pub mod mailer;
+pub mod pdf;
+pub mod price_history;
+pub mod price_list;
+pub mod pricing;
+pub mod summary;
+pub mod version_clone;
+pub mod version_migration;
+pub mod words;
\ No newline at end of file
@@ -0,0 +1,159 @@
+//! Envío de correo: enlaces de recuperación de contraseña y de verificación
+//! del email. La implementación se elige al arrancar con `from_env`:
+//!
+//! - `SmtpMailer` si está definida `SMTP_HOST` (ver `smtp`).
+//! - `FileMailer` si está definida `MAIL_DIR`: deja cada correo en un fichero
+//!   `.eml` de ese directorio, útil en desarrollo y en los tests.
+//! - `LogMailer` en otro caso: solo escribe el correo en el log.
+use std::{
+    env::var,
+    fmt, fs, io,
+    path::PathBuf,
+    sync::Arc,
+};
+use tracing::{error, info};
+
+pub mod smtp;
+
+pub use smtp::{Security, SmtpMailer};
+
+/// Correo de texto plano para un único destinatario.
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub enum MailError {
+    Io(io::Error),
+    Tls(String),
+    // Respuesta inesperada del servidor SMTP
+    Smtp(String),
+    // Dirección que no se puede usar como destinatario (ver `check_address`)
+    InvalidAddress(String),
+}
+
+impl fmt::Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Mail I/O error: {}", e),
+            Self::Tls(e) => write!(f, "Mail TLS error: {}", e),
+            Self::Smtp(e) => write!(f, "SMTP error: {}", e),
+            Self::InvalidAddress(address) => write!(f, "Invalid mail address: {}", address),
+        }
+    }
+}
+
+impl std::error::Error for MailError {}
+
+impl From<io::Error> for MailError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Forma de enviar un correo. Es síncrona: desde código asíncrono se usa
+/// `deliver`.
+pub trait Mailer: Send + Sync {
+    fn send(&self, mail: &Mail) -> Result<(), MailError>;
+}
+
+/// Comprueba que la dirección se puede escribir tal cual en `RCPT TO:<...>` y
+/// en la cabecera `To`: con una `@` y sin `<`, `>`, saltos de línea ni otros
+/// caracteres de control, con los que se podrían añadir órdenes o cabeceras.
+pub fn check_address(address: &str) -> Result<(), MailError> {
+    if !address.contains('@') || address.chars().any(|c| c.is_control() || matches!(c, '<' | '>')) {
+        return Err(MailError::InvalidAddress(address.escape_debug().to_string()));
+    }
+    Ok(())
+}
+
+/// Envía el correo en un hilo aparte para no bloquear el runtime.
+pub async fn deliver(mailer: Arc<dyn Mailer>, mail: Mail) -> Result<(), MailError> {
+    check_address(&mail.to)?;
+    tokio::task::spawn_blocking(move || mailer.send(&mail))
+        .await
+        .map_err(|e| MailError::Io(io::Error::other(e)))?
+}
+
+/// Mailer configurado en el entorno (ver la documentación del módulo).
+pub fn from_env() -> Arc<dyn Mailer> {
+    match SmtpMailer::from_env() {
+        Ok(Some(mailer)) => {
+            info!("Mailer: SMTP {}:{}", mailer.host, mailer.port);
+            return Arc::new(mailer);
+        }
+        Ok(None) => {}
+        Err(e) => error!("Invalid SMTP configuration, falling back: {}", e),
+    }
+    if let Ok(dir) = var("MAIL_DIR") {
+        info!("Mailer: files in {}", dir);
+        return Arc::new(FileMailer::new(dir));
+    }
+    info!("Mailer: log");
+    Arc::new(LogMailer)
+}
+
+/// No envía nada: escribe el correo en el log.
+pub struct LogMailer;
+
+impl Mailer for LogMailer {
+    fn send(&self, mail: &Mail) -> Result<(), MailError> {
+        info!("Mail to {}: {}\n{}", mail.to, mail.subject, mail.body);
+        Ok(())
+    }
+}
+
+/// Guarda cada correo en un fichero `.eml` del directorio indicado.
+pub struct FileMailer {
+    pub dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, mail: &Mail) -> Result<(), MailError> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!(
+            "{}-{}.eml",
+            chrono::Utc::now().format("%Y%m%d%H%M%S%6f"),
+            uuid::Uuid::new_v4().simple()
+        ));
+        let content = format!(
+            "To: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=UTF-8\r\n\r\n{}\r\n",
+            mail.to,
+            mail.subject,
+            chrono::Utc::now().to_rfc2822(),
+            mail.body
+        );
+        fs::write(&path, content)?;
+        info!("Mail to {} saved in {}", mail.to, path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_mailer() {
+        let dir = std::env::temp_dir().join(format!("mailer-{}", uuid::Uuid::new_v4()));
+        let mailer = FileMailer::new(&dir);
+        mailer
+            .send(&Mail { to: "a@test.com".to_string(), subject: "Hola".to_string(), body: "Cuerpo".to_string() })
+            .unwrap();
+        let files: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+        assert_eq!(files.len(), 1);
+        let content = fs::read_to_string(&files[0]).unwrap();
+        assert!(content.starts_with("To: a@test.com\r\nSubject: Hola\r\n"));
+        assert!(content.ends_with("\r\n\r\nCuerpo\r\n"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,368 @@
+//! Cliente SMTP mínimo (RFC 5321): EHLO, STARTTLS o TLS implícito, AUTH PLAIN
+//! y un único destinatario por correo. Se configura con las variables
+//! `SMTP_HOST`, `SMTP_PORT`, `SMTP_USERNAME`, `SMTP_PASSWORD`, `SMTP_FROM` y
+//! `SMTP_SECURITY` (`none`, `starttls` o `tls`).
+use std::{
+    env::var,
+    io::{Read, Write},
+    net::TcpStream,
+    str::FromStr,
+    time::Duration,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use openssl::ssl::{SslConnector, SslMethod, SslStream};
+use tracing::debug;
+
+use super::{check_address, Mail, MailError, Mailer};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+// Longitud máxima de las líneas del cuerpo en base64
+const LINE_LENGTH: usize = 76;
+
+/// Cifrado de la conexión con el servidor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Security {
+    None,
+    StartTls,
+    Tls,
+}
+
+impl FromStr for Security {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "starttls" => Ok(Self::StartTls),
+            "tls" => Ok(Self::Tls),
+            _ => Err(format!("Unknown SMTP security: {}", name)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpMailer {
+    pub host: String,
+    pub port: u16,
+    pub security: Security,
+    // Usuario y contraseña; sin ellos no se autentica
+    pub credentials: Option<(String, String)>,
+    pub from: String,
+}
+
+impl SmtpMailer {
+    /// Mailer configurado en el entorno, o `None` si no hay `SMTP_HOST`.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(host) = var("SMTP_HOST") else {
+            return Ok(None);
+        };
+        let security = var("SMTP_SECURITY")
+            .map(|value| value.parse())
+            .unwrap_or(Ok(Security::StartTls))?;
+        let port = match var("SMTP_PORT") {
+            Ok(port) => port.parse().map_err(|_| format!("Invalid SMTP_PORT: {}", port))?,
+            Err(_) => match security {
+                Security::None => 25,
+                Security::StartTls => 587,
+                Security::Tls => 465,
+            },
+        };
+        let credentials = var("SMTP_USERNAME").ok().map(|username| {
+            (username, var("SMTP_PASSWORD").unwrap_or_default())
+        });
+        let from = var("SMTP_FROM").map_err(|_| "SMTP_FROM is mandatory with SMTP_HOST".to_string())?;
+        Ok(Some(Self { host, port, security, credentials, from }))
+    }
+
+    fn connect(&self) -> Result<Connection, MailError> {
+        let tcp = TcpStream::connect((self.host.as_str(), self.port))?;
+        tcp.set_read_timeout(Some(TIMEOUT))?;
+        tcp.set_write_timeout(Some(TIMEOUT))?;
+        let stream = match self.security {
+            Security::Tls => Stream::Tls(Box::new(tls(&self.host, tcp)?)),
+            _ => Stream::Plain(tcp),
+        };
+        Ok(Connection { stream, buffer: Vec::new() })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, mail: &Mail) -> Result<(), MailError> {
+        check_address(&mail.to)?;
+        let mut connection = self.connect()?;
+        connection.expect(220)?;
+        connection.command("EHLO localhost", 250)?;
+        if self.security == Security::StartTls {
+            connection.command("STARTTLS", 220)?;
+            connection.stream = match connection.stream {
+                Stream::Plain(tcp) => Stream::Tls(Box::new(tls(&self.host, tcp)?)),
+                tls => tls,
+            };
+            connection.command("EHLO localhost", 250)?;
+        }
+        if let Some((username, password)) = &self.credentials {
+            let plain = STANDARD.encode(format!("\0{}\0{}", username, password));
+            connection.command(&format!("AUTH PLAIN {}", plain), 235)?;
+        }
+        connection.command(&format!("MAIL FROM:<{}>", address(&self.from)), 250)?;
+        connection.command(&format!("RCPT TO:<{}>", address(&mail.to)), 250)?;
+        connection.command("DATA", 354)?;
+        connection.write(&format!("{}\r\n.\r\n", dot_stuff(&message(&self.from, mail))))?;
+        connection.expect(250)?;
+        connection.command("QUIT", 221)?;
+        debug!("Mail to {} sent through {}", mail.to, self.host);
+        Ok(())
+    }
+}
+
+fn tls(host: &str, tcp: TcpStream) -> Result<SslStream<TcpStream>, MailError> {
+    let connector = SslConnector::builder(SslMethod::tls())
+        .map_err(|e| MailError::Tls(e.to_string()))?
+        .build();
+    connector.connect(host, tcp).map_err(|e| MailError::Tls(e.to_string()))
+}
+
+/// Dirección de correo sin el nombre: de `Presu <no-reply@presu.es>` queda
+/// `no-reply@presu.es`.
+fn address(mailbox: &str) -> &str {
+    match (mailbox.rfind('<'), mailbox.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &mailbox[start + 1..end],
+        _ => mailbox.trim(),
+    }
+}
+
+/// Mensaje RFC 5322 con el asunto codificado según RFC 2047 y el cuerpo en
+/// base64, de forma que no dependa de que el servidor admita 8BITMIME.
+fn message(from: &str, mail: &Mail) -> String {
+    let body = STANDARD.encode(mail.body.as_bytes());
+    let lines: Vec<&str> = body
+        .as_bytes()
+        .chunks(LINE_LENGTH)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+        .collect();
+    format!(
+        "From: {}\r\nTo: {}\r\nSubject: =?UTF-8?B?{}?=\r\nDate: {}\r\nMessage-ID: <{}@{}>\r\n\
+        MIME-Version: 1.0\r\nContent-Type: text/plain; charset=UTF-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}",
+        from,
+        mail.to,
+        STANDARD.encode(mail.subject.as_bytes()),
+        chrono::Utc::now().to_rfc2822(),
+        uuid::Uuid::new_v4().simple(),
+        address(from).rsplit('@').next().unwrap_or("localhost"),
+        lines.join("\r\n"),
+    )
+}
+
+/// Duplica el punto inicial de las líneas para que no se confundan con el
+/// final de los datos (RFC 5321, 4.5.2).
+fn dot_stuff(data: &str) -> String {
+    data.split("\r\n")
+        .map(|line| if line.starts_with('.') { format!(".{}", line) } else { line.to_string() })
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<SslStream<TcpStream>>),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(stream) => stream.read(buf),
+            Self::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(stream) => stream.write(buf),
+            Self::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(stream) => stream.flush(),
+            Self::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+struct Connection {
+    stream: Stream,
+    // Bytes leídos que aún no forman una línea completa
+    buffer: Vec<u8>,
+}
+
+impl Connection {
+    fn write(&mut self, data: &str) -> Result<(), MailError> {
+        self.stream.write_all(data.as_bytes())?;
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    fn command(&mut self, command: &str, code: u16) -> Result<String, MailError> {
+        // No se registran las credenciales
+        debug!("SMTP > {}", if command.starts_with("AUTH") { "AUTH ..." } else { command });
+        self.write(&format!("{}\r\n", command))?;
+        self.expect(code)
+    }
+
+    fn line(&mut self) -> Result<String, MailError> {
+        loop {
+            if let Some(end) = self.buffer.windows(2).position(|window| window == b"\r\n") {
+                let line: Vec<u8> = self.buffer.drain(..end + 2).collect();
+                return Ok(String::from_utf8_lossy(&line[..end]).to_string());
+            }
+            let mut chunk = [0; 512];
+            let read = self.stream.read(&mut chunk)?;
+            if read == 0 {
+                return Err(MailError::Smtp("Connection closed by server".to_string()));
+            }
+            self.buffer.extend_from_slice(&chunk[..read]);
+        }
+    }
+
+    /// Lee una respuesta, que puede ocupar varias líneas (`250-...`), y
+    /// comprueba su código.
+    fn expect(&mut self, code: u16) -> Result<String, MailError> {
+        let mut reply = Vec::new();
+        loop {
+            let line = self.line()?;
+            debug!("SMTP < {}", line);
+            let last = line.as_bytes().get(3) != Some(&b'-');
+            reply.push(line);
+            if last {
+                break;
+            }
+        }
+        let reply = reply.join("\n");
+        match reply.get(..3).and_then(|value| value.parse::<u16>().ok()) {
+            Some(value) if value == code => Ok(reply),
+            _ => Err(MailError::Smtp(reply)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader},
+        net::TcpListener,
+        thread,
+    };
+
+    /// Servidor SMTP falso que acepta una conexión y devuelve las órdenes y
+    /// los datos recibidos.
+    fn fake_server(rejected: Option<&'static str>) -> (u16, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut received = Vec::new();
+            writer.write_all(b"220 fake ESMTP\r\n").unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 {
+                let command = line.trim_end().to_string();
+                line.clear();
+                received.push(command.clone());
+                let reply = if Some(command.as_str()) == rejected {
+                    "550 rejected\r\n".to_string()
+                } else if command.starts_with("EHLO") {
+                    "250-fake\r\n250 AUTH PLAIN\r\n".to_string()
+                } else if command.starts_with("AUTH") {
+                    "235 ok\r\n".to_string()
+                } else if command == "DATA" {
+                    writer.write_all(b"354 go ahead\r\n").unwrap();
+                    let mut data = String::new();
+                    while reader.read_line(&mut line).unwrap() > 0 && line != ".\r\n" {
+                        data.push_str(&line);
+                        line.clear();
+                    }
+                    line.clear();
+                    received.push(data);
+                    "250 queued\r\n".to_string()
+                } else if command == "QUIT" {
+                    writer.write_all(b"221 bye\r\n").unwrap();
+                    break;
+                } else {
+                    "250 ok\r\n".to_string()
+                };
+                writer.write_all(reply.as_bytes()).unwrap();
+            }
+            received
+        });
+        (port, handle)
+    }
+
+    fn mailer(port: u16) -> SmtpMailer {
+        SmtpMailer {
+            host: "127.0.0.1".to_string(),
+            port,
+            security: Security::None,
+            credentials: Some(("user".to_string(), "secret".to_string())),
+            from: "Presu <no-reply@presu.es>".to_string(),
+        }
+    }
+
+    fn mail() -> Mail {
+        Mail {
+            to: "ana@test.com".to_string(),
+            subject: "Recuperación de contraseña".to_string(),
+            body: ".línea con punto\nhttp://localhost/reset?token=abc".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_send() {
+        let (port, handle) = fake_server(None);
+        mailer(port).send(&mail()).unwrap();
+        let received = handle.join().unwrap();
+        assert_eq!(received[0], "EHLO localhost");
+        assert_eq!(received[1], format!("AUTH PLAIN {}", STANDARD.encode("\0user\0secret")));
+        assert_eq!(received[2], "MAIL FROM:<no-reply@presu.es>");
+        assert_eq!(received[3], "RCPT TO:<ana@test.com>");
+        assert_eq!(received[4], "DATA");
+        let data = &received[5];
+        assert!(data.contains("To: ana@test.com\r\n"));
+        assert!(data.contains(&format!("Subject: =?UTF-8?B?{}?=\r\n", STANDARD.encode("Recuperación de contraseña"))));
+        let body = data.split("\r\n\r\n").nth(1).unwrap().replace("\r\n", "");
+        assert_eq!(STANDARD.decode(body).unwrap(), mail().body.as_bytes());
+        assert_eq!(received.last().unwrap(), "QUIT");
+    }
+
+    #[test]
+    fn test_rejected() {
+        let (port, handle) = fake_server(Some("RCPT TO:<ana@test.com>"));
+        let error = mailer(port).send(&mail()).unwrap_err();
+        assert!(matches!(&error, MailError::Smtp(reply) if reply == "550 rejected"), "{}", error);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_invalid_address() {
+        // No llega a conectar: el puerto 9 (discard) no tiene servidor
+        for to in ["ana@test.com>\r\nRCPT TO:<eve@test.com", "ana@test.com\nBcc: eve@test.com", "<ana@test.com>", "ana"] {
+            let mail = Mail { to: to.to_string(), ..mail() };
+            let error = mailer(9).send(&mail).unwrap_err();
+            assert!(matches!(error, MailError::InvalidAddress(_)), "{}", to);
+        }
+        assert!(check_address("ana@test.com").is_ok());
+    }
+
+    #[test]
+    fn test_helpers() {
+        assert_eq!(address("Presu <no-reply@presu.es>"), "no-reply@presu.es");
+        assert_eq!(address(" no-reply@presu.es "), "no-reply@presu.es");
+        assert_eq!(dot_stuff("a\r\n.b\r\n..c"), "a\r\n..b\r\n...c");
+        assert_eq!("STARTTLS".parse::<Security>(), Ok(Security::StartTls));
+        assert!("ssl".parse::<Security>().is_err());
+    }
+}
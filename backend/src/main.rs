@@ -1,4 +1,4 @@
-use backend::{models, http};
+use backend::{mailer, models, http};
 
 use axum::{
     Router,
@@ -6,7 +6,8 @@ use axum::{
         header::{
             ACCEPT,
             AUTHORIZATION,
-            CONTENT_TYPE
+            CONTENT_TYPE,
+            HeaderName,
         },
         Method,
     },
@@ -37,27 +38,10 @@ use tracing::{
 };
 use std::{
     str::FromStr,
+    net::SocketAddr,
     env::var,
     path::Path,
 };
-use models::{
-    Budget,
-    Descomposition,
-    Element,
-    Measurement,
-    Price,
-    Project,
-    Role,
-    Unit,
-    User,
-    Version,
-};
-use http::{
-    health,
-    auth,
-    stats,
-    fallback_404,
-};
 use dotenv::dotenv;
 use models::{
     AppState,
@@ -115,27 +99,13 @@ async fn main() -> Result<(), Error> {
         .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH,
             Method::DELETE])
         //.allow_credentials(true)
-        .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE]);
+        .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE, HeaderName::from_static(http::middleware::API_KEY_HEADER)]);
 
-    let api_routes = Router::new()
-        .nest("/budgets", Budget::router())
-        .nest("/descompositions", Descomposition::router())
-        .nest("/elements", Element::router())
-        .nest("/measurements", Measurement::router())
-        .nest("/prices", Price::router())
-        .nest("/projects", Project::router())
-        .nest("/roles", Role::router())
-        .nest("/units", Unit::router())
-        .nest("/users", User::router())
-        .nest("/versions", Version::router())
-        .nest("/health", health::router())
-        .nest("/auth", auth::router())
-        .nest("/stats", stats::router())
-        .fallback(fallback_404)
-        .with_state(Arc::new(AppState {
-            pool,
-            secret,
-            static_dir: STATIC_DIR.to_string(),
+    let api_routes = http::api_router(Arc::new(AppState {
+        pool,
+        secret,
+        static_dir: STATIC_DIR.to_string(),
+        mailer: mailer::from_env(),
     }));
 
     let app = Router::new()
@@ -147,7 +117,7 @@ async fn main() -> Result<(), Error> {
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
     tracing::info!("🚀 Server started successfully 🚀");
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    Error, FromRow, Type,
+    postgres::PgPool,
+};
+use tracing::debug;
+use super::{token, UtcTimestamp};
+use std::fmt;
+
+// =================================================================
+// 1. ESTRUCTURAS DE DATOS (STRUCTS)
+// =================================================================
+
+/// Qué puede hacer una API key, además de lo que permita el rol del usuario.
+#[derive(Debug, Type, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[sqlx(type_name = "api_key_scope_enum", rename_all = "lowercase")] // Nombre del ENUM en PostgreSQL
+pub enum ApiKeyScope {
+    // Solo lectura (`GET`)
+    #[default]
+    #[serde(rename = "read")]
+    Read,
+    // Lectura y escritura
+    #[serde(rename = "write")]
+    Write,
+}
+
+impl fmt::Display for ApiKeyScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Read => "read",
+            Self::Write => "write",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Key de larga duración para acceder a la API sin login, en nombre de un
+/// usuario. Se envía como `Authorization: Bearer pk_...` o `X-Api-Key`.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct ApiKey {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    // Comienzo de la key, para reconocerla en los listados
+    pub prefix: String,
+    #[serde(skip_serializing)]
+    pub key_hash: String,
+    pub scope: ApiKeyScope,
+    // Proyectos a los que se limita; `None` para todos los del usuario
+    pub project_ids: Option<Vec<i32>>,
+    pub expires_at: Option<UtcTimestamp>,
+    pub last_used_at: Option<UtcTimestamp>,
+    pub revoked_at: Option<UtcTimestamp>,
+    pub created_by: Option<i32>,
+    pub created_at: UtcTimestamp,
+    pub updated_at: UtcTimestamp,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewApiKey {
+    pub name: String,
+    #[serde(default)]
+    pub scope: ApiKeyScope,
+    #[serde(default)]
+    pub project_ids: Option<Vec<i32>>,
+    #[serde(default)]
+    pub expires_at: Option<UtcTimestamp>,
+}
+
+// =================================================================
+// 2. MÉTODOS (ASOCIADOS DIRECTAMENTE AL STRUCT)
+// =================================================================
+
+impl ApiKey {
+    const TABLE: &str = "api_keys";
+    /// Comienzo de todas las keys, que las distingue de un JWT.
+    pub const KEY_PREFIX: &str = "pk_";
+    // Caracteres de la key que se guardan en claro en `prefix`
+    const PREFIX_LENGTH: usize = 11;
+    const ACTIVE: &str = "revoked_at IS NULL AND (expires_at IS NULL OR expires_at > NOW())";
+
+    // =================================================================
+    // C: CREATE (Crear)
+    // =================================================================
+    /// Crea una key para el usuario y la devuelve en claro: no se puede
+    /// volver a consultar.
+    pub async fn create(
+        pg_pool: &PgPool,
+        user_id: i32,
+        created_by: Option<i32>,
+        item: NewApiKey,
+    ) -> Result<(Self, String), Error> {
+        if item.name.trim().is_empty() {
+            return Err(Error::InvalidArgument("API key name is required".to_string()));
+        }
+        if item.expires_at.is_some_and(|expires_at| expires_at <= chrono::Utc::now()) {
+            return Err(Error::InvalidArgument("API key expiration must be in the future".to_string()));
+        }
+        if let Some(project_ids) = &item.project_ids {
+            if project_ids.is_empty() {
+                return Err(Error::InvalidArgument("API key projects must not be empty".to_string()));
+            }
+            let found = sqlx::query_scalar::<_, i64>("SELECT COUNT(DISTINCT id) FROM projects WHERE id = ANY($1)")
+                .bind(project_ids)
+                .fetch_one(pg_pool)
+                .await?;
+            let mut unique = project_ids.clone();
+            unique.sort_unstable();
+            unique.dedup();
+            if found != unique.len() as i64 {
+                return Err(Error::InvalidArgument("API key projects not found".to_string()));
+            }
+        }
+        let key = format!("{}{}", Self::KEY_PREFIX, token::generate());
+        let sql = format!(
+            r#"INSERT INTO {} (user_id, name, prefix, key_hash, scope, project_ids, expires_at, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING *"#,
+            Self::TABLE
+        );
+        debug!("Create: {}", &sql);
+        let api_key = sqlx::query_as::<_, Self>(&sql)
+            .bind(user_id)
+            .bind(item.name.trim())
+            .bind(&key[..Self::PREFIX_LENGTH])
+            .bind(token::hash(&key))
+            .bind(item.scope)
+            .bind(item.project_ids)
+            .bind(item.expires_at)
+            .bind(created_by)
+            .fetch_one(pg_pool)
+            .await?;
+        Ok((api_key, key))
+    }
+
+    // =================================================================
+    // R: READ
+    // =================================================================
+    /// Key activa (no revocada ni caducada) a partir de su valor en claro.
+    pub async fn read_by_key(pg_pool: &PgPool, key: &str) -> Result<Option<Self>, Error> {
+        let sql = format!("SELECT * FROM {} WHERE key_hash = $1 AND {}", Self::TABLE, Self::ACTIVE);
+        debug!("Read by key: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(token::hash(key))
+            .fetch_optional(pg_pool)
+            .await
+    }
+
+    /// Todas las keys de un usuario, incluidas las revocadas.
+    pub async fn read_by_user(pg_pool: &PgPool, user_id: i32) -> Result<Vec<Self>, Error> {
+        let sql = format!("SELECT * FROM {} WHERE user_id = $1 ORDER BY id", Self::TABLE);
+        debug!("Read by user: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(user_id)
+            .fetch_all(pg_pool)
+            .await
+    }
+
+    // =================================================================
+    // U: UPDATE (Actualizar)
+    // =================================================================
+    /// Anota el uso de la key. Para no escribir en cada petición solo se
+    /// actualiza si el último uso tiene más de un minuto.
+    pub async fn touch(pg_pool: &PgPool, id: i32) -> Result<(), Error> {
+        let sql = format!(
+            r#"UPDATE {} SET last_used_at = NOW()
+            WHERE id = $1 AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')"#,
+            Self::TABLE
+        );
+        debug!("Touch: {}", &sql);
+        sqlx::query(&sql)
+            .bind(id)
+            .execute(pg_pool)
+            .await
+            .map(|_| ())
+    }
+
+    /// Revoca una key activa del usuario. Devuelve `None` si no la hay.
+    pub async fn revoke(pg_pool: &PgPool, id: i32, user_id: i32) -> Result<Option<Self>, Error> {
+        let sql = format!(
+            "UPDATE {} SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND {} RETURNING *",
+            Self::TABLE,
+            Self::ACTIVE
+        );
+        debug!("Revoke: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(pg_pool)
+            .await
+    }
+}
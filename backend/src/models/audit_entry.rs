@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    Error, FromRow, Type,
+    postgres::{PgExecutor, PgPool},
+};
+use tracing::debug;
+use super::UtcTimestamp;
+use std::fmt;
+
+// =================================================================
+// 1. ESTRUCTURAS DE DATOS (STRUCTS)
+// =================================================================
+
+#[derive(Debug, Type, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "audit_action_enum", rename_all = "snake_case")] // Nombre del ENUM en PostgreSQL
+pub enum AuditAction {
+    #[serde(rename = "account_locked")]
+    AccountLocked,
+    #[serde(rename = "ip_locked")]
+    IpLocked,
+    #[serde(rename = "account_unlocked")]
+    AccountUnlocked,
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::AccountLocked => "account_locked",
+            Self::IpLocked => "ip_locked",
+            Self::AccountUnlocked => "account_unlocked",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Entrada del registro de auditoría. Solo se añaden, nunca se modifican.
+#[derive(Debug, FromRow, Serialize)]
+pub struct AuditEntry {
+    pub id: i32,
+    pub action: AuditAction,
+    // Usuario afectado
+    pub user_id: Option<i32>,
+    // Usuario que realizó la acción; `None` si fue el sistema
+    pub actor_id: Option<i32>,
+    pub ip: Option<String>,
+    pub detail: String,
+    pub created_at: UtcTimestamp,
+}
+
+#[derive(Debug)]
+pub struct NewAuditEntry {
+    pub action: AuditAction,
+    pub user_id: Option<i32>,
+    pub actor_id: Option<i32>,
+    pub ip: Option<String>,
+    pub detail: String,
+}
+
+// =================================================================
+// 2. MÉTODOS (ASOCIADOS DIRECTAMENTE AL STRUCT)
+// =================================================================
+
+impl AuditEntry {
+    const TABLE: &str = "audit_entries";
+
+    // =================================================================
+    // C: CREATE (Crear)
+    // =================================================================
+    pub async fn create<'e, E>(executor: E, item: NewAuditEntry) -> Result<Self, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!(
+            "INSERT INTO {} (action, user_id, actor_id, ip, detail) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+            Self::TABLE
+        );
+        debug!("Create: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(item.action)
+            .bind(item.user_id)
+            .bind(item.actor_id)
+            .bind(item.ip)
+            .bind(item.detail)
+            .fetch_one(executor)
+            .await
+    }
+
+    // =================================================================
+    // R: READ
+    // =================================================================
+    /// Entradas que afectan a un usuario, de la más reciente a la más antigua.
+    pub async fn read_by_user(pg_pool: &PgPool, user_id: i32) -> Result<Vec<Self>, Error> {
+        let sql = format!("SELECT * FROM {} WHERE user_id = $1 ORDER BY id DESC", Self::TABLE);
+        debug!("Read by user: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(user_id)
+            .fetch_all(pg_pool)
+            .await
+    }
+}
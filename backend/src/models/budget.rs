@@ -1,14 +1,19 @@
+use axum::http::StatusCode;
 use serde::{Deserialize, Serialize};
 use sqlx::{
     self,
+    types::{BigDecimal, Json},
     Type,
     Postgres,
     QueryBuilder,
     Error, FromRow, Row,
-    postgres::{PgPool, PgRow},
+    postgres::{PgConnection, PgPool, PgRow},
 };
 use tracing::debug;
 use super::{
+    budget_status_change::{BudgetStatusChange, NewBudgetStatusChange},
+    project_member::{append_member_filter, MemberScope, ScopeParent, Scoped},
+    ApiError,
     Paginable,
     Filterable,
     UtcTimestamp,
@@ -48,6 +53,34 @@ impl fmt::Display for BudgetStatus {
     }
 }
 
+impl BudgetStatus {
+    /// Un presupuesto enviado, aprobado o archivado no se puede modificar: ni
+    /// sus datos ni sus elementos y mediciones.
+    pub fn is_locked(&self) -> bool {
+        matches!(self, Self::Submitted | Self::Approved | Self::Archived)
+    }
+
+    /// Transiciones permitidas del flujo de aprobación:
+    ///
+    /// ```text
+    /// draft ──> submitted ──> approved ──> archived
+    ///   ^           │
+    ///   │           v
+    ///   └──── rejected
+    /// ```
+    ///
+    /// Un presupuesto rechazado se corrige y se vuelve a enviar. Los borradores
+    /// y los rechazados también se pueden archivar directamente.
+    pub fn can_transition_to(&self, to: Self) -> bool {
+        matches!(
+            (self, to),
+            (Self::Draft | Self::Rejected, Self::Submitted | Self::Archived)
+                | (Self::Submitted, Self::Approved | Self::Rejected)
+                | (Self::Approved, Self::Archived)
+        )
+    }
+}
+
 impl Filterable for Option<BudgetStatus> {
     fn append_filter(&self, builder: &mut QueryBuilder<Postgres>, column: &str) {
         if let Some(val) = self {
@@ -57,8 +90,102 @@ impl Filterable for Option<BudgetStatus> {
     }
 }
 
+/// Porcentaje adicional definido por el usuario, aplicado sobre el PEM.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CustomRate {
+    pub name: String,
+    pub percentage: BigDecimal,
+}
+
+/// Porcentajes que llevan del PEM al PEC (GG, BI y los personalizados) y del
+/// PEC al total con IVA.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct BudgetRates {
+    pub general_expenses: BigDecimal,
+    pub industrial_profit: BigDecimal,
+    pub vat: BigDecimal,
+    pub custom_rates: Json<Vec<CustomRate>>,
+}
+
+impl Default for BudgetRates {
+    fn default() -> Self {
+        Self {
+            general_expenses: BigDecimal::from(13),
+            industrial_profit: BigDecimal::from(6),
+            vat: BigDecimal::from(21),
+            custom_rates: Json(Vec::new()),
+        }
+    }
+}
+
+impl BudgetRates {
+    /// Comprueba que los porcentajes estén entre 0 y 100 y que los
+    /// personalizados tengan nombre.
+    pub fn validate(&self) -> Result<(), Error> {
+        let range = BigDecimal::from(0)..=BigDecimal::from(100);
+        let rates = [
+            ("general_expenses", &self.general_expenses),
+            ("industrial_profit", &self.industrial_profit),
+            ("vat", &self.vat),
+        ];
+        for (name, percentage) in rates {
+            if !range.contains(percentage) {
+                return Err(Error::InvalidArgument(format!("Rate '{}' must be between 0 and 100", name)));
+            }
+        }
+        for rate in self.custom_rates.iter() {
+            if rate.name.trim().is_empty() {
+                return Err(Error::InvalidArgument("Custom rates must have a name".to_string()));
+            }
+            if !range.contains(&rate.percentage) {
+                return Err(Error::InvalidArgument(format!("Rate '{}' must be between 0 and 100", rate.name)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum BudgetError {
+    NotFound,
+    // El presupuesto no se puede modificar en su estado actual
+    Locked(BudgetStatus),
+    Transition { from: BudgetStatus, to: BudgetStatus },
+    Database(Error),
+}
+
+impl fmt::Display for BudgetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "Budget not found"),
+            Self::Locked(status) => write!(f, "Budget is {} and cannot be modified", status),
+            Self::Transition { from, to } => write!(f, "Cannot change budget status from {} to {}", from, to),
+            Self::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for BudgetError {}
+
+impl From<Error> for BudgetError {
+    fn from(e: Error) -> Self {
+        Self::Database(e)
+    }
+}
+
+impl ApiError for BudgetError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Locked(_) | Self::Transition { .. } => StatusCode::CONFLICT,
+            Self::Database(e) => e.status(),
+        }
+    }
+}
+
 /// Estructura del modelo de dominio para la tabla 'budgets'
-#[axum_crud(path = "/budgets", new = "NewBudget", params = "BudgetParams")]
+#[axum_crud(path = "/budgets", new = "NewBudget", params = "BudgetParams", scope = "project")]
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct Budget {
     pub id: i32,
@@ -67,6 +194,9 @@ pub struct Budget {
     pub version_number: i32,
     pub name: String,
     pub status: BudgetStatus, // Mapeado al enum nativo de Rust
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub rates: BudgetRates,
     // Campos de Auditoría
     pub created_at: UtcTimestamp,
     pub updated_at: UtcTimestamp,
@@ -80,11 +210,25 @@ pub struct NewBudget {
     pub version_number: i32,
     pub name: String,
     pub status: BudgetStatus, // Usamos el enum de Rust en el DTO
+    #[serde(flatten)]
+    pub rates: BudgetRates,
+}
+
+/// Datos de la nueva versión al copiar un presupuesto (ver `Budget::clone_version`).
+/// Por defecto el código es el del original con el número de versión y el
+/// nombre, el mismo.
+#[derive(Debug, Default, Deserialize)]
+pub struct CloneBudget {
+    pub code: Option<String>,
+    pub name: Option<String>,
 }
 
 #[derive(Debug, serde::Deserialize, macros::Paginable)]
 pub struct BudgetParams {
     pub id: Option<i32>,
+    // Proyectos a los que se restringen los resultados (ver `MemberScope`)
+    #[serde(skip)]
+    pub member_scope: Option<MemberScope>,
 
     pub project_id: Option<i32>,
     pub code: Option<String>,
@@ -98,28 +242,50 @@ pub struct BudgetParams {
     pub asc: Option<bool>,
 }
 
+impl Scoped for Budget {
+    fn scope_parent(&self) -> Option<ScopeParent> {
+        Some(ScopeParent::Project(self.project_id))
+    }
+}
+
+impl Scoped for NewBudget {
+    fn scope_parent(&self) -> Option<ScopeParent> {
+        Some(ScopeParent::Project(self.project_id))
+    }
+}
+
 // =================================================================
 // 2. MÉTODOS CRUD (ASOCIADOS DIRECTAMENTE AL STRUCT)
 // =================================================================
 
 impl Budget {
     const TABLE: &str = "budgets";
+    // Proyecto de cada fila, para filtrar por los miembros del proyecto
+    const PROJECT: &str = "project_id";
     const INSERT_QUERY: &str = r#"
         (
             project_id,
             code,
             version_number,
             status,
-            name
+            name,
+            general_expenses,
+            industrial_profit,
+            vat,
+            custom_rates
         )
-        VALUES ($1, $2, $3, $4, $5)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
     "#;
     const UPDATE_QUERY: &str = r#"
         project_id = $2,
         code = $3,
         version_number = $4,
         status = $5,
-        name = $6
+        name = $6,
+        general_expenses = $7,
+        industrial_profit = $8,
+        vat = $9,
+        custom_rates = $10
     "#;
 
     // =================================================================
@@ -146,6 +312,7 @@ impl Budget {
         let sql = format!("SELECT COUNT(*) FROM {} WHERE 1=1", Self::TABLE);
         debug!("Count paged: {}", &sql);
         let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(&sql);
+        append_member_filter(&mut query_builder, Self::PROJECT, params.member_scope.as_ref());
         params.project_id.append_filter(&mut query_builder, "project_id");
         params.code.append_filter(&mut query_builder, "code");
         params.version_number.append_filter(&mut query_builder, "version_number");
@@ -162,6 +329,7 @@ impl Budget {
         let sql = format!("SELECT * FROM {} WHERE 1=1", Self::TABLE);
         debug!("Read paged: {}", &sql);
         let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(&sql);
+        append_member_filter(&mut query_builder, Self::PROJECT, params.member_scope.as_ref());
         params.project_id.append_filter(&mut query_builder, "project_id");
         params.code.append_filter(&mut query_builder, "code");
         params.version_number.append_filter(&mut query_builder, "version_number");
@@ -185,7 +353,13 @@ impl Budget {
     // C: CREATE (Crear)
     // =================================================================
     /// Inserta un nuevo registro en la base de datos y devuelve el objeto creado.
+    /// Los porcentajes deben ser válidos (ver `BudgetRates::validate`) y el
+    /// presupuesto se crea como borrador.
     pub async fn create(pg_pool: &PgPool, item: NewBudget) -> Result<Self, Error> {
+        item.rates.validate()?;
+        if item.status != BudgetStatus::Draft {
+            return Err(Error::InvalidArgument("New budgets must be drafts".to_string()));
+        }
         let sql = format!("INSERT INTO {} {} RETURNING *", Self::TABLE, Self::INSERT_QUERY);
         debug!("Create: {}", &sql);
         sqlx::query_as::<_, Self>(&sql)
@@ -194,6 +368,10 @@ impl Budget {
         .bind(item.version_number)
         .bind(item.status)
         .bind(item.name)
+        .bind(item.rates.general_expenses)
+        .bind(item.rates.industrial_profit)
+        .bind(item.rates.vat)
+        .bind(item.rates.custom_rates)
         .fetch_one(pg_pool)
         .await
     }
@@ -202,18 +380,162 @@ impl Budget {
     // U: UPDATE (Actualizar)
     // =================================================================
     /// Actualiza un registro por ID y devuelve el objeto actualizado.
-    pub async fn update(pg_pool: &PgPool, item: Self) -> Result<Self, Error> {
+    /// Solo se pueden modificar los borradores y los rechazados, y el estado
+    /// solo cambia con `transition`.
+    pub async fn update(pg_pool: &PgPool, item: Self) -> Result<Self, BudgetError> {
+        item.rates.validate()?;
+        let mut tx = pg_pool.begin().await?;
+        let status = Self::lock_status(&mut tx, item.id).await?;
+        if status.is_locked() {
+            return Err(BudgetError::Locked(status));
+        }
+        if item.status != status {
+            return Err(Error::InvalidArgument("Budget status can only be changed with the workflow actions".to_string()).into());
+        }
         let sql = format!("UPDATE {} SET {} WHERE id = $1 RETURNING *", Self::TABLE, Self::UPDATE_QUERY);
         debug!("Update: {}", &sql);
-        sqlx::query_as::<_, Self>(&sql)
+        let budget = sqlx::query_as::<_, Self>(&sql)
         .bind(item.id)
         .bind(item.project_id)
         .bind(item.code)
         .bind(item.version_number)
         .bind(item.status)
         .bind(item.name)
-        .fetch_one(pg_pool)
-        .await
+        .bind(item.rates.general_expenses)
+        .bind(item.rates.industrial_profit)
+        .bind(item.rates.vat)
+        .bind(item.rates.custom_rates)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(budget)
+    }
+
+    /// Cambia el estado del presupuesto si la transición está permitida (ver
+    /// `BudgetStatus::can_transition_to`) y la anota en su historial. Rechazar
+    /// exige un comentario con el motivo.
+    pub async fn transition(
+        pg_pool: &PgPool,
+        id: i32,
+        to: BudgetStatus,
+        user_id: Option<i32>,
+        comment: Option<String>,
+    ) -> Result<Self, BudgetError> {
+        let comment = comment.map(|comment| comment.trim().to_string()).filter(|comment| !comment.is_empty());
+        if to == BudgetStatus::Rejected && comment.is_none() {
+            return Err(Error::InvalidArgument("A comment is required to reject a budget".to_string()).into());
+        }
+        let mut tx = pg_pool.begin().await?;
+        let from = Self::lock_status(&mut tx, id).await?;
+        if !from.can_transition_to(to) {
+            return Err(BudgetError::Transition { from, to });
+        }
+        let sql = format!("UPDATE {} SET status = $2 WHERE id = $1 RETURNING *", Self::TABLE);
+        debug!("Transition: {}", &sql);
+        let budget = sqlx::query_as::<_, Self>(&sql)
+            .bind(id)
+            .bind(to)
+            .fetch_one(&mut *tx)
+            .await?;
+        let change = NewBudgetStatusChange { budget_id: id, from_status: from, to_status: to, user_id, comment };
+        BudgetStatusChange::create(&mut *tx, change).await?;
+        tx.commit().await?;
+        debug!("Budget {} changed from {} to {} by {:?}", id, from, to, user_id);
+        Ok(budget)
+    }
+
+    /// Copia el presupuesto como nueva versión del mismo proyecto, con el
+    /// siguiente `version_number`, en borrador: todos sus elementos (con la
+    /// jerarquía) y sus mediciones. Se hace en una transacción.
+    pub async fn clone_version(pg_pool: &PgPool, id: i32, item: CloneBudget) -> Result<Self, BudgetError> {
+        let mut tx = pg_pool.begin().await?;
+        let sql = format!("SELECT * FROM {} WHERE id = $1", Self::TABLE);
+        debug!("Clone: {}", &sql);
+        let source = sqlx::query_as::<_, Self>(&sql)
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(BudgetError::NotFound)?;
+        // Bloquea el proyecto para que dos copias no tomen el mismo número
+        sqlx::query("SELECT id FROM projects WHERE id = $1 FOR UPDATE")
+            .bind(source.project_id)
+            .execute(&mut *tx)
+            .await?;
+        let sql = format!("SELECT COALESCE(MAX(version_number), 0) + 1 FROM {} WHERE project_id = $1", Self::TABLE);
+        let version_number = sqlx::query_scalar::<_, i32>(&sql)
+            .bind(source.project_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        let non_empty = |value: Option<String>| value.map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+        let code = non_empty(item.code).unwrap_or_else(|| format!("{}-V{}", source.code, version_number));
+        let name = non_empty(item.name).unwrap_or(source.name);
+
+        let sql = format!("INSERT INTO {} {} RETURNING *", Self::TABLE, Self::INSERT_QUERY);
+        debug!("Clone: {}", &sql);
+        let budget = sqlx::query_as::<_, Self>(&sql)
+            .bind(source.project_id)
+            .bind(code)
+            .bind(version_number)
+            .bind(BudgetStatus::Draft)
+            .bind(name)
+            .bind(source.rates.general_expenses)
+            .bind(source.rates.industrial_profit)
+            .bind(source.rates.vat)
+            .bind(source.rates.custom_rates)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        // Los códigos de elemento son únicos en cada presupuesto: enlazan cada
+        // copia con su original para reasignar `parent_id` y las mediciones
+        let statements = [
+            r#"INSERT INTO elements (budget_id, version_id, element_type, code, budget_code, description)
+            SELECT $2, version_id, element_type, code, budget_code, description
+            FROM elements WHERE budget_id = $1 ORDER BY id"#,
+            r#"UPDATE elements AS copy SET parent_id = parent_copy.id
+            FROM elements AS original
+            JOIN elements AS parent ON parent.id = original.parent_id
+            JOIN elements AS parent_copy ON parent_copy.budget_id = $2 AND parent_copy.code = parent.code
+            WHERE original.budget_id = $1 AND copy.budget_id = $2 AND copy.code = original.code"#,
+            r#"INSERT INTO measurements (element_id, price_id, params_json, measurement_text, measured_quantity)
+            SELECT copy.id, m.price_id, m.params_json, m.measurement_text, m.measured_quantity
+            FROM measurements AS m
+            JOIN elements AS original ON original.id = m.element_id
+            JOIN elements AS copy ON copy.budget_id = $2 AND copy.code = original.code
+            WHERE original.budget_id = $1 ORDER BY m.id"#,
+        ];
+        for sql in statements {
+            debug!("Clone: {}", sql);
+            sqlx::query(sql)
+                .bind(id)
+                .bind(budget.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        debug!("Budget {} cloned as {} (version {})", id, budget.id, budget.version_number);
+        Ok(budget)
+    }
+
+    /// Lee el presupuesto bloqueando la fila hasta el final de la transacción.
+    pub async fn lock_by_id(conn: &mut PgConnection, id: i32) -> Result<Option<Self>, Error> {
+        let sql = format!("SELECT * FROM {} WHERE id = $1 FOR UPDATE", Self::TABLE);
+        debug!("Lock by id: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(id)
+            .fetch_optional(conn)
+            .await
+    }
+
+    /// Estado actual del presupuesto, bloqueando la fila hasta el final de la
+    /// transacción para que no cambie entre la comprobación y la escritura.
+    async fn lock_status(conn: &mut PgConnection, id: i32) -> Result<BudgetStatus, BudgetError> {
+        let sql = format!("SELECT status FROM {} WHERE id = $1 FOR UPDATE", Self::TABLE);
+        debug!("Lock status: {}", &sql);
+        sqlx::query_scalar::<_, BudgetStatus>(&sql)
+            .bind(id)
+            .fetch_optional(conn)
+            .await?
+            .ok_or(BudgetError::NotFound)
     }
 
     // =================================================================
@@ -254,4 +576,38 @@ mod tests {
         assert_eq!(format!("{}", BudgetStatus::Rejected), "rejected");
         assert_eq!(format!("{}", BudgetStatus::Archived), "archived");
     }
+
+    #[test]
+    fn test_budget_status_transitions() {
+        use BudgetStatus::*;
+        assert!(Draft.can_transition_to(Submitted));
+        assert!(Submitted.can_transition_to(Approved));
+        assert!(Submitted.can_transition_to(Rejected));
+        assert!(Rejected.can_transition_to(Submitted));
+        assert!(Approved.can_transition_to(Archived));
+        assert!(!Draft.can_transition_to(Approved));
+        assert!(!Approved.can_transition_to(Draft));
+        assert!(!Submitted.can_transition_to(Archived));
+        assert!(!Archived.can_transition_to(Draft));
+        assert!(!Draft.can_transition_to(Draft));
+        assert!(Submitted.is_locked() && Approved.is_locked() && Archived.is_locked());
+        assert!(!Draft.is_locked() && !Rejected.is_locked());
+    }
+
+    #[test]
+    fn test_budget_rates_default() {
+        let budget: NewBudget = serde_json::from_value(serde_json::json!({
+            "project_id": 1,
+            "code": "B-1",
+            "version_number": 1,
+            "name": "Budget",
+            "status": "draft",
+            "vat": "10",
+        }))
+        .unwrap();
+        assert_eq!(budget.rates.general_expenses, BigDecimal::from(13));
+        assert_eq!(budget.rates.vat, BigDecimal::from(10));
+        assert!(budget.rates.custom_rates.is_empty());
+        assert!(budget.rates.validate().is_ok());
+    }
 }
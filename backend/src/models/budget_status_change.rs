@@ -0,0 +1,78 @@
+use serde::Serialize;
+use sqlx::{
+    Error, FromRow,
+    postgres::{PgExecutor, PgPool},
+};
+use tracing::debug;
+use super::{budget::BudgetStatus, UtcTimestamp};
+
+// =================================================================
+// 1. ESTRUCTURAS DE DATOS (STRUCTS)
+// =================================================================
+
+/// Cambio de estado de un presupuesto (ver `Budget::transition`). Solo se
+/// añaden, nunca se modifican.
+#[derive(Debug, FromRow, Serialize)]
+pub struct BudgetStatusChange {
+    pub id: i32,
+    pub budget_id: i32,
+    pub from_status: BudgetStatus,
+    pub to_status: BudgetStatus,
+    // Usuario que realizó el cambio
+    pub user_id: Option<i32>,
+    // Motivo del cambio; obligatorio al rechazar
+    pub comment: Option<String>,
+    pub created_at: UtcTimestamp,
+}
+
+#[derive(Debug)]
+pub struct NewBudgetStatusChange {
+    pub budget_id: i32,
+    pub from_status: BudgetStatus,
+    pub to_status: BudgetStatus,
+    pub user_id: Option<i32>,
+    pub comment: Option<String>,
+}
+
+// =================================================================
+// 2. MÉTODOS (ASOCIADOS DIRECTAMENTE AL STRUCT)
+// =================================================================
+
+impl BudgetStatusChange {
+    const TABLE: &str = "budget_status_changes";
+
+    // =================================================================
+    // C: CREATE (Crear)
+    // =================================================================
+    pub async fn create<'e, E>(executor: E, item: NewBudgetStatusChange) -> Result<Self, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!(
+            "INSERT INTO {} (budget_id, from_status, to_status, user_id, comment) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+            Self::TABLE
+        );
+        debug!("Create: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(item.budget_id)
+            .bind(item.from_status)
+            .bind(item.to_status)
+            .bind(item.user_id)
+            .bind(item.comment)
+            .fetch_one(executor)
+            .await
+    }
+
+    // =================================================================
+    // R: READ
+    // =================================================================
+    /// Historial de estados de un presupuesto, del más antiguo al más reciente.
+    pub async fn read_by_budget(pg_pool: &PgPool, budget_id: i32) -> Result<Vec<Self>, Error> {
+        let sql = format!("SELECT * FROM {} WHERE budget_id = $1 ORDER BY id", Self::TABLE);
+        debug!("Read by budget: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(budget_id)
+            .fetch_all(pg_pool)
+            .await
+    }
+}
@@ -1,3 +1,4 @@
+use axum::http::StatusCode;
 use serde::{Deserialize, Serialize};
 use sqlx::{
     self,
@@ -5,16 +6,20 @@ use sqlx::{
     Postgres,
     QueryBuilder,
     Error, FromRow, Row,
-    postgres::{PgPool, PgRow},
+    postgres::{PgConnection, PgExecutor, PgPool, PgRow},
     types::BigDecimal,
 };
 use tracing::debug;
 use super::{
+    price::PriceType,
+    ApiError,
+    Data,
     Paginable,
     Filterable,
+    Price,
     UtcTimestamp,
 };
-use serde_json::Value;
+use serde_json::{json, Value};
 use macros::axum_crud;
 use std::fmt;
 
@@ -50,7 +55,7 @@ impl Filterable for Option<CalculationMode> {
     }
 }
 
-#[axum_crud(path = "/descompositions", new = "NewDescomposition", params = "DescompositionParams")]
+#[axum_crud(path = "/descompositions", new = "NewDescomposition", params = "DescompositionParams", write = "master")]
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct Descomposition {
     pub id: i32,
@@ -77,6 +82,73 @@ pub struct NewDescomposition {
     pub params_json: Option<Value>, 
 }
 
+/// Motivo por el que se rechaza una descomposición.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Violation {
+    SelfReference,
+    Cycle,
+    BaseParent,
+    CrossVersion,
+    MissingPrice,
+}
+
+/// Precio que forma parte del camino que provoca el error.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PathNode {
+    pub id: i32,
+    pub code: String,
+    pub version_id: i32,
+}
+
+#[derive(Debug)]
+pub enum DescompositionError {
+    // Se serializa como `data` en la respuesta 422
+    Invalid { violation: Violation, path: Vec<PathNode> },
+    Database(Error),
+}
+
+impl fmt::Display for DescompositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid { violation, path } => {
+                let path = path.iter().map(|p| p.code.as_str()).collect::<Vec<_>>().join(" -> ");
+                match violation {
+                    Violation::SelfReference => write!(f, "A price cannot be a component of itself: {}", path),
+                    Violation::Cycle => write!(f, "The descomposition would create a cycle: {}", path),
+                    Violation::BaseParent => write!(f, "A base price cannot be decomposed: {}", path),
+                    Violation::CrossVersion => write!(f, "Parent and component belong to different versions: {}", path),
+                    Violation::MissingPrice => write!(f, "Price not found: {}", path),
+                }
+            }
+            Self::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for DescompositionError {}
+
+impl From<Error> for DescompositionError {
+    fn from(e: Error) -> Self {
+        Self::Database(e)
+    }
+}
+
+impl ApiError for DescompositionError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::Invalid { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Database(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+    fn data(&self) -> Data {
+        match self {
+            Self::Invalid { violation, path } => Data::Some(json!({"violation": violation, "path": path})),
+            Self::Database(_) => Data::None,
+        }
+    }
+}
+
 #[derive(Debug, serde::Deserialize, macros::Paginable)]
 pub struct DescompositionParams {
     pub id: Option<i32>,
@@ -174,35 +246,51 @@ impl Descomposition {
     // C: CREATE (Crear)
     // =================================================================
     /// Inserta un nuevo registro en la base de datos y devuelve el objeto creado.
-    pub async fn create(pg_pool: &PgPool, item: NewDescomposition) -> Result<Self, Error> {
+    /// Antes se comprueba que la descomposición no rompe el grafo de precios.
+    pub async fn create(pg_pool: &PgPool, item: NewDescomposition) -> Result<Self, DescompositionError> {
+        let mut tx = pg_pool.begin().await?;
+        let created = Self::create_in(&mut tx, item).await?;
+        tx.commit().await?;
+        Ok(created)
+    }
+
+    /// Como `create`, pero dentro de una transacción abierta por quien llama,
+    /// que mantiene el bloqueo del grafo de la versión hasta que termina.
+    pub async fn create_in(conn: &mut PgConnection, item: NewDescomposition) -> Result<Self, DescompositionError> {
+        Self::validate(conn, None, item.parent_price_id, item.component_price_id).await?;
         let sql = format!("INSERT INTO {} {} RETURNING *", Self::TABLE, Self::INSERT_QUERY);
         debug!("Create: {}", &sql);
-        sqlx::query_as::<_, Self>(&sql)
+        Ok(sqlx::query_as::<_, Self>(&sql)
         .bind(item.parent_price_id)
         .bind(item.component_price_id)
         .bind(item.calculation_mode)
         .bind(item.fixed_quantity)
         .bind(item.params_json)
-        .fetch_one(pg_pool)
-        .await
+        .fetch_one(conn)
+        .await?)
     }
 
     // =================================================================
     // U: UPDATE (Actualizar)
     // =================================================================
     /// Actualiza un registro por ID y devuelve el objeto actualizado.
-    pub async fn update(pg_pool: &PgPool, item: Self) -> Result<Self, Error> {
+    /// Antes se comprueba que la descomposición no rompe el grafo de precios.
+    pub async fn update(pg_pool: &PgPool, item: Self) -> Result<Self, DescompositionError> {
+        let mut tx = pg_pool.begin().await?;
+        Self::validate(&mut tx, Some(item.id), item.parent_price_id, item.component_price_id).await?;
         let sql = format!("UPDATE {} SET {} WHERE id = $1 RETURNING *", Self::TABLE, Self::UPDATE_QUERY);
         debug!("Update: {}", &sql);
-        sqlx::query_as::<_, Self>(&sql)
+        let updated = sqlx::query_as::<_, Self>(&sql)
         .bind(item.id)
         .bind(item.parent_price_id)
         .bind(item.component_price_id)
         .bind(item.calculation_mode)
         .bind(item.fixed_quantity)
         .bind(item.params_json)
-        .fetch_one(pg_pool)
-        .await
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(updated)
     }
 
     // =================================================================
@@ -217,6 +305,117 @@ impl Descomposition {
             .fetch_one(pg_pool)
             .await
     }
+    // =================================================================
+    // E: OTHERS
+    // =================================================================
+    /// Recupera las descomposiciones de los precios padre indicados.
+    pub async fn read_by_parents<'e, E>(executor: E, parent_ids: &[i32]) -> Result<Vec<Self>, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!("SELECT * FROM {} WHERE parent_price_id = ANY($1) ORDER BY id", Self::TABLE);
+        debug!("Read by parents: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(parent_ids)
+            .fetch_all(executor)
+            .await
+    }
+    /// Copia las descomposiciones de los precios de la versión
+    /// `from_version_id` entre los precios con el mismo código de la versión
+    /// `to_version_id`. No se validan: el grafo copiado es el de origen, que ya
+    /// era válido. Devuelve el número de descomposiciones copiadas.
+    pub async fn copy_to_version<'e, E>(executor: E, from_version_id: i32, to_version_id: i32) -> Result<u64, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!(
+            r#"INSERT INTO {table} (parent_price_id, component_price_id, calculation_mode, fixed_quantity, params_json)
+            SELECT np.id, nc.id, d.calculation_mode, d.fixed_quantity, d.params_json
+            FROM {table} d
+            JOIN prices p ON p.id = d.parent_price_id AND p.version_id = $1
+            JOIN prices c ON c.id = d.component_price_id
+            JOIN prices np ON np.version_id = $2 AND np.code = p.code
+            JOIN prices nc ON nc.version_id = $2 AND nc.code = c.code
+            ORDER BY d.id"#,
+            table = Self::TABLE
+        );
+        debug!("Copy to version: {}", &sql);
+        sqlx::query(&sql)
+            .bind(from_version_id)
+            .bind(to_version_id)
+            .execute(executor)
+            .await
+            .map(|result| result.rows_affected())
+    }
+    /// Comprueba que el componente se puede añadir al padre: no puede ser el
+    /// propio padre, el padre tiene que ser un precio descompuesto, ambos tienen
+    /// que ser de la misma versión y el padre no puede estar ya entre los
+    /// descendientes del componente. `id` es la descomposición que se actualiza,
+    /// cuya arista actual no se tiene en cuenta.
+    ///
+    /// Bloquea el grafo de la versión del padre (ver `Price::lock_graph`), así
+    /// que la comprobación y la escritura tienen que ir en la misma transacción.
+    pub async fn validate(
+        conn: &mut PgConnection,
+        id: Option<i32>,
+        parent_price_id: i32,
+        component_price_id: i32,
+    ) -> Result<(), DescompositionError> {
+        Price::lock_graph(&mut *conn, parent_price_id).await?;
+        let prices = Price::read_by_ids(&mut *conn, &[parent_price_id, component_price_id]).await?;
+        let node = |price: &Price| PathNode { id: price.id, code: price.code.clone(), version_id: price.version_id };
+        let invalid = |violation, path| Err(DescompositionError::Invalid { violation, path });
+        let find = |id: i32| prices.iter().find(|p| p.id == id);
+        let (Some(parent), Some(component)) = (find(parent_price_id), find(component_price_id)) else {
+            let missing = [parent_price_id, component_price_id]
+                .into_iter()
+                .filter(|id| find(*id).is_none())
+                .map(|id| PathNode { id, code: id.to_string(), version_id: 0 })
+                .collect();
+            return invalid(Violation::MissingPrice, missing);
+        };
+        let path = vec![node(parent), node(component)];
+        if parent.id == component.id {
+            return invalid(Violation::SelfReference, path);
+        }
+        if parent.price_type == PriceType::Base {
+            return invalid(Violation::BaseParent, path);
+        }
+        if parent.version_id != component.version_id {
+            return invalid(Violation::CrossVersion, path);
+        }
+        // Caminos desde el componente hacia abajo: si alguno llega al padre, hay ciclo
+        let sql = format!(
+            r#"
+            WITH RECURSIVE reach (price_id, path) AS (
+                SELECT component_price_id, ARRAY[parent_price_id, component_price_id]
+                FROM {table}
+                WHERE parent_price_id = $1 AND id IS DISTINCT FROM $3
+                UNION ALL
+                SELECT d.component_price_id, r.path || d.component_price_id
+                FROM {table} d
+                JOIN reach r ON d.parent_price_id = r.price_id
+                WHERE d.id IS DISTINCT FROM $3 AND NOT d.component_price_id = ANY(r.path)
+            )
+            SELECT path FROM reach WHERE price_id = $2 ORDER BY array_length(path, 1) LIMIT 1
+            "#,
+            table = Self::TABLE
+        );
+        debug!("Validate: {}", &sql);
+        let cycle: Option<Vec<i32>> = sqlx::query_scalar(&sql)
+            .bind(component_price_id)
+            .bind(parent_price_id)
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?;
+        if let Some(ids) = cycle {
+            let nodes = Price::read_by_ids(&mut *conn, &ids).await?;
+            let mut path = vec![node(parent)];
+            path.extend(ids.iter().filter_map(|id| nodes.iter().find(|p| p.id == *id)).map(node));
+            return invalid(Violation::Cycle, path);
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
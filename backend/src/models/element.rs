@@ -5,16 +5,18 @@ use sqlx::{
     Postgres,
     QueryBuilder,
     Error, FromRow, Row,
-    postgres::{PgPool, PgRow},
+    postgres::{PgExecutor, PgPool, PgRow},
+    types::BigDecimal,
 };
 use tracing::debug;
 use super::{
+    project_member::{append_member_filter, MemberScope, ScopeParent, Scoped},
     Paginable,
     Filterable,
     UtcTimestamp,
 };
 use macros::axum_crud;
-use std::fmt;
+use std::{collections::HashMap, fmt};
 
 // =================================================================
 // 1. ESTRUCTURAS DE DATOS (STRUCTS)
@@ -48,7 +50,7 @@ impl Filterable for Option<ElementType> {
     }
 }
 
-#[axum_crud(path = "/elements", new = "NewElement", params = "ElementParams")]
+#[axum_crud(path = "/elements", new = "NewElement", params = "ElementParams", scope = "project")]
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct Element {
     pub id: i32,
@@ -80,9 +82,31 @@ pub struct NewElement {
     pub description: Option<String>,
 }
 
+/// Fila del árbol de un presupuesto (ver `Element::read_tree`): un elemento y,
+/// si hay mediciones en él o en sus descendientes, uno de los precios medidos.
+#[derive(Debug, FromRow)]
+pub struct ElementTreeRow {
+    pub id: i32,
+    pub parent_id: Option<i32>,
+    pub element_type: ElementType,
+    pub budget_code: String,
+    pub description: Option<String>,
+    // 0 en los elementos raíz
+    pub depth: i32,
+    // Número de hijos, aunque no se devuelvan por el límite de profundidad
+    pub children: i64,
+    pub price_id: Option<i32>,
+    // Cantidad e importe del precio en todo el subárbol
+    pub quantity: Option<BigDecimal>,
+    pub amount: Option<BigDecimal>,
+}
+
 #[derive(Debug, serde::Deserialize, macros::Paginable)]
 pub struct ElementParams {
     pub id: Option<i32>,
+    // Proyectos a los que se restringen los resultados (ver `MemberScope`)
+    #[serde(skip)]
+    pub member_scope: Option<MemberScope>,
 
     pub parent_id: Option<i32>,
     pub version_id: Option<i32>,
@@ -97,12 +121,26 @@ pub struct ElementParams {
     pub asc: Option<bool>,
 }
 
+impl Scoped for Element {
+    fn scope_parent(&self) -> Option<ScopeParent> {
+        Some(ScopeParent::Budget(self.budget_id))
+    }
+}
+
+impl Scoped for NewElement {
+    fn scope_parent(&self) -> Option<ScopeParent> {
+        Some(ScopeParent::Budget(self.budget_id))
+    }
+}
+
 // =================================================================
 // 2. MÉTODOS CRUD (ASOCIADOS DIRECTAMENTE AL STRUCT)
 // =================================================================
 
 impl Element {
     const TABLE: &str = "elements";
+    // Proyecto de cada fila, para filtrar por los miembros del proyecto
+    const PROJECT: &str = "(SELECT project_id FROM budgets WHERE budgets.id = elements.budget_id)";
     const INSERT_QUERY: &str = r#"
         (
             budget_id,
@@ -149,6 +187,7 @@ impl Element {
         let sql = format!("SELECT COUNT(*) FROM {} WHERE 1=1", Self::TABLE);
         debug!("Count paged: {}", &sql);
         let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(&sql);
+        append_member_filter(&mut query_builder, Self::PROJECT, params.member_scope.as_ref());
         params.parent_id.append_filter(&mut query_builder, "parent_id");
         params.version_id.append_filter(&mut query_builder, "version_id");
         params.element_type.append_filter(&mut query_builder, "element_type");
@@ -166,6 +205,7 @@ impl Element {
         let sql = format!("SELECT * FROM {} WHERE 1=1", Self::TABLE);
         debug!("Read paged: {}", &sql);
         let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(&sql);
+        append_member_filter(&mut query_builder, Self::PROJECT, params.member_scope.as_ref());
         params.parent_id.append_filter(&mut query_builder, "parent_id");
         params.version_id.append_filter(&mut query_builder, "version_id");
         params.element_type.append_filter(&mut query_builder, "element_type");
@@ -237,6 +277,100 @@ impl Element {
             .fetch_one(pg_pool)
             .await
     }
+    // =================================================================
+    // E: OTHERS
+    // =================================================================
+    /// Recupera todos los elementos de un presupuesto ordenados por su código.
+    pub async fn read_by_budget<'e, E>(executor: E, budget_id: i32) -> Result<Vec<Self>, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!("SELECT * FROM {} WHERE budget_id = $1 ORDER BY budget_code", Self::TABLE);
+        debug!("Read by budget: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(budget_id)
+            .fetch_all(executor)
+            .await
+    }
+
+    /// Árbol de capítulos y líneas de un presupuesto en una sola consulta
+    /// recursiva, en profundidad y ordenado por el código en el presupuesto.
+    /// Cada elemento acumula, por precio, las mediciones de todo su subárbol.
+    /// El importe de cada precio medido en una línea es la cantidad por el
+    /// precio de `unit_prices`, redondeado a `decimals`, como en el resumen.
+    /// `max_depth` limita los niveles que se devuelven, pero no los importes.
+    pub async fn read_tree(
+        pg_pool: &PgPool,
+        budget_id: i32,
+        max_depth: Option<i32>,
+        unit_prices: &HashMap<i32, BigDecimal>,
+        decimals: i32,
+    ) -> Result<Vec<ElementTreeRow>, Error> {
+        let (price_ids, prices): (Vec<i32>, Vec<BigDecimal>) =
+            unit_prices.iter().map(|(id, price)| (*id, price.clone())).unzip();
+        let sql = format!(
+            r#"
+            WITH RECURSIVE tree (id, parent_id, element_type, budget_code, description, depth, path, codes) AS (
+                SELECT e.id, e.parent_id, e.element_type, e.budget_code, e.description, 0,
+                    ARRAY[e.id], ARRAY[e.budget_code::TEXT]
+                FROM {table} e
+                WHERE e.budget_id = $1 AND (
+                    -- Un padre de otro presupuesto se trata como raíz
+                    e.parent_id IS NULL
+                    OR NOT EXISTS (SELECT 1 FROM {table} p WHERE p.id = e.parent_id AND p.budget_id = $1)
+                )
+                UNION ALL
+                SELECT e.id, e.parent_id, e.element_type, e.budget_code, e.description, t.depth + 1,
+                    t.path || e.id, t.codes || e.budget_code::TEXT
+                FROM {table} e
+                JOIN tree t ON e.parent_id = t.id
+                WHERE e.budget_id = $1 AND NOT e.id = ANY(t.path)
+            ),
+            items AS (
+                SELECT m.element_id, m.price_id, SUM(m.measured_quantity) AS quantity,
+                    ROUND(SUM(m.measured_quantity) * COALESCE(MAX(p.unit_price), 0), $5) AS amount
+                FROM measurements m
+                JOIN tree t ON t.id = m.element_id
+                LEFT JOIN UNNEST($2::INTEGER[], $3::NUMERIC[]) AS p (price_id, unit_price) ON p.price_id = m.price_id
+                GROUP BY m.element_id, m.price_id
+            )
+            SELECT t.id, t.parent_id, t.element_type, t.budget_code, t.description, t.depth,
+                (SELECT COUNT(*) FROM tree c WHERE c.parent_id = t.id) AS children,
+                i.price_id, SUM(i.quantity) AS quantity, SUM(i.amount) AS amount
+            FROM tree t
+            JOIN tree d ON t.id = ANY(d.path)
+            LEFT JOIN items i ON i.element_id = d.id
+            WHERE $4::INTEGER IS NULL OR t.depth <= $4
+            GROUP BY t.id, t.parent_id, t.element_type, t.budget_code, t.description, t.depth, t.codes, i.price_id
+            ORDER BY t.codes, i.price_id
+            "#,
+            table = Self::TABLE
+        );
+        debug!("Read tree: {}", &sql);
+        sqlx::query_as::<_, ElementTreeRow>(&sql)
+            .bind(budget_id)
+            .bind(price_ids)
+            .bind(prices)
+            .bind(max_depth)
+            .bind(decimals)
+            .fetch_all(pg_pool)
+            .await
+    }
+
+    /// Asigna la versión del catálogo a todos los elementos de un presupuesto.
+    pub async fn set_budget_version<'e, E>(executor: E, budget_id: i32, version_id: i32) -> Result<u64, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!("UPDATE {} SET version_id = $2 WHERE budget_id = $1", Self::TABLE);
+        debug!("Set budget version: {}", &sql);
+        sqlx::query(&sql)
+            .bind(budget_id)
+            .bind(version_id)
+            .execute(executor)
+            .await
+            .map(|result| result.rows_affected())
+    }
 }
 
 #[cfg(test)]
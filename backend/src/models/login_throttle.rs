@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    Error, FromRow, Type,
+    postgres::PgPool,
+};
+use tracing::debug;
+use super::UtcTimestamp;
+use std::fmt;
+
+// =================================================================
+// 1. ESTRUCTURAS DE DATOS (STRUCTS)
+// =================================================================
+
+/// A qué se aplica el contador de fallos.
+#[derive(Debug, Type, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "login_scope_enum", rename_all = "lowercase")] // Nombre del ENUM en PostgreSQL
+pub enum LoginScope {
+    // Cuenta, por su email en minúsculas (exista o no)
+    #[serde(rename = "account")]
+    Account,
+    // Dirección IP del cliente
+    #[serde(rename = "ip")]
+    Ip,
+}
+
+impl fmt::Display for LoginScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Account => "account",
+            Self::Ip => "ip",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl LoginScope {
+    /// Fallos seguidos a partir de los que se bloquea.
+    pub fn max_failures(&self) -> i32 {
+        match self {
+            Self::Account => 5,
+            // Desde una IP pueden entrar varios usuarios legítimos
+            Self::Ip => 20,
+        }
+    }
+}
+
+/// Fallos de login seguidos de una cuenta o de una IP.
+#[derive(Debug, FromRow, Serialize)]
+pub struct LoginThrottle {
+    pub scope: LoginScope,
+    pub key: String,
+    pub failures: i32,
+    pub last_failure_at: UtcTimestamp,
+    pub locked_until: Option<UtcTimestamp>,
+}
+
+// =================================================================
+// 2. MÉTODOS (ASOCIADOS DIRECTAMENTE AL STRUCT)
+// =================================================================
+
+impl LoginThrottle {
+    const TABLE: &str = "login_throttles";
+    // Bloqueo al alcanzar el máximo de fallos; se duplica con cada fallo más
+    pub const BASE_LOCKOUT_SECONDS: i64 = 30;
+    pub const MAX_LOCKOUT_SECONDS: i64 = 3600;
+    // Tras este tiempo sin fallos el contador vuelve a empezar
+    pub const RESET_AFTER_MINUTES: i32 = 60;
+
+    /// Duración del bloqueo con `failures` fallos seguidos, o `None` si aún no
+    /// se ha alcanzado el máximo.
+    pub fn lockout(scope: LoginScope, failures: i32) -> Option<chrono::Duration> {
+        let excess = failures - scope.max_failures();
+        if excess < 0 {
+            return None;
+        }
+        let seconds = Self::BASE_LOCKOUT_SECONDS
+            .checked_shl(excess.min(32) as u32)
+            .unwrap_or(Self::MAX_LOCKOUT_SECONDS)
+            .min(Self::MAX_LOCKOUT_SECONDS);
+        Some(chrono::Duration::seconds(seconds))
+    }
+
+    // =================================================================
+    // R: READ
+    // =================================================================
+    /// Hasta cuándo está bloqueada la cuenta o la IP, si lo está.
+    pub async fn locked_until(pg_pool: &PgPool, scope: LoginScope, key: &str) -> Result<Option<UtcTimestamp>, Error> {
+        let sql = format!(
+            "SELECT locked_until FROM {} WHERE scope = $1 AND key = $2 AND locked_until > NOW()",
+            Self::TABLE
+        );
+        debug!("Locked until: {}", &sql);
+        sqlx::query_scalar::<_, UtcTimestamp>(&sql)
+            .bind(scope)
+            .bind(key)
+            .fetch_optional(pg_pool)
+            .await
+    }
+
+    // =================================================================
+    // U: UPDATE (Actualizar)
+    // =================================================================
+    /// Suma un fallo y, si se alcanza el máximo, bloquea. Devuelve el contador
+    /// actualizado.
+    pub async fn record_failure(pg_pool: &PgPool, scope: LoginScope, key: &str) -> Result<Self, Error> {
+        let mut tx = pg_pool.begin().await?;
+        let sql = format!(
+            r#"INSERT INTO {table} (scope, key, failures) VALUES ($1, $2, 1)
+            ON CONFLICT (scope, key) DO UPDATE SET
+                failures = CASE
+                    WHEN {table}.last_failure_at < NOW() - $3 * INTERVAL '1 minute' THEN 1
+                    ELSE {table}.failures + 1
+                END,
+                last_failure_at = NOW()
+            RETURNING *"#,
+            table = Self::TABLE
+        );
+        debug!("Record failure: {}", &sql);
+        let mut throttle = sqlx::query_as::<_, Self>(&sql)
+            .bind(scope)
+            .bind(key)
+            .bind(Self::RESET_AFTER_MINUTES)
+            .fetch_one(&mut *tx)
+            .await?;
+        if let Some(lockout) = Self::lockout(scope, throttle.failures) {
+            let sql = format!("UPDATE {} SET locked_until = $3 WHERE scope = $1 AND key = $2 RETURNING *", Self::TABLE);
+            debug!("Lock: {}", &sql);
+            throttle = sqlx::query_as::<_, Self>(&sql)
+                .bind(scope)
+                .bind(key)
+                .bind(chrono::Utc::now() + lockout)
+                .fetch_one(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(throttle)
+    }
+
+    // =================================================================
+    // D: DELETE (Borrar y devolver el valor)
+    // =================================================================
+    /// Olvida los fallos (login correcto o desbloqueo manual). Devuelve si la
+    /// cuenta o la IP estaba bloqueada.
+    pub async fn clear(pg_pool: &PgPool, scope: LoginScope, key: &str) -> Result<bool, Error> {
+        let sql = format!(
+            "DELETE FROM {} WHERE scope = $1 AND key = $2 RETURNING locked_until > NOW()",
+            Self::TABLE
+        );
+        debug!("Clear: {}", &sql);
+        sqlx::query_scalar::<_, Option<bool>>(&sql)
+            .bind(scope)
+            .bind(key)
+            .fetch_optional(pg_pool)
+            .await
+            .map(|locked| locked.flatten().unwrap_or(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockout() {
+        let seconds = |scope, failures| LoginThrottle::lockout(scope, failures).map(|d| d.num_seconds());
+        assert_eq!(seconds(LoginScope::Account, 4), None);
+        assert_eq!(seconds(LoginScope::Account, 5), Some(30));
+        assert_eq!(seconds(LoginScope::Account, 6), Some(60));
+        assert_eq!(seconds(LoginScope::Account, 8), Some(240));
+        assert_eq!(seconds(LoginScope::Account, 12), Some(3600));
+        assert_eq!(seconds(LoginScope::Account, 500), Some(3600));
+        assert_eq!(seconds(LoginScope::Ip, 19), None);
+        assert_eq!(seconds(LoginScope::Ip, 20), Some(30));
+    }
+}
@@ -4,14 +4,18 @@ use sqlx::{
     Postgres,
     QueryBuilder,
     Error, FromRow, Row,
-    postgres::{PgPool, PgRow},
+    postgres::{PgExecutor, PgPool, PgRow},
     types::BigDecimal,
 };
+use bigdecimal::RoundingMode;
 use tracing::debug;
+use crate::formula::Formula;
 use super::{
+    project_member::{append_member_filter, MemberScope, ScopeParent, Scoped},
     Paginable,
     Filterable,
     UtcTimestamp,
+    Unit,
 };
 use serde_json::Value;
 use macros::axum_crud;
@@ -20,7 +24,7 @@ use macros::axum_crud;
 // 1. ESTRUCTURAS DE DATOS (STRUCTS)
 // =================================================================
 /// Representa una fila en la tabla 'measurements'
-#[axum_crud(path = "/measurements", new = "NewMeasurement", params = "MeasurementParams")]
+#[axum_crud(path = "/measurements", new = "NewMeasurement", params = "MeasurementParams", scope = "project")]
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct Measurement {
     pub id: i32,
@@ -30,6 +34,8 @@ pub struct Measurement {
     // Los parámetros variables de la medición (ej: {"largo": 10.0})
     pub params_json: Value, 
     pub measurement_text: Option<String>,
+    // Se calcula con la fórmula de la unidad del precio: lo que envíe el cliente se ignora
+    #[serde(default)]
     pub measured_quantity: BigDecimal, // NUMERIC(10, 4)
     pub created_at: UtcTimestamp,
     pub updated_at: UtcTimestamp,
@@ -43,12 +49,17 @@ pub struct NewMeasurement {
     // Los parámetros variables de la medición (ej: {"largo": 10.0})
     pub params_json: Value, 
     pub measurement_text: Option<String>,
+    // Se calcula con la fórmula de la unidad del precio: lo que envíe el cliente se ignora
+    #[serde(default)]
     pub measured_quantity: BigDecimal, // NUMERIC(10, 4)
 }
 
 #[derive(Debug, serde::Deserialize, macros::Paginable)]
 pub struct MeasurementParams {
     pub id: Option<i32>,
+    // Proyectos a los que se restringen los resultados (ver `MemberScope`)
+    #[serde(skip)]
+    pub member_scope: Option<MemberScope>,
 
     pub measurement_text: Option<String>,
     pub measured_quantity: Option<BigDecimal>,
@@ -59,12 +70,31 @@ pub struct MeasurementParams {
     pub asc: Option<bool>,
 }
 
+impl Scoped for Measurement {
+    fn scope_parent(&self) -> Option<ScopeParent> {
+        Some(ScopeParent::Element(self.element_id))
+    }
+}
+
+impl Scoped for NewMeasurement {
+    fn scope_parent(&self) -> Option<ScopeParent> {
+        Some(ScopeParent::Element(self.element_id))
+    }
+}
+
 // =================================================================
 // 2. MÉTODOS CRUD (ASOCIADOS DIRECTAMENTE AL STRUCT)
 // =================================================================
 
 impl Measurement {
     const TABLE: &str = "measurements";
+    // Proyecto de cada fila, para filtrar por los miembros del proyecto
+    const PROJECT: &str = r#"(
+        SELECT b.project_id FROM elements e JOIN budgets b ON b.id = e.budget_id
+        WHERE e.id = measurements.element_id
+    )"#;
+    const QUANTITY_SCALE: i64 = 4;
+    const MAX_QUANTITY: i64 = 1_000_000; // NUMERIC(10, 4)
     const INSERT_QUERY: &str = r#"
         INSERT INTO measurements (
             element_id,
@@ -107,6 +137,7 @@ impl Measurement {
         let sql = format!("SELECT COUNT(*) FROM {} WHERE 1=1", Self::TABLE);
         debug!("Count paged: {}", &sql);
         let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(&sql);
+        append_member_filter(&mut query_builder, Self::PROJECT, params.member_scope.as_ref());
         params.measurement_text.append_filter(&mut query_builder, "measurement_text");
         params.measured_quantity.append_filter(&mut query_builder, "measured_quantity");
         query_builder
@@ -120,6 +151,7 @@ impl Measurement {
         let sql = format!("SELECT * FROM {} WHERE 1=1", Self::TABLE);
         debug!("Read paged: {}", &sql);
         let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(&sql);
+        append_member_filter(&mut query_builder, Self::PROJECT, params.member_scope.as_ref());
         params.measurement_text.append_filter(&mut query_builder, "measurement_text");
         params.measured_quantity.append_filter(&mut query_builder, "measured_quantity");
         if let Some(sort_by) = &params.sort_by {
@@ -140,7 +172,9 @@ impl Measurement {
     // C: CREATE (Crear)
     // =================================================================
     /// Inserta un nuevo registro en la base de datos y devuelve el objeto creado.
+    /// La cantidad medida se calcula a partir de `params_json`.
     pub async fn create(pg_pool: &PgPool, item: NewMeasurement) -> Result<Self, Error> {
+        let measured_quantity = Self::quantity(pg_pool, item.price_id, &item.params_json).await?;
         let sql = format!("{} RETURNING *", Self::INSERT_QUERY);
         debug!("Create: {}", &sql);
         sqlx::query_as::<_, Self>(&sql)
@@ -148,7 +182,7 @@ impl Measurement {
         .bind(item.price_id)
         .bind(item.params_json)
         .bind(item.measurement_text)
-        .bind(item.measured_quantity)
+        .bind(measured_quantity)
         .fetch_one(pg_pool)
         .await
     }
@@ -157,7 +191,9 @@ impl Measurement {
     // U: UPDATE (Actualizar)
     // =================================================================
     /// Actualiza un registro por ID y devuelve el objeto actualizado.
+    /// La cantidad medida se vuelve a calcular a partir de `params_json`.
     pub async fn update(pg_pool: &PgPool, item: Self) -> Result<Self, Error> {
+        let measured_quantity = Self::quantity(pg_pool, item.price_id, &item.params_json).await?;
         let sql = format!("UPDATE {} SET {} WHERE id = $1 RETURNING *", Self::TABLE, Self::UPDATE_QUERY);
         debug!("Update: {}", &sql);
         sqlx::query_as::<_, Self>(&sql)
@@ -166,7 +202,7 @@ impl Measurement {
         .bind(item.price_id)
         .bind(item.params_json)
         .bind(item.measurement_text)
-        .bind(item.measured_quantity)
+        .bind(measured_quantity)
         .fetch_one(pg_pool)
         .await
     }
@@ -183,4 +219,92 @@ impl Measurement {
             .fetch_one(pg_pool)
             .await
     }
+    // =================================================================
+    // E: OTHERS
+    // =================================================================
+    /// Recupera todas las mediciones de los elementos de un presupuesto.
+    pub async fn read_by_budget<'e, E>(executor: E, budget_id: i32) -> Result<Vec<Self>, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!(
+            "SELECT m.* FROM {} m JOIN elements e ON e.id = m.element_id WHERE e.budget_id = $1 ORDER BY m.id",
+            Self::TABLE
+        );
+        debug!("Read by budget: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(budget_id)
+            .fetch_all(executor)
+            .await
+    }
+    /// Precios medidos en los elementos de un presupuesto, sin repetir.
+    pub async fn read_price_ids_by_budget(pg_pool: &PgPool, budget_id: i32) -> Result<Vec<i32>, Error> {
+        let sql = format!(
+            "SELECT DISTINCT m.price_id FROM {} m JOIN elements e ON e.id = m.element_id WHERE e.budget_id = $1 ORDER BY m.price_id",
+            Self::TABLE
+        );
+        debug!("Read price ids by budget: {}", &sql);
+        sqlx::query_scalar::<_, i32>(&sql)
+            .bind(budget_id)
+            .fetch_all(pg_pool)
+            .await
+    }
+    /// Calcula la cantidad evaluando la fórmula de la unidad del precio con los
+    /// parámetros de la línea, que deben coincidir con las variables de la fórmula.
+    pub async fn quantity(pg_pool: &PgPool, price_id: i32, params: &Value) -> Result<BigDecimal, Error> {
+        let unit = Unit::read_by_price(pg_pool, price_id)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("Price {} not found", price_id)))?;
+        Self::quantity_for_unit(&unit, params)
+    }
+
+    /// Como `quantity`, con la unidad del precio ya cargada.
+    pub fn quantity_for_unit(unit: &Unit, params: &Value) -> Result<BigDecimal, Error> {
+        let formula = Formula::parse(&unit.formula)
+            .map_err(|e| Error::InvalidArgument(format!("Invalid formula '{}': {}", unit.formula, e)))?;
+        let Some(values) = params.as_object() else {
+            return Err(Error::InvalidArgument("params_json must be an object".to_string()));
+        };
+        let unexpected: Vec<&str> = values
+            .keys()
+            .filter(|k| !formula.variables().contains(k))
+            .map(String::as_str)
+            .collect();
+        if !unexpected.is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "Unexpected params for unit '{}' ({}): {}",
+                unit.symbol,
+                unit.formula,
+                unexpected.join(", ")
+            )));
+        }
+        let quantity = formula.evaluate_json(params).map_err(|e| {
+            Error::InvalidArgument(format!("Invalid params for unit '{}' ({}): {}", unit.symbol, unit.formula, e))
+        })?;
+        let quantity = quantity.with_scale_round(Self::QUANTITY_SCALE, RoundingMode::HalfUp);
+        if quantity.abs() >= Self::MAX_QUANTITY {
+            return Err(Error::InvalidArgument(format!(
+                "Quantity {} is out of range for unit '{}' ({})",
+                quantity, unit.symbol, unit.formula
+            )));
+        }
+        Ok(quantity)
+    }
+
+    /// Cambia el precio de una medición con la cantidad ya calculada para él
+    /// (ver `quantity`).
+    pub async fn set_price<'e, E>(executor: E, id: i32, price_id: i32, measured_quantity: &BigDecimal) -> Result<(), Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!("UPDATE {} SET price_id = $2, measured_quantity = $3 WHERE id = $1", Self::TABLE);
+        debug!("Set price: {}", &sql);
+        sqlx::query(&sql)
+            .bind(id)
+            .bind(price_id)
+            .bind(measured_quantity)
+            .execute(executor)
+            .await
+            .map(|_| ())
+    }
 }
@@ -1,17 +1,25 @@
+pub mod api_key;
+pub mod audit_entry;
 pub mod descomposition;
 pub mod element;
+pub mod login_throttle;
 pub mod measurement;
 pub mod price;
 pub mod project;
+pub mod project_member;
 pub mod role;
+pub mod session;
 pub mod unit;
 pub mod user;
+pub mod user_token;
 pub mod version;
 pub mod budget;
+pub mod budget_status_change;
 mod data;
 mod response;
 mod filterable;
 mod paginable;
+pub mod token;
 pub mod token_claims;
 
 pub type UtcTimestamp = chrono::DateTime<chrono::Utc>;
@@ -20,20 +28,28 @@ pub use filterable::Filterable;
 pub use paginable::Paginable;
 pub use token_claims::TokenClaims;
 
-pub use budget::Budget;
+pub use api_key::{ApiKey, ApiKeyScope, NewApiKey};
+pub use audit_entry::{AuditEntry, AuditAction, NewAuditEntry};
+pub use budget::{Budget, BudgetError, BudgetRates, BudgetStatus, CloneBudget};
+pub use budget_status_change::{BudgetStatusChange, NewBudgetStatusChange};
 pub use descomposition::{Descomposition, NewDescomposition, DescompositionParams};
 
+pub use login_throttle::{LoginThrottle, LoginScope};
 pub use measurement::Measurement;
 pub use price::{Price, NewPrice, PriceParams};
-pub use element::{Element, NewElement, ElementParams, ElementType};
+pub use element::{Element, NewElement, ElementParams, ElementTreeRow, ElementType};
 pub use project::{Project, NewProject, ProjectParams};
+pub use project_member::{ProjectMember, NewProjectMember, ProjectMemberParams, MemberScope, ScopeParent, Scoped};
 pub use role::{Role, NewRole, RoleParams};
+pub use session::Session;
 pub use unit::{Unit, NewUnit, UnitParams};
 pub use user::{User, NewUser, UserParams, UserPass};
+pub use user_token::{UserToken, TokenPurpose};
 pub use version::{Version, NewVersion, VersionParams};
 
 pub use data::Data;
 pub use response::{
+    ApiError,
     ApiResponse,
     CustomResponse,
     EmptyResponse,
@@ -42,9 +58,12 @@ pub use response::{
 };
 
 use sqlx::postgres::PgPool;
+use std::sync::Arc;
+use crate::mailer::Mailer;
 
 pub struct AppState {
     pub pool: PgPool,
     pub secret: String,
     pub static_dir: String,
+    pub mailer: Arc<dyn Mailer>,
 }
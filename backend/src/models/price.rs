@@ -5,7 +5,7 @@ use sqlx::{
     Postgres,
     QueryBuilder,
     Error, FromRow, Row,
-    postgres::{PgPool, PgRow},
+    postgres::{PgExecutor, PgPool, PgRow},
     types::BigDecimal,
 };
 use tracing::debug;
@@ -49,7 +49,7 @@ impl Filterable for Option<PriceType> {
 }
 
 /// Representa una fila en la tabla 'prices'
-#[axum_crud(path = "/prices", new = "NewPrice", params = "PriceParams")]
+#[axum_crud(path = "/prices", new = "NewPrice", params = "PriceParams", write = "master")]
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct Price {
     pub id: i32,
@@ -182,7 +182,10 @@ impl Price {
     // C: CREATE (Crear)
     // =================================================================
     /// Inserta un nuevo registro en la base de datos y devuelve el objeto creado.
-    pub async fn create(pg_pool: &PgPool, item: NewPrice) -> Result<Self, Error> {
+    pub async fn create<'e, E>(executor: E, item: NewPrice) -> Result<Self, Error>
+    where
+        E: PgExecutor<'e>,
+    {
         let sql = format!("INSERT INTO {} {} RETURNING *", Self::TABLE, Self::INSERT_QUERY);
         debug!("Create: {}", &sql);
         sqlx::query_as::<_, Self>(&sql)
@@ -192,7 +195,7 @@ impl Price {
         .bind(item.base_price)
         .bind(item.unit_id)
         .bind(item.price_type)
-        .fetch_one(pg_pool)
+        .fetch_one(executor)
         .await
     }
 
@@ -200,10 +203,38 @@ impl Price {
     // U: UPDATE (Actualizar)
     // =================================================================
     /// Actualiza un registro por ID y devuelve el objeto actualizado.
+    /// Un precio con descomposiciones no puede cambiar de versión y uno con
+    /// componentes no puede pasar a ser un precio base.
     pub async fn update(pg_pool: &PgPool, item: Self) -> Result<Self, Error> {
+        let mut tx = pg_pool.begin().await?;
+        Self::lock_graph(&mut *tx, item.id).await?;
+        let sql = format!(
+            r#"SELECT p.version_id, p.price_type,
+                EXISTS (SELECT 1 FROM descompositions d WHERE d.parent_price_id = p.id),
+                EXISTS (SELECT 1 FROM descompositions d WHERE d.component_price_id = p.id)
+            FROM {} p WHERE p.id = $1 FOR UPDATE"#,
+            Self::TABLE
+        );
+        debug!("Update edges: {}", &sql);
+        let current: Option<(i32, PriceType, bool, bool)> = sqlx::query_as(&sql)
+            .bind(item.id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if let Some((version_id, price_type, has_components, has_parents)) = current {
+            if version_id != item.version_id && (has_components || has_parents) {
+                return Err(Error::InvalidArgument(
+                    "A price with descompositions cannot change its version".to_string(),
+                ));
+            }
+            if price_type == PriceType::Decomposed && item.price_type == PriceType::Base && has_components {
+                return Err(Error::InvalidArgument(
+                    "A price with components cannot become a base price".to_string(),
+                ));
+            }
+        }
         let sql = format!("UPDATE {} SET {} WHERE id = $1 RETURNING *", Self::TABLE, Self::UPDATE_QUERY);
         debug!("Update: {}", &sql);
-        sqlx::query_as::<_, Self>(&sql)
+        let updated = sqlx::query_as::<_, Self>(&sql)
         .bind(item.id)
         .bind(item.version_id)
         .bind(item.code)
@@ -211,8 +242,10 @@ impl Price {
         .bind(item.base_price)
         .bind(item.unit_id)
         .bind(item.price_type)
-        .fetch_one(pg_pool)
-        .await
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(updated)
     }
 
     // =================================================================
@@ -227,6 +260,109 @@ impl Price {
             .fetch_one(pg_pool)
             .await
     }
+    // =================================================================
+    // E: OTHERS
+    // =================================================================
+    /// Bloquea, hasta el final de la transacción, los cambios en el grafo de
+    /// descomposiciones de la versión del precio. Serializa las comprobaciones
+    /// de ciclos: sin él, A -> B y B -> A podrían validarse a la vez.
+    pub async fn lock_graph<'e, E>(executor: E, price_id: i32) -> Result<(), Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!(
+            "SELECT pg_advisory_xact_lock('descompositions'::regclass::oid::int, version_id) FROM {} WHERE id = $1",
+            Self::TABLE
+        );
+        debug!("Lock graph: {}", &sql);
+        sqlx::query(&sql).bind(price_id).execute(executor).await?;
+        Ok(())
+    }
+    /// Recupera los precios cuyos identificadores se indican.
+    pub async fn read_by_ids<'e, E>(executor: E, ids: &[i32]) -> Result<Vec<Self>, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!("SELECT * FROM {} WHERE id = ANY($1) ORDER BY code", Self::TABLE);
+        debug!("Read by ids: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(ids)
+            .fetch_all(executor)
+            .await
+    }
+
+    /// Recupera todos los precios de una versión.
+    pub async fn read_by_version<'e, E>(executor: E, version_id: i32) -> Result<Vec<Self>, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!("SELECT * FROM {} WHERE version_id = $1 ORDER BY code", Self::TABLE);
+        debug!("Read by version: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(version_id)
+            .fetch_all(executor)
+            .await
+    }
+
+    /// Copia en la versión `to_version_id` los precios indicados de la versión
+    /// `from_version_id`, con el precio que les corresponde en `base_prices`.
+    /// Devuelve el número de precios copiados.
+    pub async fn copy_to_version<'e, E>(
+        executor: E,
+        from_version_id: i32,
+        to_version_id: i32,
+        ids: &[i32],
+        base_prices: &[BigDecimal],
+    ) -> Result<u64, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!(
+            r#"INSERT INTO {table} (version_id, code, description, base_price, unit_id, price_type)
+            SELECT $2, p.code, p.description, n.base_price, p.unit_id, p.price_type
+            FROM {table} p
+            JOIN UNNEST($3::INTEGER[], $4::NUMERIC[]) AS n (id, base_price) ON n.id = p.id
+            WHERE p.version_id = $1
+            ORDER BY p.id"#,
+            table = Self::TABLE
+        );
+        debug!("Copy to version: {}", &sql);
+        sqlx::query(&sql)
+            .bind(from_version_id)
+            .bind(to_version_id)
+            .bind(ids)
+            .bind(base_prices)
+            .execute(executor)
+            .await
+            .map(|result| result.rows_affected())
+    }
+
+    /// Recupera los precios con un código en todas las versiones.
+    pub async fn read_by_code<'e, E>(executor: E, code: &str) -> Result<Vec<Self>, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!("SELECT * FROM {} WHERE code = $1 ORDER BY version_id", Self::TABLE);
+        debug!("Read by code: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(code)
+            .fetch_all(executor)
+            .await
+    }
+
+    /// Recupera los precios de una versión con los códigos que se indican.
+    pub async fn read_by_codes<'e, E>(executor: E, version_id: i32, codes: &[String]) -> Result<Vec<Self>, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!("SELECT * FROM {} WHERE version_id = $1 AND code = ANY($2) ORDER BY code", Self::TABLE);
+        debug!("Read by codes: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(version_id)
+            .bind(codes)
+            .fetch_all(executor)
+            .await
+    }
 }
 
 #[cfg(test)]
@@ -7,17 +7,19 @@ use sqlx::{
 };
 use tracing::debug;
 use super::{
+    project_member::{append_member_filter, MemberScope, NewProjectMember, ProjectMember, ScopeParent, Scoped},
     Paginable,
     Filterable,
     UtcTimestamp,
 };
+use crate::http::permissions::Level;
 use macros::axum_crud;
 
 // =================================================================
 // 1. ESTRUCTURAS DE DATOS (STRUCTS)
 // =================================================================
 
-#[axum_crud(path = "/projects", new = "NewProject", params = "ProjectParams")]
+#[axum_crud(path = "/projects", new = "NewProject", params = "ProjectParams", scope = "project")]
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct Project {
     pub id: i32,
@@ -31,11 +33,17 @@ pub struct Project {
 pub struct NewProject {
     pub code: String,
     pub title: Option<String>,
+    // Ámbito de quien lo crea (ver `Project::create`)
+    #[serde(skip)]
+    pub member_scope: Option<MemberScope>,
 }
 
 #[derive(Debug, serde::Deserialize, macros::Paginable)]
 pub struct ProjectParams {
     pub id: Option<i32>,
+    // Proyectos a los que se restringen los resultados (ver `MemberScope`)
+    #[serde(skip)]
+    pub member_scope: Option<MemberScope>,
 
     pub code: Option<String>,
     pub title: Option<i32>,
@@ -46,12 +54,31 @@ pub struct ProjectParams {
     pub asc: Option<bool>,
 }
 
+// Los proyectos no tienen padre: basta con comprobar el propio proyecto
+impl Scoped for Project {
+    fn scope_parent(&self) -> Option<ScopeParent> {
+        None
+    }
+}
+
+impl Scoped for NewProject {
+    fn scope_parent(&self) -> Option<ScopeParent> {
+        None
+    }
+
+    fn set_member_scope(&mut self, scope: MemberScope) {
+        self.member_scope = Some(scope);
+    }
+}
+
 // =================================================================
 // 2. MÉTODOS CRUD (ASOCIADOS DIRECTAMENTE AL STRUCT)
 // =================================================================
 
 impl Project {
     const TABLE: &str = "projects";
+    // Proyecto de cada fila, para filtrar por los miembros del proyecto
+    const PROJECT: &str = "id";
     const INSERT_QUERY: &str = r#"
         (
             code,
@@ -88,6 +115,7 @@ impl Project {
         let sql = format!("SELECT COUNT(*) FROM {} WHERE 1=1", Self::TABLE);
         debug!("Count paged: {}", &sql);
         let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(&sql);
+        append_member_filter(&mut query_builder, Self::PROJECT, params.member_scope.as_ref());
         params.code.append_filter(&mut query_builder, "code");
         params.title.append_filter(&mut query_builder, "description");
         query_builder
@@ -101,6 +129,7 @@ impl Project {
         let sql = format!("SELECT * FROM {} WHERE 1=1", Self::TABLE);
         debug!("Read paged: {}", &sql);
         let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(&sql);
+        append_member_filter(&mut query_builder, Self::PROJECT, params.member_scope.as_ref());
         params.code.append_filter(&mut query_builder, "code");
         params.title.append_filter(&mut query_builder, "description");
         if let Some(sort_by) = &params.sort_by {
@@ -121,14 +150,32 @@ impl Project {
     // C: CREATE (Crear)
     // =================================================================
     /// Inserta un nuevo registro en la base de datos y devuelve el objeto creado.
+    /// Quien lo crea, si no es administrador, entra como miembro con el rol
+    /// de jefe (`master`) en la misma transacción. Una API key limitada a unos
+    /// proyectos no puede crear otros.
     pub async fn create(pg_pool: &PgPool, item: NewProject) -> Result<Self, Error> {
+        if item.member_scope.as_ref().is_some_and(|scope| scope.project_ids.is_some()) {
+            return Err(Error::InvalidArgument("A project-limited API key cannot create projects".to_string()));
+        }
+        let mut tx = pg_pool.begin().await?;
         let sql = format!("INSERT INTO {} {} RETURNING *", Self::TABLE, Self::INSERT_QUERY);
         debug!("Create: {}", &sql);
-        sqlx::query_as::<_, Self>(&sql)
+        let project = sqlx::query_as::<_, Self>(&sql)
         .bind(item.code)
         .bind(item.title)
-        .fetch_one(pg_pool)
-        .await
+        .fetch_one(&mut *tx)
+        .await?;
+        if let Some(user_id) = item.member_scope.and_then(|scope| scope.user_id) {
+            let sql = "SELECT id FROM roles WHERE name = $1";
+            debug!("Create member: {}", sql);
+            let role_id = sqlx::query_scalar::<_, i32>(sql)
+                .bind(Level::Master.name())
+                .fetch_one(&mut *tx)
+                .await?;
+            ProjectMember::create(&mut *tx, NewProjectMember { project_id: project.id, user_id, role_id }).await?;
+        }
+        tx.commit().await?;
+        Ok(project)
     }
 
     // =================================================================
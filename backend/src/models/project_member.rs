@@ -0,0 +1,369 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    Postgres,
+    QueryBuilder,
+    Error, FromRow, Row,
+    postgres::{PgExecutor, PgPool, PgRow},
+};
+use tracing::debug;
+use super::{
+    Paginable,
+    Filterable,
+    UtcTimestamp,
+};
+use crate::http::permissions::Level;
+use macros::axum_crud;
+
+// =================================================================
+// 1. ESTRUCTURAS DE DATOS (STRUCTS)
+// =================================================================
+
+/// Pertenencia de un usuario a un proyecto. Los usuarios que no son
+/// administradores solo ven los proyectos de los que son miembros, y en ellos
+/// solo escriben si su rol en el proyecto llega al nivel exigido.
+#[axum_crud(path = "/project-members", new = "NewProjectMember", params = "ProjectMemberParams", read = "admin", write = "admin")]
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct ProjectMember {
+    pub id: i32,
+    pub project_id: i32,
+    pub user_id: i32,
+    // Rol dentro del proyecto
+    pub role_id: i32,
+    pub created_at: UtcTimestamp,
+    pub updated_at: UtcTimestamp,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewProjectMember {
+    pub project_id: i32,
+    pub user_id: i32,
+    pub role_id: i32,
+}
+
+#[derive(Debug, serde::Deserialize, macros::Paginable)]
+pub struct ProjectMemberParams {
+    pub id: Option<i32>,
+
+    pub project_id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub role_id: Option<i32>,
+
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+    pub sort_by: Option<String>,
+    pub asc: Option<bool>,
+}
+
+/// Proyectos a los que se limitan las lecturas de un usuario: aquellos de
+/// los que es miembro y, si accede con una API key limitada a unos proyectos,
+/// solo esos (ver `CurrentUser::member_scope`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemberScope {
+    // Miembro de los proyectos; `None` para los administradores
+    pub user_id: Option<i32>,
+    pub project_ids: Option<Vec<i32>>,
+    // Nivel que tiene que alcanzar el rol del miembro en el proyecto; `None`
+    // si basta con ser miembro
+    pub level: Option<Level>,
+}
+
+impl MemberScope {
+    /// Proyectos de los que es miembro el usuario.
+    pub fn member(user_id: i32) -> Self {
+        Self { user_id: Some(user_id), ..Self::default() }
+    }
+
+    /// Ámbito de una escritura que exige `level`: el nivel del usuario en un
+    /// proyecto es el menor entre el de su rol y el de su rol en el proyecto.
+    pub fn with_level(self, level: Level) -> Self {
+        Self { level: Some(level), ..self }
+    }
+
+    /// Indica si el proyecto está dentro del ámbito.
+    pub async fn allows(&self, pg_pool: &PgPool, project_id: i32) -> Result<bool, Error> {
+        if self.project_ids.as_ref().is_some_and(|ids| !ids.contains(&project_id)) {
+            return Ok(false);
+        }
+        match (self.user_id, self.level) {
+            (Some(user_id), None) => ProjectMember::is_member(pg_pool, project_id, user_id).await,
+            (Some(user_id), Some(level)) => ProjectMember::has_level(pg_pool, project_id, user_id, level).await,
+            (None, _) => Ok(true),
+        }
+    }
+
+    /// Indica si el proyecto del registro padre está dentro del ámbito. Si el
+    /// padre no existe, no lo está.
+    pub async fn allows_parent(&self, pg_pool: &PgPool, parent: ScopeParent) -> Result<bool, Error> {
+        match parent.project_id(pg_pool).await? {
+            Some(project_id) => self.allows(pg_pool, project_id).await,
+            None => Ok(false),
+        }
+    }
+}
+
+/// Registro del que toma su proyecto una fila nueva (o modificada) de un
+/// modelo limitado a los miembros.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeParent {
+    Project(i32),
+    Budget(i32),
+    Element(i32),
+}
+
+impl ScopeParent {
+    /// Proyecto del registro; `None` si no existe.
+    pub async fn project_id(&self, pg_pool: &PgPool) -> Result<Option<i32>, Error> {
+        let (sql, id) = match *self {
+            Self::Project(id) => ("SELECT id FROM projects WHERE id = $1", id),
+            Self::Budget(id) => ("SELECT project_id FROM budgets WHERE id = $1", id),
+            Self::Element(id) => (
+                "SELECT b.project_id FROM elements e JOIN budgets b ON b.id = e.budget_id WHERE e.id = $1",
+                id,
+            ),
+        };
+        debug!("Scope parent: {}", sql);
+        sqlx::query_scalar::<_, i32>(sql)
+            .bind(id)
+            .fetch_optional(pg_pool)
+            .await
+    }
+}
+
+/// Modelos de `#[axum_crud(scope = "project")]` y sus `New...`: las
+/// escrituras comprueban que el padre está dentro del ámbito del usuario.
+pub trait Scoped {
+    /// Padre del registro; `None` si no tiene (los proyectos).
+    fn scope_parent(&self) -> Option<ScopeParent>;
+
+    /// Recibe el ámbito de quien crea el registro, si lo necesita al crearlo.
+    fn set_member_scope(&mut self, _scope: MemberScope) {}
+}
+
+/// Restringe una consulta a las filas cuyo proyecto (la expresión SQL
+/// `project`) está dentro del ámbito. Sin ámbito no filtra nada.
+pub fn append_member_filter(builder: &mut QueryBuilder<Postgres>, project: &str, scope: Option<&MemberScope>) {
+    let Some(scope) = scope else {
+        return;
+    };
+    if let Some(user_id) = scope.user_id {
+        builder.push(format!(" AND {} IN (SELECT project_id FROM {} WHERE user_id = ", project, ProjectMember::TABLE));
+        builder.push_bind(user_id);
+        if let Some(level) = scope.level {
+            builder.push(" AND role_id IN (SELECT id FROM roles WHERE name = ANY(");
+            builder.push_bind(level.role_names());
+            builder.push("))");
+        }
+        builder.push(")");
+    }
+    if let Some(project_ids) = &scope.project_ids {
+        builder.push(format!(" AND {} = ANY(", project));
+        builder.push_bind(project_ids.clone());
+        builder.push(")");
+    }
+}
+
+// =================================================================
+// 2. MÉTODOS CRUD (ASOCIADOS DIRECTAMENTE AL STRUCT)
+// =================================================================
+
+impl ProjectMember {
+    const TABLE: &str = "project_members";
+    const INSERT_QUERY: &str = r#"
+        (
+            project_id,
+            user_id,
+            role_id
+        )
+        VALUES ($1, $2, $3)
+    "#;
+    const UPDATE_QUERY: &str = r#"
+        project_id = $2,
+        user_id = $3,
+        role_id = $4
+    "#;
+
+    // =================================================================
+    // R: READ
+    // =================================================================
+    pub async fn read_by_id(pg_pool: &PgPool, id: i32) -> Result<Option<Self>, Error> {
+        let sql = format!(r#"SELECT * FROM {} WHERE id = $1"#, Self::TABLE);
+        debug!("Read by: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(id)
+            .fetch_optional(pg_pool)
+            .await
+    }
+
+    pub async fn read_all(pg_pool: &PgPool) -> Result<Vec<Self>, Error>{
+        let sql = format!("SELECT * FROM {}", Self::TABLE);
+        debug!("Read all: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .fetch_all(pg_pool)
+            .await
+    }
+
+    pub async fn count_paged(pool: &PgPool, params: &ProjectMemberParams) -> Result<i64, Error> {
+        let sql = format!("SELECT COUNT(*) FROM {} WHERE 1=1", Self::TABLE);
+        debug!("Count paged: {}", &sql);
+        let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(&sql);
+        params.project_id.append_filter(&mut query_builder, "project_id");
+        params.user_id.append_filter(&mut query_builder, "user_id");
+        params.role_id.append_filter(&mut query_builder, "role_id");
+        query_builder
+            .build()
+            .map(|row: PgRow| row.get::<i64, _>(0))
+            .fetch_one(pool)
+            .await
+    }
+
+    pub async fn read_paged(pool: &PgPool, params: &ProjectMemberParams) -> Result<Vec<Self>, Error> {
+        let sql = format!("SELECT * FROM {} WHERE 1=1", Self::TABLE);
+        debug!("Read paged: {}", &sql);
+        let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(&sql);
+        params.project_id.append_filter(&mut query_builder, "project_id");
+        params.user_id.append_filter(&mut query_builder, "user_id");
+        params.role_id.append_filter(&mut query_builder, "role_id");
+        if let Some(sort_by) = &params.sort_by {
+            query_builder.push(format!(" ORDER BY {} ", sort_by));
+            query_builder.push(if params.asc.unwrap_or(true) { "ASC" } else { "DESC" });
+        }
+        query_builder.push(" LIMIT ");
+        query_builder.push_bind(params.limit_or_default());
+        query_builder.push(" OFFSET ");
+        query_builder.push_bind(params.offset());
+        query_builder
+            .build_query_as::<Self>()
+            .fetch_all(pool)
+            .await
+    }
+
+    // =================================================================
+    // C: CREATE (Crear)
+    // =================================================================
+    /// Inserta un nuevo registro en la base de datos y devuelve el objeto creado.
+    pub async fn create<'e, E>(executor: E, item: NewProjectMember) -> Result<Self, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!("INSERT INTO {} {} RETURNING *", Self::TABLE, Self::INSERT_QUERY);
+        debug!("Create: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+        .bind(item.project_id)
+        .bind(item.user_id)
+        .bind(item.role_id)
+        .fetch_one(executor)
+        .await
+    }
+
+    // =================================================================
+    // U: UPDATE (Actualizar)
+    // =================================================================
+    /// Actualiza un registro por ID y devuelve el objeto actualizado.
+    pub async fn update(pg_pool: &PgPool, item: ProjectMember) -> Result<Self, Error> {
+        let sql = format!("UPDATE {} SET {} WHERE id = $1 RETURNING *", Self::TABLE, Self::UPDATE_QUERY);
+        debug!("Update: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+        .bind(item.id)
+        .bind(item.project_id)
+        .bind(item.user_id)
+        .bind(item.role_id)
+        .fetch_one(pg_pool)
+        .await
+    }
+
+    // =================================================================
+    // D: DELETE (Borrar y devolver el valor)
+    // =================================================================
+    /// Elimina un registro por ID y devuelve el objeto que fue eliminado.
+    pub async fn delete(pg_pool: &PgPool, id: i32) -> Result<Self, Error> {
+        let sql = format!(" DELETE FROM {} WHERE id = $1 RETURNING *", Self::TABLE);
+        debug!("Delete: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(id)
+            .fetch_one(pg_pool)
+            .await
+    }
+    // =================================================================
+    // E: OTHERS
+    // =================================================================
+    /// Indica si el usuario es miembro del proyecto.
+    pub async fn is_member(pg_pool: &PgPool, project_id: i32, user_id: i32) -> Result<bool, Error> {
+        let sql = format!("SELECT EXISTS (SELECT 1 FROM {} WHERE project_id = $1 AND user_id = $2)", Self::TABLE);
+        debug!("Is member: {}", &sql);
+        sqlx::query_scalar::<_, bool>(&sql)
+            .bind(project_id)
+            .bind(user_id)
+            .fetch_one(pg_pool)
+            .await
+    }
+
+    /// Indica si el usuario es miembro del proyecto con un rol que llega a `level`.
+    pub async fn has_level(pg_pool: &PgPool, project_id: i32, user_id: i32, level: Level) -> Result<bool, Error> {
+        let sql = format!(
+            r#"SELECT EXISTS (
+                SELECT 1 FROM {} m JOIN roles r ON r.id = m.role_id
+                WHERE m.project_id = $1 AND m.user_id = $2 AND r.name = ANY($3)
+            )"#,
+            Self::TABLE
+        );
+        debug!("Has level: {}", &sql);
+        sqlx::query_scalar::<_, bool>(&sql)
+            .bind(project_id)
+            .bind(user_id)
+            .bind(level.role_names())
+            .fetch_one(pg_pool)
+            .await
+    }
+
+    /// Indica si la fila `id` de `table`, cuyo proyecto es la expresión SQL
+    /// `project`, está dentro del ámbito.
+    pub async fn can_read(pg_pool: &PgPool, table: &str, project: &str, id: i32, scope: &MemberScope) -> Result<bool, Error> {
+        let mut query_builder: QueryBuilder<Postgres> =
+            QueryBuilder::new(format!("SELECT EXISTS (SELECT 1 FROM {} WHERE id = ", table));
+        query_builder.push_bind(id);
+        append_member_filter(&mut query_builder, project, Some(scope));
+        query_builder.push(")");
+        debug!("Can read: {}", query_builder.sql());
+        query_builder
+            .build_query_scalar::<bool>()
+            .fetch_one(pg_pool)
+            .await
+    }
+
+    /// Comprueba una escritura de `#[axum_crud(scope = "project")]`: la fila
+    /// `id` de `table` que se modifica o borra, si la hay, y el padre, si lo
+    /// hay, tienen que estar dentro del ámbito.
+    pub async fn can_write(
+        pg_pool: &PgPool,
+        table: &str,
+        project: &str,
+        id: Option<i32>,
+        parent: Option<ScopeParent>,
+        scope: &MemberScope,
+    ) -> Result<bool, Error> {
+        if let Some(id) = id
+            && !Self::can_read(pg_pool, table, project, id, scope).await?
+        {
+            return Ok(false);
+        }
+        match parent {
+            Some(parent) => scope.allows_parent(pg_pool, parent).await,
+            None => Ok(true),
+        }
+    }
+
+    /// Todas las filas de `table` dentro del ámbito.
+    pub async fn read_visible<T>(pg_pool: &PgPool, table: &str, project: &str, scope: &MemberScope) -> Result<Vec<T>, Error>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let mut query_builder: QueryBuilder<Postgres> =
+            QueryBuilder::new(format!("SELECT * FROM {} WHERE 1=1", table));
+        append_member_filter(&mut query_builder, project, Some(scope));
+        debug!("Read visible: {}", query_builder.sql());
+        query_builder
+            .build_query_as::<T>()
+            .fetch_all(pg_pool)
+            .await
+    }
+}
@@ -20,15 +20,19 @@ use crate::constants::DEFAULT_PAGE;
 
 #[derive(Debug, Clone)]
 pub enum CustomResponse {
-    Pdf(PdfResponse),
+    File(FileResponse),
     Api(ApiResponse),
     Empty(EmptyResponse),
     Paged(PagedResponse),
 }
 
 impl CustomResponse {
+    /// Fichero para descargar o mostrar; `headers` lleva el tipo de contenido.
+    pub fn file(headers: HeaderMap, body: Vec<u8>) -> Self {
+        CustomResponse::File((headers, body))
+    }
     pub fn pdf(headers: HeaderMap, body: Vec<u8>) -> Self {
-        CustomResponse::Pdf((headers, body))
+        Self::file(headers, body)
     }
     pub fn api(status: StatusCode, message: &str, data: Data) -> Self {
         CustomResponse::Api(ApiResponse::new(status, message, data))
@@ -45,7 +49,7 @@ impl CustomResponse {
 }
 
 
-pub type PdfResponse = (HeaderMap, Vec<u8>);
+pub type FileResponse = (HeaderMap, Vec<u8>);
 
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -65,6 +69,37 @@ impl ApiResponse {
     }
 }
 
+/// Errores que saben cómo presentarse en una respuesta de la API: por defecto
+/// un 400 con el mensaje del error y sin datos.
+pub trait ApiError: std::fmt::Display {
+    fn status(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+    fn data(&self) -> Data {
+        Data::None
+    }
+}
+
+/// SQLSTATE de `check_budget_editable`: escritura en un presupuesto bloqueado.
+const OBJECT_NOT_IN_PREREQUISITE_STATE: &str = "55000";
+
+impl ApiError for sqlx::Error {
+    fn status(&self) -> StatusCode {
+        match self {
+            sqlx::Error::Database(e) if e.code().as_deref() == Some(OBJECT_NOT_IN_PREREQUISITE_STATE) => {
+                StatusCode::CONFLICT
+            }
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl ApiResponse {
+    pub fn from_error(error: &impl ApiError) -> Self {
+        Self::new(error.status(), &error.to_string(), error.data())
+    }
+}
+
 impl From<ApiResponse> for CustomResponse {
     fn from(api_response: ApiResponse) -> Self {
         CustomResponse::Api(api_response)
@@ -77,9 +112,9 @@ impl From<EmptyResponse> for CustomResponse {
     }
 }
 
-impl From<PdfResponse> for CustomResponse {
-    fn from(pdf_response: PdfResponse) -> Self {
-        CustomResponse::Pdf(pdf_response)
+impl From<FileResponse> for CustomResponse {
+    fn from(file_response: FileResponse) -> Self {
+        CustomResponse::File(file_response)
     }
 }
 
@@ -99,7 +134,7 @@ impl IntoResponse for ApiResponse {
 impl IntoResponse for CustomResponse {
     fn into_response(self) -> Response {
         match self {
-            CustomResponse::Pdf((headers, body)) => (headers, body).into_response(),
+            CustomResponse::File((headers, body)) => (headers, body).into_response(),
             CustomResponse::Api(api_response) => api_response.into_response(),
             CustomResponse::Empty(empty_response) => empty_response.into_response(),
             CustomResponse::Paged(page_response) => page_response.into_response(),
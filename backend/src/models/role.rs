@@ -17,7 +17,7 @@ use macros::axum_crud;
 // 1. ESTRUCTURAS DE DATOS (STRUCTS)
 // =================================================================
 
-#[axum_crud(path = "/roles", new = "NewRole", params = "RoleParams")]
+#[axum_crud(path = "/roles", new = "NewRole", params = "RoleParams", write = "admin")]
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct Role {
     pub id: i32,
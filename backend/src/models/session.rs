@@ -0,0 +1,158 @@
+use serde::Serialize;
+use sqlx::{
+    Error, FromRow,
+    postgres::{PgExecutor, PgPool},
+};
+use tracing::debug;
+use super::{token, UtcTimestamp};
+
+// =================================================================
+// 1. ESTRUCTURAS DE DATOS (STRUCTS)
+// =================================================================
+
+/// Sesión de un usuario en un dispositivo. Se abre en el login y se renueva con
+/// su refresh token, del que solo se guarda el hash.
+#[derive(Debug, FromRow, Serialize)]
+pub struct Session {
+    pub id: i32,
+    pub user_id: i32,
+    #[serde(skip_serializing)]
+    pub refresh_token_hash: String,
+    pub user_agent: Option<String>,
+    pub expires_at: UtcTimestamp,
+    pub last_used_at: UtcTimestamp,
+    pub revoked_at: Option<UtcTimestamp>,
+    pub created_at: UtcTimestamp,
+    pub updated_at: UtcTimestamp,
+}
+
+// =================================================================
+// 2. MÉTODOS (ASOCIADOS DIRECTAMENTE AL STRUCT)
+// =================================================================
+
+impl Session {
+    const TABLE: &str = "sessions";
+    // Duración de una sesión sin renovar
+    pub const REFRESH_TOKEN_DAYS: i64 = 30;
+    const ACTIVE: &str = "revoked_at IS NULL AND expires_at > NOW()";
+
+    fn expires_at() -> UtcTimestamp {
+        chrono::Utc::now() + chrono::Duration::days(Self::REFRESH_TOKEN_DAYS)
+    }
+
+    // =================================================================
+    // C: CREATE (Crear)
+    // =================================================================
+    /// Abre una sesión y devuelve también su refresh token, que no se guarda.
+    pub async fn create(pg_pool: &PgPool, user_id: i32, user_agent: Option<String>) -> Result<(Self, String), Error> {
+        let refresh_token = token::generate();
+        let sql = format!(
+            "INSERT INTO {} (user_id, refresh_token_hash, user_agent, expires_at) VALUES ($1, $2, $3, $4) RETURNING *",
+            Self::TABLE
+        );
+        debug!("Create: {}", &sql);
+        let session = sqlx::query_as::<_, Self>(&sql)
+            .bind(user_id)
+            .bind(token::hash(&refresh_token))
+            .bind(user_agent)
+            .bind(Self::expires_at())
+            .fetch_one(pg_pool)
+            .await?;
+        Ok((session, refresh_token))
+    }
+
+    // =================================================================
+    // R: READ
+    // =================================================================
+    /// Sesión activa (no revocada ni caducada) de un refresh token.
+    pub async fn read_by_token(pg_pool: &PgPool, refresh_token: &str) -> Result<Option<Self>, Error> {
+        let sql = format!("SELECT * FROM {} WHERE refresh_token_hash = $1 AND {}", Self::TABLE, Self::ACTIVE);
+        debug!("Read by token: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(token::hash(refresh_token))
+            .fetch_optional(pg_pool)
+            .await
+    }
+
+    /// Sesiones activas de un usuario, de la más reciente a la más antigua.
+    pub async fn read_active_by_user(pg_pool: &PgPool, user_id: i32) -> Result<Vec<Self>, Error> {
+        let sql = format!(
+            "SELECT * FROM {} WHERE user_id = $1 AND {} ORDER BY last_used_at DESC",
+            Self::TABLE,
+            Self::ACTIVE
+        );
+        debug!("Read active by user: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(user_id)
+            .fetch_all(pg_pool)
+            .await
+    }
+
+    /// Indica si la sesión sigue activa y es del usuario.
+    pub async fn is_active(pg_pool: &PgPool, id: i32, user_id: i32) -> Result<bool, Error> {
+        let sql = format!(
+            "SELECT EXISTS (SELECT 1 FROM {} WHERE id = $1 AND user_id = $2 AND {})",
+            Self::TABLE,
+            Self::ACTIVE
+        );
+        debug!("Is active: {}", &sql);
+        sqlx::query_scalar::<_, bool>(&sql)
+            .bind(id)
+            .bind(user_id)
+            .fetch_one(pg_pool)
+            .await
+    }
+
+    // =================================================================
+    // U: UPDATE (Actualizar)
+    // =================================================================
+    /// Renueva la sesión activa de un refresh token con uno nuevo: el anterior
+    /// deja de valer. Devuelve `None` si el token ya no es válido, también
+    /// cuando otra petición lo ha rotado antes.
+    pub async fn rotate(pg_pool: &PgPool, refresh_token: &str) -> Result<Option<(Self, String)>, Error> {
+        let new_token = token::generate();
+        let sql = format!(
+            "UPDATE {} SET refresh_token_hash = $2, expires_at = $3, last_used_at = NOW() \
+             WHERE refresh_token_hash = $1 AND {} RETURNING *",
+            Self::TABLE,
+            Self::ACTIVE
+        );
+        debug!("Rotate: {}", &sql);
+        let session = sqlx::query_as::<_, Self>(&sql)
+            .bind(token::hash(refresh_token))
+            .bind(token::hash(&new_token))
+            .bind(Self::expires_at())
+            .fetch_optional(pg_pool)
+            .await?;
+        Ok(session.map(|session| (session, new_token)))
+    }
+
+    /// Revoca una sesión activa del usuario. Devuelve `None` si no la hay.
+    pub async fn revoke(pg_pool: &PgPool, id: i32, user_id: i32) -> Result<Option<Self>, Error> {
+        let sql = format!(
+            "UPDATE {} SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND {} RETURNING *",
+            Self::TABLE,
+            Self::ACTIVE
+        );
+        debug!("Revoke: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(pg_pool)
+            .await
+    }
+
+    /// Revoca todas las sesiones activas del usuario y devuelve cuántas eran.
+    pub async fn revoke_all<'e, E>(executor: E, user_id: i32) -> Result<u64, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!("UPDATE {} SET revoked_at = NOW() WHERE user_id = $1 AND {}", Self::TABLE, Self::ACTIVE);
+        debug!("Revoke all: {}", &sql);
+        sqlx::query(&sql)
+            .bind(user_id)
+            .execute(executor)
+            .await
+            .map(|result| result.rows_affected())
+    }
+}
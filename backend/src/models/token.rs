@@ -0,0 +1,31 @@
+//! Tokens opacos que se entregan al cliente (refresh tokens, enlaces de
+//! verificación y de recuperación de contraseña). En la base de datos solo se
+//! guarda su hash.
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// Token aleatorio: dos UUID v4 (244 bits de azar) en hexadecimal.
+pub fn generate() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// Hash SHA-256 en hexadecimal con el que se guarda y se busca el token.
+pub fn hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens() {
+        let token = generate();
+        assert_eq!(token.len(), 64);
+        assert_ne!(token, generate());
+        let hashed = hash(&token);
+        assert_eq!(hashed.len(), 64);
+        assert_eq!(hashed, hash(&token));
+        assert_ne!(hashed, token);
+    }
+}
@@ -1,9 +1,11 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenClaims {
     pub sub: String,
     pub role: String,
+    // Sesión (`Session`) que emitió el token
+    pub sid: i32,
     pub iat: usize,
     pub exp: usize,
 }
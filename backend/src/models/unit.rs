@@ -1,11 +1,14 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use sqlx::{
     Postgres,
     QueryBuilder,
     Error, FromRow, Row,
-    postgres::{PgPool, PgRow},
+    postgres::{PgExecutor, PgPool, PgRow},
+    types::BigDecimal,
 };
 use tracing::debug;
+use crate::formula::{Formula, FormulaError};
 use super::{
     Paginable,
     Filterable,
@@ -17,7 +20,7 @@ use macros::axum_crud;
 // 1. ESTRUCTURAS DE DATOS (STRUCTS)
 // =================================================================
 
-#[axum_crud(path = "/units", new = "NewUnit", params = "UnitParams")]
+#[axum_crud(path = "/units", new = "NewUnit", params = "UnitParams", write = "master")]
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct Unit {
     pub id: i32,
@@ -25,6 +28,9 @@ pub struct Unit {
     pub symbol: String,
     pub description: Option<String>,
     pub formula: String, 
+    // Variables de la fórmula, calculadas al guardar: ["a", "b"]
+    #[serde(default)]
+    pub params: Value,
     pub created_at: UtcTimestamp,
     pub updated_at: UtcTimestamp,
 }
@@ -64,14 +70,16 @@ impl Unit {
             symbol,
             description,
             formula,
+            params
         )
-        VALUES ($1, $2, $3, $4)
+        VALUES ($1, $2, $3, $4, $5)
     "#;
     const UPDATE_QUERY: &str = r#"
         name = $2,
         symbol = $3,
-        description = $4
+        description = $4,
         formula = $5,
+        params = $6
     "#;
 
     // =================================================================
@@ -131,7 +139,12 @@ impl Unit {
     // C: CREATE (Crear)
     // =================================================================
     /// Inserta un nuevo registro en la base de datos y devuelve el objeto creado.
-    pub async fn create(pg_pool: &PgPool, item: NewUnit) -> Result<Self, Error> {
+    /// La fórmula debe ser válida; sus variables se guardan en `params`.
+    pub async fn create<'e, E>(executor: E, item: NewUnit) -> Result<Self, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let params = Self::formula_params(&item.formula)?;
         let sql = format!("INSERT INTO {} {} RETURNING *", Self::TABLE, Self::INSERT_QUERY);
         debug!("Create: {}", &sql);
         sqlx::query_as::<_, Self>(&sql)
@@ -139,7 +152,8 @@ impl Unit {
         .bind(item.symbol)
         .bind(item.description)
         .bind(item.formula)
-        .fetch_one(pg_pool)
+        .bind(params)
+        .fetch_one(executor)
         .await
     }
 
@@ -147,7 +161,9 @@ impl Unit {
     // U: UPDATE (Actualizar)
     // =================================================================
     /// Actualiza un registro por ID y devuelve el objeto actualizado.
+    /// Los `params` se vuelven a calcular a partir de la fórmula.
     pub async fn update(pg_pool: &PgPool, item: Unit) -> Result<Self, Error> {
+        let params = Self::formula_params(&item.formula)?;
         let sql = format!("UPDATE {} SET {} WHERE id = $1 RETURNING *", Self::TABLE, Self::UPDATE_QUERY);
         debug!("Update: {}", &sql);
         sqlx::query_as::<_, Self>(&sql)
@@ -156,6 +172,7 @@ impl Unit {
         .bind(item.symbol)
         .bind(item.description)
         .bind(item.formula)
+        .bind(params)
         .fetch_one(pg_pool)
         .await
     }
@@ -172,4 +189,53 @@ impl Unit {
             .fetch_one(pg_pool)
             .await
     }
+    // =================================================================
+    // E: OTHERS
+    // =================================================================
+    /// Recupera la primera unidad con el símbolo indicado.
+    pub async fn read_by_symbol<'e, E>(executor: E, symbol: &str) -> Result<Option<Self>, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!(r#"SELECT * FROM {} WHERE symbol = $1 ORDER BY id LIMIT 1"#, Self::TABLE);
+        debug!("Read by: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(symbol)
+            .fetch_optional(executor)
+            .await
+    }
+    /// Recupera las unidades cuyos identificadores se indican.
+    pub async fn read_by_ids<'e, E>(executor: E, ids: &[i32]) -> Result<Vec<Self>, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!("SELECT * FROM {} WHERE id = ANY($1)", Self::TABLE);
+        debug!("Read by ids: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(ids)
+            .fetch_all(executor)
+            .await
+    }
+    /// Analiza la fórmula y devuelve la lista de sus variables.
+    pub fn formula_params(formula: &str) -> Result<Value, Error> {
+        let parsed = Formula::parse(formula)
+            .map_err(|e| Error::InvalidArgument(format!("Invalid formula '{}': {}", formula, e)))?;
+        Ok(json!(parsed.variables()))
+    }
+    /// Calcula la cantidad a partir de los valores de las variables (`{"a": 2, "b": 3}`).
+    pub fn evaluate(&self, params: &Value) -> Result<BigDecimal, FormulaError> {
+        Formula::parse(&self.formula)?.evaluate_json(params)
+    }
+    /// Recupera la unidad del precio indicado.
+    pub async fn read_by_price(pg_pool: &PgPool, price_id: i32) -> Result<Option<Self>, Error> {
+        let sql = format!(
+            "SELECT u.* FROM {} u JOIN prices p ON p.unit_id = u.id WHERE p.id = $1",
+            Self::TABLE
+        );
+        debug!("Read by price: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(price_id)
+            .fetch_optional(pg_pool)
+            .await
+    }
 }
@@ -5,47 +5,100 @@ use sqlx::{
     Error, FromRow, Row,
     postgres::{PgPool, PgRow},
 };
+use std::{fmt, io};
 use tracing::debug;
 use super::{
+    Session,
     Paginable,
     Filterable,
     UtcTimestamp,
 };
+use crate::mailer::check_address;
 use macros::axum_crud;
 
 // =================================================================
 // 1. ESTRUCTURAS DE DATOS (STRUCTS)
 // =================================================================
 
-#[axum_crud(path = "/users", new = "NewUser", params = "UserParams")]
-#[derive(Debug, FromRow, Serialize, Deserialize)]
+// Lo que se muestra en los logs en lugar de las contraseñas y sus hashes
+const REDACTED: &str = "<redacted>";
+
+#[axum_crud(path = "/users", new = "NewUser", params = "UserParams", read = "admin", write = "admin")]
+#[derive(FromRow, Serialize, Deserialize)]
 pub struct User {
     pub id: i32,
     pub username: String,
     pub email: String,
+    // Nunca sale del servidor ni se acepta del cliente
+    #[serde(skip)]
     pub hashed_password: String,
+    // Contraseña nueva en claro al actualizar; sin ella se conserva la actual
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
     pub role_id: i32,
     pub is_active: bool,
+    // Cuándo confirmó el usuario su email; no se modifica con `update`
+    #[serde(default)]
+    pub email_verified_at: Option<UtcTimestamp>,
     pub created_at: UtcTimestamp,
     pub updated_at: UtcTimestamp,
 }
 
-// DTO para la actualización de datos del usuario (la contraseña se maneja aparte)
-#[derive(Debug, Deserialize, Serialize)]
+// DTO para la creación de un usuario: la contraseña llega en claro y se
+// guarda su hash (ver `User::hash_password`)
+#[derive(Deserialize, Serialize)]
 pub struct NewUser {
     pub username: String,
     pub email: String,
-    pub hashed_password: String,
+    pub password: String,
     pub role_id: i32,
     pub is_active: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Deserialize, Serialize)]
 pub struct UserPass {
     pub email: String,
     pub password: String,
 }
 
+// `Debug` a mano para que las contraseñas no lleguen a los logs
+
+impl fmt::Debug for User {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("User")
+            .field("id", &self.id)
+            .field("username", &self.username)
+            .field("email", &self.email)
+            .field("hashed_password", &REDACTED)
+            .field("password", &self.password.as_ref().map(|_| REDACTED))
+            .field("role_id", &self.role_id)
+            .field("is_active", &self.is_active)
+            .field("email_verified_at", &self.email_verified_at)
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .finish()
+    }
+}
+
+impl fmt::Debug for NewUser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NewUser")
+            .field("username", &self.username)
+            .field("email", &self.email)
+            .field("password", &REDACTED)
+            .field("role_id", &self.role_id)
+            .field("is_active", &self.is_active)
+            .finish()
+    }
+}
+
+impl fmt::Debug for UserPass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UserPass").field("email", &self.email).field("password", &REDACTED).finish()
+    }
+}
+
 #[derive(Debug, serde::Deserialize, macros::Paginable)]
 pub struct UserParams {
     pub id: Option<i32>,
@@ -67,6 +120,9 @@ pub struct UserParams {
 
 impl User {
     const TABLE: &str = "users";
+    pub const MIN_PASSWORD_LENGTH: usize = 8;
+    // bcrypt solo tiene en cuenta los primeros 72 bytes
+    pub const MAX_PASSWORD_BYTES: usize = 72;
     const INSERT_QUERY: &str = r#"
         (
             username,
@@ -80,7 +136,7 @@ impl User {
     const UPDATE_QUERY: &str = r#"
         username = $2,
         email = $3,
-        hashed_password = $4,
+        hashed_password = COALESCE($4, hashed_password),
         role_id = $5,
         is_active = $6
     "#;
@@ -146,35 +202,112 @@ impl User {
     // C: CREATE (Crear)
     // =================================================================
     /// Inserta un nuevo registro en la base de datos y devuelve el objeto creado.
+    /// La contraseña debe cumplir la política (ver `validate_password`) y el
+    /// email tiene que ser válido (ver `validate_email`).
     pub async fn create(pg_pool: &PgPool, item: NewUser) -> Result<Self, Error> {
+        Self::validate_email(&item.email)?;
+        let hashed_password = Self::hash_password(&item.password).await?;
         let sql = format!("INSERT INTO {} {} RETURNING *", Self::TABLE, Self::INSERT_QUERY);
         debug!("Create: {}", &sql);
         sqlx::query_as::<_, Self>(&sql)
         .bind(item.username)
         .bind(item.email)
-        .bind(item.hashed_password)
+        .bind(hashed_password)
         .bind(item.role_id)
         .bind(item.is_active)
         .fetch_one(pg_pool)
         .await
     }
 
+    /// Crea el primer usuario de la instalación. Devuelve `None` si ya hay
+    /// alguno. La tabla queda bloqueada hasta el final de la transacción para
+    /// que dos peticiones simultáneas no creen dos usuarios.
+    pub async fn create_first(pg_pool: &PgPool, item: NewUser) -> Result<Option<Self>, Error> {
+        Self::validate_email(&item.email)?;
+        let hashed_password = Self::hash_password(&item.password).await?;
+        let mut tx = pg_pool.begin().await?;
+        let sql = format!("LOCK TABLE {} IN SHARE ROW EXCLUSIVE MODE", Self::TABLE);
+        debug!("Create first: {}", &sql);
+        sqlx::query(&sql).execute(&mut *tx).await?;
+        let sql = format!("SELECT EXISTS (SELECT 1 FROM {})", Self::TABLE);
+        if sqlx::query_scalar::<_, bool>(&sql).fetch_one(&mut *tx).await? {
+            return Ok(None);
+        }
+        let sql = format!("INSERT INTO {} {} RETURNING *", Self::TABLE, Self::INSERT_QUERY);
+        debug!("Create first: {}", &sql);
+        let user = sqlx::query_as::<_, Self>(&sql)
+            .bind(item.username)
+            .bind(item.email)
+            .bind(hashed_password)
+            .bind(item.role_id)
+            .bind(item.is_active)
+            .fetch_one(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(Some(user))
+    }
+
     // =================================================================
     // U: UPDATE (Actualizar)
     // =================================================================
     /// Actualiza un registro por ID y devuelve el objeto actualizado.
+    /// Si llega una contraseña nueva se guarda su hash y se revocan todas las
+    /// sesiones del usuario.
     pub async fn update(pg_pool: &PgPool, item: Self) -> Result<Self, Error> {
+        Self::validate_email(&item.email)?;
+        let hashed_password = match item.password.as_deref() {
+            Some(password) => Some(Self::hash_password(password).await?),
+            None => None,
+        };
+        let mut tx = pg_pool.begin().await?;
         let sql = format!("UPDATE {} SET {} WHERE id = $1 RETURNING *", Self::TABLE, Self::UPDATE_QUERY);
         debug!("Update: {}", &sql);
-        sqlx::query_as::<_, Self>(&sql)
+        let user = sqlx::query_as::<_, Self>(&sql)
         .bind(item.id)
         .bind(item.username)
         .bind(item.email)
-        .bind(item.hashed_password)
+        .bind(&hashed_password)
         .bind(item.role_id)
         .bind(item.is_active)
-        .fetch_one(pg_pool)
-        .await
+        .fetch_one(&mut *tx)
+        .await?;
+        if hashed_password.is_some() {
+            let revoked = Session::revoke_all(&mut *tx, user.id).await?;
+            debug!("Password changed for user {}: {} sessions revoked", user.id, revoked);
+        }
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    /// Cambia la contraseña (p. ej. al recuperarla) y revoca todas las
+    /// sesiones del usuario.
+    pub async fn set_password(pg_pool: &PgPool, id: i32, password: &str) -> Result<Self, Error> {
+        let hashed_password = Self::hash_password(password).await?;
+        let mut tx = pg_pool.begin().await?;
+        let sql = format!("UPDATE {} SET hashed_password = $2 WHERE id = $1 RETURNING *", Self::TABLE);
+        debug!("Set password: {}", &sql);
+        let user = sqlx::query_as::<_, Self>(&sql)
+            .bind(id)
+            .bind(hashed_password)
+            .fetch_one(&mut *tx)
+            .await?;
+        let revoked = Session::revoke_all(&mut *tx, user.id).await?;
+        debug!("Password reset for user {}: {} sessions revoked", user.id, revoked);
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    /// Marca el email del usuario como verificado, si no lo estaba ya.
+    pub async fn verify_email(pg_pool: &PgPool, id: i32) -> Result<Self, Error> {
+        let sql = format!(
+            "UPDATE {} SET email_verified_at = COALESCE(email_verified_at, NOW()) WHERE id = $1 RETURNING *",
+            Self::TABLE
+        );
+        debug!("Verify email: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(id)
+            .fetch_one(pg_pool)
+            .await
     }
 
     // =================================================================
@@ -192,6 +325,53 @@ impl User {
     // =================================================================
     // E: OTHERS
     // =================================================================
+    /// Comprueba la política de contraseñas: entre `MIN_PASSWORD_LENGTH`
+    /// caracteres y `MAX_PASSWORD_BYTES` bytes, con letras y números.
+    pub fn validate_password(password: &str) -> Result<(), Error> {
+        if password.chars().count() < Self::MIN_PASSWORD_LENGTH {
+            return Err(Error::InvalidArgument(format!(
+                "Password must be at least {} characters long",
+                Self::MIN_PASSWORD_LENGTH
+            )));
+        }
+        if password.len() > Self::MAX_PASSWORD_BYTES {
+            return Err(Error::InvalidArgument(format!(
+                "Password must be at most {} bytes long",
+                Self::MAX_PASSWORD_BYTES
+            )));
+        }
+        if !password.chars().any(char::is_alphabetic) || !password.chars().any(|c| c.is_ascii_digit()) {
+            return Err(Error::InvalidArgument("Password must contain letters and digits".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Comprueba que el email se puede usar como destinatario de un correo
+    /// (ver `mailer::check_address`).
+    pub fn validate_email(email: &str) -> Result<(), Error> {
+        check_address(email).map_err(|_| Error::InvalidArgument("Invalid email address".to_string()))
+    }
+
+    /// Valida la contraseña y devuelve su hash bcrypt. El hash se calcula en
+    /// un hilo aparte para no bloquear el runtime.
+    pub async fn hash_password(password: &str) -> Result<String, Error> {
+        Self::validate_password(password)?;
+        let password = password.to_string();
+        tokio::task::spawn_blocking(move || bcrypt::hash(password, bcrypt::DEFAULT_COST))
+            .await
+            .map_err(|e| Error::Io(io::Error::other(e)))?
+            .map_err(|e| Error::Encode(Box::new(e)))
+    }
+
+    /// Comprueba la contraseña con el hash guardado, como `hash_password`, en
+    /// un hilo aparte.
+    pub async fn verify_password(&self, password: &str) -> bool {
+        let (password, hashed_password) = (password.to_string(), self.hashed_password.clone());
+        tokio::task::spawn_blocking(move || bcrypt::verify(password, &hashed_password).unwrap_or(false))
+            .await
+            .unwrap_or(false)
+    }
+
     /// Recupera un usuario por su email.
     pub async fn read_by_email(pg_pool: &PgPool, email: String) -> Result<Option<Self>, Error> {
         let sql = format!(r#"SELECT * FROM {} WHERE email = $1"#, Self::TABLE);
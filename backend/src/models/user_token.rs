@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    Error, FromRow, Type,
+    postgres::PgPool,
+};
+use tracing::debug;
+use super::{token, UtcTimestamp};
+use std::fmt;
+
+// =================================================================
+// 1. ESTRUCTURAS DE DATOS (STRUCTS)
+// =================================================================
+
+/// Para qué sirve un token enviado por email.
+#[derive(Debug, Type, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "user_token_purpose_enum", rename_all = "lowercase")] // Nombre del ENUM en PostgreSQL
+pub enum TokenPurpose {
+    // Recuperación de la contraseña olvidada
+    #[serde(rename = "reset")]
+    Reset,
+    // Verificación del email
+    #[serde(rename = "verify")]
+    Verify,
+}
+
+impl fmt::Display for TokenPurpose {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Reset => "reset",
+            Self::Verify => "verify",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl TokenPurpose {
+    /// Tiempo durante el que vale el token.
+    pub fn ttl(&self) -> chrono::Duration {
+        match self {
+            Self::Reset => chrono::Duration::hours(1),
+            Self::Verify => chrono::Duration::hours(48),
+        }
+    }
+}
+
+/// Token de un solo uso y con caducidad enviado al usuario por email. Solo se
+/// guarda su hash.
+#[derive(Debug, FromRow, Serialize)]
+pub struct UserToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub purpose: TokenPurpose,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub expires_at: UtcTimestamp,
+    pub used_at: Option<UtcTimestamp>,
+    pub created_at: UtcTimestamp,
+}
+
+// =================================================================
+// 2. MÉTODOS (ASOCIADOS DIRECTAMENTE AL STRUCT)
+// =================================================================
+
+impl UserToken {
+    const TABLE: &str = "user_tokens";
+
+    // =================================================================
+    // C: CREATE (Crear)
+    // =================================================================
+    /// Emite un token para el usuario y lo devuelve en claro. Los tokens
+    /// anteriores del mismo tipo que no se hayan usado dejan de valer.
+    pub async fn create(pg_pool: &PgPool, user_id: i32, purpose: TokenPurpose) -> Result<(Self, String), Error> {
+        let token = token::generate();
+        let mut tx = pg_pool.begin().await?;
+        let sql = format!("DELETE FROM {} WHERE user_id = $1 AND purpose = $2 AND used_at IS NULL", Self::TABLE);
+        debug!("Delete unused: {}", &sql);
+        sqlx::query(&sql)
+            .bind(user_id)
+            .bind(purpose)
+            .execute(&mut *tx)
+            .await?;
+        let sql = format!(
+            "INSERT INTO {} (user_id, purpose, token_hash, expires_at) VALUES ($1, $2, $3, $4) RETURNING *",
+            Self::TABLE
+        );
+        debug!("Create: {}", &sql);
+        let user_token = sqlx::query_as::<_, Self>(&sql)
+            .bind(user_id)
+            .bind(purpose)
+            .bind(token::hash(&token))
+            .bind(chrono::Utc::now() + purpose.ttl())
+            .fetch_one(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok((user_token, token))
+    }
+
+    // =================================================================
+    // U: UPDATE (Actualizar)
+    // =================================================================
+    /// Marca como usado un token vigente del tipo indicado y lo devuelve.
+    /// Devuelve `None` si no existe, ha caducado o ya se usó.
+    pub async fn consume(pg_pool: &PgPool, token: &str, purpose: TokenPurpose) -> Result<Option<Self>, Error> {
+        let sql = format!(
+            r#"UPDATE {} SET used_at = NOW()
+            WHERE token_hash = $1 AND purpose = $2 AND used_at IS NULL AND expires_at > NOW()
+            RETURNING *"#,
+            Self::TABLE
+        );
+        debug!("Consume: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(token::hash(token))
+            .bind(purpose)
+            .fetch_optional(pg_pool)
+            .await
+    }
+}
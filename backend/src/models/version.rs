@@ -3,7 +3,7 @@ use sqlx::{
     Postgres,
     QueryBuilder,
     Error, FromRow, Row,
-    postgres::{PgPool, PgRow},
+    postgres::{PgExecutor, PgPool, PgRow},
 };
 use tracing::debug;
 use super::{
@@ -17,7 +17,7 @@ use macros::axum_crud;
 // 1. ESTRUCTURAS DE DATOS (STRUCTS)
 // =================================================================
 
-#[axum_crud(path = "/versions", new = "NewVersion", params = "VersionParams")]
+#[axum_crud(path = "/versions", new = "NewVersion", params = "VersionParams", write = "master")]
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct Version {
     pub id: i32,
@@ -114,12 +114,15 @@ impl Version {
     // C: CREATE (Crear)
     // =================================================================
     /// Inserta un nuevo registro en la base de datos y devuelve el objeto creado.
-    pub async fn create(pg_pool: &PgPool, item: NewVersion) -> Result<Self, Error> {
+    pub async fn create<'e, E>(executor: E, item: NewVersion) -> Result<Self, Error>
+    where
+        E: PgExecutor<'e>,
+    {
         let sql = format!("INSERT INTO {} {} RETURNING *", Self::TABLE, Self::INSERT_QUERY);
         debug!("Create: {}", &sql);
         sqlx::query_as::<_, Self>(&sql)
         .bind(item.name)
-        .fetch_one(pg_pool)
+        .fetch_one(executor)
         .await
     }
 
@@ -149,4 +152,19 @@ impl Version {
             .fetch_one(pg_pool)
             .await
     }
+    // =================================================================
+    // E: OTHERS
+    // =================================================================
+    /// Recupera las versiones indicadas, de la más antigua a la más reciente.
+    pub async fn read_by_ids<'e, E>(executor: E, ids: &[i32]) -> Result<Vec<Self>, Error>
+    where
+        E: PgExecutor<'e>,
+    {
+        let sql = format!("SELECT * FROM {} WHERE id = ANY($1) ORDER BY created_at, id", Self::TABLE);
+        debug!("Read by ids: {}", &sql);
+        sqlx::query_as::<_, Self>(&sql)
+            .bind(ids)
+            .fetch_all(executor)
+            .await
+    }
 }
@@ -0,0 +1,295 @@
+//! Documento imprimible de un presupuesto: portada, mediciones y presupuesto
+//! por capítulos, resumen por capítulos y hoja final con los porcentajes hasta
+//! el total con IVA y las firmas.
+use std::collections::HashMap;
+use serde_json::Value;
+use sqlx::{postgres::PgPool, types::BigDecimal};
+
+use super::{
+    document::Font,
+    layout::{format_number, Cell, Layout, LEFT, RIGHT},
+};
+use crate::{
+    models::{
+        Budget,
+        ElementType,
+        Measurement,
+        Project,
+    },
+    pricing::PricingError,
+    summary::{self, BudgetSummary, SummaryNode, SummaryOptions},
+    words,
+};
+
+const TITLE_SIZE: f32 = 14.0;
+const HEADING_SIZE: f32 = 10.0;
+const TEXT_SIZE: f32 = 8.5;
+const QUANTITY_DECIMALS: i64 = 2;
+const MONTHS: [&str; 12] = [
+    "enero", "febrero", "marzo", "abril", "mayo", "junio",
+    "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre",
+];
+
+// Columnas de las mediciones
+const CODE_X: f32 = LEFT;
+const TEXT_X: f32 = LEFT + 70.0;
+const QUANTITY_X: f32 = RIGHT - 150.0;
+const PRICE_X: f32 = RIGHT - 75.0;
+const AMOUNT_X: f32 = RIGHT;
+
+/// Genera el PDF de un presupuesto. Devuelve `None` si no existe.
+pub async fn render_budget(pool: &PgPool, budget_id: i32) -> Result<Option<Vec<u8>>, PricingError> {
+    let Some(budget) = Budget::read_by_id(pool, budget_id).await? else {
+        return Ok(None);
+    };
+    let Some(summary) = summary::summarize(pool, budget_id, SummaryOptions::default()).await? else {
+        return Ok(None);
+    };
+    let project = Project::read_by_id(pool, budget.project_id).await?;
+    let measurements = Measurement::read_by_budget(pool, budget_id).await?;
+    Ok(Some(render(&budget, project.as_ref(), &summary, &measurements)))
+}
+
+/// Compone el documento con los datos ya cargados.
+pub fn render(
+    budget: &Budget,
+    project: Option<&Project>,
+    summary: &BudgetSummary,
+    measurements: &[Measurement],
+) -> Vec<u8> {
+    let mut layout = Layout::new(&budget.name, &format!("{} - {}", budget.code, budget.name));
+    let decimals = summary.options.decimals;
+    let date = long_date(chrono::Utc::now().date_naive());
+
+    // Portada
+    layout.new_page();
+    layout.space(200.0);
+    layout.row(22.0, &[Cell::center((LEFT + RIGHT) / 2.0, Font::Bold, "PRESUPUESTO")]);
+    layout.space(10.0);
+    layout.row(HEADING_SIZE + 4.0, &[Cell::center((LEFT + RIGHT) / 2.0, Font::Regular, budget.name.clone())]);
+    layout.space(60.0);
+    let mut cover = vec![
+        ("Presupuesto", budget.code.clone()),
+        ("Versión", budget.version_number.to_string()),
+        ("Estado", budget.status.to_string()),
+        ("Fecha", date.clone()),
+    ];
+    if let Some(project) = project {
+        cover.insert(0, ("Proyecto", format!("{} - {}", project.code, project.title)));
+    }
+    for (label, value) in cover {
+        layout.row(HEADING_SIZE, &[
+            Cell::left(LEFT + 60.0, Font::Bold, format!("{}:", label)),
+            Cell::left(LEFT + 160.0, Font::Regular, value),
+        ]);
+    }
+
+    // Mediciones y presupuesto
+    let mut lines: HashMap<(i32, i32), Vec<&Measurement>> = HashMap::new();
+    for measurement in measurements {
+        lines.entry((measurement.element_id, measurement.price_id)).or_default().push(measurement);
+    }
+    layout.new_page();
+    layout.row(TITLE_SIZE, &[Cell::left(LEFT, Font::Bold, "MEDICIONES Y PRESUPUESTO")]);
+    layout.space(6.0);
+    layout.set_columns(Some((TEXT_SIZE, vec![
+        Cell::left(CODE_X + 2.0, Font::Bold, "Código"),
+        Cell::left(TEXT_X, Font::Bold, "Descripción"),
+        Cell::right(QUANTITY_X, Font::Bold, "Cantidad"),
+        Cell::right(PRICE_X, Font::Bold, "Precio"),
+        Cell::right(AMOUNT_X - 2.0, Font::Bold, "Importe"),
+    ])));
+    for node in &summary.chapters {
+        node_block(&mut layout, node, 0, &lines, decimals);
+    }
+    layout.set_columns(None);
+
+    // Resumen por capítulos
+    layout.new_page();
+    layout.row(TITLE_SIZE, &[Cell::left(LEFT, Font::Bold, "RESUMEN DE PRESUPUESTO")]);
+    layout.space(10.0);
+    for (depth, node) in summary.nodes() {
+        if node.element_type != ElementType::Chapter {
+            continue;
+        }
+        let font = if depth == 0 { Font::Bold } else { Font::Regular };
+        let indent = 12.0 * depth as f32;
+        let mut cells = vec![
+            Cell::left(LEFT + indent, font, node.code.clone()),
+            Cell::left(TEXT_X + indent, font, node.description.clone().unwrap_or_default()),
+            Cell::right(PRICE_X, font, format_number(&node.amount, decimals)),
+        ];
+        if depth == 0 {
+            cells.push(Cell::right(AMOUNT_X, Font::Regular, format!("{} %", format_number(&share(&node.amount, &summary.pem), 2))));
+        }
+        layout.row(TEXT_SIZE + 1.0, &cells);
+    }
+
+    // Porcentajes y total
+    layout.space(10.0);
+    layout.ensure(200.0);
+    let total_row = |layout: &mut Layout, label: &str, amount: &BigDecimal| {
+        layout.rule_between(TEXT_X, RIGHT, 0.5);
+        layout.space(2.0);
+        layout.row(HEADING_SIZE, &[
+            Cell::left(TEXT_X, Font::Bold, label),
+            Cell::right(AMOUNT_X, Font::Bold, format_number(amount, decimals)),
+        ]);
+        layout.space(4.0);
+    };
+    let rate_row = |layout: &mut Layout, rate: &summary::SummaryRate| {
+        layout.row(TEXT_SIZE + 1.0, &[
+            Cell::right(TEXT_X + 50.0, Font::Regular, format!("{} %", format_number(&rate.percentage, 2))),
+            Cell::left(TEXT_X + 60.0, Font::Regular, rate.name.clone()),
+            Cell::right(PRICE_X, Font::Regular, format_number(&rate.amount, decimals)),
+        ]);
+    };
+    total_row(&mut layout, "TOTAL EJECUCIÓN MATERIAL", &summary.pem);
+    for rate in &summary.rates {
+        rate_row(&mut layout, rate);
+    }
+    layout.space(4.0);
+    total_row(&mut layout, "TOTAL PRESUPUESTO DE EJECUCIÓN POR CONTRATA", &summary.pec);
+    rate_row(&mut layout, &summary.vat);
+    layout.space(4.0);
+    total_row(&mut layout, "TOTAL PRESUPUESTO GENERAL", &summary.total);
+    layout.space(10.0);
+    layout.paragraph(
+        LEFT,
+        RIGHT - LEFT,
+        Font::Regular,
+        TEXT_SIZE + 1.0,
+        &format!("Asciende el presupuesto general a la expresada cantidad de {}.", words::amount(&summary.total)),
+    );
+
+    // Firmas
+    layout.space(30.0);
+    layout.row(TEXT_SIZE + 1.0, &[Cell::right(RIGHT, Font::Regular, format!("En ...................., a {}", date))]);
+    layout.space(20.0);
+    let left = LEFT + (RIGHT - LEFT) / 4.0;
+    let right = RIGHT - (RIGHT - LEFT) / 4.0;
+    layout.row(TEXT_SIZE + 1.0, &[
+        Cell::center(left, Font::Bold, "LA PROPIEDAD"),
+        Cell::center(right, Font::Bold, "EL TÉCNICO REDACTOR"),
+    ]);
+    layout.space(60.0);
+    layout.rule_between(left - 70.0, left + 70.0, 0.5);
+    layout.rule_between(right - 70.0, right + 70.0, 0.5);
+
+    layout.finish()
+}
+
+/// Capítulo o línea con sus precios, mediciones e importes.
+fn node_block(
+    layout: &mut Layout,
+    node: &SummaryNode,
+    depth: usize,
+    lines: &HashMap<(i32, i32), Vec<&Measurement>>,
+    decimals: i64,
+) {
+    let description = node.description.clone().unwrap_or_default();
+    match node.element_type {
+        ElementType::Chapter => {
+            let label = if depth == 0 { "CAPÍTULO" } else { "SUBCAPÍTULO" };
+            layout.ensure(HEADING_SIZE * 4.0);
+            layout.space(4.0);
+            layout.row(HEADING_SIZE, &[
+                Cell::left(CODE_X, Font::Bold, node.code.clone()),
+                Cell::left(TEXT_X, Font::Bold, format!("{} {}", label, description)),
+            ]);
+        }
+        ElementType::Line => {
+            layout.ensure(TEXT_SIZE * 4.0);
+            layout.row(TEXT_SIZE + 0.5, &[
+                Cell::left(CODE_X, Font::Bold, node.code.clone()),
+                Cell::left(TEXT_X, Font::Bold, description),
+            ]);
+        }
+    }
+
+    for item in &node.items {
+        layout.ensure(TEXT_SIZE * 5.0);
+        layout.row(TEXT_SIZE, &[
+            Cell::left(CODE_X, Font::Regular, item.code.clone()),
+            Cell::left(TEXT_X, Font::Bold, item.unit.clone()),
+        ]);
+        layout.paragraph(TEXT_X, QUANTITY_X - TEXT_X - 40.0, Font::Regular, TEXT_SIZE, &item.description);
+        for measurement in lines.get(&(node.element_id, item.price_id)).map(Vec::as_slice).unwrap_or_default() {
+            let text = measurement.measurement_text.clone().unwrap_or_else(|| params(&measurement.params_json));
+            layout.row(TEXT_SIZE, &[
+                Cell::left(TEXT_X + 10.0, Font::Regular, text),
+                Cell::right(QUANTITY_X, Font::Regular, format_number(&measurement.measured_quantity, QUANTITY_DECIMALS)),
+            ]);
+        }
+        layout.rule_between(QUANTITY_X - 60.0, RIGHT, 0.3);
+        layout.row(TEXT_SIZE, &[
+            Cell::right(QUANTITY_X - 65.0, Font::Regular, format!("Total {}", item.unit)),
+            Cell::right(QUANTITY_X, Font::Bold, format_number(&item.quantity, QUANTITY_DECIMALS)),
+            Cell::right(PRICE_X, Font::Regular, format_number(&item.unit_price, decimals)),
+            Cell::right(AMOUNT_X, Font::Bold, format_number(&item.amount, decimals)),
+        ]);
+        layout.space(4.0);
+    }
+
+    for child in &node.children {
+        node_block(layout, child, depth + 1, lines, decimals);
+    }
+
+    if node.element_type == ElementType::Chapter {
+        layout.ensure(HEADING_SIZE * 2.0);
+        layout.rule_between(TEXT_X, RIGHT, 0.5);
+        let label = if depth == 0 { "TOTAL CAPÍTULO" } else { "TOTAL SUBCAPÍTULO" };
+        layout.row(HEADING_SIZE, &[
+            Cell::left(TEXT_X, Font::Bold, format!("{} {}", label, node.code)),
+            Cell::right(AMOUNT_X, Font::Bold, format_number(&node.amount, decimals)),
+        ]);
+        layout.space(6.0);
+    }
+}
+
+/// Parámetros de una medición como texto: "a = 2 · b = 3".
+fn params(params: &Value) -> String {
+    match params {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| match value {
+                Value::String(s) => format!("{} = {}", key, s),
+                value => format!("{} = {}", key, value),
+            })
+            .collect::<Vec<_>>()
+            .join(" · "),
+        _ => String::new(),
+    }
+}
+
+/// Porcentaje que representa `amount` sobre `total`.
+fn share(amount: &BigDecimal, total: &BigDecimal) -> BigDecimal {
+    if total == &BigDecimal::from(0) {
+        return BigDecimal::from(0);
+    }
+    amount * BigDecimal::from(100) / total
+}
+
+/// Fecha en castellano: "18 de octubre de 2026".
+pub fn long_date(date: chrono::NaiveDate) -> String {
+    use chrono::Datelike;
+    format!("{} de {} de {}", date.day(), MONTHS[date.month0() as usize], date.year())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_params() {
+        assert_eq!(params(&json!({"a": "2", "b": 3.5})), "a = 2 · b = 3.5");
+        assert_eq!(params(&json!(null)), "");
+    }
+
+    #[test]
+    fn test_long_date() {
+        let date = chrono::NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+        assert_eq!(long_date(date), "18 de octubre de 2026");
+    }
+}
@@ -0,0 +1,195 @@
+//! Escritura de documentos PDF 1.4 mínimos: páginas A4 con texto en las
+//! fuentes estándar Helvetica (codificación WinAnsi), líneas y rectángulos.
+use std::fmt::Write;
+use encoding_rs::WINDOWS_1252;
+
+pub const PAGE_WIDTH: f32 = 595.28;
+pub const PAGE_HEIGHT: f32 = 841.89;
+const PRODUCER: &str = "Presu";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Self::Regular => "F1",
+            Self::Bold => "F2",
+        }
+    }
+}
+
+/// Página con su flujo de contenido. Las coordenadas son en puntos, con el
+/// origen en la esquina inferior izquierda.
+#[derive(Debug, Default)]
+pub struct Page {
+    content: Vec<u8>,
+}
+
+impl Page {
+    pub fn text(&mut self, x: f32, y: f32, font: Font, size: f32, text: &str) {
+        self.push(&format!("BT /{} {:.1} Tf {:.2} {:.2} Td (", font.resource(), size, x, y));
+        self.content.extend(escape(&encode(text)));
+        self.push(") Tj ET\n");
+    }
+
+    pub fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, width: f32) {
+        self.push(&format!("{:.2} w {:.2} {:.2} m {:.2} {:.2} l S\n", width, x1, y1, x2, y2));
+    }
+
+    /// Rectángulo relleno en escala de grises (0 negro, 1 blanco).
+    pub fn fill(&mut self, x: f32, y: f32, width: f32, height: f32, gray: f32) {
+        self.push(&format!("{:.2} g {:.2} {:.2} {:.2} {:.2} re f 0 g\n", gray, x, y, width, height));
+    }
+
+    fn push(&mut self, operators: &str) {
+        self.content.extend_from_slice(operators.as_bytes());
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Document {
+    pub title: String,
+    pub pages: Vec<Page>,
+}
+
+impl Document {
+    pub fn new(title: &str) -> Self {
+        Self { title: title.to_string(), pages: Vec::new() }
+    }
+
+    pub fn add_page(&mut self) -> &mut Page {
+        self.pages.push(Page::default());
+        self.pages.last_mut().unwrap()
+    }
+
+    /// Serializa el documento: catálogo, árbol de páginas, fuentes, información
+    /// y, por cada página, su diccionario y su flujo de contenido.
+    pub fn render(&self) -> Vec<u8> {
+        let font = |name: &str| {
+            format!("<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>", name).into_bytes()
+        };
+        let page_id = |index: usize| 6 + 2 * index;
+        let kids: Vec<String> = (0..self.pages.len()).map(|i| format!("{} 0 R", page_id(i))).collect();
+
+        let mut info = b"<< /Title (".to_vec();
+        info.extend(escape(&encode(&self.title)));
+        info.extend(
+            format!(
+                ") /Producer ({}) /CreationDate (D:{}Z) >>",
+                PRODUCER,
+                chrono::Utc::now().format("%Y%m%d%H%M%S")
+            )
+            .into_bytes(),
+        );
+
+        let mut objects: Vec<Vec<u8>> = vec![
+            b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+            format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), self.pages.len()).into_bytes(),
+            font("Helvetica"),
+            font("Helvetica-Bold"),
+            info,
+        ];
+        for (index, page) in self.pages.iter().enumerate() {
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+                     /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                    PAGE_WIDTH,
+                    PAGE_HEIGHT,
+                    page_id(index) + 1
+                )
+                .into_bytes(),
+            );
+            let mut stream = format!("<< /Length {} >>\nstream\n", page.content.len()).into_bytes();
+            stream.extend_from_slice(&page.content);
+            stream.extend_from_slice(b"\nendstream");
+            objects.push(stream);
+        }
+
+        let mut out = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend(format!("{} 0 obj\n", index + 1).into_bytes());
+            out.extend_from_slice(object);
+            out.extend_from_slice(b"\nendobj\n");
+        }
+        let xref = out.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(table, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(
+            table,
+            "trailer\n<< /Size {} /Root 1 0 R /Info 5 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        );
+        out.extend(table.into_bytes());
+        out
+    }
+}
+
+/// Codifica el texto en WinAnsi; los caracteres que no existen en ella se
+/// sustituyen por '?'.
+fn encode(text: &str) -> Vec<u8> {
+    let mut buffer = [0u8; 4];
+    text.chars()
+        .flat_map(|c| {
+            let (bytes, _, errors) = WINDOWS_1252.encode(c.encode_utf8(&mut buffer));
+            if errors { vec![b'?'] } else { bytes.into_owned() }
+        })
+        .collect()
+}
+
+/// Escapa los caracteres especiales de una cadena literal de PDF.
+fn escape(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    for &b in bytes {
+        match b {
+            b'(' | b')' | b'\\' => out.extend_from_slice(&[b'\\', b]),
+            b'\r' | b'\n' => out.push(b' '),
+            b => out.push(b),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_and_escape() {
+        assert_eq!(encode("Año 2€ ✓"), b"A\xF1o 2\x80 ?".to_vec());
+        assert_eq!(escape(b"(a\\b)\n"), b"\\(a\\\\b\\) ".to_vec());
+    }
+
+    #[test]
+    fn test_render() {
+        let mut document = Document::new("Prueba");
+        document.add_page().text(50.0, 800.0, Font::Bold, 12.0, "Capítulo (1)");
+        document.add_page().line(50.0, 50.0, 100.0, 50.0, 0.5);
+        let pdf = document.render();
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.starts_with("%PDF-1.4"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("/Count 2"));
+        assert!(text.contains("(Cap\u{FFFD}tulo \\(1\\)) Tj"));
+
+        // Cada entrada de la tabla xref apunta al comienzo de su objeto
+        let xref = pdf.windows(6).rposition(|w| w == b"\nxref\n").unwrap() + 1;
+        let table = String::from_utf8(pdf[xref..].to_vec()).unwrap();
+        let entries: Vec<&str> = table.lines().skip(3).take(9).collect();
+        for (index, entry) in entries.iter().enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj", index + 1).as_bytes()));
+        }
+        let startxref: usize = table.lines().rev().nth(1).unwrap().parse().unwrap();
+        assert_eq!(startxref, xref);
+    }
+}
@@ -0,0 +1,282 @@
+//! Composición de texto en páginas: un cursor vertical que salta de página
+//! cuando no cabe el siguiente bloque, con cabecera de tabla repetida y pie con
+//! la numeración de páginas.
+use bigdecimal::RoundingMode;
+use sqlx::types::BigDecimal;
+
+use super::{
+    document::{Document, Font, PAGE_HEIGHT, PAGE_WIDTH},
+    metrics,
+};
+
+pub const MARGIN: f32 = 50.0;
+pub const LEFT: f32 = MARGIN;
+pub const RIGHT: f32 = PAGE_WIDTH - MARGIN;
+const TOP: f32 = PAGE_HEIGHT - MARGIN;
+const BOTTOM: f32 = MARGIN + 20.0;
+const HEADER_SIZE: f32 = 8.0;
+const LEADING: f32 = 1.3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/// Texto en una posición horizontal. Con `Align::Right` la x es el borde
+/// derecho y con `Align::Center` el centro.
+#[derive(Debug, Clone)]
+pub struct Cell {
+    pub x: f32,
+    pub align: Align,
+    pub font: Font,
+    pub text: String,
+}
+
+impl Cell {
+    pub fn left(x: f32, font: Font, text: impl Into<String>) -> Self {
+        Self { x, align: Align::Left, font, text: text.into() }
+    }
+
+    pub fn right(x: f32, font: Font, text: impl Into<String>) -> Self {
+        Self { x, align: Align::Right, font, text: text.into() }
+    }
+
+    pub fn center(x: f32, font: Font, text: impl Into<String>) -> Self {
+        Self { x, align: Align::Center, font, text: text.into() }
+    }
+}
+
+#[derive(Debug)]
+pub struct Layout {
+    document: Document,
+    // Texto de la cabecera de cada página (excepto la portada)
+    header: String,
+    // Cabecera de tabla que se repite al comienzo de cada página
+    columns: Option<(f32, Vec<Cell>)>,
+    y: f32,
+}
+
+impl Layout {
+    pub fn new(title: &str, header: &str) -> Self {
+        Self {
+            document: Document::new(title),
+            header: header.to_string(),
+            columns: None,
+            y: TOP,
+        }
+    }
+
+    /// Posición vertical actual (línea base del siguiente texto).
+    pub fn y(&self) -> f32 {
+        self.y
+    }
+
+    pub fn new_page(&mut self) {
+        self.document.add_page();
+        self.y = TOP;
+        if self.document.pages.len() > 1 && !self.header.is_empty() {
+            let header = self.header.clone();
+            self.row(HEADER_SIZE, &[Cell::left(LEFT, Font::Regular, header)]);
+            self.rule(0.5);
+            self.space(6.0);
+        }
+        if let Some((size, cells)) = self.columns.clone() {
+            self.draw_columns(size, &cells);
+        }
+    }
+
+    /// Fija (o quita) la cabecera de tabla y la dibuja en la posición actual.
+    pub fn set_columns(&mut self, columns: Option<(f32, Vec<Cell>)>) {
+        self.columns = columns;
+        if let Some((size, cells)) = self.columns.clone() {
+            self.ensure(size * LEADING * 3.0);
+            self.draw_columns(size, &cells);
+        }
+    }
+
+    fn draw_columns(&mut self, size: f32, cells: &[Cell]) {
+        let height = size * LEADING + 4.0;
+        let y = self.y - 4.0;
+        self.page().fill(LEFT, y, RIGHT - LEFT, height, 0.9);
+        self.row(size, cells);
+        self.space(4.0);
+    }
+
+    /// Salta de página si no quedan `height` puntos libres.
+    pub fn ensure(&mut self, height: f32) {
+        if self.document.pages.is_empty() || self.y - height < BOTTOM {
+            self.new_page();
+        }
+    }
+
+    pub fn space(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    /// Escribe una fila de celdas y avanza una línea.
+    pub fn row(&mut self, size: f32, cells: &[Cell]) {
+        self.ensure(size * LEADING);
+        let y = self.y;
+        let page = self.page();
+        for cell in cells {
+            let x = match cell.align {
+                Align::Left => cell.x,
+                Align::Right => cell.x - metrics::width(&cell.text, cell.font, size),
+                Align::Center => cell.x - metrics::width(&cell.text, cell.font, size) / 2.0,
+            };
+            page.text(x, y, cell.font, size, &cell.text);
+        }
+        self.y -= size * LEADING;
+    }
+
+    /// Escribe un texto partido en líneas de como máximo `width` puntos.
+    pub fn paragraph(&mut self, x: f32, width: f32, font: Font, size: f32, text: &str) {
+        for line in wrap(text, font, size, width) {
+            self.row(size, &[Cell::left(x, font, line)]);
+        }
+    }
+
+    /// Fila de celdas cuyo texto se parte en líneas del ancho de cada celda.
+    /// La fila no se corta entre páginas salvo que no quepa en una sola.
+    pub fn table_row(&mut self, size: f32, cells: &[(Cell, f32)]) {
+        let wrapped: Vec<Vec<String>> = cells
+            .iter()
+            .map(|(cell, width)| wrap(&cell.text, cell.font, size, *width))
+            .collect();
+        let height = wrapped.iter().map(Vec::len).max().unwrap_or(1);
+        self.ensure(size * LEADING * height as f32);
+        for line in 0..height {
+            let row: Vec<Cell> = cells
+                .iter()
+                .zip(&wrapped)
+                .filter_map(|((cell, _), lines)| {
+                    lines.get(line).map(|text| Cell { text: text.clone(), ..cell.clone() })
+                })
+                .collect();
+            self.row(size, &row);
+        }
+    }
+
+    /// Línea horizontal entre los márgenes, justo bajo el texto anterior.
+    pub fn rule(&mut self, width: f32) {
+        self.rule_between(LEFT, RIGHT, width);
+    }
+
+    pub fn rule_between(&mut self, x1: f32, x2: f32, width: f32) {
+        let y = self.y + 6.0;
+        self.page().line(x1, y, x2, y, width);
+    }
+
+    fn page(&mut self) -> &mut super::document::Page {
+        if self.document.pages.is_empty() {
+            self.document.add_page();
+        }
+        self.document.pages.last_mut().unwrap()
+    }
+
+    /// Añade el pie "Página n de N" (salvo en la portada) y serializa el documento.
+    pub fn finish(mut self) -> Vec<u8> {
+        let total = self.document.pages.len();
+        for (index, page) in self.document.pages.iter_mut().enumerate().skip(1) {
+            let text = format!("Página {} de {}", index + 1, total);
+            let x = RIGHT - metrics::width(&text, Font::Regular, HEADER_SIZE);
+            page.line(LEFT, MARGIN + 10.0, RIGHT, MARGIN + 10.0, 0.5);
+            page.text(x, MARGIN, Font::Regular, HEADER_SIZE, &text);
+        }
+        self.document.render()
+    }
+}
+
+/// Parte el texto en líneas que no superen `width` puntos, respetando los
+/// saltos de línea. Las palabras más largas que el ancho se cortan.
+pub fn wrap(text: &str, font: Font, size: f32, width: f32) -> Vec<String> {
+    let fits = |s: &str| metrics::width(s, font, size) <= width;
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+            if fits(&candidate) {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for c in word.chars() {
+                line.push(c);
+                if !fits(&line) && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(std::mem::take(&mut line));
+                    line.push(c);
+                }
+            }
+        }
+        lines.push(line);
+    }
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    lines
+}
+
+/// Formatea un número al estilo español: separador de miles '.' y decimal ','.
+pub fn format_number(value: &BigDecimal, decimals: i64) -> String {
+    let plain = value.with_scale_round(decimals, RoundingMode::HalfUp).to_plain_string();
+    let (sign, digits) = match plain.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", plain.as_str()),
+    };
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let mut grouped = String::new();
+    for (index, c) in integer.chars().enumerate() {
+        if index > 0 && (integer.len() - index) % 3 == 0 {
+            grouped.push('.');
+        }
+        grouped.push(c);
+    }
+    if fraction.is_empty() {
+        format!("{}{}", sign, grouped)
+    } else {
+        format!("{}{},{}", sign, grouped, fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_format_number() {
+        let number = |s: &str, decimals| format_number(&BigDecimal::from_str(s).unwrap(), decimals);
+        assert_eq!(number("0", 2), "0,00");
+        assert_eq!(number("1234567.891", 2), "1.234.567,89");
+        assert_eq!(number("-999.995", 2), "-1.000,00");
+        assert_eq!(number("123", 0), "123");
+        assert_eq!(number("21", 2), "21,00");
+    }
+
+    #[test]
+    fn test_wrap() {
+        let lines = wrap("Solado de baldosa cerámica\ncon rodapié", Font::Regular, 10.0, 80.0);
+        assert_eq!(lines, vec!["Solado de", "baldosa cerámica", "con rodapié"]);
+        assert_eq!(wrap("", Font::Regular, 10.0, 80.0), vec![""]);
+        let long = wrap("AAAAAAAAAAAAAAAAAAAA", Font::Regular, 10.0, 40.0);
+        assert!(long.len() > 1);
+        assert!(long.iter().all(|l| metrics::width(l, Font::Regular, 10.0) <= 40.0));
+    }
+
+    #[test]
+    fn test_layout_breaks_pages() {
+        let mut layout = Layout::new("Prueba", "Cabecera");
+        for i in 0..200 {
+            layout.row(10.0, &[Cell::left(LEFT, Font::Regular, format!("Línea {}", i))]);
+        }
+        assert!(layout.document.pages.len() > 1);
+        let pdf = layout.finish();
+        assert!(String::from_utf8_lossy(&pdf).contains("de 4) Tj"));
+    }
+}
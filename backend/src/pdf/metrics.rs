@@ -0,0 +1,72 @@
+//! Anchos de carácter de las fuentes estándar Helvetica y Helvetica-Bold, en
+//! milésimas del tamaño de la fuente (tomados de sus ficheros AFM).
+use super::document::Font;
+
+// Caracteres 32 (espacio) a 126 (~)
+const HELVETICA: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+const HELVETICA_BOLD: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611,
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556,
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611,
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+/// Ancho de un carácter en milésimas. Las letras acentuadas miden lo mismo
+/// que su letra base.
+fn char_width(c: char, font: Font) -> u16 {
+    let table = match font {
+        Font::Regular => &HELVETICA,
+        Font::Bold => &HELVETICA_BOLD,
+    };
+    let base = match c {
+        'á' | 'à' | 'ä' | 'â' => 'a',
+        'é' | 'è' | 'ë' | 'ê' => 'e',
+        'í' | 'ì' | 'ï' | 'î' => 'i',
+        'ó' | 'ò' | 'ö' | 'ô' => 'o',
+        'ú' | 'ù' | 'ü' | 'û' => 'u',
+        'ñ' => 'n',
+        'ç' => 'c',
+        'Á' | 'À' | 'Ä' | 'Â' => 'A',
+        'É' | 'È' | 'Ë' | 'Ê' => 'E',
+        'Í' | 'Ì' | 'Ï' | 'Î' => 'I',
+        'Ó' | 'Ò' | 'Ö' | 'Ô' => 'O',
+        'Ú' | 'Ù' | 'Ü' | 'Û' => 'U',
+        'Ñ' => 'N',
+        'Ç' => 'C',
+        'º' | 'ª' | '²' | '³' => 'o',
+        '€' => '0',
+        c => c,
+    };
+    match base as u32 {
+        code @ 32..=126 => table[(code - 32) as usize],
+        _ => 556,
+    }
+}
+
+/// Ancho del texto en puntos.
+pub fn width(text: &str, font: Font, size: f32) -> f32 {
+    text.chars().map(|c| char_width(c, font) as f32).sum::<f32>() * size / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_width() {
+        assert_eq!(width("", Font::Regular, 10.0), 0.0);
+        assert!((width("Hola", Font::Regular, 10.0) - 20.56).abs() < 0.001);
+        assert_eq!(width("Ñ", Font::Bold, 10.0), width("N", Font::Bold, 10.0));
+        assert!(width("Total", Font::Bold, 10.0) > width("Total", Font::Regular, 10.0));
+    }
+}
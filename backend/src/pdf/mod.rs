@@ -0,0 +1,11 @@
+//! Generación de documentos PDF imprimibles.
+pub mod budget;
+pub mod document;
+pub mod layout;
+pub mod metrics;
+pub mod price_list;
+
+pub use budget::render_budget;
+pub use document::{Document, Font, Page};
+pub use layout::{format_number, Cell, Layout};
+pub use price_list::{render_first, render_second};
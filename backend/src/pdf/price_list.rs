@@ -0,0 +1,113 @@
+//! Cuadros de precios nº 1 (precios en letra) y nº 2 (precios descompuestos).
+use super::{
+    document::Font,
+    layout::{format_number, Cell, Layout, LEFT, RIGHT},
+};
+use crate::price_list::PriceList;
+
+const TITLE_SIZE: f32 = 14.0;
+const TEXT_SIZE: f32 = 8.5;
+const PRICE_DECIMALS: i64 = 2;
+const QUANTITY_DECIMALS: i64 = 4;
+
+/// Cuadro de precios nº 1: número, código, unidad, descripción y precio en
+/// cifra y en letra.
+pub fn render_first(list: &PriceList) -> Vec<u8> {
+    const NUMBER_X: f32 = LEFT;
+    const CODE_X: f32 = LEFT + 22.0;
+    const UNIT_X: f32 = LEFT + 80.0;
+    const DESCRIPTION_X: f32 = LEFT + 105.0;
+    const WORDS_X: f32 = LEFT + 300.0;
+    const PRICE_X: f32 = RIGHT;
+
+    let mut layout = start(list, "CUADRO DE PRECIOS Nº 1");
+    layout.set_columns(Some((TEXT_SIZE, vec![
+        Cell::left(NUMBER_X + 2.0, Font::Bold, "Nº"),
+        Cell::left(CODE_X, Font::Bold, "Código"),
+        Cell::left(UNIT_X, Font::Bold, "Ud"),
+        Cell::left(DESCRIPTION_X, Font::Bold, "Descripción"),
+        Cell::left(WORDS_X, Font::Bold, "Precio en letra"),
+        Cell::right(PRICE_X - 2.0, Font::Bold, "Importe"),
+    ])));
+    for entry in &list.entries {
+        layout.table_row(TEXT_SIZE, &[
+            (Cell::left(NUMBER_X, Font::Regular, entry.number.to_string()), CODE_X - NUMBER_X - 4.0),
+            (Cell::left(CODE_X, Font::Bold, entry.code.clone()), UNIT_X - CODE_X - 4.0),
+            (Cell::left(UNIT_X, Font::Regular, entry.unit.clone()), DESCRIPTION_X - UNIT_X - 4.0),
+            (Cell::left(DESCRIPTION_X, Font::Regular, entry.description.clone()), WORDS_X - DESCRIPTION_X - 10.0),
+            (Cell::left(WORDS_X, Font::Bold, entry.words.clone()), PRICE_X - WORDS_X - 60.0),
+            (Cell::right(PRICE_X, Font::Bold, format_number(&entry.unit_price, PRICE_DECIMALS)), 60.0),
+        ]);
+        layout.space(6.0);
+    }
+    layout.finish()
+}
+
+/// Cuadro de precios nº 2: cada precio con sus componentes, el porcentaje de
+/// cada uno y el total de los precios auxiliares.
+pub fn render_second(list: &PriceList) -> Vec<u8> {
+    const CODE_X: f32 = LEFT;
+    const QUANTITY_X: f32 = LEFT + 110.0;
+    const UNIT_X: f32 = LEFT + 115.0;
+    const DESCRIPTION_X: f32 = LEFT + 140.0;
+    const PRICE_X: f32 = RIGHT - 110.0;
+    const AMOUNT_X: f32 = RIGHT - 45.0;
+    const PERCENTAGE_X: f32 = RIGHT;
+
+    let mut layout = start(list, "CUADRO DE PRECIOS Nº 2");
+    layout.set_columns(Some((TEXT_SIZE, vec![
+        Cell::left(CODE_X + 2.0, Font::Bold, "Código"),
+        Cell::right(QUANTITY_X, Font::Bold, "Cantidad"),
+        Cell::left(UNIT_X, Font::Bold, "Ud"),
+        Cell::left(DESCRIPTION_X, Font::Bold, "Descripción"),
+        Cell::right(PRICE_X, Font::Bold, "Precio"),
+        Cell::right(AMOUNT_X, Font::Bold, "Importe"),
+        Cell::right(PERCENTAGE_X - 2.0, Font::Bold, "%"),
+    ])));
+    for entry in &list.entries {
+        layout.ensure(TEXT_SIZE * 6.0);
+        layout.table_row(TEXT_SIZE + 0.5, &[
+            (Cell::left(CODE_X, Font::Bold, format!("{} {}", entry.number, entry.code)), QUANTITY_X - CODE_X),
+            (Cell::left(UNIT_X, Font::Bold, entry.unit.clone()), DESCRIPTION_X - UNIT_X - 4.0),
+            (Cell::left(DESCRIPTION_X, Font::Bold, entry.description.clone()), RIGHT - DESCRIPTION_X),
+        ]);
+        for component in &entry.components {
+            let code = if component.auxiliary { format!("{} (aux.)", component.code) } else { component.code.clone() };
+            layout.table_row(TEXT_SIZE, &[
+                (Cell::left(CODE_X + 8.0, Font::Regular, code), QUANTITY_X - CODE_X - 60.0),
+                (Cell::right(QUANTITY_X, Font::Regular, format_number(&component.quantity, QUANTITY_DECIMALS)), 50.0),
+                (Cell::left(UNIT_X, Font::Regular, component.unit.clone()), DESCRIPTION_X - UNIT_X - 4.0),
+                (Cell::left(DESCRIPTION_X, Font::Regular, component.description.clone()), PRICE_X - DESCRIPTION_X - 50.0),
+                (Cell::right(PRICE_X, Font::Regular, format_number(&component.unit_price, PRICE_DECIMALS)), 50.0),
+                (Cell::right(AMOUNT_X, Font::Regular, format_number(&component.amount, PRICE_DECIMALS)), 50.0),
+                (Cell::right(PERCENTAGE_X, Font::Regular, format_number(&component.percentage, 2)), 40.0),
+            ]);
+        }
+        if entry.components.iter().any(|c| c.auxiliary) {
+            layout.row(TEXT_SIZE, &[
+                Cell::left(DESCRIPTION_X, Font::Regular, "Precios auxiliares"),
+                Cell::right(AMOUNT_X, Font::Regular, format_number(&entry.auxiliary, PRICE_DECIMALS)),
+                Cell::right(PERCENTAGE_X, Font::Regular, format_number(&entry.auxiliary_percentage, 2)),
+            ]);
+        }
+        layout.rule_between(DESCRIPTION_X, RIGHT, 0.3);
+        layout.row(TEXT_SIZE, &[
+            Cell::left(DESCRIPTION_X, Font::Bold, "TOTAL"),
+            Cell::right(AMOUNT_X, Font::Bold, format_number(&entry.unit_price, PRICE_DECIMALS)),
+        ]);
+        layout.space(8.0);
+    }
+    layout.finish()
+}
+
+/// Portada con el título del cuadro y primera página del contenido.
+fn start(list: &PriceList, title: &str) -> Layout {
+    let mut layout = Layout::new(&format!("{} - {}", title, list.name), &format!("{} - {}", list.code, list.name));
+    layout.new_page();
+    layout.space(300.0);
+    layout.row(22.0, &[Cell::center((LEFT + RIGHT) / 2.0, Font::Bold, title)]);
+    layout.space(10.0);
+    layout.row(TITLE_SIZE, &[Cell::center((LEFT + RIGHT) / 2.0, Font::Regular, list.name.clone())]);
+    layout.new_page();
+    layout
+}
@@ -0,0 +1,187 @@
+//! Evolución de los precios entre versiones del catálogo.
+//!
+//! - Histórico de un código: su precio en cada versión en la que existe, de la
+//!   más antigua a la más reciente.
+//! - Informe entre dos versiones: los códigos cuyo precio cambia, ordenados por
+//!   el porcentaje de cambio, y los que aparecen o desaparecen.
+//!
+//! Se compara siempre el precio calculado (ver `pricing`): en los
+//! descompuestos refleja el cambio de sus componentes aunque el precio guardado
+//! no se haya actualizado.
+use std::collections::{HashMap, HashSet};
+use bigdecimal::RoundingMode;
+use serde::Serialize;
+use sqlx::{postgres::PgPool, types::BigDecimal};
+use tracing::debug;
+
+use crate::{
+    models::{Price, UtcTimestamp, Version},
+    pricing::{Catalog, PricingError},
+};
+
+const PERCENTAGE_SCALE: i64 = 2;
+
+/// Precio de un código en una versión.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryPoint {
+    pub version_id: i32,
+    pub version_name: String,
+    pub version_created_at: UtcTimestamp,
+    pub price_id: i32,
+    pub description: String,
+    pub base_price: BigDecimal,
+    pub unit_price: BigDecimal,
+    // Cambio del precio calculado respecto a la versión anterior; `None` en la
+    // primera o si el precio anterior era cero
+    pub percentage: Option<BigDecimal>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceHistory {
+    pub code: String,
+    pub points: Vec<HistoryPoint>,
+}
+
+/// Precio que solo está en una de las dos versiones.
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionPrice {
+    pub price_id: i32,
+    pub code: String,
+    pub description: String,
+    pub unit_price: BigDecimal,
+}
+
+/// Precio que está en las dos versiones con distinto precio calculado.
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceChange {
+    pub code: String,
+    pub description: String,
+    pub from: BigDecimal,
+    pub to: BigDecimal,
+    pub delta: BigDecimal,
+    // `None` si el precio de origen era cero
+    pub percentage: Option<BigDecimal>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VersionComparison {
+    pub from: Version,
+    pub to: Version,
+    // De mayor a menor cambio en valor absoluto; los que no tienen porcentaje, al final
+    pub changed: Vec<PriceChange>,
+    pub unchanged: usize,
+    pub added: Vec<VersionPrice>,
+    pub removed: Vec<VersionPrice>,
+}
+
+/// Porcentaje de cambio de `from` a `to`, o `None` si `from` es cero.
+fn percentage(from: &BigDecimal, to: &BigDecimal) -> Option<BigDecimal> {
+    if *from == 0 {
+        return None;
+    }
+    Some(((to - from) * BigDecimal::from(100) / from).with_scale_round(PERCENTAGE_SCALE, RoundingMode::HalfUp))
+}
+
+/// Histórico del precio de un código. Devuelve `None` si no está en ninguna
+/// versión.
+pub async fn history(pool: &PgPool, code: &str) -> Result<Option<PriceHistory>, PricingError> {
+    let prices = Price::read_by_code(pool, code).await?;
+    if prices.is_empty() {
+        return Ok(None);
+    }
+    let ids: Vec<i32> = prices.iter().map(|p| p.id).collect();
+    let unit_prices = Catalog::load(pool, &ids).await?.unit_prices()?;
+    let version_ids: Vec<i32> = prices.iter().map(|p| p.version_id).collect();
+    let versions = Version::read_by_ids(pool, &version_ids).await?;
+
+    let mut points: Vec<HistoryPoint> = Vec::with_capacity(versions.len());
+    for version in versions {
+        let Some(price) = prices.iter().find(|p| p.version_id == version.id) else {
+            continue;
+        };
+        let unit_price = unit_prices.get(&price.id).cloned().ok_or(PricingError::MissingPrice(price.id))?;
+        points.push(HistoryPoint {
+            version_id: version.id,
+            version_name: version.name,
+            version_created_at: version.created_at,
+            price_id: price.id,
+            description: price.description.clone(),
+            base_price: price.base_price.clone(),
+            percentage: points.last().and_then(|last| percentage(&last.unit_price, &unit_price)),
+            unit_price,
+        });
+    }
+    debug!("Price history of {}: {} versions", code, points.len());
+    Ok(Some(PriceHistory { code: code.to_string(), points }))
+}
+
+/// Compara los precios de la versión `from` con los de `to`. `limit` recorta
+/// la lista de cambios. Devuelve `None` si alguna de las dos no existe.
+pub async fn compare_versions(
+    pool: &PgPool,
+    from_id: i32,
+    to_id: i32,
+    limit: Option<usize>,
+) -> Result<Option<VersionComparison>, PricingError> {
+    let (Some(from), Some(to)) = (Version::read_by_id(pool, from_id).await?, Version::read_by_id(pool, to_id).await?) else {
+        return Ok(None);
+    };
+    let from_prices = Price::read_by_version(pool, from_id).await?;
+    let to_prices = Price::read_by_version(pool, to_id).await?;
+    let ids: Vec<i32> = from_prices.iter().chain(&to_prices).map(|p| p.id).collect();
+    let unit_prices = Catalog::load(pool, &ids).await?.unit_prices()?;
+    let unit_price = |price: &Price| unit_prices.get(&price.id).cloned().ok_or(PricingError::MissingPrice(price.id));
+    let version_price = |price: &Price| -> Result<VersionPrice, PricingError> {
+        Ok(VersionPrice {
+            price_id: price.id,
+            code: price.code.clone(),
+            description: price.description.clone(),
+            unit_price: unit_price(price)?,
+        })
+    };
+
+    let to_by_code: HashMap<&str, &Price> = to_prices.iter().map(|p| (p.code.as_str(), p)).collect();
+    let mut changed = Vec::new();
+    let mut unchanged = 0;
+    let mut removed = Vec::new();
+    for price in &from_prices {
+        let Some(other) = to_by_code.get(price.code.as_str()) else {
+            removed.push(version_price(price)?);
+            continue;
+        };
+        let (old, new) = (unit_price(price)?, unit_price(other)?);
+        if old == new {
+            unchanged += 1;
+            continue;
+        }
+        changed.push(PriceChange {
+            code: other.code.clone(),
+            description: other.description.clone(),
+            delta: &new - &old,
+            percentage: percentage(&old, &new),
+            from: old,
+            to: new,
+        });
+    }
+    let from_codes: HashSet<&str> = from_prices.iter().map(|p| p.code.as_str()).collect();
+    let added = to_prices
+        .iter()
+        .filter(|p| !from_codes.contains(p.code.as_str()))
+        .map(version_price)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    changed.sort_by(|a, b| match (&a.percentage, &b.percentage) {
+        (Some(a), Some(b)) => b.abs().cmp(&a.abs()),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => b.delta.abs().cmp(&a.delta.abs()),
+    });
+    if let Some(limit) = limit {
+        changed.truncate(limit);
+    }
+    debug!(
+        "Versions {} -> {}: {} changed, {} unchanged, {} added, {} removed",
+        from_id, to_id, changed.len(), unchanged, added.len(), removed.len()
+    );
+    Ok(Some(VersionComparison { from, to, changed, unchanged, added, removed }))
+}
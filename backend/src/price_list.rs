@@ -0,0 +1,193 @@
+//! Cuadros de precios de un presupuesto: todos los precios medidos en él,
+//! ordenados por código y numerados.
+//!
+//! - Cuadro nº 1: precio de cada unidad en cifra y en letra.
+//! - Cuadro nº 2: justificación del precio con sus componentes, el peso de cada
+//!   uno sobre el total y el de los precios auxiliares (componentes que a su vez
+//!   están descompuestos).
+use bigdecimal::RoundingMode;
+use serde::Serialize;
+use sqlx::{postgres::PgPool, types::BigDecimal};
+use tracing::debug;
+
+use crate::{
+    csv::CsvWriter,
+    models::{price::PriceType, Budget, Measurement},
+    pricing::{Breakdown, Catalog, PricingError, PRICE_SCALE},
+    words,
+};
+
+const PERCENTAGE_SCALE: i64 = 2;
+
+/// Componente de un precio en el cuadro nº 2.
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceListComponent {
+    pub price_id: i32,
+    pub code: String,
+    pub unit: String,
+    pub description: String,
+    // Precio auxiliar (descompuesto)
+    pub auxiliary: bool,
+    pub quantity: BigDecimal,
+    pub unit_price: BigDecimal,
+    pub amount: BigDecimal,
+    // Porcentaje sobre el precio de la unidad
+    pub percentage: BigDecimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceListEntry {
+    pub number: usize,
+    pub price_id: i32,
+    pub code: String,
+    pub unit: String,
+    pub description: String,
+    pub unit_price: BigDecimal,
+    pub words: String,
+    pub components: Vec<PriceListComponent>,
+    // Suma de los precios auxiliares y su porcentaje sobre el precio
+    pub auxiliary: BigDecimal,
+    pub auxiliary_percentage: BigDecimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceList {
+    pub budget_id: i32,
+    pub code: String,
+    pub name: String,
+    pub entries: Vec<PriceListEntry>,
+}
+
+impl PriceList {
+    /// Cuadro nº 1 en CSV: una fila por precio.
+    pub fn first_csv(&self) -> String {
+        let mut csv = CsvWriter::new();
+        csv.row(["number", "code", "unit", "description", "price", "price_in_words"]);
+        for entry in &self.entries {
+            csv.row([
+                entry.number.to_string(),
+                entry.code.clone(),
+                entry.unit.clone(),
+                entry.description.clone(),
+                money(&entry.unit_price),
+                entry.words.clone(),
+            ]);
+        }
+        csv.finish()
+    }
+
+    /// Cuadro nº 2 en CSV: una fila por componente. Los precios sin
+    /// descomponer ocupan una fila con las columnas del componente vacías.
+    pub fn second_csv(&self) -> String {
+        let mut csv = CsvWriter::new();
+        csv.row([
+            "number",
+            "code",
+            "unit",
+            "description",
+            "price",
+            "component_code",
+            "component_unit",
+            "component_description",
+            "auxiliary",
+            "quantity",
+            "component_price",
+            "amount",
+            "percentage",
+        ]);
+        for entry in &self.entries {
+            let head = [
+                entry.number.to_string(),
+                entry.code.clone(),
+                entry.unit.clone(),
+                entry.description.clone(),
+                money(&entry.unit_price),
+            ];
+            if entry.components.is_empty() {
+                csv.row(head.iter().cloned().chain(std::iter::repeat_n(String::new(), 8)));
+            }
+            for component in &entry.components {
+                csv.row(head.iter().cloned().chain([
+                    component.code.clone(),
+                    component.unit.clone(),
+                    component.description.clone(),
+                    component.auxiliary.to_string(),
+                    component.quantity.normalized().to_plain_string(),
+                    money(&component.unit_price),
+                    money(&component.amount),
+                    component.percentage.to_plain_string(),
+                ]));
+            }
+        }
+        csv.finish()
+    }
+}
+
+fn money(value: &BigDecimal) -> String {
+    value.with_scale_round(PRICE_SCALE, RoundingMode::HalfUp).to_plain_string()
+}
+
+/// Porcentaje que representa `amount` sobre `total`.
+fn percentage(amount: &BigDecimal, total: &BigDecimal) -> BigDecimal {
+    if total == &BigDecimal::from(0) {
+        return BigDecimal::from(0).with_scale(PERCENTAGE_SCALE);
+    }
+    (amount * BigDecimal::from(100) / total).with_scale_round(PERCENTAGE_SCALE, RoundingMode::HalfUp)
+}
+
+fn entry(number: usize, breakdown: Breakdown) -> PriceListEntry {
+    let components: Vec<PriceListComponent> = breakdown
+        .components
+        .into_iter()
+        .map(|c| PriceListComponent {
+            price_id: c.price_id,
+            percentage: percentage(&c.amount, &breakdown.unit_price),
+            auxiliary: c.price_type == PriceType::Decomposed && !c.components.is_empty(),
+            code: c.code,
+            unit: c.unit,
+            description: c.description,
+            quantity: c.quantity,
+            unit_price: c.unit_price,
+            amount: c.amount,
+        })
+        .collect();
+    let auxiliary: BigDecimal = components.iter().filter(|c| c.auxiliary).map(|c| &c.amount).sum();
+    PriceListEntry {
+        number,
+        price_id: breakdown.price_id,
+        code: breakdown.code,
+        unit: breakdown.unit,
+        description: breakdown.description,
+        words: words::amount(&breakdown.unit_price),
+        auxiliary_percentage: percentage(&auxiliary, &breakdown.unit_price),
+        auxiliary,
+        unit_price: breakdown.unit_price,
+        components,
+    }
+}
+
+/// Cuadros de precios de un presupuesto. Devuelve `None` si no existe.
+pub async fn price_list(pool: &PgPool, budget_id: i32) -> Result<Option<PriceList>, PricingError> {
+    let Some(budget) = Budget::read_by_id(pool, budget_id).await? else {
+        return Ok(None);
+    };
+    let measurements = Measurement::read_by_budget(pool, budget_id).await?;
+    let mut price_ids: Vec<i32> = measurements.iter().map(|m| m.price_id).collect();
+    price_ids.sort_unstable();
+    price_ids.dedup();
+    let catalog = Catalog::load(pool, &price_ids).await?;
+    let mut prices: Vec<_> = price_ids.iter().filter_map(|id| catalog.price(*id)).collect();
+    prices.sort_by(|a, b| a.code.cmp(&b.code));
+    debug!("Price list of budget {}: {} prices", budget.code, prices.len());
+
+    let mut entries = Vec::with_capacity(prices.len());
+    for (index, price) in prices.into_iter().enumerate() {
+        entries.push(entry(index + 1, catalog.breakdown(price.id)?));
+    }
+    Ok(Some(PriceList {
+        budget_id: budget.id,
+        code: budget.code,
+        name: budget.name,
+        entries,
+    }))
+}
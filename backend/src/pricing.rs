@@ -0,0 +1,433 @@
+//! Cálculo del precio unitario de los precios descompuestos.
+//!
+//! El precio de un `PriceType::Decomposed` es la suma de cantidad × precio de
+//! sus componentes, que a su vez pueden estar descompuestos. La cantidad es la
+//! `fixed_quantity` o, en modo fórmula, el resultado de evaluar la fórmula de la
+//! unidad del componente con `params_json`.
+use std::{collections::{HashMap, HashSet}, fmt};
+use bigdecimal::RoundingMode;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{postgres::{PgConnection, PgPool}, types::BigDecimal};
+use tracing::debug;
+
+use crate::{
+    formula::{Formula, FormulaError},
+    models::{
+        descomposition::CalculationMode,
+        price::PriceType,
+        Descomposition,
+        Price,
+        Unit,
+    },
+};
+
+// Escalas de las columnas: NUMERIC(10, 2) para precios y NUMERIC(10, 4) para cantidades
+pub const PRICE_SCALE: i64 = 2;
+pub const QUANTITY_SCALE: i64 = 4;
+// Nodos como máximo en el árbol de una descomposición: los componentes
+// compartidos se repiten en cada rama en la que aparecen
+pub const MAX_BREAKDOWN_NODES: usize = 10_000;
+
+#[derive(Debug)]
+pub enum PricingError {
+    Cycle(Vec<String>),
+    Formula { parent: String, component: String, error: FormulaError },
+    MissingPrice(i32),
+    TooLarge(String),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for PricingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cycle(path) => write!(f, "Cycle in descompositions: {}", path.join(" -> ")),
+            Self::Formula { parent, component, error } => {
+                write!(f, "Cannot compute the quantity of {} in {}: {}", component, parent, error)
+            }
+            Self::MissingPrice(id) => write!(f, "Price {} not found", id),
+            Self::TooLarge(code) => {
+                write!(f, "The breakdown of {} has more than {} components", code, MAX_BREAKDOWN_NODES)
+            }
+            Self::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PricingError {}
+
+impl From<sqlx::Error> for PricingError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Database(e)
+    }
+}
+
+/// Árbol de descomposición de un precio con los importes calculados.
+#[derive(Debug, Clone, Serialize)]
+pub struct Breakdown {
+    pub price_id: i32,
+    pub code: String,
+    pub description: String,
+    pub unit: String,
+    pub price_type: PriceType,
+    // None en la raíz del árbol
+    pub calculation_mode: Option<CalculationMode>,
+    pub quantity: BigDecimal,
+    // Precio guardado en la base de datos
+    pub base_price: BigDecimal,
+    // Precio calculado a partir de los componentes
+    pub unit_price: BigDecimal,
+    pub amount: BigDecimal,
+    pub components: Vec<Breakdown>,
+}
+
+/// Precios, unidades y descomposiciones necesarios para calcular un conjunto de
+/// precios. Se cargan de una vez, recorriendo el grafo por niveles.
+#[derive(Debug, Default)]
+pub struct Catalog {
+    prices: HashMap<i32, Price>,
+    units: HashMap<i32, Unit>,
+    components: HashMap<i32, Vec<Descomposition>>,
+}
+
+impl Catalog {
+    /// Carga los precios indicados y, recursivamente, todos sus componentes.
+    pub async fn load(pool: &PgPool, price_ids: &[i32]) -> Result<Self, sqlx::Error> {
+        let mut conn = pool.acquire().await?;
+        Self::load_in(&mut conn, price_ids).await
+    }
+
+    /// Como `load`, en una conexión o transacción ya abierta.
+    pub async fn load_in(conn: &mut PgConnection, price_ids: &[i32]) -> Result<Self, sqlx::Error> {
+        let mut seen: HashSet<i32> = HashSet::new();
+        let mut pending: Vec<i32> = price_ids.iter().copied().filter(|id| seen.insert(*id)).collect();
+        let mut components: HashMap<i32, Vec<Descomposition>> = HashMap::new();
+        while !pending.is_empty() {
+            let rows = Descomposition::read_by_parents(&mut *conn, &pending).await?;
+            pending = rows.iter().map(|d| d.component_price_id).filter(|id| seen.insert(*id)).collect();
+            for row in rows {
+                components.entry(row.parent_price_id).or_default().push(row);
+            }
+        }
+        let ids: Vec<i32> = seen.into_iter().collect();
+        let prices: HashMap<i32, Price> = Price::read_by_ids(&mut *conn, &ids)
+            .await?
+            .into_iter()
+            .map(|p| (p.id, p))
+            .collect();
+        let unit_ids: Vec<i32> = prices.values().map(|p| p.unit_id).collect::<HashSet<_>>().into_iter().collect();
+        let units = Unit::read_by_ids(&mut *conn, &unit_ids)
+            .await?
+            .into_iter()
+            .map(|u| (u.id, u))
+            .collect();
+        debug!("Catalog loaded: {} prices, {} decomposed", prices.len(), components.len());
+        Ok(Self { prices, units, components })
+    }
+
+    pub fn price(&self, id: i32) -> Option<&Price> {
+        self.prices.get(&id)
+    }
+
+    /// Precios cargados, ordenados por código.
+    pub fn prices(&self) -> Vec<&Price> {
+        let mut prices: Vec<&Price> = self.prices.values().collect();
+        prices.sort_by(|a, b| a.code.cmp(&b.code));
+        prices
+    }
+
+    /// Cambia el precio guardado de un precio cargado, para calcular los
+    /// descompuestos con otros precios base.
+    pub fn set_base_price(&mut self, price_id: i32, base_price: BigDecimal) {
+        if let Some(price) = self.prices.get_mut(&price_id) {
+            price.base_price = base_price;
+        }
+    }
+
+    pub fn unit(&self, price: &Price) -> Option<&Unit> {
+        self.units.get(&price.unit_id)
+    }
+
+    /// Descomposiciones del precio, en el orden en que se crearon.
+    pub fn components(&self, price_id: i32) -> &[Descomposition] {
+        self.components.get(&price_id).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Cantidad de un componente por unidad del precio padre.
+    pub fn quantity(&self, descomposition: &Descomposition) -> Result<BigDecimal, PricingError> {
+        if descomposition.calculation_mode == CalculationMode::Fixed {
+            return Ok(descomposition.fixed_quantity.clone().unwrap_or_default());
+        }
+        let code = |id| self.prices.get(&id).map(|p| p.code.clone()).unwrap_or_else(|| id.to_string());
+        let component = self
+            .prices
+            .get(&descomposition.component_price_id)
+            .ok_or(PricingError::MissingPrice(descomposition.component_price_id))?;
+        let formula = self.unit(component).map(|u| u.formula.as_str()).unwrap_or_default();
+        let params = descomposition.params_json.clone().unwrap_or(Value::Null);
+        Formula::parse(formula)
+            .and_then(|f| f.evaluate_json(&params))
+            .map(|q| q.with_scale_round(QUANTITY_SCALE, RoundingMode::HalfUp))
+            .map_err(|error| PricingError::Formula {
+                parent: code(descomposition.parent_price_id),
+                component: component.code.clone(),
+                error,
+            })
+    }
+
+    /// Precio unitario calculado de un precio.
+    pub fn unit_price(&self, price_id: i32) -> Result<BigDecimal, PricingError> {
+        self.compute(price_id, &mut Vec::new(), &mut HashMap::new())
+    }
+
+    /// Precios unitarios calculados de todos los precios cargados.
+    pub fn unit_prices(&self) -> Result<HashMap<i32, BigDecimal>, PricingError> {
+        let mut memo = HashMap::new();
+        for id in self.prices.keys() {
+            self.compute(*id, &mut Vec::new(), &mut memo)?;
+        }
+        Ok(memo)
+    }
+
+    /// Árbol completo de la descomposición de un precio. Falla si tiene más de
+    /// `MAX_BREAKDOWN_NODES` nodos.
+    pub fn breakdown(&self, price_id: i32) -> Result<Breakdown, PricingError> {
+        let mut memo = HashMap::new();
+        // Detecta los ciclos antes de contar los nodos
+        self.compute(price_id, &mut Vec::new(), &mut memo)?;
+        if self.size(price_id, &mut HashMap::new()) > MAX_BREAKDOWN_NODES {
+            let price = self.prices.get(&price_id).ok_or(PricingError::MissingPrice(price_id))?;
+            return Err(PricingError::TooLarge(price.code.clone()));
+        }
+        self.node(price_id, None, BigDecimal::from(1), &mut Vec::new(), &mut memo)
+    }
+
+    /// Nodos del árbol de un precio, sin construirlo. El grafo no debe tener ciclos.
+    fn size(&self, price_id: i32, memo: &mut HashMap<i32, usize>) -> usize {
+        if let Some(size) = memo.get(&price_id) {
+            return *size;
+        }
+        let size = match self.prices.get(&price_id) {
+            Some(price) if price.price_type == PriceType::Decomposed => self
+                .components(price_id)
+                .iter()
+                .fold(1usize, |size, d| size.saturating_add(self.size(d.component_price_id, memo))),
+            _ => 1,
+        };
+        memo.insert(price_id, size);
+        size
+    }
+
+    fn compute(
+        &self,
+        price_id: i32,
+        path: &mut Vec<i32>,
+        memo: &mut HashMap<i32, BigDecimal>,
+    ) -> Result<BigDecimal, PricingError> {
+        if let Some(value) = memo.get(&price_id) {
+            return Ok(value.clone());
+        }
+        let price = self.prices.get(&price_id).ok_or(PricingError::MissingPrice(price_id))?;
+        let components = self.components(price_id);
+        if price.price_type == PriceType::Base || components.is_empty() {
+            memo.insert(price_id, price.base_price.clone());
+            return Ok(price.base_price.clone());
+        }
+        if path.contains(&price_id) {
+            return Err(self.cycle(path, price_id));
+        }
+        path.push(price_id);
+        let mut total = BigDecimal::from(0);
+        for descomposition in components {
+            let quantity = self.quantity(descomposition)?;
+            total += quantity * self.compute(descomposition.component_price_id, path, memo)?;
+        }
+        path.pop();
+        let total = total.with_scale_round(PRICE_SCALE, RoundingMode::HalfUp);
+        memo.insert(price_id, total.clone());
+        Ok(total)
+    }
+
+    fn node(
+        &self,
+        price_id: i32,
+        descomposition: Option<&Descomposition>,
+        quantity: BigDecimal,
+        path: &mut Vec<i32>,
+        memo: &mut HashMap<i32, BigDecimal>,
+    ) -> Result<Breakdown, PricingError> {
+        let price = self.prices.get(&price_id).ok_or(PricingError::MissingPrice(price_id))?;
+        if path.contains(&price_id) {
+            return Err(self.cycle(path, price_id));
+        }
+        path.push(price_id);
+        let mut components = Vec::new();
+        if price.price_type == PriceType::Decomposed {
+            for child in self.components(price_id) {
+                let child_quantity = self.quantity(child)?;
+                components.push(self.node(child.component_price_id, Some(child), child_quantity, path, memo)?);
+            }
+        }
+        path.pop();
+        let unit_price = self.compute(price_id, path, memo)?;
+        Ok(Breakdown {
+            price_id,
+            code: price.code.clone(),
+            description: price.description.clone(),
+            unit: self.unit(price).map(|u| u.symbol.clone()).unwrap_or_default(),
+            price_type: price.price_type,
+            calculation_mode: descomposition.map(|d| d.calculation_mode),
+            amount: &quantity * &unit_price,
+            quantity,
+            base_price: price.base_price.clone(),
+            unit_price,
+            components,
+        })
+    }
+
+    fn cycle(&self, path: &[i32], price_id: i32) -> PricingError {
+        let start = path.iter().position(|id| *id == price_id).unwrap_or_default();
+        let code = |id: &i32| self.prices.get(id).map(|p| p.code.clone()).unwrap_or_else(|| id.to_string());
+        let mut codes: Vec<String> = path[start..].iter().map(code).collect();
+        codes.push(code(&price_id));
+        PricingError::Cycle(codes)
+    }
+}
+
+/// Árbol de descomposición de un precio. Devuelve `None` si el precio no existe.
+pub async fn breakdown(pool: &PgPool, price_id: i32) -> Result<Option<Breakdown>, PricingError> {
+    let catalog = Catalog::load(pool, &[price_id]).await?;
+    if catalog.price(price_id).is_none() {
+        return Ok(None);
+    }
+    catalog.breakdown(price_id).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serde_json::json;
+    use std::str::FromStr;
+
+    fn decimal(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    fn price(id: i32, code: &str, base_price: &str, unit_id: i32, price_type: PriceType) -> Price {
+        Price {
+            id,
+            version_id: 1,
+            code: code.to_string(),
+            description: format!("Precio {}", code),
+            base_price: decimal(base_price),
+            unit_id,
+            price_type,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn catalog() -> Catalog {
+        let mut catalog = Catalog::default();
+        let unit = |id, formula: &str| Unit {
+            id,
+            name: formula.to_string(),
+            symbol: formula.to_string(),
+            description: None,
+            formula: formula.to_string(),
+            params: json!([]),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        catalog.units.insert(1, unit(1, "a"));
+        catalog.units.insert(2, unit(2, "a * b"));
+        catalog.prices.insert(1, price(1, "SOL", "99", 1, PriceType::Decomposed));
+        catalog.prices.insert(2, price(2, "MO", "20", 1, PriceType::Base));
+        catalog.prices.insert(3, price(3, "BAL", "12.50", 2, PriceType::Base));
+        catalog.prices.insert(4, price(4, "MOR", "0", 1, PriceType::Decomposed));
+        catalog.prices.insert(5, price(5, "CEM", "0.10", 1, PriceType::Base));
+        catalog.add(1, 2, Some("0.5"), None);
+        catalog.add(1, 3, None, Some(json!({"a": 1.05, "b": 1})));
+        catalog.add(1, 4, Some("0.02"), None);
+        catalog.add(4, 5, Some("300"), None);
+        catalog
+    }
+
+    impl Catalog {
+        fn add(&mut self, parent: i32, component: i32, fixed: Option<&str>, params: Option<Value>) {
+            let descomposition = Descomposition {
+                id: component,
+                parent_price_id: parent,
+                component_price_id: component,
+                calculation_mode: if fixed.is_some() { CalculationMode::Fixed } else { CalculationMode::Formula },
+                fixed_quantity: fixed.map(decimal),
+                params_json: params,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            };
+            self.components.entry(parent).or_default().push(descomposition);
+        }
+    }
+
+    #[test]
+    fn test_unit_price() {
+        let catalog = catalog();
+        // MOR = 300 × 0.10 = 30; SOL = 0.5 × 20 + 1.05 × 12.50 + 0.02 × 30 = 23.725
+        assert_eq!(catalog.unit_price(4).unwrap(), decimal("30"));
+        assert_eq!(catalog.unit_price(1).unwrap(), decimal("23.73"));
+        assert_eq!(catalog.unit_price(2).unwrap(), decimal("20"));
+        let prices = catalog.unit_prices().unwrap();
+        assert_eq!(prices.len(), 5);
+        assert_eq!(prices[&1], decimal("23.73"));
+    }
+
+    #[test]
+    fn test_breakdown() {
+        let breakdown = catalog().breakdown(1).unwrap();
+        assert_eq!(breakdown.base_price, decimal("99"));
+        assert_eq!(breakdown.unit_price, decimal("23.73"));
+        assert_eq!(breakdown.components.len(), 3);
+        let tile = &breakdown.components[1];
+        assert_eq!(tile.calculation_mode, Some(CalculationMode::Formula));
+        assert_eq!(tile.quantity, decimal("1.05"));
+        assert_eq!(tile.amount, decimal("13.125"));
+        assert_eq!(breakdown.components[2].components[0].code, "CEM");
+    }
+
+    #[test]
+    fn test_breakdown_too_large() {
+        let mut catalog = catalog();
+        // Cada nivel usa dos veces el siguiente: 2^20 nodos
+        for id in 10..30 {
+            catalog.prices.insert(id, price(id, &format!("N{}", id), "1", 1, PriceType::Decomposed));
+            catalog.add(id, id + 1, Some("1"), None);
+            catalog.add(id, id + 1, Some("1"), None);
+        }
+        catalog.prices.insert(30, price(30, "N30", "1", 1, PriceType::Base));
+        assert_eq!(catalog.unit_price(10).unwrap(), decimal("1048576"));
+        let error = catalog.breakdown(10).unwrap_err();
+        assert_eq!(error.to_string(), "The breakdown of N10 has more than 10000 components");
+        assert_eq!(catalog.breakdown(25).unwrap().components.len(), 2);
+    }
+
+    #[test]
+    fn test_cycle() {
+        let mut catalog = catalog();
+        catalog.add(4, 1, Some("1"), None);
+        let error = catalog.unit_price(1).unwrap_err();
+        assert_eq!(error.to_string(), "Cycle in descompositions: SOL -> MOR -> SOL");
+        assert!(matches!(catalog.breakdown(4), Err(PricingError::Cycle(_))));
+    }
+
+    #[test]
+    fn test_formula_error() {
+        let mut catalog = catalog();
+        catalog.add(4, 3, None, Some(json!({"a": 1})));
+        let error = catalog.unit_price(4).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Cannot compute the quantity of BAL in MOR: Missing value for variable 'b'"
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! Resumen económico de un presupuesto: importe de cada línea, subtotal de cada
+//! capítulo y presupuesto de ejecución material (PEM).
+//!
+//! El importe de una línea es la suma, para cada precio medido en ella, de la
+//! cantidad total medida por el precio unitario calculado (ver `pricing`),
+//! redondeado según las opciones. Los capítulos suman los importes ya
+//! redondeados de sus hijos, de modo que el total cuadra con lo que se imprime.
+//!
+//! Sobre el PEM se aplican, en orden, los gastos generales, el beneficio
+//! industrial y los porcentajes personalizados del presupuesto para obtener el
+//! presupuesto de ejecución por contrata (PEC); sobre este se aplica el IVA.
+use std::collections::{HashMap, HashSet};
+use bigdecimal::RoundingMode;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::{PgConnection, PgPool}, types::BigDecimal};
+use tracing::debug;
+
+use crate::{
+    models::{
+        Budget,
+        BudgetRates,
+        Element,
+        ElementType,
+        Measurement,
+    },
+    pricing::{Catalog, PricingError},
+};
+
+pub const DEFAULT_DECIMALS: i64 = 2;
+pub const MAX_DECIMALS: i64 = 6;
+pub const GENERAL_EXPENSES: &str = "Gastos generales";
+pub const INDUSTRIAL_PROFIT: &str = "Beneficio industrial";
+pub const VAT: &str = "IVA";
+
+/// Modo de redondeo de los importes.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    #[default]
+    HalfUp,
+    HalfDown,
+    HalfEven,
+    Up,
+    Down,
+    Ceiling,
+    Floor,
+}
+
+impl From<Rounding> for RoundingMode {
+    fn from(rounding: Rounding) -> Self {
+        match rounding {
+            Rounding::HalfUp => RoundingMode::HalfUp,
+            Rounding::HalfDown => RoundingMode::HalfDown,
+            Rounding::HalfEven => RoundingMode::HalfEven,
+            Rounding::Up => RoundingMode::Up,
+            Rounding::Down => RoundingMode::Down,
+            Rounding::Ceiling => RoundingMode::Ceiling,
+            Rounding::Floor => RoundingMode::Floor,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SummaryOptions {
+    pub decimals: i64,
+    pub rounding: Rounding,
+}
+
+impl Default for SummaryOptions {
+    fn default() -> Self {
+        Self { decimals: DEFAULT_DECIMALS, rounding: Rounding::default() }
+    }
+}
+
+impl SummaryOptions {
+    pub fn round(&self, value: BigDecimal) -> BigDecimal {
+        value.with_scale_round(self.decimals, self.rounding.into())
+    }
+
+    /// Importe redondeado de un porcentaje sobre una base.
+    pub fn rate(&self, name: &str, percentage: &BigDecimal, base: &BigDecimal) -> SummaryRate {
+        SummaryRate {
+            name: name.to_string(),
+            percentage: percentage.clone(),
+            amount: self.round(base * percentage / BigDecimal::from(100)),
+        }
+    }
+}
+
+/// Porcentaje aplicado en el resumen y su importe.
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryRate {
+    pub name: String,
+    pub percentage: BigDecimal,
+    pub amount: BigDecimal,
+}
+
+/// Precio medido en un elemento.
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryItem {
+    pub price_id: i32,
+    pub code: String,
+    pub description: String,
+    pub unit: String,
+    pub quantity: BigDecimal,
+    pub unit_price: BigDecimal,
+    pub amount: BigDecimal,
+}
+
+/// Capítulo o línea del presupuesto. En los capítulos `amount` es el subtotal.
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryNode {
+    pub element_id: i32,
+    pub element_type: ElementType,
+    pub code: String,
+    pub description: Option<String>,
+    pub items: Vec<SummaryItem>,
+    pub amount: BigDecimal,
+    pub children: Vec<SummaryNode>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetSummary {
+    pub budget_id: i32,
+    pub code: String,
+    pub name: String,
+    pub options: SummaryOptions,
+    pub chapters: Vec<SummaryNode>,
+    // Presupuesto de ejecución material
+    pub pem: BigDecimal,
+    // GG, BI y personalizados, sobre el PEM
+    pub rates: Vec<SummaryRate>,
+    // Presupuesto de ejecución por contrata
+    pub pec: BigDecimal,
+    pub vat: SummaryRate,
+    // Total con IVA
+    pub total: BigDecimal,
+}
+
+impl BudgetSummary {
+    /// Recorre el árbol en profundidad (primero el padre, luego los hijos).
+    pub fn nodes(&self) -> Vec<(usize, &SummaryNode)> {
+        fn walk<'a>(nodes: &'a [SummaryNode], depth: usize, out: &mut Vec<(usize, &'a SummaryNode)>) {
+            for node in nodes {
+                out.push((depth, node));
+                walk(&node.children, depth + 1, out);
+            }
+        }
+        let mut out = Vec::new();
+        walk(&self.chapters, 0, &mut out);
+        out
+    }
+}
+
+/// Calcula el resumen del presupuesto. Devuelve `None` si no existe.
+pub async fn summarize(
+    pool: &PgPool,
+    budget_id: i32,
+    options: SummaryOptions,
+) -> Result<Option<BudgetSummary>, PricingError> {
+    let Some(budget) = Budget::read_by_id(pool, budget_id).await? else {
+        return Ok(None);
+    };
+    let mut conn = pool.acquire().await?;
+    let elements = Element::read_by_budget(&mut *conn, budget_id).await?;
+    let measurements = Measurement::read_by_budget(&mut *conn, budget_id).await?;
+    summarize_measurements(&mut conn, &budget, &elements, &measurements, options).await.map(Some)
+}
+
+/// Calcula el resumen del presupuesto con los elementos y mediciones dados,
+/// que pueden no estar guardados (por ejemplo, para previsualizar un cambio).
+pub async fn summarize_measurements(
+    conn: &mut PgConnection,
+    budget: &Budget,
+    elements: &[Element],
+    measurements: &[Measurement],
+    options: SummaryOptions,
+) -> Result<BudgetSummary, PricingError> {
+    let price_ids: Vec<i32> = measurements.iter().map(|m| m.price_id).collect();
+    let catalog = Catalog::load_in(conn, &price_ids).await?;
+    let unit_prices = catalog.unit_prices()?;
+    debug!(
+        "Summarizing budget {}: {} elements, {} measurements",
+        budget.code,
+        elements.len(),
+        measurements.len()
+    );
+
+    // Cantidad total por elemento y precio, en el orden en que se midió
+    let mut quantities: HashMap<i32, Vec<(i32, BigDecimal)>> = HashMap::new();
+    for measurement in measurements {
+        let items = quantities.entry(measurement.element_id).or_default();
+        match items.iter_mut().find(|(price_id, _)| *price_id == measurement.price_id) {
+            Some((_, quantity)) => *quantity += &measurement.measured_quantity,
+            None => items.push((measurement.price_id, measurement.measured_quantity.clone())),
+        }
+    }
+    let items = |element_id: i32| -> Vec<SummaryItem> {
+        quantities
+            .get(&element_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|(price_id, quantity)| {
+                let price = catalog.price(*price_id)?;
+                let unit_price = unit_prices.get(price_id).cloned().unwrap_or_default();
+                Some(SummaryItem {
+                    price_id: *price_id,
+                    code: price.code.clone(),
+                    description: price.description.clone(),
+                    unit: catalog.unit(price).map(|u| u.symbol.clone()).unwrap_or_default(),
+                    amount: options.round(quantity * &unit_price),
+                    quantity: quantity.clone(),
+                    unit_price,
+                })
+            })
+            .collect()
+    };
+
+    let ids: HashSet<i32> = elements.iter().map(|e| e.id).collect();
+    let mut children: HashMap<Option<i32>, Vec<&Element>> = HashMap::new();
+    for element in elements {
+        // Un padre de otro presupuesto se trata como raíz
+        let parent = element.parent_id.filter(|id| ids.contains(id));
+        children.entry(parent).or_default().push(element);
+    }
+    let mut visited = HashSet::new();
+    let chapters = build(None, &children, &items, &mut visited);
+    let pem: BigDecimal = chapters.iter().map(|c| &c.amount).sum();
+    let rates = rates(&budget.rates, &pem, options);
+    let pec = &pem + rates.iter().map(|r| &r.amount).sum::<BigDecimal>();
+    let vat = options.rate(VAT, &budget.rates.vat, &pec);
+    let total = &pec + &vat.amount;
+    Ok(BudgetSummary {
+        budget_id: budget.id,
+        code: budget.code.clone(),
+        name: budget.name.clone(),
+        options,
+        chapters,
+        pem,
+        rates,
+        pec,
+        vat,
+        total,
+    })
+}
+
+/// Porcentajes que se suman al PEM para obtener el PEC, en el orden en que se aplican.
+fn rates(rates: &BudgetRates, pem: &BigDecimal, options: SummaryOptions) -> Vec<SummaryRate> {
+    let mut out = vec![
+        options.rate(GENERAL_EXPENSES, &rates.general_expenses, pem),
+        options.rate(INDUSTRIAL_PROFIT, &rates.industrial_profit, pem),
+    ];
+    out.extend(rates.custom_rates.iter().map(|r| options.rate(&r.name, &r.percentage, pem)));
+    out
+}
+
+fn build(
+    parent: Option<i32>,
+    children: &HashMap<Option<i32>, Vec<&Element>>,
+    items: &dyn Fn(i32) -> Vec<SummaryItem>,
+    visited: &mut HashSet<i32>,
+) -> Vec<SummaryNode> {
+    let mut nodes = Vec::new();
+    for element in children.get(&parent).map(Vec::as_slice).unwrap_or_default() {
+        // Protección frente a ciclos en parent_id
+        if !visited.insert(element.id) {
+            continue;
+        }
+        let element_items = items(element.id);
+        let element_children = build(Some(element.id), children, items, visited);
+        let amount = element_items
+            .iter()
+            .map(|i| &i.amount)
+            .chain(element_children.iter().map(|n| &n.amount))
+            .sum();
+        nodes.push(SummaryNode {
+            element_id: element.id,
+            element_type: element.element_type,
+            code: element.budget_code.clone(),
+            description: element.description.clone(),
+            items: element_items,
+            amount,
+            children: element_children,
+        });
+    }
+    nodes
+}
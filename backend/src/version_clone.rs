@@ -0,0 +1,217 @@
+//! Copia de una versión del catálogo de precios en otra nueva (por ejemplo, la
+//! del trimestre siguiente).
+//!
+//! Se copian todos los precios y sus descomposiciones. A los precios base se
+//! les puede aplicar un porcentaje, global o por prefijo de código: si varios
+//! prefijos coinciden se aplica el más largo. Después se vuelve a calcular el
+//! precio de los descompuestos con los nuevos precios de sus componentes.
+use std::fmt;
+use axum::http::StatusCode;
+use bigdecimal::RoundingMode;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgPool, types::BigDecimal};
+use tracing::{debug, info};
+
+use crate::{
+    models::{
+        price::PriceType,
+        ApiError,
+        Data,
+        Descomposition,
+        NewVersion,
+        Price,
+        Version,
+    },
+    pricing::{Catalog, PricingError, PRICE_SCALE},
+};
+
+const MAX_PRICE: i64 = 100_000_000; // NUMERIC(10, 2)
+
+/// Porcentaje que se aplica a los precios base cuyo código empieza por `prefix`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PriceAdjustment {
+    pub prefix: String,
+    pub percentage: BigDecimal,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CloneVersion {
+    pub name: String,
+    // Porcentaje para los precios base que no tienen uno por prefijo
+    #[serde(default)]
+    pub percentage: Option<BigDecimal>,
+    #[serde(default)]
+    pub adjustments: Vec<PriceAdjustment>,
+}
+
+impl CloneVersion {
+    /// Porcentaje que corresponde a un código: el del prefijo más largo que
+    /// coincide o, si no hay ninguno, el global.
+    pub fn percentage_for(&self, code: &str) -> Option<&BigDecimal> {
+        self.adjustments
+            .iter()
+            .filter(|a| code.starts_with(&a.prefix))
+            .max_by_key(|a| a.prefix.len())
+            .map(|a| &a.percentage)
+            .or(self.percentage.as_ref())
+    }
+
+    fn validate(&self) -> Result<(), CloneError> {
+        if self.name.trim().is_empty() {
+            return Err(CloneError::Invalid("Version name is required".to_string()));
+        }
+        if self.adjustments.iter().any(|a| a.prefix.is_empty()) {
+            return Err(CloneError::Invalid("Adjustment prefix is required".to_string()));
+        }
+        let mut percentages = self.adjustments.iter().map(|a| &a.percentage).chain(self.percentage.as_ref());
+        if percentages.any(|p| *p <= -100) {
+            return Err(CloneError::Invalid("Percentage must be greater than -100".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Resultado de la copia.
+#[derive(Debug, Serialize)]
+pub struct VersionClone {
+    pub version: Version,
+    pub source_version_id: i32,
+    pub prices: u64,
+    pub descompositions: u64,
+    // Precios base a los que se ha aplicado un porcentaje
+    pub adjusted: usize,
+    // Precios descompuestos cuyo precio ha cambiado al recalcularlo
+    pub recomputed: usize,
+}
+
+#[derive(Debug)]
+pub enum CloneError {
+    Invalid(String),
+    Pricing(PricingError),
+}
+
+impl fmt::Display for CloneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(message) => write!(f, "{}", message),
+            Self::Pricing(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CloneError {}
+
+impl From<PricingError> for CloneError {
+    fn from(e: PricingError) -> Self {
+        Self::Pricing(e)
+    }
+}
+
+impl From<sqlx::Error> for CloneError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Pricing(PricingError::Database(e))
+    }
+}
+
+impl ApiError for CloneError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::Invalid(_) => StatusCode::BAD_REQUEST,
+            Self::Pricing(PricingError::Database(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Pricing(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+    fn data(&self) -> Data {
+        Data::None
+    }
+}
+
+/// Copia la versión en una nueva, en una única transacción. Devuelve `None`
+/// si la versión no existe.
+///
+/// La transacción es REPEATABLE READ: los precios, el catálogo con el que se
+/// recalculan y las descomposiciones que se copian salen de la misma foto de
+/// la versión, aunque otras peticiones la modifiquen mientras tanto.
+pub async fn clone_version(pool: &PgPool, id: i32, item: CloneVersion) -> Result<Option<VersionClone>, CloneError> {
+    item.validate()?;
+    if Version::read_by_id(pool, id).await?.is_none() {
+        return Ok(None);
+    }
+    let mut tx = pool.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ").execute(&mut *tx).await?;
+    let prices = Price::read_by_version(&mut *tx, id).await?;
+    let ids: Vec<i32> = prices.iter().map(|p| p.id).collect();
+    let mut catalog = Catalog::load_in(&mut tx, &ids).await?;
+    let mut adjusted = 0;
+    for price in prices.iter().filter(|p| p.price_type == PriceType::Base) {
+        if let Some(percentage) = item.percentage_for(&price.code) {
+            let factor = BigDecimal::from(1) + percentage / BigDecimal::from(100);
+            let base_price = (&price.base_price * factor).with_scale_round(PRICE_SCALE, RoundingMode::HalfUp);
+            catalog.set_base_price(price.id, base_price);
+            adjusted += 1;
+        }
+    }
+    let unit_prices = catalog.unit_prices()?;
+    let mut recomputed = 0;
+    let mut base_prices = Vec::with_capacity(prices.len());
+    for price in &prices {
+        let base_price = unit_prices.get(&price.id).cloned().ok_or(PricingError::MissingPrice(price.id))?;
+        if base_price.abs() >= MAX_PRICE {
+            return Err(CloneError::Invalid(format!("Price {} is out of range after the adjustment", price.code)));
+        }
+        if price.price_type == PriceType::Decomposed && base_price != price.base_price {
+            recomputed += 1;
+        }
+        base_prices.push(base_price);
+    }
+
+    info!("Cloning version {} into '{}' ({} prices)", id, item.name.trim(), prices.len());
+    let version = Version::create(&mut *tx, NewVersion { name: item.name.trim().to_string() }).await?;
+    let copied = Price::copy_to_version(&mut *tx, id, version.id, &ids, &base_prices).await?;
+    let descompositions = Descomposition::copy_to_version(&mut *tx, id, version.id).await?;
+    tx.commit().await?;
+    debug!(
+        "Version {} cloned into {}: {} prices ({} adjusted, {} recomputed), {} descompositions",
+        id, version.id, copied, adjusted, recomputed, descompositions
+    );
+    Ok(Some(VersionClone {
+        version,
+        source_version_id: id,
+        prices: copied,
+        descompositions,
+        adjusted,
+        recomputed,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn adjustment(prefix: &str, percentage: &str) -> PriceAdjustment {
+        PriceAdjustment { prefix: prefix.to_string(), percentage: BigDecimal::from_str(percentage).unwrap() }
+    }
+
+    #[test]
+    fn test_percentage_for_longest_prefix() {
+        let item = CloneVersion {
+            name: "2026.Q2".to_string(),
+            percentage: Some(BigDecimal::from(3)),
+            adjustments: vec![adjustment("MO", "5"), adjustment("MOOF", "7.5")],
+        };
+        assert_eq!(item.percentage_for("MOOF010"), Some(&BigDecimal::from_str("7.5").unwrap()));
+        assert_eq!(item.percentage_for("MOPE010"), Some(&BigDecimal::from(5)));
+        assert_eq!(item.percentage_for("ACER01"), Some(&BigDecimal::from(3)));
+        assert_eq!(CloneVersion::default().percentage_for("ACER01"), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        let item = |adjustments| CloneVersion { name: "2026.Q2".to_string(), percentage: None, adjustments };
+        assert!(item(vec![adjustment("MO", "-20")]).validate().is_ok());
+        assert!(item(vec![adjustment("", "5")]).validate().is_err());
+        assert!(item(vec![adjustment("MO", "-100")]).validate().is_err());
+        assert!(CloneVersion::default().validate().is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! Migración de un presupuesto a otra versión del catálogo de precios (por
+//! ejemplo, de "2025" a "2026.Q1").
+//!
+//! Cada precio medido se sustituye por el que tiene el mismo código en la
+//! versión de destino, y la cantidad se vuelve a calcular con la fórmula de
+//! su unidad. Todos los elementos del presupuesto pasan a la nueva versión.
+//! Antes de aplicarla se puede previsualizar: los precios que no se pueden
+//! migrar y el impacto en el presupuesto (ver `compare`). Si queda algún
+//! precio sin migrar no se aplica nada.
+use std::{collections::{BTreeSet, HashMap}, fmt};
+use axum::http::StatusCode;
+use serde::Serialize;
+use sqlx::postgres::{PgConnection, PgPool};
+use tracing::debug;
+
+use crate::{
+    compare::{self, BudgetComparison},
+    models::{
+        ApiError,
+        Budget,
+        BudgetStatus,
+        Data,
+        Element,
+        Measurement,
+        Price,
+        Unit,
+        Version,
+    },
+    pricing::PricingError,
+    summary::{self, SummaryOptions},
+};
+
+/// Precio medido en el presupuesto que no se puede llevar a la nueva versión.
+#[derive(Debug, Clone, Serialize)]
+pub struct UnmatchedPrice {
+    pub price_id: i32,
+    pub code: String,
+    pub description: String,
+    // Líneas del presupuesto en las que se mide
+    pub budget_codes: Vec<String>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionMigration {
+    pub budget_id: i32,
+    pub version_id: i32,
+    // Versiones en las que están ahora los elementos
+    pub from_version_ids: Vec<i32>,
+    pub elements: usize,
+    // Mediciones que cambian de precio o de cantidad
+    pub measurements: usize,
+    pub unmatched: Vec<UnmatchedPrice>,
+    // Presupuesto actual frente al migrado, solo con las líneas que cambian
+    pub impact: BudgetComparison,
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    VersionNotFound,
+    Locked(BudgetStatus),
+    // Se serializa como `data` en la respuesta 422
+    Unmatched(Box<VersionMigration>),
+    Pricing(PricingError),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::VersionNotFound => write!(f, "Version not found"),
+            Self::Locked(status) => write!(f, "Budget is {} and cannot be modified", status),
+            Self::Unmatched(migration) => {
+                let codes: Vec<&str> = migration.unmatched.iter().map(|p| p.code.as_str()).collect();
+                write!(f, "Some prices cannot be migrated: {}", codes.join(", "))
+            }
+            Self::Pricing(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<PricingError> for MigrationError {
+    fn from(e: PricingError) -> Self {
+        Self::Pricing(e)
+    }
+}
+
+impl From<sqlx::Error> for MigrationError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Pricing(PricingError::Database(e))
+    }
+}
+
+impl ApiError for MigrationError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::VersionNotFound => StatusCode::NOT_FOUND,
+            Self::Locked(_) => StatusCode::CONFLICT,
+            Self::Unmatched(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Pricing(PricingError::Database(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Pricing(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+    fn data(&self) -> Data {
+        match self {
+            Self::Unmatched(migration) => Data::Some(serde_json::to_value(migration).unwrap()),
+            _ => Data::None,
+        }
+    }
+}
+
+/// Previsualiza la migración sin cambiar nada. Devuelve `None` si el
+/// presupuesto no existe.
+pub async fn preview(pool: &PgPool, budget_id: i32, version_id: i32) -> Result<Option<VersionMigration>, MigrationError> {
+    let Some(budget) = Budget::read_by_id(pool, budget_id).await? else {
+        return Ok(None);
+    };
+    let mut conn = pool.acquire().await?;
+    let (migration, _) = prepare(&mut conn, &budget, version_id).await?;
+    Ok(Some(migration))
+}
+
+/// Migra el presupuesto en una transacción. Solo se puede migrar un
+/// presupuesto modificable y si todos sus precios tienen equivalente. La fila
+/// del presupuesto queda bloqueada desde que se lee hasta que se escribe.
+pub async fn apply(pool: &PgPool, budget_id: i32, version_id: i32) -> Result<Option<VersionMigration>, MigrationError> {
+    let mut tx = pool.begin().await?;
+    let Some(budget) = Budget::lock_by_id(&mut tx, budget_id).await? else {
+        return Ok(None);
+    };
+    if budget.status.is_locked() {
+        return Err(MigrationError::Locked(budget.status));
+    }
+    let (migration, measurements) = prepare(&mut tx, &budget, version_id).await?;
+    if !migration.unmatched.is_empty() {
+        return Err(MigrationError::Unmatched(Box::new(migration)));
+    }
+    Element::set_budget_version(&mut *tx, budget_id, version_id).await?;
+    for measurement in &measurements {
+        Measurement::set_price(&mut *tx, measurement.id, measurement.price_id, &measurement.measured_quantity).await?;
+    }
+    tx.commit().await?;
+    debug!(
+        "Budget {} migrated to version {}: {} elements, {} measurements",
+        budget_id, version_id, migration.elements, migration.measurements
+    );
+    Ok(Some(migration))
+}
+
+/// Calcula la migración y devuelve las mediciones con el precio y la
+/// cantidad nuevos (las que no se pueden migrar quedan como estaban).
+async fn prepare(
+    conn: &mut PgConnection,
+    budget: &Budget,
+    version_id: i32,
+) -> Result<(VersionMigration, Vec<Measurement>), MigrationError> {
+    if Version::read_by_ids(&mut *conn, &[version_id]).await?.is_empty() {
+        return Err(MigrationError::VersionNotFound);
+    }
+    let options = SummaryOptions::default();
+    let elements = Element::read_by_budget(&mut *conn, budget.id).await?;
+    let mut measurements = Measurement::read_by_budget(&mut *conn, budget.id).await?;
+    let before = summary::summarize_measurements(conn, budget, &elements, &measurements, options).await?;
+
+    let price_ids: Vec<i32> = measurements.iter().map(|m| m.price_id).collect::<BTreeSet<_>>().into_iter().collect();
+    let prices = Price::read_by_ids(&mut *conn, &price_ids).await?;
+    let codes: Vec<String> = prices.iter().map(|p| p.code.clone()).collect();
+    let targets: HashMap<String, Price> = Price::read_by_codes(&mut *conn, version_id, &codes)
+        .await?
+        .into_iter()
+        .map(|p| (p.code.clone(), p))
+        .collect();
+    let unit_ids: Vec<i32> = targets.values().map(|p| p.unit_id).collect::<BTreeSet<_>>().into_iter().collect();
+    let units: HashMap<i32, Unit> = Unit::read_by_ids(&mut *conn, &unit_ids)
+        .await?
+        .into_iter()
+        .map(|u| (u.id, u))
+        .collect();
+    let budget_codes: HashMap<i32, &str> = elements.iter().map(|e| (e.id, e.budget_code.as_str())).collect();
+
+    let mut unmatched: Vec<UnmatchedPrice> = Vec::new();
+    let mut changed = 0;
+    for measurement in measurements.iter_mut() {
+        let Some(price) = prices.iter().find(|p| p.id == measurement.price_id) else {
+            return Err(PricingError::MissingPrice(measurement.price_id).into());
+        };
+        let reason = match targets.get(&price.code) {
+            None => "Price code not found in the target version".to_string(),
+            // Los parámetros medidos deben servir para la unidad del nuevo precio
+            Some(target) => match units
+                .get(&target.unit_id)
+                .ok_or_else(|| sqlx::Error::InvalidArgument(format!("Unit {} not found", target.unit_id)))
+                .and_then(|unit| Measurement::quantity_for_unit(unit, &measurement.params_json))
+            {
+                Ok(quantity) => {
+                    if target.id != measurement.price_id || quantity != measurement.measured_quantity {
+                        changed += 1;
+                    }
+                    measurement.price_id = target.id;
+                    measurement.measured_quantity = quantity;
+                    continue;
+                }
+                Err(sqlx::Error::InvalidArgument(message)) => message,
+                Err(e) => return Err(e.into()),
+            },
+        };
+        let budget_code = budget_codes.get(&measurement.element_id).copied().unwrap_or_default().to_string();
+        match unmatched.iter_mut().find(|u| u.price_id == price.id) {
+            Some(entry) => {
+                if !entry.budget_codes.contains(&budget_code) {
+                    entry.budget_codes.push(budget_code);
+                }
+            }
+            None => unmatched.push(UnmatchedPrice {
+                price_id: price.id,
+                code: price.code.clone(),
+                description: price.description.clone(),
+                budget_codes: vec![budget_code],
+                reason,
+            }),
+        }
+    }
+    unmatched.sort_by(|a, b| a.code.cmp(&b.code));
+
+    let after = summary::summarize_measurements(conn, budget, &elements, &measurements, options).await?;
+    let mut impact = compare::diff(&before, &after);
+    impact.retain_changed();
+    let migration = VersionMigration {
+        budget_id: budget.id,
+        version_id,
+        from_version_ids: elements.iter().map(|e| e.version_id).collect::<BTreeSet<_>>().into_iter().collect(),
+        elements: elements.len(),
+        measurements: changed,
+        unmatched,
+        impact,
+    };
+    Ok((migration, measurements))
+}
@@ -0,0 +1,137 @@
+//! Importes escritos en letra, en castellano, tal y como se exigen en el cuadro
+//! de precios nº 1: "VEINTITRÉS EUROS CON SETENTA Y TRES CÉNTIMOS".
+use bigdecimal::{RoundingMode, ToPrimitive};
+use sqlx::types::BigDecimal;
+
+const UNITS: [&str; 30] = [
+    "cero", "uno", "dos", "tres", "cuatro", "cinco", "seis", "siete", "ocho", "nueve",
+    "diez", "once", "doce", "trece", "catorce", "quince", "dieciséis", "diecisiete", "dieciocho", "diecinueve",
+    "veinte", "veintiuno", "veintidós", "veintitrés", "veinticuatro", "veinticinco", "veintiséis", "veintisiete",
+    "veintiocho", "veintinueve",
+];
+const TENS: [&str; 10] = [
+    "", "", "", "treinta", "cuarenta", "cincuenta", "sesenta", "setenta", "ochenta", "noventa",
+];
+const HUNDREDS: [&str; 10] = [
+    "", "ciento", "doscientos", "trescientos", "cuatrocientos", "quinientos", "seiscientos", "setecientos",
+    "ochocientos", "novecientos",
+];
+const MILLION: u64 = 1_000_000;
+const BILLION: u64 = MILLION * MILLION;
+
+/// Número entero en letra. Con `apocope` el "uno" final pasa a "un" (y
+/// "veintiuno" a "veintiún"), como delante de un sustantivo: "veintiún euros".
+pub fn number(n: u64, apocope: bool) -> String {
+    if n == 0 {
+        return UNITS[0].to_string();
+    }
+    let mut parts = Vec::new();
+    let billions = n / BILLION;
+    let millions = n / MILLION % MILLION;
+    let rest = n % MILLION;
+    if billions > 0 {
+        parts.push(if billions == 1 { "un billón".to_string() } else { format!("{} billones", number(billions, true)) });
+    }
+    if millions > 0 {
+        parts.push(if millions == 1 { "un millón".to_string() } else { format!("{} millones", number(millions, true)) });
+    }
+    let thousands = rest / 1000;
+    if thousands > 0 {
+        parts.push(if thousands == 1 { "mil".to_string() } else { format!("{} mil", hundreds(thousands, true)) });
+    }
+    let units = rest % 1000;
+    if units > 0 {
+        parts.push(hundreds(units, apocope));
+    }
+    parts.join(" ")
+}
+
+/// Número de 1 a 999 en letra.
+fn hundreds(n: u64, apocope: bool) -> String {
+    let mut parts = Vec::new();
+    let (h, rest) = ((n / 100) as usize, (n % 100) as usize);
+    if h > 0 {
+        parts.push(if n == 100 { "cien" } else { HUNDREDS[h] }.to_string());
+    }
+    if rest > 0 {
+        let tens = match rest {
+            1 if apocope => "un".to_string(),
+            21 if apocope => "veintiún".to_string(),
+            0..30 => UNITS[rest].to_string(),
+            _ if rest % 10 == 0 => TENS[rest / 10].to_string(),
+            _ if rest % 10 == 1 && apocope => format!("{} y un", TENS[rest / 10]),
+            _ => format!("{} y {}", TENS[rest / 10], UNITS[rest % 10]),
+        };
+        parts.push(tens);
+    }
+    parts.join(" ")
+}
+
+/// Importe en euros en letra y en mayúsculas, redondeado a céntimos.
+pub fn amount(value: &BigDecimal) -> String {
+    let cents = (value.abs() * BigDecimal::from(100))
+        .with_scale_round(0, RoundingMode::HalfUp)
+        .to_u64()
+        .unwrap_or_default();
+    let (euros, cents) = (cents / 100, cents % 100);
+    let unit = |n: u64, singular: &str, plural: &str| {
+        format!("{} {}", number(n, true), if n == 1 { singular } else { plural })
+    };
+    let mut text = unit(euros, "euro", "euros");
+    if cents > 0 {
+        text = format!("{} con {}", text, unit(cents, "céntimo", "céntimos"));
+    }
+    if value < &BigDecimal::from(0) && (euros > 0 || cents > 0) {
+        text = format!("menos {}", text);
+    }
+    text.to_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_number() {
+        let cases = [
+            (0, "cero"),
+            (1, "uno"),
+            (16, "dieciséis"),
+            (21, "veintiuno"),
+            (30, "treinta"),
+            (45, "cuarenta y cinco"),
+            (100, "cien"),
+            (101, "ciento uno"),
+            (115, "ciento quince"),
+            (500, "quinientos"),
+            (1000, "mil"),
+            (1001, "mil uno"),
+            (2021, "dos mil veintiuno"),
+            (21_000, "veintiún mil"),
+            (31_000, "treinta y un mil"),
+            (100_000, "cien mil"),
+            (1_000_000, "un millón"),
+            (2_500_000, "dos millones quinientos mil"),
+            (1_000_000_000, "mil millones"),
+            (21_000_000, "veintiún millones"),
+            (1_000_000_000_000, "un billón"),
+        ];
+        for (n, text) in cases {
+            assert_eq!(number(n, false), text, "{}", n);
+        }
+        assert_eq!(number(21, true), "veintiún");
+        assert_eq!(number(41, true), "cuarenta y un");
+    }
+
+    #[test]
+    fn test_amount() {
+        let amount = |s: &str| amount(&BigDecimal::from_str(s).unwrap());
+        assert_eq!(amount("23.73"), "VEINTITRÉS EUROS CON SETENTA Y TRES CÉNTIMOS");
+        assert_eq!(amount("1"), "UN EURO");
+        assert_eq!(amount("0.01"), "CERO EUROS CON UN CÉNTIMO");
+        assert_eq!(amount("21.21"), "VEINTIÚN EUROS CON VEINTIÚN CÉNTIMOS");
+        assert_eq!(amount("1234.565"), "MIL DOSCIENTOS TREINTA Y CUATRO EUROS CON CINCUENTA Y SIETE CÉNTIMOS");
+        assert_eq!(amount("-5"), "MENOS CINCO EUROS");
+    }
+}
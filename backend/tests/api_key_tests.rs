@@ -0,0 +1,212 @@
+use std::sync::Arc;
+use axum::{http::StatusCode, Router};
+use backend::mailer::LogMailer;
+use backend::{
+    http,
+    models::{
+        api_key::ApiKey,
+        project::{NewProject, Project},
+        project_member::{NewProjectMember, ProjectMember},
+        role::Role,
+        user::User,
+        AppState,
+    },
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[path = "common.rs"]
+mod common;
+
+use common::{create_user, login, request, send, send_json, PASSWORD};
+
+async fn setup() -> (PgPool, Router, String) {
+    let _ = &common::TRACING;
+    let pool = common::setup_pool().await;
+    let app = http::api_router(Arc::new(AppState {
+        pool: pool.clone(),
+        secret: "test_secret".to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+    let admin = create_user(&pool, "admin", true).await;
+    let (_, body) = login(&app, &admin.email, PASSWORD).await;
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+    (pool, app, token)
+}
+
+async fn create_project(pool: &PgPool) -> Project {
+    Project::create(pool, NewProject {
+        code: format!("P-APIKEY-{}", Uuid::new_v4()),
+        title: Some("API".to_string()),
+        member_scope: None,
+    })
+    .await
+    .unwrap()
+}
+
+/// Crea una API key como administrador y devuelve la key en claro.
+async fn create_key(app: &Router, admin_token: &str, user: &User, body: Value) -> String {
+    let uri = format!("/users/{}/api-keys", user.id);
+    let (status, body) = send_json(app, "POST", &uri, Some(admin_token), body).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    body["data"]["key"].as_str().unwrap().to_string()
+}
+
+fn ids(body: &Value) -> Vec<i64> {
+    body["data"].as_array().unwrap().iter().map(|item| item["id"].as_i64().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn test_api_key_lifecycle() {
+    let (pool, app, admin_token) = setup().await;
+    let user = create_user(&pool, "writer", true).await;
+    let read_key = create_key(&app, &admin_token, &user, json!({"name": "ERP"})).await;
+    let write_key = create_key(&app, &admin_token, &user, json!({"name": "Nightly", "scope": "write"})).await;
+    assert!(read_key.starts_with(ApiKey::KEY_PREFIX));
+
+    // Solo se guarda el hash: el listado no incluye la key
+    let (status, body) = send_json(&app, "GET", &format!("/users/{}/api-keys", user.id), Some(&admin_token), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let keys = body["data"].as_array().unwrap();
+    assert_eq!(keys.len(), 2);
+    assert_eq!(keys[0]["prefix"], read_key[..11]);
+    assert_eq!(keys[0]["scope"], "read");
+    assert!(keys[0].get("key").is_none() && keys[0].get("key_hash").is_none());
+    assert!(keys[0]["last_used_at"].is_null());
+
+    let (status, body) = send_json(&app, "GET", "/auth/me", Some(&read_key), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["id"], user.id);
+    let stored = ApiKey::read_by_key(&pool, &read_key).await.unwrap().unwrap();
+    assert!(stored.last_used_at.is_some());
+
+    // La key de lectura no escribe aunque el usuario pueda
+    let project = json!({"code": format!("P-APIKEY-{}", Uuid::new_v4()), "title": "API"});
+    let (status, _) = send_json(&app, "POST", "/projects", Some(&read_key), project.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let mut request = request("POST", "/projects", None, project);
+    request.headers_mut().insert("X-Api-Key", write_key.parse().unwrap());
+    let (status, _) = send(&app, request).await;
+    assert_eq!(status, StatusCode::CREATED);
+    // Ni con una key de escritura se supera el rol del usuario
+    let (status, _) = send_json(&app, "POST", "/units", Some(&write_key), json!({})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Revocación
+    let uri = format!("/users/{}/api-keys/{}", user.id, stored.id);
+    let (status, body) = send_json(&app, "DELETE", &uri, Some(&admin_token), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["data"]["revoked_at"].is_string());
+    let (status, body) = send_json(&app, "GET", "/auth/me", Some(&read_key), Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["message"], "Invalid API key");
+    let (status, _) = send_json(&app, "DELETE", &uri, Some(&admin_token), Value::Null).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_only_admins_manage_api_keys() {
+    let (pool, app, admin_token) = setup().await;
+    let user = create_user(&pool, "writer", true).await;
+    let key = create_key(&app, &admin_token, &user, json!({"name": "ERP", "scope": "write"})).await;
+    let uri = format!("/users/{}/api-keys", user.id);
+    let (status, _) = send_json(&app, "POST", &uri, Some(&key), json!({"name": "Other"})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = send_json(&app, "GET", &uri, Some(&key), Value::Null).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_project_limited_keys_skip_admin_routes() {
+    let (pool, app, admin_token) = setup().await;
+    let project = create_project(&pool).await;
+    let admin = create_user(&pool, "admin", true).await;
+    let body = json!({"name": "ERP", "scope": "write", "project_ids": [project.id]});
+    let key = create_key(&app, &admin_token, &admin, body).await;
+
+    // Aunque sea de un administrador, no crea otras keys sin límite ni usuarios
+    let uri = format!("/users/{}/api-keys", admin.id);
+    let (status, _) = send_json(&app, "POST", &uri, Some(&key), json!({"name": "Other"})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = send_json(&app, "GET", "/project-members", Some(&key), Value::Null).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let user = json!({"username": format!("U-APIKEY-{}", Uuid::new_v4()), "email": "apikey@test.com", "password": PASSWORD});
+    let (status, _) = send_json(&app, "POST", "/auth/register", Some(&key), user).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Dentro de sus proyectos escribe
+    let mut project = serde_json::to_value(&project).unwrap();
+    project["title"] = json!("Changed");
+    let (status, body) = send_json(&app, "PATCH", "/projects", Some(&key), project).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[tokio::test]
+async fn test_api_key_project_scope() {
+    let (pool, app, admin_token) = setup().await;
+    let (allowed, other) = (create_project(&pool).await, create_project(&pool).await);
+
+    // Un administrador ve todos los proyectos, salvo con una key limitada
+    let admin = create_user(&pool, "admin", true).await;
+    let key = create_key(&app, &admin_token, &admin, json!({"name": "ERP", "project_ids": [allowed.id]})).await;
+    let (_, body) = send_json(&app, "GET", "/projects", Some(&key), Value::Null).await;
+    assert_eq!(ids(&body), vec![allowed.id as i64]);
+    let (status, _) = send_json(&app, "GET", &format!("/projects?id={}", other.id), Some(&key), Value::Null).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = send_json(&app, "GET", &format!("/projects?id={}", allowed.id), Some(&key), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Ni crea proyectos fuera de los suyos
+    let key = create_key(&app, &admin_token, &admin, json!({"name": "ERP", "scope": "write", "project_ids": [allowed.id]})).await;
+    let project = json!({"code": format!("P-APIKEY-{}", Uuid::new_v4()), "title": "API"});
+    let (status, body) = send_json(&app, "POST", "/projects", Some(&key), project).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "A project-limited API key cannot create projects");
+
+    // Para el resto, la key no amplía los proyectos de los que es miembro
+    let user = create_user(&pool, "reader", true).await;
+    let role = Role::read_by_name(&pool, "reader").await.unwrap().unwrap();
+    ProjectMember::create(&pool, NewProjectMember { project_id: allowed.id, user_id: user.id, role_id: role.id })
+        .await
+        .unwrap();
+    let key = create_key(&app, &admin_token, &user, json!({"name": "ERP", "project_ids": [allowed.id, other.id]})).await;
+    let (_, body) = send_json(&app, "GET", "/projects", Some(&key), Value::Null).await;
+    assert_eq!(ids(&body), vec![allowed.id as i64]);
+}
+
+#[tokio::test]
+async fn test_invalid_api_keys() {
+    let (pool, app, admin_token) = setup().await;
+    let user = create_user(&pool, "reader", true).await;
+    let uri = format!("/users/{}/api-keys", user.id);
+    let (status, body) = send_json(&app, "POST", &uri, Some(&admin_token), json!({"name": "ERP", "project_ids": [-1]})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "API key projects not found");
+    let (status, _) = send_json(&app, "POST", &uri, Some(&admin_token), json!({"name": " "})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = send_json(&app, "POST", &uri, Some(&admin_token), json!({"name": "ERP", "expires_at": "2000-01-01T00:00:00Z"})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = send_json(&app, "POST", "/users/-1/api-keys", Some(&admin_token), json!({"name": "ERP"})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = send_json(&app, "GET", "/auth/me", Some("pk_unknown"), Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Caducada
+    let key = create_key(&app, &admin_token, &user, json!({"name": "ERP"})).await;
+    sqlx::query("UPDATE api_keys SET expires_at = NOW() - INTERVAL '1 second' WHERE key_hash = $1")
+        .bind(ApiKey::read_by_key(&pool, &key).await.unwrap().unwrap().key_hash)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let (status, _) = send_json(&app, "GET", "/auth/me", Some(&key), Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Usuario desactivado
+    let key = create_key(&app, &admin_token, &user, json!({"name": "ERP"})).await;
+    sqlx::query("UPDATE users SET is_active = false WHERE id = $1").bind(user.id).execute(&pool).await.unwrap();
+    let (status, _) = send_json(&app, "GET", "/auth/me", Some(&key), Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
@@ -3,6 +3,7 @@ use axum::{
     body::Body,
     http::{header, Request, StatusCode},
 };
+use backend::mailer::LogMailer;
 use backend::models::{
     user::{NewUser, User, UserPass},
     role::{NewRole, Role},
@@ -31,8 +32,12 @@ fn test_app(pool: PgPool) -> axum::Router {
         pool,
         secret: "test_secret".to_string(),
         static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
     });
-    http::auth::router().with_state(app_state)
+    http::auth::router()
+        .merge(http::auth::private_router())
+        .merge(http::auth::admin_router())
+        .with_state(app_state)
 }
 
 #[tokio::test]
@@ -42,12 +47,12 @@ async fn test_register() {
 
     let username = format!("U-TEST-{}", Uuid::new_v4());
     let email = format!("{}@test.com", username);
-    let password = "password";
+    let password = "password1";
 
     let new_user = NewUser {
         username: username.clone(),
         email: email.clone(),
-        hashed_password: password.to_string(),
+        password: password.to_string(),
         role_id: role.id,
         is_active: true,
     };
@@ -73,12 +78,12 @@ async fn test_login() {
     
     let username = format!("U-TEST-{}", Uuid::new_v4());
     let email = format!("{}@test.com", username);
-    let password = "password";
+    let password = "password1";
 
     let new_user = NewUser {
         username: username.clone(),
         email: email.clone(),
-        hashed_password: bcrypt::hash(password, 4).unwrap(),
+        password: password.to_string(),
         role_id: role.id,
         is_active: true,
     };
@@ -0,0 +1,221 @@
+use std::sync::Arc;
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use backend::mailer::LogMailer;
+use backend::{
+    http,
+    models::{
+        role::Role,
+        session::Session,
+        user::User,
+        AppState,
+        TokenClaims,
+    },
+};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[path = "common.rs"]
+mod common;
+
+use common::{create_user, send, PASSWORD};
+
+const SECRET: &str = "test_secret";
+
+async fn setup() -> (PgPool, Router) {
+    let _ = &common::TRACING;
+    let pool = common::setup_pool().await;
+    let app = http::api_router(Arc::new(AppState {
+        pool: pool.clone(),
+        secret: SECRET.to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+    (pool, app)
+}
+
+async fn session(pool: &PgPool, user: &User) -> i32 {
+    Session::create(pool, user.id, None).await.unwrap().0.id
+}
+
+fn token(email: &str, sid: i32, exp: i64, secret: &str) -> String {
+    let now = chrono::Utc::now().timestamp();
+    let claims = TokenClaims {
+        sub: email.to_string(),
+        role: "reader".to_string(),
+        sid,
+        iat: now as usize,
+        exp: (now + exp) as usize,
+    };
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes())).unwrap()
+}
+
+fn get(uri: &str) -> axum::http::request::Builder {
+    Request::builder().uri(uri)
+}
+
+#[tokio::test]
+async fn test_public_routes() {
+    let (_, app) = setup().await;
+    let (status, _) = send(&app, get("/health").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, get("/auth/logout").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let (status, _) = send(&app, get("/unknown").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_first_user_setup() {
+    let _ = &common::TRACING;
+    let (pool, url) = common::setup_empty_pool().await;
+    let app = http::api_router(Arc::new(AppState {
+        pool: pool.clone(),
+        secret: SECRET.to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+    let post = |body: Value| {
+        Request::builder()
+            .method("POST")
+            .uri("/auth/setup")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
+    let admin = json!({"username": "admin", "email": "admin@test.com", "password": PASSWORD});
+
+    let (status, body) = send(&app, get("/auth/setup").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["required"], true);
+    let (status, _) = send(&app, post(json!({"username": "admin", "email": "admin@test.com", "password": "short"}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = send(&app, post(admin.clone())).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+    let request = get("/auth/me").header(header::AUTHORIZATION, format!("Bearer {}", token)).body(Body::empty()).unwrap();
+    let (status, _) = send(&app, request).await;
+    assert_eq!(status, StatusCode::OK);
+    let user = User::read_by_email(&pool, "admin@test.com".to_string()).await.unwrap().unwrap();
+    assert_eq!(Role::read_by_id(&pool, user.role_id).await.unwrap().unwrap().name, "admin");
+
+    // Con un usuario ya no se puede volver a usar
+    let (status, body) = send(&app, get("/auth/setup").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["required"], false);
+    let (status, _) = send(&app, post(json!({"username": "other", "email": "other@test.com", "password": PASSWORD}))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(User::count_all(&pool).await.unwrap(), 1);
+
+    common::drop_database(pool, &url).await;
+}
+
+#[tokio::test]
+async fn test_missing_or_invalid_token() {
+    let (pool, app) = setup().await;
+    let user = create_user(&pool, "reader", true).await;
+
+    let (status, body) = send(&app, get("/budgets").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["message"], "Missing token");
+
+    let sid = session(&pool, &user).await;
+    let bearer = |token: String| format!("Bearer {}", token);
+    let cases = [
+        (bearer(token(&user.email, sid, -120, SECRET)), "Token expired"),
+        (bearer(token(&user.email, sid, 3600, "other_secret")), "Invalid token"),
+        (bearer("not-a-jwt".to_string()), "Invalid token"),
+        (format!("Basic {}", token(&user.email, sid, 3600, SECRET)), "Missing token"),
+    ];
+    for (authorization, message) in cases {
+        let request = get("/budgets").header(header::AUTHORIZATION, authorization).body(Body::empty()).unwrap();
+        let (status, body) = send(&app, request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["message"], message);
+    }
+
+    // Token válido de un usuario inactivo o inexistente
+    let inactive = create_user(&pool, "reader", false).await;
+    let inactive_sid = session(&pool, &inactive).await;
+    for (email, sid) in [(inactive.email.as_str(), inactive_sid), ("nobody@test.com", sid)] {
+        let request = get("/budgets")
+            .header(header::AUTHORIZATION, bearer(token(email, sid, 3600, SECRET)))
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = send(&app, request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[tokio::test]
+async fn test_login_and_access() {
+    let (pool, app) = setup().await;
+    let user = create_user(&pool, "reader", true).await;
+
+    let (status, body) = common::login(&app, &user.email, PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let request = get("/auth/me")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+    let (status, body) = send(&app, request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["email"], user.email);
+
+    let request = get("/budgets?page=1&limit=1")
+        .header(header::COOKIE, format!("token={}", token))
+        .body(Body::empty())
+        .unwrap();
+    let (status, _) = send(&app, request).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_role_permissions() {
+    let (pool, app) = setup().await;
+    let mut tokens = std::collections::HashMap::new();
+    for role in ["reader", "writer", "master", "admin"] {
+        let user = create_user(&pool, role, true).await;
+        tokens.insert(role, token(&user.email, session(&pool, &user).await, 3600, SECRET));
+    }
+    let unknown = create_user(&pool, &format!("R-AUTH-{}", Uuid::new_v4()), true).await;
+    tokens.insert("unknown", token(&unknown.email, session(&pool, &unknown).await, 3600, SECRET));
+
+    // (rol, método, ruta, permitido). Los cuerpos vacíos no son válidos: una
+    // petición permitida llega al handler y falla con 4xx distinto de 401/403.
+    let cases = [
+        ("reader", "GET", "/budgets", true),
+        ("reader", "GET", "/prices", true),
+        ("reader", "POST", "/budgets", false),
+        ("reader", "GET", "/users", false),
+        ("writer", "POST", "/budgets", true),
+        ("writer", "POST", "/units", false),
+        ("writer", "DELETE", "/prices?id=0", false),
+        ("master", "POST", "/units", true),
+        ("master", "POST", "/budgets", true),
+        ("master", "POST", "/roles", false),
+        ("master", "POST", "/auth/register", false),
+        ("admin", "GET", "/users", true),
+        ("admin", "POST", "/roles", true),
+        ("admin", "POST", "/units", true),
+        ("unknown", "GET", "/budgets", false),
+    ];
+    for (role, method, uri, allowed) in cases {
+        let (status, body) = send(&app, common::request(method, uri, Some(&tokens[role]), json!({}))).await;
+        if allowed {
+            assert_ne!(status, StatusCode::FORBIDDEN, "{} {} {}", role, method, uri);
+            assert_ne!(status, StatusCode::UNAUTHORIZED, "{} {} {}", role, method, uri);
+        } else {
+            assert_eq!(status, StatusCode::FORBIDDEN, "{} {} {}", role, method, uri);
+            assert_eq!(body["message"], "Insufficient permissions");
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use std::{str::FromStr, sync::Arc};
+use axum::{
+    body::{self, Body},
+    http::{Request, StatusCode},
+};
+use backend::mailer::LogMailer;
+use backend::{
+    bc3,
+    http,
+    models::{
+        budget::{Budget, BudgetRates, BudgetStatus, NewBudget},
+        descomposition::{CalculationMode, Descomposition, DescompositionParams, NewDescomposition},
+        element::{Element, ElementType, NewElement},
+        measurement::{Measurement, NewMeasurement},
+        price::{NewPrice, Price, PriceParams, PriceType},
+        project::{NewProject, Project},
+        unit::{NewUnit, Unit},
+        version::{NewVersion, Version},
+        AppState,
+    },
+};
+use serde_json::{json, Value};
+use sqlx::{types::BigDecimal, PgPool};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+#[path = "common.rs"]
+mod common;
+
+async fn setup() -> PgPool {
+    let _ = &common::TRACING;
+    common::setup_pool().await
+}
+
+fn sample(prefix: &str) -> String {
+    format!(
+        "~V|PRESU|FIEBDC-3/2016|Presu|\\Catálogo de prueba|ANSI|\r\n\
+~C|{p}##||Catálogo|0||0|\r\n\
+~C|{p}01#||Capítulo|0||0|\r\n\
+~C|{p}E01|m3|Excavación|25.50||0|\r\n\
+~C|{p}MO1|h|Peón|20.00||1|\r\n\
+~C|{p}MT1|kg|Cemento|0.10||3|\r\n\
+~D|{p}01#|{p}E01\\1\\1\\|\r\n\
+~D|{p}E01|{p}MO1\\1\\0.5\\{p}MT1\\1\\2\\{p}MT1\\1\\3\\{p}XX\\1\\1\\|\r\n\
+~T|{p}E01|Excavación en zanja|\r\n\
+~M|{p}01\\{p}E01|1|10|\\\\10\\\\\\\\|\r\n",
+        p = prefix
+    )
+}
+
+fn prefix() -> String {
+    format!("{}-", Uuid::new_v4().to_string().chars().take(8).collect::<String>())
+}
+
+async fn read_prices(pool: &PgPool, version_id: i32) -> Vec<Price> {
+    let params = PriceParams {
+        id: None,
+        version_id: Some(version_id),
+        code: None,
+        description: None,
+        base_price: None,
+        unit_id: None,
+        price_type: None,
+        page: Some(1),
+        limit: Some(100),
+        sort_by: Some("code".to_string()),
+        asc: Some(true),
+    };
+    Price::read_paged(pool, &params).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_import_catalog() {
+    let pool = setup().await;
+    let p = prefix();
+    let document = bc3::parse(&sample(&p));
+    let name = format!("V-BC3-{}", Uuid::new_v4());
+    let report = bc3::import_catalog(&pool, &name, &document).await.unwrap();
+
+    assert_eq!(report.version.name, name);
+    assert_eq!(report.prices_created, 3);
+    assert_eq!(report.descompositions_created, 2);
+
+    let prices = read_prices(&pool, report.version.id).await;
+    assert_eq!(prices.len(), 3);
+    let excavation = prices.iter().find(|p2| p2.code == format!("{}E01", p)).unwrap();
+    assert_eq!(excavation.price_type, PriceType::Decomposed);
+    assert_eq!(excavation.description, "Excavación en zanja");
+    assert_eq!(excavation.base_price, BigDecimal::from_str("25.50").unwrap());
+    let labour = prices.iter().find(|p2| p2.code == format!("{}MO1", p)).unwrap();
+    assert_eq!(labour.price_type, PriceType::Base);
+
+    let params = DescompositionParams {
+        id: None,
+        parent_price_id: Some(excavation.id),
+        component_price_id: None,
+        calculation_mode: None,
+        page: Some(1),
+        limit: Some(10),
+        sort_by: Some("id".to_string()),
+        asc: Some(true),
+    };
+    let descompositions = Descomposition::read_paged(&pool, &params).await.unwrap();
+    assert_eq!(descompositions.len(), 2);
+    // Las dos apariciones de MT1 se suman
+    assert_eq!(descompositions[1].fixed_quantity, Some(BigDecimal::from(5)));
+
+    let reasons: Vec<(&str, Option<&str>)> = report
+        .skipped
+        .iter()
+        .map(|s| (s.record.as_str(), s.code.as_deref()))
+        .collect();
+    let missing = format!("{}E01\\{}XX", p, p);
+    assert!(reasons.contains(&("~D", Some(missing.as_str()))));
+    assert!(reasons.contains(&("~M", Some(format!("{}E01", p).as_str()))));
+    assert_eq!(report.skipped.iter().filter(|s| s.record == "~C").count(), 2);
+}
+
+#[tokio::test]
+async fn test_import_empty_file() {
+    let pool = setup().await;
+    let document = bc3::parse("~V|PRESU|FIEBDC-3/2016|Presu||ANSI|\r\n");
+    let result = bc3::import_catalog(&pool, "V-EMPTY", &document).await;
+    assert!(matches!(result, Err(bc3::Bc3Error::Empty)));
+}
+
+#[tokio::test]
+async fn test_import_skips_cycles() {
+    let pool = setup().await;
+    let p = prefix();
+    let text = format!(
+        "~C|{p}A|ud|A|1||0|\r\n~C|{p}B|ud|B|1||0|\r\n~C|{p}C|ud|C|1||0|\r\n\
+~D|{p}A|{p}B\\1\\1\\|\r\n~D|{p}B|{p}C\\1\\1\\|\r\n~D|{p}C|{p}A\\1\\1\\|\r\n",
+        p = p
+    );
+    let report = bc3::import_catalog(&pool, &format!("V-CYCLE-{}", p), &bc3::parse(&text)).await.unwrap();
+    assert_eq!(report.descompositions_created, 2);
+    let skipped = report.skipped.iter().find(|s| s.record == "~D").unwrap();
+    assert_eq!(skipped.code, Some(format!("{p}C\\{p}A", p = p)));
+    assert!(skipped.reason.contains(&format!("{p}C -> {p}A -> {p}B -> {p}C", p = p)));
+}
+
+#[tokio::test]
+async fn test_import_endpoint() {
+    let pool = setup().await;
+    let app_state = Arc::new(AppState {
+        pool,
+        secret: "test_secret".to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    });
+    let app = http::versions::router().with_state(app_state);
+    let p = prefix();
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/import")
+                .body(Body::from(sample(&p)))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body = body::to_bytes(response.into_body(), 1024 * 1024).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["data"]["version"]["name"], "Catálogo de prueba");
+    assert_eq!(body["data"]["prices_created"], 3);
+}
+
+async fn sample_budget(pool: &PgPool, p: &str) -> Budget {
+    let project = Project::create(pool, NewProject {
+        code: format!("{}PRJ", p),
+        title: Some("BC3 export".to_string()),
+        member_scope: None,
+    })
+    .await
+    .unwrap();
+    let budget = Budget::create(pool, NewBudget {
+        project_id: project.id,
+        code: format!("{}OBRA", p),
+        version_number: 1,
+        name: "Reforma de vivienda".to_string(),
+        status: BudgetStatus::Draft,
+        rates: BudgetRates::default(),
+    })
+    .await
+    .unwrap();
+    let version = Version::create(pool, NewVersion { name: format!("V-{}", p) }).await.unwrap();
+    let unit = Unit::create(pool, NewUnit {
+        name: format!("{}m2", p),
+        symbol: "m2".to_string(),
+        description: None,
+        formula: "a".to_string(),
+    })
+    .await
+    .unwrap();
+    let price = |code: &str, description: &str, base_price: &str, price_type| NewPrice {
+        version_id: version.id,
+        code: format!("{}{}", p, code),
+        description: description.to_string(),
+        base_price: BigDecimal::from_str(base_price).unwrap(),
+        unit_id: unit.id,
+        price_type,
+    };
+    let floor = Price::create(pool, price("SOL", "Solado cerámico\ncon rodapié", "30.00", PriceType::Decomposed))
+        .await
+        .unwrap();
+    let tile = Price::create(pool, price("BAL", "Baldosa", "12.50", PriceType::Base)).await.unwrap();
+    Descomposition::create(pool, NewDescomposition {
+        parent_price_id: floor.id,
+        component_price_id: tile.id,
+        calculation_mode: CalculationMode::Fixed,
+        fixed_quantity: Some(BigDecimal::from_str("1.05").unwrap()),
+        params_json: None,
+    })
+    .await
+    .unwrap();
+    let element = |parent_id, element_type, code: &str, description: &str| NewElement {
+        budget_id: budget.id,
+        parent_id,
+        version_id: version.id,
+        element_type,
+        code: format!("{}{}", p, code),
+        budget_code: format!("{}{}", p, code),
+        description: Some(description.to_string()),
+    };
+    let chapter = Element::create(pool, element(None, ElementType::Chapter, "01", "Pavimentos")).await.unwrap();
+    let line = Element::create(pool, element(Some(chapter.id), ElementType::Line, "01.01", "Salón"))
+        .await
+        .unwrap();
+    for quantity in ["20", "5.5"] {
+        Measurement::create(pool, NewMeasurement {
+            element_id: line.id,
+            price_id: floor.id,
+            params_json: json!({"a": quantity}),
+            measurement_text: None,
+            measured_quantity: BigDecimal::from(0),
+        })
+        .await
+        .unwrap();
+    }
+    budget
+}
+
+#[tokio::test]
+async fn test_export_round_trip() {
+    let pool = setup().await;
+    let p = prefix();
+    let budget = sample_budget(&pool, &p).await;
+
+    let document = bc3::export_budget(&pool, budget.id).await.unwrap().unwrap();
+    let text = bc3::write(&document);
+    assert!(text.starts_with("~V|Presu|FIEBDC-3/2016\\"));
+    assert!(text.contains(&format!("~C|{}OBRA##||Reforma de vivienda|334.82||0|", p)));
+    assert!(text.contains(&format!("~D|{p}01#|{p}SOL\\1\\25.5\\|")));
+    assert!(text.contains(&format!("~D|{p}SOL|{p}BAL\\1\\1.05\\|")));
+
+    let parsed = bc3::parse(&bc3::decode(&bc3::encode(&text)));
+    assert!(parsed.issues.is_empty());
+    assert_eq!(parsed.concept(&format!("{}01", p)).unwrap().kind, bc3::ConceptKind::Chapter);
+    assert_eq!(parsed.texts[&format!("{}SOL", p)], "Solado cerámico\ncon rodapié");
+    assert_eq!(parsed.measurements[0].lines.len(), 2);
+
+    let report = bc3::import_catalog(&pool, &format!("V-RT-{}", p), &parsed).await.unwrap();
+    assert_eq!(report.prices_created, 2);
+    assert_eq!(report.descompositions_created, 1);
+    let prices = read_prices(&pool, report.version.id).await;
+    let floor = prices.iter().find(|p2| p2.code == format!("{}SOL", p)).unwrap();
+    assert_eq!(floor.price_type, PriceType::Decomposed);
+    assert_eq!(floor.description, "Solado cerámico\ncon rodapié");
+    // Se exporta el precio calculado a partir de la descomposición: 1.05 × 12.50
+    assert_eq!(floor.base_price, BigDecimal::from_str("13.13").unwrap());
+}
+
+#[tokio::test]
+async fn test_export_chapter_and_price_with_the_same_code() {
+    let pool = setup().await;
+    let p = prefix();
+    let budget = sample_budget(&pool, &p).await;
+    let elements = Element::read_by_budget(&pool, budget.id).await.unwrap();
+    let line = elements.iter().find(|e| e.budget_code == format!("{}01.01", p)).unwrap();
+    // Precio con el mismo código que el capítulo 01
+    let price = Price::create(&pool, NewPrice {
+        version_id: line.version_id,
+        code: format!("{}01", p),
+        description: "Limpieza".to_string(),
+        base_price: BigDecimal::from(2),
+        unit_id: Price::read_by_codes(&pool, line.version_id, &[format!("{}SOL", p)]).await.unwrap()[0].unit_id,
+        price_type: PriceType::Base,
+    })
+    .await
+    .unwrap();
+    Measurement::create(&pool, NewMeasurement {
+        element_id: line.id,
+        price_id: price.id,
+        params_json: json!({"a": 3}),
+        measurement_text: None,
+        measured_quantity: BigDecimal::from(0),
+    })
+    .await
+    .unwrap();
+
+    let text = bc3::write(&bc3::export_budget(&pool, budget.id).await.unwrap().unwrap());
+    // 25.5 × 13.13 + 3 × 2
+    assert!(text.contains(&format!("~C|{p}OBRA##||Reforma de vivienda|340.82||0|")));
+    assert!(text.contains(&format!("~C|{p}01#||Pavimentos|340.82||0|")));
+    assert!(text.contains(&format!("~C|{p}01|m2|Limpieza|2||0|")));
+    assert!(text.contains(&format!("~D|{p}01#|{p}SOL\\1\\25.5\\{p}01\\1\\3\\|")));
+    assert!(!text.contains(&format!("~D|{p}01|")));
+}
+
+#[tokio::test]
+async fn test_export_endpoint() {
+    let pool = setup().await;
+    let p = prefix();
+    let budget = sample_budget(&pool, &p).await;
+    let app = http::budgets::router().with_state(Arc::new(AppState {
+        pool,
+        secret: "test_secret".to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+
+    let response = app
+        .clone()
+        .oneshot(Request::builder().uri(format!("/{}/bc3", budget.id)).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let disposition = response.headers()["content-disposition"].to_str().unwrap().to_string();
+    assert_eq!(disposition, format!("attachment; filename=\"{}OBRA.bc3\"", p));
+    let body = body::to_bytes(response.into_body(), 1024 * 1024).await.unwrap();
+    // "ó" de "Salón" en Windows-1252
+    assert!(body.windows(6).any(|w| w == b"Sal\xf3n\\"));
+
+    let response = app
+        .oneshot(Request::builder().uri("/0/bc3").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -1,8 +1,14 @@
 use backend::models::{
-    budget::{Budget, NewBudget, BudgetParams, BudgetStatus},
+    budget::{Budget, NewBudget, BudgetParams, BudgetRates, BudgetStatus, CloneBudget, CustomRate},
+    element::{Element, ElementType, NewElement},
+    measurement::{Measurement, NewMeasurement},
+    price::{NewPrice, Price, PriceType},
     project::{Project, NewProject},
+    unit::{NewUnit, Unit},
+    version::{NewVersion, Version},
 };
-use sqlx::PgPool;
+use serde_json::json;
+use sqlx::{types::BigDecimal, PgPool};
 use uuid::Uuid;
 use rand::Rng;
 
@@ -16,6 +22,7 @@ async fn setup() -> (PgPool, Project) {
     let new_project = NewProject {
         code: format!("P-TEST-{}", Uuid::new_v4()),
         title: Some("Test Project".to_string()),
+        member_scope: None,
     };
     let project = Project::create(&pool, new_project).await.unwrap();
     (pool, project)
@@ -31,6 +38,7 @@ async fn test_create_budget() {
         version_number: rng.gen_range(1..100000),
         name: "Budget 1".to_string(),
         status: BudgetStatus::Draft,
+        rates: BudgetRates::default(),
     };
     let budget = Budget::create(&pool, new_budget).await.unwrap();
     assert_eq!(budget.project_id, project.id);
@@ -49,6 +57,7 @@ async fn test_read_budget() {
         version_number: rng.gen_range(1..100000),
         name: "Budget 2".to_string(),
         status: BudgetStatus::Draft,
+        rates: BudgetRates::default(),
     };
     let budget = Budget::create(&pool, new_budget).await.unwrap();
     let read_budget = Budget::read_by_id(&pool, budget.id).await.unwrap().unwrap();
@@ -69,6 +78,7 @@ async fn test_update_budget() {
         version_number: rng.gen_range(1..100000),
         name: "Budget 3".to_string(),
         status: BudgetStatus::Draft,
+        rates: BudgetRates::default(),
     };
     let mut budget = Budget::create(&pool, new_budget).await.unwrap();
     budget.name = "Budget 3 updated".to_string();
@@ -86,6 +96,7 @@ async fn test_delete_budget() {
         version_number: rng.gen_range(1..100000),
         name: "Budget 4".to_string(),
         status: BudgetStatus::Draft,
+        rates: BudgetRates::default(),
     };
     let budget = Budget::create(&pool, new_budget).await.unwrap();
     let deleted_budget = Budget::delete(&pool, budget.id).await.unwrap();
@@ -104,6 +115,7 @@ async fn test_list_budgets() {
         version_number: rng.gen_range(1..100000),
         name: "Budget 5".to_string(),
         status: BudgetStatus::Draft,
+        rates: BudgetRates::default(),
     };
     Budget::create(&pool, new_budget).await.unwrap();
     let new_budget = NewBudget {
@@ -112,10 +124,12 @@ async fn test_list_budgets() {
         version_number: rng.gen_range(1..100000),
         name: "Budget 6".to_string(),
         status: BudgetStatus::Draft,
+        rates: BudgetRates::default(),
     };
     Budget::create(&pool, new_budget).await.unwrap();
     let params = BudgetParams {
         id: None,
+        member_scope: None,
         project_id: Some(project.id),
         code: None,
         version_number: None,
@@ -129,3 +143,136 @@ async fn test_list_budgets() {
     let budgets = Budget::read_paged(&pool, &params).await.unwrap();
     assert!(budgets.len() >= 2);
 }
+
+#[tokio::test]
+async fn test_budget_rates() {
+    let (pool, project) = setup().await;
+    let mut rng = rand::thread_rng();
+    let mut new_budget = |rates| NewBudget {
+        project_id: project.id,
+        code: format!("P-006-{}", Uuid::new_v4()),
+        version_number: rng.gen_range(1..100000),
+        name: "Budget 6".to_string(),
+        status: BudgetStatus::Draft,
+        rates,
+    };
+    let invalid = BudgetRates { vat: BigDecimal::from(150), ..Default::default() };
+    assert!(Budget::create(&pool, new_budget(invalid)).await.is_err());
+
+    let mut budget = Budget::create(&pool, new_budget(BudgetRates::default())).await.unwrap();
+    assert_eq!(budget.rates, BudgetRates::default());
+    budget.rates.general_expenses = BigDecimal::from(17);
+    budget.rates.custom_rates.0.push(CustomRate { name: "Seguridad y salud".to_string(), percentage: BigDecimal::from(1) });
+    let budget = Budget::update(&pool, budget).await.unwrap();
+    assert_eq!(budget.rates.general_expenses, BigDecimal::from(17));
+    assert_eq!(budget.rates.custom_rates.0.len(), 1);
+
+    let mut budget = budget;
+    budget.rates.custom_rates.0[0].name = " ".to_string();
+    assert!(Budget::update(&pool, budget).await.is_err());
+}
+
+#[tokio::test]
+async fn test_clone_budget() {
+    let (pool, project) = setup().await;
+    let p = Uuid::new_v4().to_string().chars().take(8).collect::<String>();
+    let mut rates = BudgetRates { vat: BigDecimal::from(10), ..Default::default() };
+    rates.custom_rates.0.push(CustomRate { name: "Seguridad y salud".to_string(), percentage: BigDecimal::from(1) });
+    let budget = Budget::create(&pool, NewBudget {
+        project_id: project.id,
+        code: format!("P-007-{}", p),
+        version_number: 1,
+        name: "Budget 7".to_string(),
+        status: BudgetStatus::Draft,
+        rates,
+    })
+    .await
+    .unwrap();
+    let version = Version::create(&pool, NewVersion { name: format!("V-CLONE-{}", p) }).await.unwrap();
+    let unit = Unit::create(&pool, NewUnit {
+        name: format!("CLONE-{}", p),
+        symbol: "u".to_string(),
+        description: None,
+        formula: "a".to_string(),
+    })
+    .await
+    .unwrap();
+    let price = Price::create(&pool, NewPrice {
+        version_id: version.id,
+        code: format!("CLONE-{}", p),
+        description: "Price".to_string(),
+        base_price: BigDecimal::from(10),
+        unit_id: unit.id,
+        price_type: PriceType::Base,
+    })
+    .await
+    .unwrap();
+    let element = |code: &str, budget_code: &str, element_type| NewElement {
+        budget_id: budget.id,
+        parent_id: None,
+        version_id: version.id,
+        element_type,
+        code: code.to_string(),
+        budget_code: budget_code.to_string(),
+        description: None,
+    };
+    // El hijo se crea antes que el padre: la copia no depende del orden de los ids
+    let mut item = Element::create(&pool, element("LINE", "01.01", ElementType::Line)).await.unwrap();
+    let chapter = Element::create(&pool, element("CHAPTER", "01", ElementType::Chapter)).await.unwrap();
+    item.parent_id = Some(chapter.id);
+    let item = Element::update(&pool, item).await.unwrap();
+    for a in [2, 3] {
+        Measurement::create(&pool, NewMeasurement {
+            element_id: item.id,
+            price_id: price.id,
+            params_json: json!({"a": a}),
+            measurement_text: Some(format!("Line {}", a)),
+            measured_quantity: BigDecimal::from(0),
+        })
+        .await
+        .unwrap();
+    }
+    // También se copian los presupuestos enviados, y la copia es un borrador
+    Budget::transition(&pool, budget.id, BudgetStatus::Submitted, None, None).await.unwrap();
+
+    let copy = Budget::clone_version(&pool, budget.id, CloneBudget::default()).await.unwrap();
+    assert_eq!(copy.project_id, project.id);
+    assert_eq!(copy.version_number, 2);
+    assert_eq!(copy.code, format!("P-007-{}-V2", p));
+    assert_eq!(copy.name, "Budget 7");
+    assert_eq!(copy.status, BudgetStatus::Draft);
+    assert_eq!(copy.rates, budget.rates);
+
+    let elements = Element::read_by_budget(&pool, copy.id).await.unwrap();
+    assert_eq!(elements.len(), 2);
+    let copied_chapter = elements.iter().find(|e| e.code == "CHAPTER").unwrap();
+    let copied_item = elements.iter().find(|e| e.code == "LINE").unwrap();
+    assert_ne!(copied_item.id, item.id);
+    assert_eq!(copied_chapter.parent_id, None);
+    assert_eq!(copied_item.parent_id, Some(copied_chapter.id));
+    assert_eq!(copied_item.budget_code, "01.01");
+
+    let measurements = Measurement::read_by_budget(&pool, copy.id).await.unwrap();
+    assert_eq!(measurements.len(), 2);
+    assert!(measurements.iter().all(|m| m.element_id == copied_item.id && m.price_id == price.id));
+    assert_eq!(measurements[0].measured_quantity, BigDecimal::from(2));
+    assert_eq!(measurements[1].measurement_text.as_deref(), Some("Line 3"));
+    // El original no cambia
+    assert_eq!(Measurement::read_by_budget(&pool, budget.id).await.unwrap().len(), 2);
+
+    let item = CloneBudget { code: Some(format!("P-007-{}-B", p)), name: Some("Alternative".to_string()) };
+    let copy = Budget::clone_version(&pool, budget.id, item).await.unwrap();
+    assert_eq!(copy.version_number, 3);
+    assert_eq!(copy.name, "Alternative");
+
+    // Un código repetido no deja nada a medias
+    let item = CloneBudget { code: Some(copy.code.clone()), name: None };
+    assert!(Budget::clone_version(&pool, budget.id, item).await.is_err());
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM budgets WHERE project_id = $1")
+        .bind(project.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 3);
+    assert!(Budget::clone_version(&pool, -1, CloneBudget::default()).await.is_err());
+}
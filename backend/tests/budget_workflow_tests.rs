@@ -0,0 +1,262 @@
+use std::sync::Arc;
+use axum::{http::StatusCode, Router};
+use backend::mailer::LogMailer;
+use backend::{
+    http,
+    models::{
+        budget::{Budget, BudgetError, BudgetRates, BudgetStatus, NewBudget},
+        budget_status_change::BudgetStatusChange,
+        element::{Element, ElementType, NewElement},
+        measurement::{Measurement, NewMeasurement},
+        price::{NewPrice, Price, PriceType},
+        project::{NewProject, Project},
+        project_member::{NewProjectMember, ProjectMember},
+        unit::{NewUnit, Unit},
+        version::{NewVersion, Version},
+        AppState,
+    },
+};
+use serde_json::{json, Value};
+use sqlx::{types::BigDecimal, PgPool};
+use uuid::Uuid;
+
+#[path = "common.rs"]
+mod common;
+
+use common::{create_user, login, send_json, PASSWORD};
+
+/// Presupuesto en borrador con un capítulo medido.
+struct Fixture {
+    project: Project,
+    budget: Budget,
+    element: Element,
+    measurement: Measurement,
+}
+
+async fn setup() -> (PgPool, Router, Fixture) {
+    let _ = &common::TRACING;
+    let pool = common::setup_pool().await;
+    let app = http::api_router(Arc::new(AppState {
+        pool: pool.clone(),
+        secret: "test_secret".to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+    let fixture = create_fixture(&pool).await;
+    (pool, app, fixture)
+}
+
+async fn create_fixture(pool: &PgPool) -> Fixture {
+    let code = format!("WF-{}", Uuid::new_v4().to_string().chars().take(8).collect::<String>());
+    let project = Project::create(pool, NewProject {
+        code: code.clone(),
+        title: Some("Workflow".to_string()),
+        member_scope: None,
+    })
+    .await
+    .unwrap();
+    let budget = Budget::create(pool, NewBudget {
+        project_id: project.id,
+        code: code.clone(),
+        version_number: 1,
+        name: "Workflow".to_string(),
+        status: BudgetStatus::Draft,
+        rates: BudgetRates::default(),
+    })
+    .await
+    .unwrap();
+    let version = Version::create(pool, NewVersion { name: code.clone() }).await.unwrap();
+    let unit = Unit::create(pool, NewUnit {
+        name: code.clone(),
+        symbol: "u".to_string(),
+        description: None,
+        formula: "a".to_string(),
+    })
+    .await
+    .unwrap();
+    let price = Price::create(pool, NewPrice {
+        version_id: version.id,
+        code: code.clone(),
+        description: "Price".to_string(),
+        base_price: BigDecimal::from(10),
+        unit_id: unit.id,
+        price_type: PriceType::Base,
+    })
+    .await
+    .unwrap();
+    let element = Element::create(pool, NewElement {
+        budget_id: budget.id,
+        parent_id: None,
+        version_id: version.id,
+        element_type: ElementType::Chapter,
+        code: code.clone(),
+        budget_code: "01".to_string(),
+        description: None,
+    })
+    .await
+    .unwrap();
+    let measurement = Measurement::create(pool, NewMeasurement {
+        element_id: element.id,
+        price_id: price.id,
+        params_json: json!({"a": 2}),
+        measurement_text: None,
+        measured_quantity: BigDecimal::from(0),
+    })
+    .await
+    .unwrap();
+    Fixture { project, budget, element, measurement }
+}
+
+/// Miembro del proyecto con el rol indicado. Devuelve su token.
+async fn member_token(pool: &PgPool, app: &Router, project: &Project, role_name: &str) -> String {
+    let user = create_user(pool, role_name, true).await;
+    ProjectMember::create(pool, NewProjectMember { project_id: project.id, user_id: user.id, role_id: user.role_id })
+        .await
+        .unwrap();
+    let (_, body) = login(app, &user.email, PASSWORD).await;
+    body["data"]["token"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_budget_workflow() {
+    let (pool, app, fixture) = setup().await;
+    let writer = member_token(&pool, &app, &fixture.project, "writer").await;
+    let master = member_token(&pool, &app, &fixture.project, "master").await;
+    let uri = |action: &str| format!("/budgets/{}/{}", fixture.budget.id, action);
+
+    let (status, body) = send_json(&app, "POST", &uri("submit"), Some(&writer), json!({})).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["status"], "submitted");
+
+    // Solo los usuarios con rol master revisan
+    let (status, _) = send_json(&app, "POST", &uri("approve"), Some(&writer), json!({})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = send_json(&app, "POST", &uri("reject"), Some(&master), json!({"comment": " "})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "A comment is required to reject a budget");
+    let (status, body) = send_json(&app, "POST", &uri("reject"), Some(&master), json!({"comment": "Missing chapter"})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["status"], "rejected");
+
+    // Rechazado se puede corregir y volver a enviar
+    let mut element = Element::read_by_id(&pool, fixture.element.id).await.unwrap().unwrap();
+    element.description = Some("Fixed".to_string());
+    Element::update(&pool, element).await.unwrap();
+    send_json(&app, "POST", &uri("submit"), Some(&writer), json!({})).await;
+    let (status, body) = send_json(&app, "POST", &uri("approve"), Some(&master), json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["status"], "approved");
+    let (status, body) = send_json(&app, "POST", &uri("submit"), Some(&writer), json!({})).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["message"], "Cannot change budget status from approved to submitted");
+
+    let (status, body) = send_json(&app, "GET", &uri("history"), Some(&writer), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let history = body["data"].as_array().unwrap();
+    let steps: Vec<(&str, &str)> = history
+        .iter()
+        .map(|change| (change["from_status"].as_str().unwrap(), change["to_status"].as_str().unwrap()))
+        .collect();
+    assert_eq!(steps, vec![
+        ("draft", "submitted"),
+        ("submitted", "rejected"),
+        ("rejected", "submitted"),
+        ("submitted", "approved"),
+    ]);
+    assert_eq!(history[1]["comment"], "Missing chapter");
+    assert!(history[0]["user_id"].is_number());
+
+    let (status, body) = send_json(&app, "POST", &uri("archive"), Some(&master), json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["status"], "archived");
+}
+
+#[tokio::test]
+async fn test_locked_budget_refuses_writes() {
+    let (pool, app, fixture) = setup().await;
+    let writer = member_token(&pool, &app, &fixture.project, "writer").await;
+    Budget::transition(&pool, fixture.budget.id, BudgetStatus::Submitted, None, None).await.unwrap();
+
+    // Elementos y mediciones, por la API y directamente en la base de datos
+    let mut element = Element::read_by_id(&pool, fixture.element.id).await.unwrap().unwrap();
+    element.description = Some("Changed".to_string());
+    let (status, _) = send_json(&app, "PATCH", "/elements", Some(&writer), json!(element)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let uri = format!("/measurements?id={}", fixture.measurement.id);
+    let (status, _) = send_json(&app, "DELETE", &uri, Some(&writer), Value::Null).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let child = NewElement {
+        budget_id: fixture.budget.id,
+        parent_id: Some(fixture.element.id),
+        version_id: fixture.element.version_id,
+        element_type: ElementType::Chapter,
+        code: format!("{}-1", fixture.element.code),
+        budget_code: "01.01".to_string(),
+        description: None,
+    };
+    assert!(Element::create(&pool, child).await.is_err());
+    assert!(Measurement::delete(&pool, fixture.measurement.id).await.is_err());
+
+    // Ni los datos del presupuesto
+    let mut budget = Budget::read_by_id(&pool, fixture.budget.id).await.unwrap().unwrap();
+    budget.name = "Changed".to_string();
+    let (status, body) = send_json(&app, "PATCH", "/budgets", Some(&writer), json!(budget)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["message"], "Budget is submitted and cannot be modified");
+
+    let budget = Budget::read_by_id(&pool, fixture.budget.id).await.unwrap().unwrap();
+    assert_eq!(budget.name, "Workflow");
+    assert!(Measurement::read_by_id(&pool, fixture.measurement.id).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_transition_waits_for_pending_writes() {
+    let (pool, _, fixture) = setup().await;
+
+    // Un cambio sin confirmar en un elemento: el envío, desde otra conexión,
+    // espera a que se confirme
+    let mut tx = pool.begin().await.unwrap();
+    sqlx::query("UPDATE elements SET description = 'Changed' WHERE id = $1")
+        .bind(fixture.element.id)
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+    let pending = tokio::spawn({
+        let (pool, id) = (common::setup_pool().await, fixture.budget.id);
+        async move { Budget::transition(&pool, id, BudgetStatus::Submitted, None, None).await }
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert!(!pending.is_finished());
+    tx.commit().await.unwrap();
+    assert_eq!(pending.await.unwrap().unwrap().status, BudgetStatus::Submitted);
+
+    let element = Element::read_by_id(&pool, fixture.element.id).await.unwrap().unwrap();
+    assert_eq!(element.description.as_deref(), Some("Changed"));
+}
+
+#[tokio::test]
+async fn test_status_only_changes_through_workflow() {
+    let (pool, _, fixture) = setup().await;
+    let mut budget = Budget::read_by_id(&pool, fixture.budget.id).await.unwrap().unwrap();
+    budget.status = BudgetStatus::Approved;
+    assert!(matches!(Budget::update(&pool, budget).await, Err(BudgetError::Database(_))));
+
+    let result = Budget::transition(&pool, fixture.budget.id, BudgetStatus::Approved, None, None).await;
+    assert!(matches!(
+        result,
+        Err(BudgetError::Transition { from: BudgetStatus::Draft, to: BudgetStatus::Approved })
+    ));
+    let result = Budget::transition(&pool, -1, BudgetStatus::Submitted, None, None).await;
+    assert!(matches!(result, Err(BudgetError::NotFound)));
+    assert!(BudgetStatusChange::read_by_budget(&pool, fixture.budget.id).await.unwrap().is_empty());
+
+    let new_budget = NewBudget {
+        project_id: fixture.project.id,
+        code: format!("P-WORKFLOW-{}", Uuid::new_v4()),
+        version_number: 1,
+        name: "Approved".to_string(),
+        status: BudgetStatus::Approved,
+        rates: BudgetRates::default(),
+    };
+    assert!(Budget::create(&pool, new_budget).await.is_err());
+}
@@ -1,11 +1,33 @@
+use axum::{
+    body::{self, Body},
+    http::{header, HeaderMap, Request, StatusCode},
+    Router,
+};
+use backend::models::{
+    role::{NewRole, Role},
+    user::{NewUser, User},
+};
+use serde_json::{json, Value};
 use sqlx::{
+    migrate::{MigrateDatabase, Migrator},
     postgres::PgPoolOptions,
     PgPool,
+    Postgres,
 };
 use dotenv::dotenv;
-use std::env;
+use std::{env, path::Path};
 use once_cell::sync::Lazy;
+use tower::ServiceExt;
+use uuid::Uuid;
 
+// Contraseña de los usuarios de `create_user`
+#[allow(dead_code)]
+pub const PASSWORD: &str = "password1";
+// `User-Agent` de las peticiones de `request`
+#[allow(dead_code)]
+pub const USER_AGENT: &str = "presu-tests";
+
+#[allow(dead_code)]
 pub static TRACING: Lazy<()> = Lazy::new(|| {
     dotenv().ok();
     let log_level = env::var("RUST_LOG").unwrap_or("debug".to_string());
@@ -23,3 +45,100 @@ pub async fn setup_pool() -> PgPool {
         .await
         .expect("Failed to create pool.")
 }
+
+/// Base de datos nueva y vacía, con las migraciones aplicadas, para las pruebas
+/// que no pueden compartir la de `DATABASE_URL` (por ejemplo, sin usuarios).
+/// Se borra con `drop_database`.
+#[allow(dead_code)]
+pub async fn setup_empty_pool() -> (PgPool, String) {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let (server, _) = database_url.rsplit_once('/').expect("DATABASE_URL must name a database");
+    let url = format!("{}/presu_test_{}", server, uuid::Uuid::new_v4().simple());
+    Postgres::create_database(&url).await.expect("Failed to create database.");
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&url)
+        .await
+        .expect("Failed to create pool.");
+    Migrator::new(Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations"))
+        .await
+        .expect("Failed to read migrations.")
+        .run(&pool)
+        .await
+        .expect("Failed to run migrations.");
+    (pool, url)
+}
+
+#[allow(dead_code)]
+pub async fn drop_database(pool: PgPool, url: &str) {
+    pool.close().await;
+    Postgres::drop_database(url).await.expect("Failed to drop database.");
+}
+
+/// Usuario con el rol indicado, que se crea si no existe, y la contraseña
+/// `PASSWORD`.
+#[allow(dead_code)]
+pub async fn create_user(pool: &PgPool, role: &str, is_active: bool) -> User {
+    let role = match Role::read_by_name(pool, role).await.unwrap() {
+        Some(role) => role,
+        None => Role::create(pool, NewRole { name: role.to_string() }).await.unwrap(),
+    };
+    let username = format!("U-TEST-{}", Uuid::new_v4());
+    User::create(pool, NewUser {
+        email: format!("{}@test.com", username),
+        username,
+        password: PASSWORD.to_string(),
+        role_id: role.id,
+        is_active,
+    })
+    .await
+    .unwrap()
+}
+
+/// Petición JSON con un JWT o una API key opcional en `Authorization`. Con
+/// `Value::Null` se envía sin cuerpo.
+#[allow(dead_code)]
+pub fn request(method: &str, uri: &str, token: Option<&str>, body: Value) -> Request<Body> {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::USER_AGENT, USER_AGENT);
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    let body = if body.is_null() { Body::empty() } else { Body::from(body.to_string()) };
+    request.body(body).unwrap()
+}
+
+/// Estado, cabeceras y cuerpo JSON (`Value::Null` si no lo es) de la respuesta.
+#[allow(dead_code)]
+pub async fn send_with_headers(app: &Router, request: Request<Body>) -> (StatusCode, HeaderMap, Value) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let (status, headers) = (response.status(), response.headers().clone());
+    let body = body::to_bytes(response.into_body(), 10 * 1024 * 1024).await.unwrap();
+    (status, headers, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[allow(dead_code)]
+pub async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+    let (status, _, body) = send_with_headers(app, request).await;
+    (status, body)
+}
+
+#[allow(dead_code)]
+pub async fn send_json(app: &Router, method: &str, uri: &str, token: Option<&str>, body: Value) -> (StatusCode, Value) {
+    send(app, request(method, uri, token, body)).await
+}
+
+#[allow(dead_code)]
+pub fn login_request(email: &str, password: &str) -> Request<Body> {
+    request("POST", "/auth/login", None, json!({"email": email, "password": password}))
+}
+
+/// Respuesta de `/auth/login`; el access token está en `data.token`.
+#[allow(dead_code)]
+pub async fn login(app: &Router, email: &str, password: &str) -> (StatusCode, Value) {
+    send(app, login_request(email, password)).await
+}
@@ -1,13 +1,28 @@
+use std::sync::Arc;
+use axum::{
+    body::{self, Body},
+    http::{header::CONTENT_TYPE, Request, StatusCode},
+};
+use backend::mailer::LogMailer;
 use backend::models::{
-    descomposition::{Descomposition, NewDescomposition, DescompositionParams, CalculationMode},
+    descomposition::{
+        Descomposition,
+        DescompositionError,
+        NewDescomposition,
+        DescompositionParams,
+        CalculationMode,
+        Violation,
+    },
     price::{Price, NewPrice, PriceType},
     project::{Project, NewProject},
     unit::{Unit, NewUnit},
     version::{Version, NewVersion},
+    AppState,
 };
 use sqlx::PgPool;
 use uuid::Uuid;
-use serde_json::json;
+use serde_json::{json, Value};
+use tower::ServiceExt;
 use sqlx::types::BigDecimal;
 use rand::Rng;
 use num_traits::cast::FromPrimitive;
@@ -29,6 +44,7 @@ async fn setup() -> (PgPool, Price, Price) {
     let new_project = NewProject {
         code: format!("P-TEST-{}", Uuid::new_v4()),
         title: Some("Test Project".to_string()),
+        member_scope: None,
     };
     let _project = Project::create(&pool, new_project).await.unwrap();
 
@@ -51,7 +67,7 @@ async fn setup() -> (PgPool, Price, Price) {
         description: "Parent Price".to_string(),
         base_price: BigDecimal::from_f64(rng.gen_range(100.0..200.0)).unwrap(),
         unit_id: unit.id,
-        price_type: PriceType::Base,
+        price_type: PriceType::Decomposed,
     };
     let parent_price = Price::create(&pool, new_parent_price).await.unwrap();
 
@@ -187,3 +203,158 @@ async fn test_list_descompositions() {
     let descompositions = Descomposition::read_paged(&pool, &params).await.unwrap();
     assert!(descompositions.len() >= 2);
 }
+
+async fn create_price(pool: &PgPool, template: &Price, version_id: i32, price_type: PriceType) -> Price {
+    let new_price = NewPrice {
+        version_id,
+        code: format!("PRICE-{}", Uuid::new_v4().to_string().chars().take(30).collect::<String>()),
+        description: "Graph Price".to_string(),
+        base_price: BigDecimal::from(1),
+        unit_id: template.unit_id,
+        price_type,
+    };
+    Price::create(pool, new_price).await.unwrap()
+}
+
+fn fixed(parent: &Price, component: &Price) -> NewDescomposition {
+    NewDescomposition {
+        parent_price_id: parent.id,
+        component_price_id: component.id,
+        calculation_mode: CalculationMode::Fixed,
+        fixed_quantity: Some(BigDecimal::from(1)),
+        params_json: None,
+    }
+}
+
+fn violation(error: DescompositionError) -> (Violation, Vec<i32>) {
+    match error {
+        DescompositionError::Invalid { violation, path } => (violation, path.iter().map(|p| p.id).collect()),
+        DescompositionError::Database(e) => panic!("Unexpected database error: {}", e),
+    }
+}
+
+#[tokio::test]
+async fn test_reject_invalid_descompositions() {
+    let (pool, parent_price, component_price) = setup().await;
+
+    let error = Descomposition::create(&pool, fixed(&parent_price, &parent_price)).await.unwrap_err();
+    assert_eq!(violation(error), (Violation::SelfReference, vec![parent_price.id, parent_price.id]));
+
+    let error = Descomposition::create(&pool, fixed(&component_price, &parent_price)).await.unwrap_err();
+    assert_eq!(violation(error), (Violation::BaseParent, vec![component_price.id, parent_price.id]));
+
+    let other_version = Version::create(&pool, NewVersion { name: format!("V-TEST-{}", Uuid::new_v4()) })
+        .await
+        .unwrap();
+    let foreign = create_price(&pool, &parent_price, other_version.id, PriceType::Base).await;
+    let error = Descomposition::create(&pool, fixed(&parent_price, &foreign)).await.unwrap_err();
+    assert_eq!(violation(error), (Violation::CrossVersion, vec![parent_price.id, foreign.id]));
+
+    let error = Descomposition::create(&pool, NewDescomposition { component_price_id: 0, ..fixed(&parent_price, &foreign) })
+        .await
+        .unwrap_err();
+    assert_eq!(violation(error), (Violation::MissingPrice, vec![0]));
+}
+
+#[tokio::test]
+async fn test_reject_cycles() {
+    let (pool, parent_price, component_price) = setup().await;
+    // parent -> middle -> leaf
+    let middle = create_price(&pool, &parent_price, parent_price.version_id, PriceType::Decomposed).await;
+    let leaf = create_price(&pool, &parent_price, parent_price.version_id, PriceType::Decomposed).await;
+    Descomposition::create(&pool, fixed(&parent_price, &middle)).await.unwrap();
+    let edge = Descomposition::create(&pool, fixed(&middle, &leaf)).await.unwrap();
+
+    let error = Descomposition::create(&pool, fixed(&leaf, &parent_price)).await.unwrap_err();
+    assert_eq!(error.to_string().split(": ").next(), Some("The descomposition would create a cycle"));
+    assert_eq!(violation(error), (Violation::Cycle, vec![leaf.id, parent_price.id, middle.id, leaf.id]));
+
+    // Al actualizar no se tiene en cuenta la arista que se sustituye
+    let mut moved = edge;
+    moved.component_price_id = component_price.id;
+    let moved = Descomposition::update(&pool, moved).await.unwrap();
+    Descomposition::create(&pool, fixed(&leaf, &parent_price)).await.unwrap();
+
+    // middle -> leaf -> parent -> middle
+    let mut back = moved;
+    back.component_price_id = leaf.id;
+    let error = Descomposition::update(&pool, back).await.unwrap_err();
+    assert_eq!(violation(error).0, Violation::Cycle);
+}
+
+#[tokio::test]
+async fn test_reject_concurrent_cycles() {
+    let (pool, parent_price, _component_price) = setup().await;
+    let a = create_price(&pool, &parent_price, parent_price.version_id, PriceType::Decomposed).await;
+    let b = create_price(&pool, &parent_price, parent_price.version_id, PriceType::Decomposed).await;
+
+    // A -> B sin confirmar: B -> A, desde otra conexión, espera al bloqueo
+    // del grafo de la versión
+    let mut tx = pool.begin().await.unwrap();
+    Descomposition::create_in(&mut tx, fixed(&a, &b)).await.unwrap();
+    let pending = tokio::spawn({
+        let (pool, item) = (common::setup_pool().await, fixed(&b, &a));
+        async move { Descomposition::create(&pool, item).await }
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert!(!pending.is_finished());
+    tx.commit().await.unwrap();
+    let error = pending.await.unwrap().unwrap_err();
+    assert_eq!(violation(error).0, Violation::Cycle);
+}
+
+#[tokio::test]
+async fn test_price_update_with_descompositions() {
+    let (pool, parent_price, component_price) = setup().await;
+    Descomposition::create(&pool, fixed(&parent_price, &component_price)).await.unwrap();
+    let other_version = Version::create(&pool, NewVersion { name: format!("V-TEST-{}", Uuid::new_v4()) })
+        .await
+        .unwrap();
+
+    let moved = Price { version_id: other_version.id, ..component_price };
+    let error = Price::update(&pool, moved).await.unwrap_err();
+    assert!(matches!(error, sqlx::Error::InvalidArgument(message) if message.contains("cannot change its version")));
+
+    let base = Price { price_type: PriceType::Base, ..parent_price };
+    let error = Price::update(&pool, base).await.unwrap_err();
+    assert!(matches!(error, sqlx::Error::InvalidArgument(message) if message.contains("cannot become a base price")));
+
+    // Sin componentes sí puede pasar a ser base
+    let (_, leaf, _) = setup().await;
+    let base = Price { price_type: PriceType::Base, ..leaf };
+    assert_eq!(Price::update(&pool, base).await.unwrap().price_type, PriceType::Base);
+}
+
+#[tokio::test]
+async fn test_create_descomposition_endpoint_unprocessable() {
+    let (pool, parent_price, _component_price) = setup().await;
+    let app = Descomposition::router().with_state(Arc::new(AppState {
+        pool,
+        secret: "test_secret".to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+    let payload = json!({
+        "parent_price_id": parent_price.id,
+        "component_price_id": parent_price.id,
+        "calculation_mode": "fixed",
+        "fixed_quantity": "1",
+        "params_json": null,
+    });
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/")
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(payload.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = body::to_bytes(response.into_body(), 1024 * 1024).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["data"]["violation"], "self_reference");
+    assert_eq!(body["data"]["path"][0]["code"], parent_price.code);
+}
@@ -1,7 +1,7 @@
 use backend::models::{
     element::{Element, NewElement, ElementParams, ElementType},
     project::{Project, NewProject},
-    budget::{Budget, NewBudget},
+    budget::{Budget, NewBudget, BudgetRates},
     version::{Version, NewVersion},
 };
 use sqlx::PgPool;
@@ -18,6 +18,7 @@ async fn setup() -> (PgPool, Budget, Version) {
     let new_project = NewProject {
         code: format!("P-ELEM-{}", Uuid::new_v4().to_string().chars().take(10).collect::<String>()),
         title: Some("Element Test Project".to_string()),
+        member_scope: None,
     };
     let project = Project::create(&pool, new_project).await.unwrap();
 
@@ -28,6 +29,7 @@ async fn setup() -> (PgPool, Budget, Version) {
         version_number: 1,
         name: "Element Test Budget".to_string(),
         status: backend::models::budget::BudgetStatus::Draft,
+        rates: BudgetRates::default(),
     };
     let budget = Budget::create(&pool, new_budget).await.unwrap();
 
@@ -164,6 +166,7 @@ async fn test_list_elements() {
 
     let params = ElementParams {
         id: None,
+        member_scope: None,
         parent_id: None,
         version_id: Some(version.id),
         element_type: None,
@@ -7,6 +7,7 @@ use axum::{
 };
 use tower::ServiceExt;
 use std::sync::Arc;
+use backend::mailer::LogMailer;
 use backend::models::{AppState, ApiResponse};
 use backend::http::fallback_404;
 
@@ -17,6 +18,7 @@ async fn test_fallback_404() {
         pool,
         secret: "secret".to_string(),
         static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
     });
 
     let app = Router::new()
@@ -5,6 +5,7 @@ use axum::{
 };
 use tower::ServiceExt;
 use std::sync::Arc;
+use backend::mailer::LogMailer;
 use backend::models::{AppState, ApiResponse, Data};
 use backend::http::health;
 
@@ -15,6 +16,7 @@ async fn test_check_health() {
         pool,
         secret: "secret".to_string(),
         static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
     });
 
     let app = health::router().with_state(app_state);
@@ -0,0 +1,169 @@
+use std::{net::SocketAddr, sync::Arc};
+use axum::{
+    extract::ConnectInfo,
+    http::{header, StatusCode},
+    Router,
+};
+use backend::mailer::LogMailer;
+use backend::{
+    http,
+    models::{
+        audit_entry::{AuditAction, AuditEntry},
+        login_throttle::{LoginScope, LoginThrottle},
+        AppState,
+    },
+};
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[path = "common.rs"]
+mod common;
+
+use common::{create_user, login_request, request, send, send_with_headers, PASSWORD};
+
+async fn setup() -> (PgPool, Router) {
+    let _ = &common::TRACING;
+    let pool = common::setup_pool().await;
+    let app = http::api_router(Arc::new(AppState {
+        pool: pool.clone(),
+        secret: "test_secret".to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+    (pool, app)
+}
+
+/// Login desde `addr`, si se indica, como si llegara por la red. Devuelve
+/// también la cabecera `Retry-After`.
+async fn login_from(app: &Router, email: &str, password: &str, addr: Option<SocketAddr>) -> (StatusCode, Option<String>, Value) {
+    let mut request = login_request(email, password);
+    if let Some(addr) = addr {
+        request.extensions_mut().insert(ConnectInfo(addr));
+    }
+    let (status, headers, body) = send_with_headers(app, request).await;
+    let retry_after = headers.get(header::RETRY_AFTER).map(|value| value.to_str().unwrap().to_string());
+    (status, retry_after, body)
+}
+
+/// Dirección de pruebas distinta en cada test.
+fn random_addr() -> SocketAddr {
+    let bytes = Uuid::new_v4().into_bytes();
+    SocketAddr::from(([10, bytes[0], bytes[1], bytes[2]], 40000))
+}
+
+#[tokio::test]
+async fn test_account_lockout_and_unlock() {
+    let (pool, app) = setup().await;
+    let user = create_user(&pool, "reader", true).await;
+    for _ in 0..LoginScope::Account.max_failures() {
+        let (status, _, _) = login_from(&app, &user.email, "wrong-password1", None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    // Bloqueada incluso con la contraseña correcta, y sin distinguir mayúsculas
+    let (status, retry_after, body) = login_from(&app, &user.email.to_uppercase(), PASSWORD, None).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["message"], "Too many failed login attempts, try again later");
+    let retry_after: i64 = retry_after.unwrap().parse().unwrap();
+    assert!(retry_after > 0 && retry_after <= LoginThrottle::BASE_LOCKOUT_SECONDS);
+
+    let entries = AuditEntry::read_by_user(&pool, user.id).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].action, AuditAction::AccountLocked);
+    assert!(entries[0].actor_id.is_none());
+
+    // Solo un administrador puede desbloquear
+    let admin = create_user(&pool, "admin", true).await;
+    let (_, _, body) = login_from(&app, &admin.email, PASSWORD, None).await;
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+    let reader = create_user(&pool, "reader", true).await;
+    let (_, _, body) = login_from(&app, &reader.email, PASSWORD, None).await;
+    let reader_token = body["data"]["token"].as_str().unwrap().to_string();
+    let unlock = |token: &str| {
+        request("POST", &format!("/users/{}/unlock", user.id), Some(token), Value::Null)
+    };
+    let (status, _) = send(&app, unlock(&reader_token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = send(&app, unlock(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["was_locked"], true);
+
+    let (status, _, _) = login_from(&app, &user.email, PASSWORD, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = format!("/users/{}/audit", user.id);
+    let (status, body) = send(&app, request("GET", &uri, Some(&token), Value::Null)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"][0]["action"], "account_unlocked");
+    assert_eq!(body["data"][0]["actor_id"], admin.id);
+    assert_eq!(body["data"][1]["action"], "account_locked");
+}
+
+#[tokio::test]
+async fn test_exponential_backoff() {
+    let (pool, app) = setup().await;
+    let user = create_user(&pool, "reader", true).await;
+    let key = user.email.to_lowercase();
+    for _ in 0..LoginScope::Account.max_failures() {
+        login_from(&app, &user.email, "wrong-password1", None).await;
+    }
+
+    // Al expirar el bloqueo, un nuevo fallo bloquea el doble de tiempo
+    sqlx::query("UPDATE login_throttles SET locked_until = NOW() - INTERVAL '1 second' WHERE scope = 'account' AND key = $1")
+        .bind(&key)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let (status, _, _) = login_from(&app, &user.email, "wrong-password1", None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let locked_until = LoginThrottle::locked_until(&pool, LoginScope::Account, &key).await.unwrap().unwrap();
+    let seconds = (locked_until - chrono::Utc::now()).num_seconds();
+    assert!(seconds > LoginThrottle::BASE_LOCKOUT_SECONDS && seconds <= 2 * LoginThrottle::BASE_LOCKOUT_SECONDS);
+    assert_eq!(AuditEntry::read_by_user(&pool, user.id).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_successful_login_resets_failures() {
+    let (pool, app) = setup().await;
+    let user = create_user(&pool, "reader", true).await;
+    let below_limit = LoginScope::Account.max_failures() - 1;
+    for _ in 0..below_limit {
+        login_from(&app, &user.email, "wrong-password1", None).await;
+    }
+    let (status, _, _) = login_from(&app, &user.email, PASSWORD, None).await;
+    assert_eq!(status, StatusCode::OK);
+    for _ in 0..below_limit {
+        let (status, _, _) = login_from(&app, &user.email, "wrong-password1", None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+    let (status, _, _) = login_from(&app, &user.email, PASSWORD, None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_ip_lockout() {
+    let (pool, app) = setup().await;
+    let user = create_user(&pool, "reader", true).await;
+    let addr = random_addr();
+    // Fallos contra cuentas distintas desde la misma IP
+    for _ in 0..LoginScope::Ip.max_failures() {
+        let email = format!("{}@test.com", Uuid::new_v4());
+        let (status, _, _) = login_from(&app, &email, "wrong-password1", Some(addr)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+    let (status, _, _) = login_from(&app, &user.email, PASSWORD, Some(addr)).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    // Desde otra IP la cuenta no está bloqueada
+    let (status, _, _) = login_from(&app, &user.email, PASSWORD, Some(random_addr())).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let ip = addr.ip().to_string();
+    let locks: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM audit_entries WHERE action = 'ip_locked' AND ip = $1")
+        .bind(&ip)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(locks, 1);
+    assert!(LoginThrottle::clear(&pool, LoginScope::Ip, &ip).await.unwrap());
+}
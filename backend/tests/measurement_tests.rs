@@ -1,7 +1,7 @@
 use backend::models::{
     measurement::{Measurement, NewMeasurement, MeasurementParams},
     element::{Element, NewElement, ElementType},
-    budget::{Budget, NewBudget, BudgetStatus},
+    budget::{Budget, NewBudget, BudgetRates, BudgetStatus},
     project::{Project, NewProject},
     version::{Version, NewVersion},
     price::{Price, NewPrice, PriceType},
@@ -24,6 +24,7 @@ async fn setup() -> (PgPool, Budget, Version, Element, Price, Unit) {
     let new_project = NewProject {
         code: format!("P-MEAS-{}", Uuid::new_v4().to_string().chars().take(10).collect::<String>()),
         title: Some("Measurement Test Project".to_string()),
+        member_scope: None,
     };
     let project = Project::create(&pool, new_project).await.unwrap();
 
@@ -34,6 +35,7 @@ async fn setup() -> (PgPool, Budget, Version, Element, Price, Unit) {
         version_number: 1,
         name: "Measurement Test Budget".to_string(),
         status: BudgetStatus::Draft,
+        rates: BudgetRates::default(),
     };
     let budget = Budget::create(&pool, new_budget).await.unwrap();
 
@@ -87,7 +89,7 @@ async fn test_create_measurement() {
     let new_measurement = NewMeasurement {
         element_id: element.id,
         price_id: price.id,
-        params_json: json!({"length": 10.0, "width": 2.0}),
+        params_json: json!({"a": 5.0, "b": 2.0}),
         measurement_text: Some("Test measurement 1".to_string()),
         measured_quantity: BigDecimal::from_f64(10.0).unwrap(),
     };
@@ -103,7 +105,7 @@ async fn test_read_measurement() {
     let new_measurement = NewMeasurement {
         element_id: element.id,
         price_id: price.id,
-        params_json: json!({"length": 10.0, "width": 2.0}),
+        params_json: json!({"a": 10.0, "b": 2.0}),
         measurement_text: Some("Test measurement 2".to_string()),
         measured_quantity: BigDecimal::from_f64(20.0).unwrap(),
     };
@@ -121,12 +123,12 @@ async fn test_update_measurement() {
     let new_measurement = NewMeasurement {
         element_id: element.id,
         price_id: price.id,
-        params_json: json!({"length": 10.0, "width": 2.0}),
+        params_json: json!({"a": 15.0, "b": 2.0}),
         measurement_text: Some("Test measurement 3".to_string()),
         measured_quantity: BigDecimal::from_f64(30.0).unwrap(),
     };
     let mut measurement = Measurement::create(&pool, new_measurement).await.unwrap();
-    measurement.measured_quantity = BigDecimal::from_f64(35.0).unwrap();
+    measurement.params_json = json!({"a": 17.5, "b": 2.0});
     let updated_measurement = Measurement::update(&pool, measurement).await.unwrap();
     assert_eq!(updated_measurement.measured_quantity, BigDecimal::from_f64(35.0).unwrap());
 }
@@ -137,7 +139,7 @@ async fn test_delete_measurement() {
     let new_measurement = NewMeasurement {
         element_id: element.id,
         price_id: price.id,
-        params_json: json!({"length": 10.0, "width": 2.0}),
+        params_json: json!({"a": 20.0, "b": 2.0}),
         measurement_text: Some("Test measurement 4".to_string()),
         measured_quantity: BigDecimal::from_f64(40.0).unwrap(),
     };
@@ -154,7 +156,7 @@ async fn test_list_measurements() {
     let new_measurement1 = NewMeasurement {
         element_id: element.id,
         price_id: price.id,
-        params_json: json!({"length": 10.0, "width": 2.0}),
+        params_json: json!({"a": 25.0, "b": 2.0}),
         measurement_text: Some("Test measurement 5".to_string()),
         measured_quantity: BigDecimal::from_f64(50.0).unwrap(),
     };
@@ -163,7 +165,7 @@ async fn test_list_measurements() {
     let new_measurement2 = NewMeasurement {
         element_id: element.id,
         price_id: price.id,
-        params_json: json!({"length": 5.0, "width": 5.0}),
+        params_json: json!({"a": 30.0, "b": 2.0}),
         measurement_text: Some("Test measurement 6".to_string()),
         measured_quantity: BigDecimal::from_f64(60.0).unwrap(),
     };
@@ -171,6 +173,7 @@ async fn test_list_measurements() {
 
     let params = MeasurementParams {
         id: None,
+        member_scope: None,
         measurement_text: None,
         measured_quantity: None,
         page: None,
@@ -181,3 +184,61 @@ async fn test_list_measurements() {
     let measurements = Measurement::read_paged(&pool, &params).await.unwrap();
     assert!(measurements.len() >= 2);
 }
+
+#[tokio::test]
+async fn test_measurement_quantity_from_formula() {
+    let (pool, _budget, _version, element, price, _unit) = setup().await;
+    let new_measurement = NewMeasurement {
+        element_id: element.id,
+        price_id: price.id,
+        params_json: json!({"a": "3.25", "b": 1.5}),
+        measurement_text: Some("Test measurement 7".to_string()),
+        // Lo que envíe el cliente se ignora
+        measured_quantity: BigDecimal::from_f64(999.0).unwrap(),
+    };
+    let measurement = Measurement::create(&pool, new_measurement).await.unwrap();
+    assert_eq!(measurement.measured_quantity, BigDecimal::from_f64(4.875).unwrap());
+}
+
+#[tokio::test]
+async fn test_measurement_params_mismatch() {
+    let (pool, _budget, _version, element, price, unit) = setup().await;
+    let new_measurement = |params_json| NewMeasurement {
+        element_id: element.id,
+        price_id: price.id,
+        params_json,
+        measurement_text: None,
+        measured_quantity: BigDecimal::from_f64(0.0).unwrap(),
+    };
+
+    let error = Measurement::create(&pool, new_measurement(json!({"a": 1.0}))).await.unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!("Invalid params for unit '{}' (a * b): Missing value for variable 'b'", unit.symbol)
+    );
+    let error = Measurement::create(&pool, new_measurement(json!({"a": 1.0, "b": 2.0, "c": 3.0})))
+        .await
+        .unwrap_err();
+    assert_eq!(error.to_string(), format!("Unexpected params for unit '{}' (a * b): c", unit.symbol));
+    let error = Measurement::create(&pool, new_measurement(json!({"a": 1.0, "b": "ancho"})))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!("Invalid params for unit '{}' (a * b): The value of 'b' is not a number", unit.symbol)
+    );
+    assert!(Measurement::create(&pool, new_measurement(json!([1, 2]))).await.is_err());
+    // NUMERIC(10, 4) no admite cantidades de un millón o más
+    let error = Measurement::create(&pool, new_measurement(json!({"a": 1000, "b": 1000})))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, sqlx::Error::InvalidArgument(_)));
+    assert_eq!(error.to_string(), format!("Quantity 1000000.0000 is out of range for unit '{}' (a * b)", unit.symbol));
+    assert!(Measurement::create(&pool, new_measurement(json!({"a": 1000, "b": "999.9999"}))).await.is_ok());
+
+    let mut measurement = Measurement::create(&pool, new_measurement(json!({"a": 1.0, "b": 2.0})))
+        .await
+        .unwrap();
+    measurement.params_json = json!({"length": 1.0});
+    assert!(Measurement::update(&pool, measurement).await.is_err());
+}
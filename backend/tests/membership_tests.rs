@@ -0,0 +1,333 @@
+use std::sync::Arc;
+use axum::http::StatusCode;
+use backend::mailer::LogMailer;
+use backend::{
+    http,
+    models::{
+        budget::{Budget, BudgetRates, BudgetStatus, NewBudget},
+        element::{Element, ElementType, NewElement},
+        measurement::{Measurement, MeasurementParams, NewMeasurement},
+        price::{NewPrice, Price, PriceType},
+        project::{NewProject, Project},
+        project_member::{MemberScope, NewProjectMember, ProjectMember},
+        role::Role,
+        session::Session,
+        unit::{NewUnit, Unit},
+        user::User,
+        version::{NewVersion, Version},
+        AppState,
+        TokenClaims,
+    },
+};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use serde_json::{json, Value};
+use sqlx::{types::BigDecimal, PgPool};
+use uuid::Uuid;
+
+#[path = "common.rs"]
+mod common;
+
+use common::{create_user, send_json};
+
+const SECRET: &str = "test_secret";
+
+/// Presupuesto de un proyecto con un capítulo medido.
+struct Fixture {
+    project: Project,
+    budget: Budget,
+    element: Element,
+    measurement: Measurement,
+}
+
+/// Usuario con el rol indicado y un token de una sesión suya.
+async fn user_with_token(pool: &PgPool, role: &str) -> (User, String) {
+    let user = create_user(pool, role, true).await;
+    let (session, _) = Session::create(pool, user.id, None).await.unwrap();
+    let now = chrono::Utc::now().timestamp();
+    let claims = TokenClaims {
+        sub: user.email.clone(),
+        role: role.to_string(),
+        sid: session.id,
+        iat: now as usize,
+        exp: (now + 3600) as usize,
+    };
+    let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(SECRET.as_bytes())).unwrap();
+    (user, token)
+}
+
+async fn create_fixture(pool: &PgPool, p: &str, name: &str) -> Fixture {
+    let project = Project::create(pool, NewProject {
+        code: format!("{}{}", p, name),
+        title: Some(name.to_string()),
+        member_scope: None,
+    })
+    .await
+    .unwrap();
+    let budget = Budget::create(pool, NewBudget {
+        project_id: project.id,
+        code: format!("{}{}", p, name),
+        version_number: 1,
+        name: name.to_string(),
+        status: BudgetStatus::Draft,
+        rates: BudgetRates::default(),
+    })
+    .await
+    .unwrap();
+    let version = Version::create(pool, NewVersion { name: format!("V-{}{}", p, name) }).await.unwrap();
+    let unit = Unit::create(pool, NewUnit {
+        name: format!("{}{}", p, name),
+        symbol: "u".to_string(),
+        description: None,
+        formula: "a".to_string(),
+    })
+    .await
+    .unwrap();
+    let price = Price::create(pool, NewPrice {
+        version_id: version.id,
+        code: format!("{}{}", p, name),
+        description: name.to_string(),
+        base_price: BigDecimal::from(10),
+        unit_id: unit.id,
+        price_type: PriceType::Base,
+    })
+    .await
+    .unwrap();
+    let element = Element::create(pool, NewElement {
+        budget_id: budget.id,
+        parent_id: None,
+        version_id: version.id,
+        element_type: ElementType::Chapter,
+        code: format!("{}{}", p, name),
+        budget_code: "01".to_string(),
+        description: None,
+    })
+    .await
+    .unwrap();
+    let measurement = Measurement::create(pool, NewMeasurement {
+        element_id: element.id,
+        price_id: price.id,
+        params_json: json!({"a": 2}),
+        measurement_text: Some(format!("{}{}", p, name)),
+        measured_quantity: BigDecimal::from(0),
+    })
+    .await
+    .unwrap();
+    Fixture { project, budget, element, measurement }
+}
+
+fn ids(body: &Value) -> Vec<i64> {
+    body["data"].as_array().unwrap().iter().map(|item| item["id"].as_i64().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn test_reads_scoped_to_memberships() {
+    let _ = &common::TRACING;
+    let pool = common::setup_pool().await;
+    let p = format!("{}-", Uuid::new_v4().to_string().chars().take(8).collect::<String>());
+    let app = http::api_router(Arc::new(AppState {
+        pool: pool.clone(),
+        secret: SECRET.to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+    let invited = create_fixture(&pool, &p, "A").await;
+    let other = create_fixture(&pool, &p, "B").await;
+
+    let (member, member_token) = user_with_token(&pool, "reader").await;
+    let (_, admin_token) = user_with_token(&pool, "admin").await;
+    let role = Role::read_by_name(&pool, "reader").await.unwrap().unwrap();
+    ProjectMember::create(&pool, NewProjectMember {
+        project_id: invited.project.id,
+        user_id: member.id,
+        role_id: role.id,
+    })
+    .await
+    .unwrap();
+
+    // Listados paginados
+    for (resource, invited_id, other_id) in [
+        ("projects", invited.project.id, other.project.id),
+        ("budgets", invited.budget.id, other.budget.id),
+        ("elements", invited.element.id, other.element.id),
+    ] {
+        let uri = format!("/{}?page=1&limit=100&code={}", resource, p);
+        let (status, body) = send_json(&app, "GET", &uri, Some(&member_token), Value::Null).await;
+        assert_eq!(status, StatusCode::OK, "{}", resource);
+        assert_eq!(ids(&body), vec![invited_id as i64], "{}", resource);
+
+        let (_, body) = send_json(&app, "GET", &uri, Some(&admin_token), Value::Null).await;
+        let mut all = ids(&body);
+        all.sort();
+        assert_eq!(all, vec![invited_id as i64, other_id as i64], "{}", resource);
+
+        // Lectura por ID
+        let (status, _) = send_json(&app, "GET", &format!("/{}?id={}", resource, invited_id), Some(&member_token), Value::Null).await;
+        assert_eq!(status, StatusCode::OK, "{}", resource);
+        let (status, _) = send_json(&app, "GET", &format!("/{}?id={}", resource, other_id), Some(&member_token), Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{}", resource);
+        let (status, _) = send_json(&app, "GET", &format!("/{}?id={}", resource, other_id), Some(&admin_token), Value::Null).await;
+        assert_eq!(status, StatusCode::OK, "{}", resource);
+    }
+
+    // Listado completo (sin paginar)
+    let (status, body) = send_json(&app, "GET", "/measurements", Some(&member_token), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let visible = ids(&body);
+    assert!(visible.contains(&(invited.measurement.id as i64)));
+    assert!(!visible.contains(&(other.measurement.id as i64)));
+
+    let params = MeasurementParams {
+        id: None,
+        member_scope: Some(MemberScope::member(member.id)),
+        measurement_text: Some(p.clone()),
+        measured_quantity: None,
+        page: Some(1),
+        limit: Some(100),
+        sort_by: None,
+        asc: None,
+    };
+    let measurements = Measurement::read_paged(&pool, &params).await.unwrap();
+    assert_eq!(measurements.iter().map(|m| m.id).collect::<Vec<_>>(), vec![invited.measurement.id]);
+    assert_eq!(Measurement::count_paged(&pool, &params).await.unwrap(), 1);
+
+    // Rutas propias de un presupuesto
+    let (status, _) = send_json(&app, "GET", &format!("/budgets/{}/summary", invited.budget.id), Some(&member_token), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send_json(&app, "GET", &format!("/budgets/{}/summary", other.budget.id), Some(&member_token), Value::Null).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["message"], "Budget not found");
+    let (status, _) = send_json(&app, "GET", &format!("/budgets/{}/summary", other.budget.id), Some(&admin_token), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let uri = format!("/budgets/compare?from={}&to={}", invited.budget.id, other.budget.id);
+    let (status, _) = send_json(&app, "GET", &uri, Some(&member_token), Value::Null).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = send_json(&app, "GET", &uri, Some(&admin_token), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Solo los administradores gestionan los miembros
+    let uri = format!("/project-members?page=1&project_id={}", invited.project.id);
+    let (status, _) = send_json(&app, "GET", &uri, Some(&member_token), Value::Null).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = send_json(&app, "GET", &uri, Some(&admin_token), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"][0]["user_id"], member.id);
+    assert!(ProjectMember::is_member(&pool, invited.project.id, member.id).await.unwrap());
+    assert!(!ProjectMember::is_member(&pool, other.project.id, member.id).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_writes_scoped_to_memberships() {
+    let _ = &common::TRACING;
+    let pool = common::setup_pool().await;
+    let p = format!("{}-", Uuid::new_v4().to_string().chars().take(8).collect::<String>());
+    let app = http::api_router(Arc::new(AppState {
+        pool: pool.clone(),
+        secret: SECRET.to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+    let invited = create_fixture(&pool, &p, "A").await;
+    let read_only = create_fixture(&pool, &p, "B").await;
+    let other = create_fixture(&pool, &p, "C").await;
+
+    // Escritor en A; en B su rol en el proyecto es de lector
+    let (member, member_token) = user_with_token(&pool, "writer").await;
+    let (_, admin_token) = user_with_token(&pool, "admin").await;
+    for (project, role) in [(&invited.project, "writer"), (&read_only.project, "reader")] {
+        let role = Role::read_by_name(&pool, role).await.unwrap().unwrap();
+        ProjectMember::create(&pool, NewProjectMember { project_id: project.id, user_id: member.id, role_id: role.id })
+            .await
+            .unwrap();
+    }
+
+    // Crear un elemento en un presupuesto
+    let element = |budget: &Budget, code: &str| json!({
+        "budget_id": budget.id,
+        "parent_id": null,
+        "version_id": invited.element.version_id,
+        "element_type": serde_json::to_value(invited.element.element_type).unwrap(),
+        "code": format!("{}{}", p, code),
+        "budget_code": "02",
+        "description": null,
+    });
+    let (status, body) = send_json(&app, "POST", "/elements", Some(&member_token), element(&invited.budget, "A2")).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    for fixture in [&read_only, &other] {
+        let (status, _) = send_json(&app, "POST", "/elements", Some(&member_token), element(&fixture.budget, "X2")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    // Modificar un presupuesto, también para llevarlo a otro proyecto
+    let budget = |budget: &Budget| serde_json::to_value(budget).unwrap();
+    let (status, body) = send_json(&app, "PATCH", "/budgets", Some(&member_token), budget(&invited.budget)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    for fixture in [&read_only, &other] {
+        let (status, _) = send_json(&app, "PATCH", "/budgets", Some(&member_token), budget(&fixture.budget)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+    let mut moved = budget(&invited.budget);
+    moved["project_id"] = json!(other.project.id);
+    let (status, _) = send_json(&app, "PATCH", "/budgets", Some(&member_token), moved).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Borrar mediciones
+    for fixture in [&read_only, &other] {
+        let uri = format!("/measurements?id={}", fixture.measurement.id);
+        let (status, _) = send_json(&app, "DELETE", &uri, Some(&member_token), Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+    let uri = format!("/measurements?id={}", invited.measurement.id);
+    let (status, _) = send_json(&app, "DELETE", &uri, Some(&member_token), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let uri = format!("/measurements?id={}", other.measurement.id);
+    let (status, _) = send_json(&app, "DELETE", &uri, Some(&admin_token), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Rutas propias de un presupuesto: con rol de lector en el proyecto solo lee
+    let (status, _) = send_json(&app, "GET", &format!("/budgets/{}/summary", read_only.budget.id), Some(&member_token), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let uri = format!("/budgets/{}/submit", read_only.budget.id);
+    let (status, body) = send_json(&app, "POST", &uri, Some(&member_token), json!({})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["message"], "Budget not found");
+    let uri = format!("/budgets/{}/submit", invited.budget.id);
+    let (status, body) = send_json(&app, "POST", &uri, Some(&member_token), json!({})).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[tokio::test]
+async fn test_project_creator_becomes_master() {
+    let _ = &common::TRACING;
+    let pool = common::setup_pool().await;
+    let app = http::api_router(Arc::new(AppState {
+        pool: pool.clone(),
+        secret: SECRET.to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+    let (writer, writer_token) = user_with_token(&pool, "writer").await;
+    let (admin, admin_token) = user_with_token(&pool, "admin").await;
+
+    // Quien crea el proyecto entra como jefe y lo ve
+    let project = json!({"code": format!("P-MEMBER-{}", Uuid::new_v4()), "title": "Own"});
+    let (status, body) = send_json(&app, "POST", "/projects", Some(&writer_token), project).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let project_id = body["data"]["id"].as_i64().unwrap() as i32;
+    let (status, _) = send_json(&app, "GET", &format!("/projects?id={}", project_id), Some(&writer_token), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let roles: Vec<(i32, String)> = sqlx::query_as(
+        "SELECT m.user_id, r.name FROM project_members m JOIN roles r ON r.id = m.role_id WHERE m.project_id = $1",
+    )
+    .bind(project_id)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(roles, vec![(writer.id, "master".to_string())]);
+
+    // Los administradores ven todos los proyectos: no hace falta
+    let project = json!({"code": format!("P-MEMBER-{}", Uuid::new_v4()), "title": "Admin"});
+    let (status, body) = send_json(&app, "POST", "/projects", Some(&admin_token), project).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let project_id = body["data"]["id"].as_i64().unwrap() as i32;
+    assert!(!ProjectMember::is_member(&pool, project_id, admin.id).await.unwrap());
+}
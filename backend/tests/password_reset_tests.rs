@@ -0,0 +1,165 @@
+use std::{fs, path::PathBuf, sync::Arc};
+use axum::{http::StatusCode, Router};
+use backend::mailer::FileMailer;
+use backend::{
+    http,
+    models::{
+        session::Session,
+        user::User,
+        user_token::{TokenPurpose, UserToken},
+        AppState,
+    },
+};
+use regex::Regex;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[path = "common.rs"]
+mod common;
+
+use common::{create_user, login, send_json, PASSWORD};
+
+const NEW_PASSWORD: &str = "new-password2";
+
+/// Cada test deja los correos en su propio directorio.
+async fn setup() -> (PgPool, Router, User, PathBuf) {
+    let _ = &common::TRACING;
+    let pool = common::setup_pool().await;
+    let mail_dir = std::env::temp_dir().join(format!("presu-mail-{}", Uuid::new_v4()));
+    let app = http::api_router(Arc::new(AppState {
+        pool: pool.clone(),
+        secret: "test_secret".to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(FileMailer::new(&mail_dir)),
+    }));
+    let user = create_user(&pool, "reader", true).await;
+    (pool, app, user, mail_dir)
+}
+
+/// Correos enviados, del más antiguo al más reciente.
+fn mails(mail_dir: &PathBuf) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(mail_dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries.map(|entry| entry.unwrap().path()).collect();
+    paths.sort();
+    paths.into_iter().map(|path| fs::read_to_string(path).unwrap()).collect()
+}
+
+/// Espera a que se hayan enviado `count` correos (`forgot` los envía en
+/// segundo plano) y los devuelve.
+async fn wait_for_mails(mail_dir: &PathBuf, count: usize) -> Vec<String> {
+    for _ in 0..50 {
+        let sent = mails(mail_dir);
+        if sent.len() >= count {
+            return sent;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    mails(mail_dir)
+}
+
+/// Token del enlace del último correo enviado.
+fn last_token(mail_dir: &PathBuf, path: &str) -> String {
+    let mail = mails(mail_dir).pop().expect("no mail sent");
+    let regex = Regex::new(&format!(r"/{}\?token=([0-9a-f]{{64}})", path)).unwrap();
+    regex.captures(&mail).expect("no link in mail")[1].to_string()
+}
+
+#[tokio::test]
+async fn test_password_reset() {
+    let (pool, app, user, mail_dir) = setup().await;
+    let (_, body) = login(&app, &user.email, PASSWORD).await;
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let (status, _) = send_json(&app, "POST", "/auth/forgot", None, json!({"email": user.email})).await;
+    assert_eq!(status, StatusCode::OK);
+    let mail = wait_for_mails(&mail_dir, 1).await.pop().unwrap();
+    assert!(mail.starts_with(&format!("To: {}\r\n", user.email)));
+    let reset_token = last_token(&mail_dir, "reset-password");
+
+    // Una contraseña que no cumple la política no gasta el token
+    let (status, body) = send_json(&app, "POST", "/auth/reset", None, json!({"token": reset_token, "password": "short"})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["message"].as_str().unwrap().contains("at least"));
+
+    let (status, _) = send_json(&app, "POST", "/auth/reset", None, json!({"token": reset_token, "password": NEW_PASSWORD})).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // El token es de un solo uso y las sesiones abiertas se cierran
+    let (status, body) = send_json(&app, "POST", "/auth/reset", None, json!({"token": reset_token, "password": PASSWORD})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "Invalid or expired token");
+    let (status, _) = send_json(&app, "GET", "/auth/me", Some(&token), Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(Session::read_active_by_user(&pool, user.id).await.unwrap().is_empty());
+
+    assert_eq!(login(&app, &user.email, PASSWORD).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(login(&app, &user.email, NEW_PASSWORD).await.0, StatusCode::OK);
+    let _ = fs::remove_dir_all(&mail_dir);
+}
+
+#[tokio::test]
+async fn test_forgot_does_not_reveal_emails() {
+    let (_, app, user, mail_dir) = setup().await;
+    let (status, known) = send_json(&app, "POST", "/auth/forgot", None, json!({"email": user.email})).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, unknown) = send_json(&app, "POST", "/auth/forgot", None, json!({"email": "nobody@test.com"})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(known, unknown);
+    assert_eq!(wait_for_mails(&mail_dir, 1).await.len(), 1);
+    let _ = fs::remove_dir_all(&mail_dir);
+}
+
+#[tokio::test]
+async fn test_expired_and_replaced_tokens() {
+    let (pool, app, user, mail_dir) = setup().await;
+    let (_, first) = UserToken::create(&pool, user.id, TokenPurpose::Reset).await.unwrap();
+    let (second_token, second) = UserToken::create(&pool, user.id, TokenPurpose::Reset).await.unwrap();
+
+    // Pedir un token nuevo invalida el anterior
+    let (status, _) = send_json(&app, "POST", "/auth/reset", None, json!({"token": first, "password": NEW_PASSWORD})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Un token de recuperación no sirve para verificar el email
+    let (status, _) = send_json(&app, "POST", "/auth/verify", None, json!({"token": second})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    sqlx::query("UPDATE user_tokens SET expires_at = NOW() - INTERVAL '1 minute' WHERE id = $1")
+        .bind(second_token.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let (status, body) = send_json(&app, "POST", "/auth/reset", None, json!({"token": second, "password": NEW_PASSWORD})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "Invalid or expired token");
+    assert_eq!(login(&app, &user.email, PASSWORD).await.0, StatusCode::OK);
+    let _ = fs::remove_dir_all(&mail_dir);
+}
+
+#[tokio::test]
+async fn test_email_verification() {
+    let (_, app, user, mail_dir) = setup().await;
+    assert!(user.email_verified_at.is_none());
+    let (_, body) = login(&app, &user.email, PASSWORD).await;
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let (status, _) = send_json(&app, "POST", "/auth/verify/request", None, Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = send_json(&app, "POST", "/auth/verify/request", Some(&token), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let verify_token = last_token(&mail_dir, "verify-email");
+
+    let (status, body) = send_json(&app, "POST", "/auth/verify", None, json!({"token": verify_token})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["data"]["email_verified_at"].is_string());
+    let (status, _) = send_json(&app, "POST", "/auth/verify", None, json!({"token": verify_token})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_, body) = send_json(&app, "GET", "/auth/me", Some(&token), Value::Null).await;
+    assert!(body["data"]["email_verified_at"].is_string());
+    let (status, _) = send_json(&app, "POST", "/auth/verify/request", Some(&token), Value::Null).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let _ = fs::remove_dir_all(&mail_dir);
+}
@@ -0,0 +1,169 @@
+use std::{str::FromStr, sync::Arc};
+use axum::{
+    body::{self, Body},
+    http::{Request, StatusCode},
+    Router,
+};
+use backend::mailer::LogMailer;
+use backend::{
+    http,
+    models::{
+        descomposition::{CalculationMode, Descomposition, NewDescomposition},
+        price::{NewPrice, Price, PriceType},
+        unit::{NewUnit, Unit},
+        version::{NewVersion, Version},
+        AppState,
+    },
+    price_history,
+    version_clone::{self, CloneVersion, PriceAdjustment},
+};
+use serde_json::{json, Value};
+use sqlx::{types::BigDecimal, PgPool};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+#[path = "common.rs"]
+mod common;
+
+fn decimal(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).unwrap()
+}
+
+/// Versión `old` con A (10), B (20), C (5), F (1) y D = 2 × A. La versión
+/// `new` es una copia con A un 10 % más caro y B un 50 % más barato, sin F y
+/// con un precio nuevo, E (7).
+struct Fixture {
+    pool: PgPool,
+    old: Version,
+    new: Version,
+    p: String,
+}
+
+async fn setup() -> Fixture {
+    let _ = &common::TRACING;
+    let pool = common::setup_pool().await;
+    let p = format!("{}-", Uuid::new_v4().to_string().chars().take(8).collect::<String>());
+    let old = Version::create(&pool, NewVersion { name: format!("{}2025.Q4", p) }).await.unwrap();
+    let unit = Unit::create(&pool, NewUnit {
+        name: format!("{}ud", p),
+        symbol: "ud".to_string(),
+        description: None,
+        formula: "a".to_string(),
+    })
+    .await
+    .unwrap();
+    let price = |version_id, code: &str, base_price: &str, price_type| NewPrice {
+        version_id,
+        code: format!("{}{}", p, code),
+        description: format!("Price {}", code),
+        base_price: decimal(base_price),
+        unit_id: unit.id,
+        price_type,
+    };
+    let a = Price::create(&pool, price(old.id, "A", "10", PriceType::Base)).await.unwrap();
+    for (code, base_price) in [("B", "20"), ("C", "5"), ("F", "1")] {
+        Price::create(&pool, price(old.id, code, base_price, PriceType::Base)).await.unwrap();
+    }
+    let d = Price::create(&pool, price(old.id, "D", "20", PriceType::Decomposed)).await.unwrap();
+    Descomposition::create(&pool, NewDescomposition {
+        parent_price_id: d.id,
+        component_price_id: a.id,
+        calculation_mode: CalculationMode::Fixed,
+        fixed_quantity: Some(BigDecimal::from(2)),
+        params_json: None,
+    })
+    .await
+    .unwrap();
+
+    let adjustment = |code: &str, percentage: &str| PriceAdjustment {
+        prefix: format!("{}{}", p, code),
+        percentage: decimal(percentage),
+    };
+    let clone = version_clone::clone_version(&pool, old.id, CloneVersion {
+        name: format!("{}2026.Q1", p),
+        percentage: None,
+        adjustments: vec![adjustment("A", "10"), adjustment("B", "-50")],
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    let new = clone.version;
+    let f = Price::read_by_codes(&pool, new.id, &[format!("{}F", p)]).await.unwrap().remove(0);
+    Price::delete(&pool, f.id).await.unwrap();
+    Price::create(&pool, price(new.id, "E", "7", PriceType::Base)).await.unwrap();
+    Fixture { pool, old, new, p }
+}
+
+fn app(pool: &PgPool, router: Router<Arc<AppState>>) -> Router {
+    router.with_state(Arc::new(AppState {
+        pool: pool.clone(),
+        secret: "test_secret".to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }))
+}
+
+async fn get(app: &Router, uri: &str) -> (StatusCode, Value) {
+    let response = app.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap()).await.unwrap();
+    let status = response.status();
+    let body = body::to_bytes(response.into_body(), 1024 * 1024).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn test_price_history() {
+    let fixture = setup().await;
+    let history = price_history::history(&fixture.pool, &format!("{}D", fixture.p)).await.unwrap().unwrap();
+    let versions: Vec<i32> = history.points.iter().map(|point| point.version_id).collect();
+    assert_eq!(versions, vec![fixture.old.id, fixture.new.id]);
+    // El precio calculado de D sigue al de A
+    assert_eq!(history.points[0].unit_price, decimal("20.00"));
+    assert_eq!(history.points[0].percentage, None);
+    assert_eq!(history.points[1].unit_price, decimal("22.00"));
+    assert_eq!(history.points[1].percentage, Some(decimal("10.00")));
+
+    let app = app(&fixture.pool, http::prices::router());
+    let (status, body) = get(&app, &format!("/history?code={}B", fixture.p)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["points"][1]["percentage"], "-50.00");
+    let (status, _) = get(&app, &format!("/history?code={}F", fixture.p)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = get(&app, &format!("/history?code={}Z", fixture.p)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_compare_versions() {
+    let fixture = setup().await;
+    let comparison = price_history::compare_versions(&fixture.pool, fixture.old.id, fixture.new.id, None)
+        .await
+        .unwrap()
+        .unwrap();
+    let code = |code: &str| format!("{}{}", fixture.p, code);
+    let changed: Vec<(String, Option<BigDecimal>)> =
+        comparison.changed.iter().map(|c| (c.code.clone(), c.percentage.clone())).collect();
+    assert_eq!(changed, vec![
+        (code("B"), Some(decimal("-50.00"))),
+        (code("A"), Some(decimal("10.00"))),
+        (code("D"), Some(decimal("10.00"))),
+    ]);
+    assert_eq!(comparison.changed[0].delta, decimal("-10.00"));
+    assert_eq!(comparison.unchanged, 1);
+    assert_eq!(comparison.added.iter().map(|p| p.code.clone()).collect::<Vec<_>>(), vec![code("E")]);
+    assert_eq!(comparison.removed.iter().map(|p| p.code.clone()).collect::<Vec<_>>(), vec![code("F")]);
+
+    let app = app(&fixture.pool, http::versions::router());
+    let (status, body) = get(&app, &format!("/compare?from={}&to={}&limit=1", fixture.old.id, fixture.new.id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["changed"], json!([{
+        "code": code("B"),
+        "description": "Price B",
+        "from": "20",
+        "to": "10",
+        "delta": "-10",
+        "percentage": "-50.00",
+    }]));
+    let (status, body) = get(&app, &format!("/compare?from={}&to=-1", fixture.old.id)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["message"], "Version not found");
+}
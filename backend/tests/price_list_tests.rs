@@ -0,0 +1,201 @@
+use std::{str::FromStr, sync::Arc};
+use axum::{
+    body::{self, Body},
+    http::{Request, StatusCode},
+};
+use backend::mailer::LogMailer;
+use backend::{
+    http,
+    models::{
+        budget::{Budget, BudgetRates, BudgetStatus, NewBudget},
+        descomposition::{CalculationMode, Descomposition, NewDescomposition},
+        element::{Element, ElementType, NewElement},
+        measurement::{Measurement, NewMeasurement},
+        price::{NewPrice, Price, PriceType},
+        project::{NewProject, Project},
+        unit::{NewUnit, Unit},
+        version::{NewVersion, Version},
+        AppState,
+    },
+    price_list,
+};
+use serde_json::{json, Value};
+use sqlx::{types::BigDecimal, PgPool};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+#[path = "common.rs"]
+mod common;
+
+fn decimal(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).unwrap()
+}
+
+/// Presupuesto que mide un solado descompuesto (mano de obra, baldosa y un
+/// mortero auxiliar) y un rodapié sin descomponer.
+async fn setup() -> (PgPool, Budget, String) {
+    let _ = &common::TRACING;
+    let pool = common::setup_pool().await;
+    let p = format!("{}-", Uuid::new_v4().to_string().chars().take(8).collect::<String>());
+
+    let project = Project::create(&pool, NewProject {
+        code: format!("{}PRJ", p),
+        title: Some("Price list".to_string()),
+        member_scope: None,
+    })
+    .await
+    .unwrap();
+    let budget = Budget::create(&pool, NewBudget {
+        project_id: project.id,
+        code: format!("{}OBRA", p),
+        version_number: 1,
+        name: "Cuadros de precios".to_string(),
+        status: BudgetStatus::Draft,
+        rates: BudgetRates::default(),
+    })
+    .await
+    .unwrap();
+    let version = Version::create(&pool, NewVersion { name: format!("V-{}", p) }).await.unwrap();
+    let unit = Unit::create(&pool, NewUnit {
+        name: format!("{}m2", p),
+        symbol: "m2".to_string(),
+        description: None,
+        formula: "a".to_string(),
+    })
+    .await
+    .unwrap();
+    let price = |code: &str, description: &str, base_price: &str, price_type| NewPrice {
+        version_id: version.id,
+        code: format!("{}{}", p, code),
+        description: description.to_string(),
+        base_price: decimal(base_price),
+        unit_id: unit.id,
+        price_type,
+    };
+    let floor = Price::create(&pool, price("E01", "Solado de gres", "0", PriceType::Decomposed)).await.unwrap();
+    let skirting = Price::create(&pool, price("E02", "Rodapié", "1.21", PriceType::Base)).await.unwrap();
+    let labour = Price::create(&pool, price("MO", "Oficial 1ª", "20", PriceType::Base)).await.unwrap();
+    let tile = Price::create(&pool, price("BAL", "Baldosa", "12.50", PriceType::Base)).await.unwrap();
+    let mortar = Price::create(&pool, price("MOR", "Mortero", "0", PriceType::Decomposed)).await.unwrap();
+    let cement = Price::create(&pool, price("CEM", "Cemento", "0.10", PriceType::Base)).await.unwrap();
+    let fixed = |parent: &Price, component: &Price, quantity: &str| NewDescomposition {
+        parent_price_id: parent.id,
+        component_price_id: component.id,
+        calculation_mode: CalculationMode::Fixed,
+        fixed_quantity: Some(decimal(quantity)),
+        params_json: None,
+    };
+    Descomposition::create(&pool, fixed(&floor, &labour, "0.5")).await.unwrap();
+    Descomposition::create(&pool, fixed(&floor, &tile, "1.05")).await.unwrap();
+    Descomposition::create(&pool, fixed(&floor, &mortar, "0.02")).await.unwrap();
+    Descomposition::create(&pool, fixed(&mortar, &cement, "300")).await.unwrap();
+
+    let line = Element::create(&pool, NewElement {
+        budget_id: budget.id,
+        parent_id: None,
+        version_id: version.id,
+        element_type: ElementType::Line,
+        code: format!("{}01", p),
+        budget_code: "01".to_string(),
+        description: Some("Salón".to_string()),
+    })
+    .await
+    .unwrap();
+    for (price, a) in [(&skirting, "12"), (&floor, "20"), (&floor, "5")] {
+        Measurement::create(&pool, NewMeasurement {
+            element_id: line.id,
+            price_id: price.id,
+            params_json: json!({"a": a}),
+            measurement_text: None,
+            measured_quantity: BigDecimal::from(0),
+        })
+        .await
+        .unwrap();
+    }
+    (pool, budget, p)
+}
+
+#[tokio::test]
+async fn test_price_list() {
+    let (pool, budget, p) = setup().await;
+    let list = price_list::price_list(&pool, budget.id).await.unwrap().unwrap();
+    let codes: Vec<&str> = list.entries.iter().map(|e| e.code.as_str()).collect();
+    assert_eq!(codes, vec![format!("{}E01", p), format!("{}E02", p)]);
+
+    let floor = &list.entries[0];
+    assert_eq!(floor.number, 1);
+    assert_eq!(floor.unit_price, decimal("23.73"));
+    assert_eq!(floor.words, "VEINTITRÉS EUROS CON SETENTA Y TRES CÉNTIMOS");
+    let percentages: Vec<BigDecimal> = floor.components.iter().map(|c| c.percentage.clone()).collect();
+    assert_eq!(percentages, vec![decimal("42.14"), decimal("55.31"), decimal("2.53")]);
+    assert!(floor.components[2].auxiliary);
+    assert!(!floor.components[0].auxiliary);
+    assert_eq!(floor.auxiliary, decimal("0.6"));
+    assert_eq!(floor.auxiliary_percentage, decimal("2.53"));
+
+    let skirting = &list.entries[1];
+    assert_eq!(skirting.words, "UN EURO CON VEINTIÚN CÉNTIMOS");
+    assert!(skirting.components.is_empty());
+
+    let csv = list.first_csv();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[2], format!("2,{}E02,m2,Rodapié,1.21,UN EURO CON VEINTIÚN CÉNTIMOS", p));
+
+    let csv = list.second_csv();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(
+        lines[3],
+        format!("1,{p}E01,m2,Solado de gres,23.73,{p}MOR,m2,Mortero,true,0.02,30.00,0.60,2.53", p = p)
+    );
+    assert!(lines[4].ends_with(",,,,,,,,"));
+
+    assert!(price_list::price_list(&pool, 0).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_price_list_endpoints() {
+    let (pool, budget, _) = setup().await;
+    let app = http::budgets::router().with_state(Arc::new(AppState {
+        pool,
+        secret: "test_secret".to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+    let get = |uri: String| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+    let response = app.clone().oneshot(get(format!("/{}/price-list", budget.id))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body::to_bytes(response.into_body(), 1024 * 1024).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["data"]["entries"].as_array().unwrap().len(), 2);
+
+    for number in [1, 2] {
+        let response = app.clone().oneshot(get(format!("/{}/price-list/{}", budget.id, number))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/pdf");
+        let body = body::to_bytes(response.into_body(), 1024 * 1024).await.unwrap();
+        assert!(body.starts_with(b"%PDF-1.4"));
+        assert!(String::from_utf8_lossy(&body).contains(&format!("CUADRO DE PRECIOS N\u{FFFD} {}", number)));
+    }
+
+    let response = app
+        .clone()
+        .oneshot(get(format!("/{}/price-list/2?format=csv", budget.id)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/csv; charset=utf-8");
+    assert_eq!(
+        response.headers()["content-disposition"],
+        format!("attachment; filename=\"{}-cuadro-precios-2.csv\"", budget.code).as_str()
+    );
+    let body = body::to_bytes(response.into_body(), 1024 * 1024).await.unwrap();
+    assert!(body.starts_with(b"number,code,unit,description,price,component_code"));
+
+    let response = app.clone().oneshot(get(format!("/{}/price-list/3", budget.id))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = app.oneshot(get("/0/price-list/1".to_string())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
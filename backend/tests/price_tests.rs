@@ -8,7 +8,6 @@ use sqlx::{PgPool, types::BigDecimal};
 use uuid::Uuid;
 use rand::Rng;
 use num_traits::cast::FromPrimitive;
-use serde_json::json;
 
 #[path = "common.rs"]
 mod common;
@@ -151,3 +150,22 @@ async fn test_list_prices() {
     let prices = Price::read_paged(&pool, &params).await.unwrap();
     assert!(prices.len() >= 2);
 }
+
+#[tokio::test]
+async fn test_price_code_unique_per_version() {
+    let (pool, version, unit) = setup().await;
+    let other = Version::create(&pool, NewVersion { name: format!("V-PRICE-{}", Uuid::new_v4()) }).await.unwrap();
+    let code = format!("P-CODE-{}", Uuid::new_v4().to_string().chars().take(10).collect::<String>());
+    let price = |version_id| NewPrice {
+        version_id,
+        code: code.clone(),
+        description: "Test Price".to_string(),
+        base_price: BigDecimal::from(1),
+        unit_id: unit.id,
+        price_type: PriceType::Base,
+    };
+    Price::create(&pool, price(version.id)).await.unwrap();
+    // El mismo código puede estar en otra versión, pero no repetirse en la misma
+    Price::create(&pool, price(other.id)).await.unwrap();
+    assert!(Price::create(&pool, price(version.id)).await.is_err());
+}
@@ -0,0 +1,130 @@
+use std::{str::FromStr, sync::Arc};
+use axum::{
+    body::{self, Body},
+    http::{Request, StatusCode},
+};
+use backend::mailer::LogMailer;
+use backend::{
+    http,
+    models::{
+        descomposition::{CalculationMode, Descomposition, NewDescomposition},
+        price::{NewPrice, Price, PriceType},
+        unit::{NewUnit, Unit},
+        version::{NewVersion, Version},
+        AppState,
+    },
+    pricing,
+};
+use serde_json::{json, Value};
+use sqlx::{types::BigDecimal, PgPool};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+#[path = "common.rs"]
+mod common;
+
+struct Sample {
+    floor: Price,
+    mortar: Price,
+}
+
+async fn setup() -> (PgPool, Sample) {
+    let _ = &common::TRACING;
+    let pool = common::setup_pool().await;
+
+    let version = Version::create(&pool, NewVersion {
+        name: format!("V-PRICING-{}", Uuid::new_v4()),
+    })
+    .await
+    .unwrap();
+    let unit = |formula: &str| NewUnit {
+        name: format!("U-{}", Uuid::new_v4().to_string().chars().take(8).collect::<String>()),
+        symbol: "ud".to_string(),
+        description: None,
+        formula: formula.to_string(),
+    };
+    let linear = Unit::create(&pool, unit("a")).await.unwrap();
+    let surface = Unit::create(&pool, unit("a * b")).await.unwrap();
+    let prefix = Uuid::new_v4().to_string().chars().take(8).collect::<String>();
+    let price = |code: &str, base_price: &str, unit_id, price_type| NewPrice {
+        version_id: version.id,
+        code: format!("{}-{}", prefix, code),
+        description: format!("Price {}", code),
+        base_price: BigDecimal::from_str(base_price).unwrap(),
+        unit_id,
+        price_type,
+    };
+    let floor = Price::create(&pool, price("SOL", "99", linear.id, PriceType::Decomposed)).await.unwrap();
+    let labour = Price::create(&pool, price("MO", "20", linear.id, PriceType::Base)).await.unwrap();
+    let tile = Price::create(&pool, price("BAL", "12.50", surface.id, PriceType::Base)).await.unwrap();
+    let mortar = Price::create(&pool, price("MOR", "0", linear.id, PriceType::Decomposed)).await.unwrap();
+    let cement = Price::create(&pool, price("CEM", "0.10", linear.id, PriceType::Base)).await.unwrap();
+
+    let fixed = |parent: &Price, component: &Price, quantity: &str| NewDescomposition {
+        parent_price_id: parent.id,
+        component_price_id: component.id,
+        calculation_mode: CalculationMode::Fixed,
+        fixed_quantity: Some(BigDecimal::from_str(quantity).unwrap()),
+        params_json: None,
+    };
+    Descomposition::create(&pool, fixed(&floor, &labour, "0.5")).await.unwrap();
+    Descomposition::create(&pool, NewDescomposition {
+        parent_price_id: floor.id,
+        component_price_id: tile.id,
+        calculation_mode: CalculationMode::Formula,
+        fixed_quantity: None,
+        params_json: Some(json!({"a": 1.05, "b": 1})),
+    })
+    .await
+    .unwrap();
+    Descomposition::create(&pool, fixed(&floor, &mortar, "0.02")).await.unwrap();
+    Descomposition::create(&pool, fixed(&mortar, &cement, "300")).await.unwrap();
+
+    (pool, Sample { floor, mortar })
+}
+
+#[tokio::test]
+async fn test_breakdown() {
+    let (pool, sample) = setup().await;
+    let breakdown = pricing::breakdown(&pool, sample.floor.id).await.unwrap().unwrap();
+    assert_eq!(breakdown.unit_price, BigDecimal::from_str("23.73").unwrap());
+    assert_eq!(breakdown.components.len(), 3);
+    assert_eq!(breakdown.components[1].quantity, BigDecimal::from_str("1.05").unwrap());
+    assert_eq!(breakdown.components[2].unit_price, BigDecimal::from(30));
+    assert_eq!(breakdown.components[2].components[0].quantity, BigDecimal::from(300));
+
+    assert!(pricing::breakdown(&pool, 0).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_breakdown_endpoint() {
+    let (pool, sample) = setup().await;
+    let app = http::prices::router().with_state(Arc::new(AppState {
+        pool,
+        secret: "test_secret".to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/{}/breakdown", sample.mortar.id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body::to_bytes(response.into_body(), 1024 * 1024).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["data"]["code"], sample.mortar.code);
+    assert_eq!(body["data"]["components"].as_array().unwrap().len(), 1);
+
+    let response = app
+        .oneshot(Request::builder().uri("/0/breakdown").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
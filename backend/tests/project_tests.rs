@@ -19,6 +19,7 @@ async fn test_create_project() {
     let new_project = NewProject {
         code: code.clone(),
         title: Some("Test Project".to_string()),
+        member_scope: None,
     };
     let project = Project::create(&pool, new_project).await.unwrap();
     assert_eq!(project.code, code);
@@ -32,6 +33,7 @@ async fn test_read_project() {
     let new_project = NewProject {
         code: code.clone(),
         title: Some("Test Project".to_string()),
+        member_scope: None,
     };
     let project = Project::create(&pool, new_project).await.unwrap();
     let read_project = Project::read_by_id(&pool, project.id).await.unwrap().unwrap();
@@ -47,6 +49,7 @@ async fn test_update_project() {
     let new_project = NewProject {
         code: code.clone(),
         title: Some("Test Project".to_string()),
+        member_scope: None,
     };
     let mut project = Project::create(&pool, new_project).await.unwrap();
     let updated_title = "Updated Project".to_string();
@@ -62,6 +65,7 @@ async fn test_delete_project() {
     let new_project = NewProject {
         code: code.clone(),
         title: Some("Test Project".to_string()),
+        member_scope: None,
     };
     let project = Project::create(&pool, new_project).await.unwrap();
     let deleted_project = Project::delete(&pool, project.id).await.unwrap();
@@ -77,6 +81,7 @@ async fn test_list_projects() {
     let new_project1 = NewProject {
         code: code1,
         title: Some("Test Project 1".to_string()),
+        member_scope: None,
     };
     Project::create(&pool, new_project1).await.unwrap();
 
@@ -84,11 +89,13 @@ async fn test_list_projects() {
     let new_project2 = NewProject {
         code: code2,
         title: Some("Test Project 2".to_string()),
+        member_scope: None,
     };
     Project::create(&pool, new_project2).await.unwrap();
 
     let params = ProjectParams {
         id: None,
+        member_scope: None,
         code: None,
         title: None,
         page: None,
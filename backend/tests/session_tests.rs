@@ -0,0 +1,139 @@
+use std::sync::Arc;
+use axum::{http::StatusCode, Router};
+use backend::mailer::LogMailer;
+use backend::{
+    http,
+    models::{
+        session::Session,
+        user::User,
+        AppState,
+    },
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+#[path = "common.rs"]
+mod common;
+
+use common::{create_user, login, send_json, PASSWORD};
+
+async fn setup() -> (PgPool, Router, User) {
+    let _ = &common::TRACING;
+    let pool = common::setup_pool().await;
+    let app = http::api_router(Arc::new(AppState {
+        pool: pool.clone(),
+        secret: "test_secret".to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+    let user = create_user(&pool, "reader", true).await;
+    (pool, app, user)
+}
+
+/// Inicia sesión y devuelve el access token y el refresh token.
+async fn login_tokens(app: &Router, user: &User) -> (String, String) {
+    let (status, body) = login(app, &user.email, PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["expires_in"], 3600);
+    (
+        body["data"]["token"].as_str().unwrap().to_string(),
+        body["data"]["refresh_token"].as_str().unwrap().to_string(),
+    )
+}
+
+#[tokio::test]
+async fn test_refresh() {
+    let (_, app, user) = setup().await;
+    let (token, refresh_token) = login_tokens(&app, &user).await;
+
+    let (status, body) = send_json(&app, "POST", "/auth/refresh", None, json!({"refresh_token": refresh_token})).await;
+    assert_eq!(status, StatusCode::OK);
+    let new_token = body["data"]["token"].as_str().unwrap();
+    let new_refresh_token = body["data"]["refresh_token"].as_str().unwrap();
+    assert_ne!(new_refresh_token, refresh_token);
+
+    // El refresh token se rota: el anterior ya no vale
+    let (status, body) = send_json(&app, "POST", "/auth/refresh", None, json!({"refresh_token": refresh_token})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["message"], "Invalid refresh token");
+
+    // Ambos access tokens son de la misma sesión
+    for token in [token.as_str(), new_token] {
+        let (status, _) = send_json(&app, "GET", "/auth/me", Some(token), Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (_, body) = send_json(&app, "GET", "/auth/sessions", Some(new_token), Value::Null).await;
+    let sessions = body["data"].as_array().unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0]["current"], true);
+    assert_eq!(sessions[0]["user_agent"], common::USER_AGENT);
+    assert!(sessions[0].get("refresh_token_hash").is_none());
+}
+
+#[tokio::test]
+async fn test_concurrent_refresh() {
+    let (pool, app, user) = setup().await;
+    let (_, refresh_token) = login_tokens(&app, &user).await;
+
+    // Con el mismo refresh token solo una petición obtiene tokens nuevos
+    let body = json!({"refresh_token": refresh_token});
+    let (first, second) = tokio::join!(
+        send_json(&app, "POST", "/auth/refresh", None, body.clone()),
+        send_json(&app, "POST", "/auth/refresh", None, body.clone()),
+    );
+    let mut statuses = vec![first.0, second.0];
+    statuses.sort();
+    assert_eq!(statuses, vec![StatusCode::OK, StatusCode::UNAUTHORIZED]);
+    assert!(Session::rotate(&pool, &refresh_token).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_revocation() {
+    let (pool, app, user) = setup().await;
+    let (laptop, laptop_refresh) = login_tokens(&app, &user).await;
+    let (phone, _) = login_tokens(&app, &user).await;
+
+    let (_, body) = send_json(&app, "GET", "/auth/sessions", Some(&laptop), Value::Null).await;
+    let sessions = body["data"].as_array().unwrap();
+    assert_eq!(sessions.len(), 2);
+    let phone_id = sessions.iter().find(|s| s["current"] == false).unwrap()["id"].as_i64().unwrap();
+
+    // Cerrar la sesión de otro dispositivo
+    let (status, _) = send_json(&app, "DELETE", &format!("/auth/sessions/{}", phone_id), Some(&laptop), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send_json(&app, "GET", "/auth/me", Some(&phone), Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["message"], "Session revoked");
+    let (status, _) = send_json(&app, "DELETE", &format!("/auth/sessions/{}", phone_id), Some(&laptop), Value::Null).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Logout revoca la sesión del token
+    let (status, _) = send_json(&app, "GET", "/auth/logout", Some(&laptop), Value::Null).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let (status, _) = send_json(&app, "GET", "/auth/me", Some(&laptop), Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = send_json(&app, "POST", "/auth/refresh", None, json!({"refresh_token": laptop_refresh})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(Session::read_active_by_user(&pool, user.id).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_password_change_revokes_sessions() {
+    let (pool, app, user) = setup().await;
+    let (token, _) = login_tokens(&app, &user).await;
+    login_tokens(&app, &user).await;
+    assert_eq!(Session::read_active_by_user(&pool, user.id).await.unwrap().len(), 2);
+
+    // Actualizar otros datos no cierra las sesiones
+    let mut user = User::read_by_id(&pool, user.id).await.unwrap().unwrap();
+    user.username = format!("{}-renamed", user.username);
+    let mut user = User::update(&pool, user).await.unwrap();
+    let (status, _) = send_json(&app, "GET", "/auth/me", Some(&token), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+
+    user.password = Some("new password 2".to_string());
+    User::update(&pool, user).await.unwrap();
+    let (status, body) = send_json(&app, "GET", "/auth/me", Some(&token), Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["message"], "Session revoked");
+}
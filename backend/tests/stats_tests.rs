@@ -4,6 +4,7 @@ use axum::{
     body::{self, Body},
     http::{Request, StatusCode},
 };
+use backend::mailer::LogMailer;
 use backend::models::{
     user::{NewUser, User},
     role::{NewRole, Role},
@@ -32,7 +33,7 @@ async fn setup() -> (PgPool, Role, User, Project) {
     let new_user = NewUser {
         username,
         email,
-        hashed_password: "password".to_string(),
+        password: "password1".to_string(),
         role_id: role.id,
         is_active: true,
     };
@@ -40,6 +41,7 @@ async fn setup() -> (PgPool, Role, User, Project) {
     let new_project = NewProject {
         code: format!("P-TEST-{}", Uuid::new_v4()),
         title: Some("description".to_string()),
+        member_scope: None,
     };
     let project = Project::create(&pool, new_project).await.unwrap();
     (pool, role, user, project)
@@ -50,6 +52,7 @@ fn test_app(pool: PgPool) -> axum::Router {
         pool,
         secret: "test_secret".to_string(),
         static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
     });
     http::stats::router().with_state(app_state)
 }
@@ -85,6 +88,7 @@ async fn test_count_budgets() {
         code: format!("C-TEST-{}", Uuid::new_v4()),
         version_number: 1,
         status: backend::models::budget::BudgetStatus::Draft,
+        rates: backend::models::budget::BudgetRates::default(),
     };
     let _ = Budget::create(&pool, new_budget).await.unwrap();
 
@@ -0,0 +1,362 @@
+use std::{str::FromStr, sync::Arc};
+use axum::{
+    body::{self, Body},
+    http::{Request, StatusCode},
+};
+use backend::mailer::LogMailer;
+use backend::{
+    budget_tree,
+    http,
+    models::{
+        budget::{Budget, BudgetRates, BudgetStatus, CloneBudget, CustomRate, NewBudget},
+        element::{Element, ElementType, NewElement},
+        measurement::{Measurement, NewMeasurement},
+        price::{NewPrice, Price, PriceType},
+        project::{NewProject, Project},
+        unit::{NewUnit, Unit},
+        version::{NewVersion, Version},
+        AppState,
+    },
+    summary::{self, Rounding, SummaryOptions},
+};
+use serde_json::{json, Value};
+use sqlx::{types::BigDecimal, PgPool};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+#[path = "common.rs"]
+mod common;
+
+fn decimal(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).unwrap()
+}
+
+/// Presupuesto con dos capítulos, uno de ellos con un subcapítulo:
+/// 01 > 01.01 (1.333 × 10.05) y 02 > 02.01 > 02.01.01 (3 + 0.5 × 3.33).
+async fn setup() -> (PgPool, Budget) {
+    let _ = &common::TRACING;
+    let pool = common::setup_pool().await;
+    let p = format!("{}-", Uuid::new_v4().to_string().chars().take(8).collect::<String>());
+
+    let project = Project::create(&pool, NewProject {
+        code: format!("{}PRJ", p),
+        title: Some("Summary".to_string()),
+        member_scope: None,
+    })
+    .await
+    .unwrap();
+    let budget = Budget::create(&pool, NewBudget {
+        project_id: project.id,
+        code: format!("{}OBRA", p),
+        version_number: 1,
+        name: "Resumen".to_string(),
+        status: BudgetStatus::Draft,
+        rates: BudgetRates::default(),
+    })
+    .await
+    .unwrap();
+    let version = Version::create(&pool, NewVersion { name: format!("V-{}", p) }).await.unwrap();
+    let unit = Unit::create(&pool, NewUnit {
+        name: format!("{}m", p),
+        symbol: "m".to_string(),
+        description: None,
+        formula: "a".to_string(),
+    })
+    .await
+    .unwrap();
+    let price = |code: &str, base_price: &str| NewPrice {
+        version_id: version.id,
+        code: format!("{}{}", p, code),
+        description: format!("Price {}", code),
+        base_price: decimal(base_price),
+        unit_id: unit.id,
+        price_type: PriceType::Base,
+    };
+    let skirting = Price::create(&pool, price("ROD", "10.05")).await.unwrap();
+    let paint = Price::create(&pool, price("PIN", "3.33")).await.unwrap();
+
+    let element = |parent_id, element_type, code: &str| NewElement {
+        budget_id: budget.id,
+        parent_id,
+        version_id: version.id,
+        element_type,
+        code: format!("{}{}", p, code),
+        budget_code: code.to_string(),
+        description: Some(format!("Element {}", code)),
+    };
+    let measure = |element: &Element, price: &Price, a: &str| NewMeasurement {
+        element_id: element.id,
+        price_id: price.id,
+        params_json: json!({"a": a}),
+        measurement_text: None,
+        measured_quantity: BigDecimal::from(0),
+    };
+    let first = Element::create(&pool, element(None, ElementType::Chapter, "01")).await.unwrap();
+    let line = Element::create(&pool, element(Some(first.id), ElementType::Line, "01.01")).await.unwrap();
+    Measurement::create(&pool, measure(&line, &skirting, "1.333")).await.unwrap();
+
+    let second = Element::create(&pool, element(None, ElementType::Chapter, "02")).await.unwrap();
+    let sub = Element::create(&pool, element(Some(second.id), ElementType::Chapter, "02.01")).await.unwrap();
+    let line = Element::create(&pool, element(Some(sub.id), ElementType::Line, "02.01.01")).await.unwrap();
+    Measurement::create(&pool, measure(&line, &paint, "3")).await.unwrap();
+    Measurement::create(&pool, measure(&line, &paint, "0.5")).await.unwrap();
+
+    (pool, budget)
+}
+
+#[tokio::test]
+async fn test_summary() {
+    let (pool, budget) = setup().await;
+    let summary = summary::summarize(&pool, budget.id, SummaryOptions::default()).await.unwrap().unwrap();
+    assert_eq!(summary.chapters.len(), 2);
+    assert_eq!(summary.chapters[0].amount, decimal("13.40"));
+    assert_eq!(summary.chapters[1].amount, decimal("11.66"));
+    assert_eq!(summary.pem, decimal("25.06"));
+
+    let nodes = summary.nodes();
+    let codes: Vec<(usize, &str)> = nodes.iter().map(|(depth, n)| (*depth, n.code.as_str())).collect();
+    assert_eq!(codes, vec![(0, "01"), (1, "01.01"), (0, "02"), (1, "02.01"), (2, "02.01.01")]);
+    // Las mediciones del mismo precio se agrupan en una sola partida
+    let (_, line) = nodes[4];
+    assert_eq!(line.items.len(), 1);
+    assert_eq!(line.items[0].quantity, decimal("3.5"));
+    assert_eq!(line.items[0].unit_price, decimal("3.33"));
+
+    let options = SummaryOptions { decimals: 2, rounding: Rounding::Down };
+    let summary = summary::summarize(&pool, budget.id, options).await.unwrap().unwrap();
+    assert_eq!(summary.pem, decimal("25.04"));
+
+    assert!(summary::summarize(&pool, 0, SummaryOptions::default()).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_summary_rates() {
+    let (pool, mut budget) = setup().await;
+    let summary = summary::summarize(&pool, budget.id, SummaryOptions::default()).await.unwrap().unwrap();
+    let amounts: Vec<(&str, BigDecimal)> = summary.rates.iter().map(|r| (r.name.as_str(), r.amount.clone())).collect();
+    assert_eq!(amounts, vec![
+        (summary::GENERAL_EXPENSES, decimal("3.26")),
+        (summary::INDUSTRIAL_PROFIT, decimal("1.50")),
+    ]);
+    assert_eq!(summary.pec, decimal("29.82"));
+    assert_eq!(summary.vat.amount, decimal("6.26"));
+    assert_eq!(summary.total, decimal("36.08"));
+
+    // Los porcentajes personalizados se aplican sobre el PEM, tras GG y BI
+    budget.rates.custom_rates.0.push(CustomRate {
+        name: "Seguridad y salud".to_string(),
+        percentage: decimal("1"),
+    });
+    budget.rates.vat = decimal("10");
+    let budget = Budget::update(&pool, budget).await.unwrap();
+    let summary = summary::summarize(&pool, budget.id, SummaryOptions::default()).await.unwrap().unwrap();
+    assert_eq!(summary.rates.len(), 3);
+    assert_eq!(summary.rates[2].amount, decimal("0.25"));
+    assert_eq!(summary.pec, decimal("30.07"));
+    assert_eq!(summary.vat.amount, decimal("3.01"));
+    assert_eq!(summary.total, decimal("33.08"));
+}
+
+#[tokio::test]
+async fn test_summary_endpoint() {
+    let (pool, budget) = setup().await;
+    let app = http::budgets::router().with_state(Arc::new(AppState {
+        pool,
+        secret: "test_secret".to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+    let get = |uri: String| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(get(format!("/{}/summary?decimals=3&rounding=floor", budget.id)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body::to_bytes(response.into_body(), 1024 * 1024).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["data"]["options"]["rounding"], "floor");
+    assert_eq!(decimal(body["data"]["pem"].as_str().unwrap()), decimal("25.051"));
+
+    let response = app.clone().oneshot(get(format!("/{}/summary?decimals=9", budget.id))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = app.oneshot(get("/0/summary".to_string())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_pdf_endpoint() {
+    let (pool, budget) = setup().await;
+    let app = http::budgets::router().with_state(Arc::new(AppState {
+        pool,
+        secret: "test_secret".to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+    let get = |uri: String| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+    let response = app.clone().oneshot(get(format!("/{}/pdf", budget.id))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/pdf");
+    assert_eq!(
+        response.headers()["content-disposition"],
+        format!("inline; filename=\"{}.pdf\"", budget.code).as_str()
+    );
+    let body = body::to_bytes(response.into_body(), 1024 * 1024).await.unwrap();
+    assert!(body.starts_with(b"%PDF-1.4"));
+    let text = String::from_utf8_lossy(&body);
+    assert!(text.contains("(Element 02.01.01) Tj"));
+    assert!(text.contains("(TOTAL PRESUPUESTO GENERAL) Tj"));
+    assert!(text.contains("(36,08) Tj"));
+
+    let response = app.oneshot(get("/0/pdf".to_string())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_compare_endpoint() {
+    let (pool, budget) = setup().await;
+    // v2: 01.01 pasa de 1.333 a 2 m y se añade la línea 01.02
+    let copy = Budget::clone_version(&pool, budget.id, CloneBudget::default()).await.unwrap();
+    let measurements = Measurement::read_by_budget(&pool, copy.id).await.unwrap();
+    let mut first = Measurement::read_by_id(&pool, measurements[0].id).await.unwrap().unwrap();
+    first.params_json = json!({"a": "2"});
+    Measurement::update(&pool, first).await.unwrap();
+    let elements = Element::read_by_budget(&pool, copy.id).await.unwrap();
+    let chapter = elements.iter().find(|e| e.budget_code == "01").unwrap();
+    let line = Element::create(&pool, NewElement {
+        budget_id: copy.id,
+        parent_id: Some(chapter.id),
+        version_id: chapter.version_id,
+        element_type: ElementType::Line,
+        code: format!("{}-NEW", chapter.code),
+        budget_code: "01.02".to_string(),
+        description: None,
+    })
+    .await
+    .unwrap();
+    Measurement::create(&pool, NewMeasurement {
+        element_id: line.id,
+        price_id: measurements[0].price_id,
+        params_json: json!({"a": "1"}),
+        measurement_text: None,
+        measured_quantity: BigDecimal::from(0),
+    })
+    .await
+    .unwrap();
+
+    let app = http::budgets::router().with_state(Arc::new(AppState {
+        pool,
+        secret: "test_secret".to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+    let get = |uri: String| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(get(format!("/compare?from={}&to={}", budget.id, copy.id)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body::to_bytes(response.into_body(), 1024 * 1024).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    let data = &body["data"];
+    let lines: Vec<(&str, &str)> = data["lines"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|l| (l["budget_code"].as_str().unwrap(), l["change"].as_str().unwrap()))
+        .collect();
+    assert_eq!(lines, vec![("01.01", "modified"), ("01.02", "added")]);
+    let item = &data["lines"][0]["items"][0];
+    assert_eq!(decimal(item["quantity"]["delta"].as_str().unwrap()), decimal("0.667"));
+    assert_eq!(decimal(item["unit_price"]["delta"].as_str().unwrap()), decimal("0"));
+    // 01: 13.40 -> 20.10 + 10.05; 02 no cambia
+    assert_eq!(decimal(data["chapters"][0]["amount"]["to"].as_str().unwrap()), decimal("30.15"));
+    assert_eq!(data["chapters"][1]["change"], "unchanged");
+    assert_eq!(decimal(data["pem"]["delta"].as_str().unwrap()), decimal("16.75"));
+
+    let response = app
+        .clone()
+        .oneshot(get(format!("/compare?from={}&to={}&unchanged=true", budget.id, copy.id)))
+        .await
+        .unwrap();
+    let body = body::to_bytes(response.into_body(), 1024 * 1024).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["data"]["lines"].as_array().unwrap().len(), 3);
+
+    let response = app
+        .clone()
+        .oneshot(get(format!("/compare?from={}&to={}&format=csv", budget.id, copy.id)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/csv; charset=utf-8");
+    let body = body::to_bytes(response.into_body(), 1024 * 1024).await.unwrap();
+    let csv = String::from_utf8(body.to_vec()).unwrap();
+    assert!(csv.contains("\r\nline,01.02,,added,"));
+    assert!(csv.contains("\r\ntotal,PEM,,modified,,,,,,,,,25.06,41.81,16.75\r\n"));
+
+    let response = app.oneshot(get(format!("/compare?from={}&to=0", budget.id))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_tree() {
+    let (pool, budget) = setup().await;
+    let tree = budget_tree::tree(&pool, budget.id, None).await.unwrap().unwrap();
+    let summary = summary::summarize(&pool, budget.id, SummaryOptions::default()).await.unwrap().unwrap();
+    // Los importes cuadran con el resumen
+    assert_eq!(tree.amount, summary.pem);
+    let amounts: Vec<(&str, BigDecimal)> = tree.nodes.iter().map(|n| (n.code.as_str(), n.amount.clone())).collect();
+    assert_eq!(amounts, vec![("01", decimal("13.40")), ("02", decimal("11.66"))]);
+    let line = &tree.nodes[1].children[0].children[0];
+    assert_eq!((line.code.as_str(), line.depth), ("02.01.01", 2));
+    assert_eq!(line.items[0].quantity, decimal("3.5"));
+    // Los capítulos acumulan las cantidades de sus líneas
+    assert_eq!(tree.nodes[1].items.len(), 1);
+    assert_eq!(tree.nodes[1].items[0].quantity, decimal("3.5"));
+    assert_eq!(tree.nodes[1].items[0].unit_price, decimal("3.33"));
+    assert!(tree.nodes.iter().all(|n| !n.truncated));
+
+    // Con límite de profundidad los subtotales incluyen los niveles que no se devuelven
+    let tree = budget_tree::tree(&pool, budget.id, Some(1)).await.unwrap().unwrap();
+    let sub = &tree.nodes[1].children[0];
+    assert_eq!(sub.code, "02.01");
+    assert!(sub.truncated && sub.children.is_empty());
+    assert_eq!(sub.amount, decimal("11.66"));
+    assert!(!tree.nodes[0].children[0].truncated);
+    let tree = budget_tree::tree(&pool, budget.id, Some(0)).await.unwrap().unwrap();
+    assert!(tree.nodes.iter().all(|n| n.truncated && n.children.is_empty()));
+    assert_eq!(tree.amount, summary.pem);
+
+    assert!(budget_tree::tree(&pool, 0, None).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_tree_endpoint() {
+    let (pool, budget) = setup().await;
+    let app = http::budgets::router().with_state(Arc::new(AppState {
+        pool,
+        secret: "test_secret".to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }));
+    let get = |uri: String| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+    let response = app.clone().oneshot(get(format!("/{}/tree?depth=1", budget.id))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body::to_bytes(response.into_body(), 1024 * 1024).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["data"]["max_depth"], 1);
+    assert_eq!(body["data"]["nodes"][0]["children"][0]["code"], "01.01");
+    assert_eq!(body["data"]["nodes"][1]["children"][0]["truncated"], true);
+
+    let response = app.clone().oneshot(get(format!("/{}/tree?depth=-1", budget.id))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = app.oneshot(get("/0/tree".to_string())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -1,7 +1,9 @@
 use backend::models::{
     unit::{Unit, NewUnit, UnitParams},
 };
-use sqlx::PgPool;
+use std::str::FromStr;
+use serde_json::json;
+use sqlx::{types::BigDecimal, PgPool};
 use uuid::Uuid;
 
 #[path = "common.rs"]
@@ -123,3 +125,50 @@ async fn test_list_units() {
     let units = Unit::read_paged(&pool, &params).await.unwrap();
     assert!(units.len() >= 2);
 }
+
+#[tokio::test]
+async fn test_unit_formula_params() {
+    let pool = setup().await;
+    let name = format!("U-{}", Uuid::new_v4().to_string().chars().take(8).collect::<String>());
+    let new_unit = NewUnit {
+        name,
+        symbol: "m3".to_string(),
+        description: None,
+        formula: "b * a * round(h, 2)".to_string(),
+    };
+    let mut unit = Unit::create(&pool, new_unit).await.unwrap();
+    assert_eq!(unit.params, json!(["a", "b", "h"]));
+    assert_eq!(
+        unit.evaluate(&json!({"a": 2, "b": "1.5", "h": 0.333})).unwrap(),
+        BigDecimal::from_str("0.99").unwrap()
+    );
+
+    unit.formula = "pi * r ^ 2".to_string();
+    let updated_unit = Unit::update(&pool, unit).await.unwrap();
+    assert_eq!(updated_unit.params, json!(["r"]));
+}
+
+#[tokio::test]
+async fn test_invalid_unit_formula() {
+    let pool = setup().await;
+    let name = format!("U-{}", Uuid::new_v4().to_string().chars().take(8).collect::<String>());
+    let new_unit = NewUnit {
+        name: name.clone(),
+        symbol: "ud".to_string(),
+        description: None,
+        formula: "a * (b +".to_string(),
+    };
+    let error = Unit::create(&pool, new_unit).await.unwrap_err();
+    assert_eq!(error.to_string(), "Invalid formula 'a * (b +': Unexpected end of formula");
+
+    let new_unit = NewUnit {
+        name,
+        symbol: "ud".to_string(),
+        description: None,
+        formula: "a".to_string(),
+    };
+    let mut unit = Unit::create(&pool, new_unit).await.unwrap();
+    unit.formula = "a $ b".to_string();
+    let error = Unit::update(&pool, unit).await.unwrap_err();
+    assert_eq!(error.to_string(), "Invalid formula 'a $ b': Unexpected character '$' at position 3");
+}
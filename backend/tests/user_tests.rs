@@ -26,7 +26,7 @@ async fn test_create_user() {
     let new_user = NewUser {
         username: username.clone(),
         email: email.clone(),
-        hashed_password: "password".to_string(),
+        password: "password1".to_string(),
         role_id: role.id,
         is_active: true,
     };
@@ -45,7 +45,7 @@ async fn test_read_user() {
     let new_user = NewUser {
         username: username.clone(),
         email: email.clone(),
-        hashed_password: "password".to_string(),
+        password: "password1".to_string(),
         role_id: role.id,
         is_active: true,
     };
@@ -66,7 +66,7 @@ async fn test_update_user() {
     let new_user = NewUser {
         username: username.clone(),
         email: email.clone(),
-        hashed_password: "password".to_string(),
+        password: "password1".to_string(),
         role_id: role.id,
         is_active: true,
     };
@@ -85,7 +85,7 @@ async fn test_delete_user() {
     let new_user = NewUser {
         username: username.clone(),
         email: email.clone(),
-        hashed_password: "password".to_string(),
+        password: "password1".to_string(),
         role_id: role.id,
         is_active: true,
     };
@@ -104,7 +104,7 @@ async fn test_list_users() {
     let new_user1 = NewUser {
         username: username1,
         email: email1,
-        hashed_password: "password".to_string(),
+        password: "password1".to_string(),
         role_id: role.id,
         is_active: true,
     };
@@ -115,7 +115,7 @@ async fn test_list_users() {
     let new_user2 = NewUser {
         username: username2,
         email: email2,
-        hashed_password: "password".to_string(),
+        password: "password1".to_string(),
         role_id: role.id,
         is_active: true,
     };
@@ -135,3 +135,92 @@ async fn test_list_users() {
     let users = User::read_paged(&pool, &params).await.unwrap();
     assert!(users.len() >= 2);
 }
+
+#[tokio::test]
+async fn test_password_is_hashed() {
+    let (pool, role) = setup().await;
+    let username = format!("U-TEST-{}", Uuid::new_v4());
+    let new_user = NewUser {
+        username: username.clone(),
+        email: format!("{}@test.com", username),
+        password: "password1".to_string(),
+        role_id: role.id,
+        is_active: true,
+    };
+    // Las contraseñas no llegan a los logs
+    assert!(!format!("{:?}", new_user).contains("password1"));
+    let mut user = User::create(&pool, new_user).await.unwrap();
+    assert_ne!(user.hashed_password, "password1");
+    assert!(bcrypt::verify("password1", &user.hashed_password).unwrap());
+    assert!(user.verify_password("password1").await);
+    assert!(!user.verify_password("password2").await);
+    assert!(!format!("{:?}", user).contains(&user.hashed_password));
+
+    // El hash nunca se serializa ni se acepta del cliente
+    let value = serde_json::to_value(&user).unwrap();
+    assert!(value.get("hashed_password").is_none());
+    assert!(value.get("password").is_none());
+    let mut value = value;
+    value["hashed_password"] = serde_json::json!("plain");
+    let deserialized: User = serde_json::from_value(value).unwrap();
+    assert_eq!(deserialized.hashed_password, "");
+    assert_eq!(deserialized.password, None);
+
+    // Sin contraseña nueva se conserva la actual
+    let hashed_password = user.hashed_password.clone();
+    user.username = format!("{}-renamed", username);
+    let mut user = User::update(&pool, user).await.unwrap();
+    assert_eq!(user.hashed_password, hashed_password);
+
+    user.password = Some("password2".to_string());
+    assert!(!format!("{:?}", user).contains("password2"));
+    let user = User::update(&pool, user).await.unwrap();
+    assert!(bcrypt::verify("password2", &user.hashed_password).unwrap());
+}
+
+#[tokio::test]
+async fn test_password_policy() {
+    let (pool, role) = setup().await;
+    for password in ["short1", "onlyletters", "1234567890", &format!("{}1", "a".repeat(72))] {
+        let username = format!("U-TEST-{}", Uuid::new_v4());
+        let new_user = NewUser {
+            username: username.clone(),
+            email: format!("{}@test.com", username),
+            password: password.to_string(),
+            role_id: role.id,
+            is_active: true,
+        };
+        assert!(User::create(&pool, new_user).await.is_err(), "{}", password);
+    }
+    assert!(User::validate_password("contraseña1").is_ok());
+    assert!(User::validate_password("añoñoño1").is_ok());
+}
+
+#[tokio::test]
+async fn test_email_validation() {
+    let (pool, role) = setup().await;
+    let username = format!("U-TEST-{}", Uuid::new_v4());
+    for email in [format!("{}@test.com\r\nBcc: eve@test.com", username), format!("<{}@test.com>", username), username.clone()] {
+        let new_user = NewUser {
+            username: username.clone(),
+            email: email.clone(),
+            password: "password1".to_string(),
+            role_id: role.id,
+            is_active: true,
+        };
+        let error = User::create(&pool, new_user).await.unwrap_err();
+        assert!(matches!(error, sqlx::Error::InvalidArgument(_)), "{:?}", email);
+    }
+
+    let mut user = User::create(&pool, NewUser {
+        username: username.clone(),
+        email: format!("{}@test.com", username),
+        password: "password1".to_string(),
+        role_id: role.id,
+        is_active: true,
+    })
+    .await
+    .unwrap();
+    user.email = format!("{}@test.com>", username);
+    assert!(matches!(User::update(&pool, user).await, Err(sqlx::Error::InvalidArgument(_))));
+}
@@ -0,0 +1,202 @@
+use std::{str::FromStr, sync::Arc};
+use axum::{
+    body::{self, Body},
+    http::{header, Request, StatusCode},
+    Router,
+};
+use backend::mailer::LogMailer;
+use backend::{
+    http,
+    models::{
+        descomposition::{CalculationMode, Descomposition, NewDescomposition},
+        price::{NewPrice, Price, PriceType},
+        unit::{NewUnit, Unit},
+        version::{NewVersion, Version},
+        AppState,
+    },
+    pricing,
+};
+use serde_json::{json, Value};
+use sqlx::{types::BigDecimal, PgPool};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+#[path = "common.rs"]
+mod common;
+
+fn decimal(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).unwrap()
+}
+
+/// Versión con dos precios base, MOOF (20) y MAT (10), y PART, descompuesto
+/// en 2 × MOOF + 3 × MAT (70) pero guardado a 99.
+async fn setup() -> (PgPool, Version, String) {
+    let _ = &common::TRACING;
+    let pool = common::setup_pool().await;
+    let p = format!("{}-", Uuid::new_v4().to_string().chars().take(8).collect::<String>());
+    let version = Version::create(&pool, NewVersion { name: format!("{}2026.Q1", p) }).await.unwrap();
+    let unit = Unit::create(&pool, NewUnit {
+        name: format!("{}ud", p),
+        symbol: "ud".to_string(),
+        description: None,
+        formula: "a".to_string(),
+    })
+    .await
+    .unwrap();
+    let price = |code: &str, base_price: &str, price_type| NewPrice {
+        version_id: version.id,
+        code: format!("{}{}", p, code),
+        description: format!("Price {}", code),
+        base_price: decimal(base_price),
+        unit_id: unit.id,
+        price_type,
+    };
+    let labour = Price::create(&pool, price("MOOF", "20", PriceType::Base)).await.unwrap();
+    let material = Price::create(&pool, price("MAT", "10", PriceType::Base)).await.unwrap();
+    let part = Price::create(&pool, price("PART", "99", PriceType::Decomposed)).await.unwrap();
+    Descomposition::create(&pool, NewDescomposition {
+        parent_price_id: part.id,
+        component_price_id: labour.id,
+        calculation_mode: CalculationMode::Fixed,
+        fixed_quantity: Some(BigDecimal::from(2)),
+        params_json: None,
+    })
+    .await
+    .unwrap();
+    Descomposition::create(&pool, NewDescomposition {
+        parent_price_id: part.id,
+        component_price_id: material.id,
+        calculation_mode: CalculationMode::Formula,
+        fixed_quantity: None,
+        params_json: Some(json!({"a": 3})),
+    })
+    .await
+    .unwrap();
+    (pool, version, p)
+}
+
+fn app(pool: &PgPool) -> Router {
+    http::versions::router().with_state(Arc::new(AppState {
+        pool: pool.clone(),
+        secret: "test_secret".to_string(),
+        static_dir: "".to_string(),
+        mailer: Arc::new(LogMailer),
+    }))
+}
+
+async fn clone(app: &Router, id: i32, body: Value) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/{}/clone", id))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = body::to_bytes(response.into_body(), 1024 * 1024).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// Precios de la versión por código, sin el prefijo del test.
+async fn prices(pool: &PgPool, version_id: i32, p: &str) -> Vec<(String, BigDecimal)> {
+    Price::read_by_version(pool, version_id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|price| (price.code.trim_start_matches(p).to_string(), price.base_price))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_clone_version() {
+    let (pool, version, p) = setup().await;
+    let app = app(&pool);
+
+    // 10 % en general y 5 % para la mano de obra: MOOF 21, MAT 11 y PART 2 × 21 + 3 × 11
+    let body = json!({
+        "name": format!("{}2026.Q2", p),
+        "percentage": 10,
+        "adjustments": [{"prefix": format!("{}MO", p), "percentage": 5}],
+    });
+    let (status, body) = clone(&app, version.id, body).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["prices"], 3);
+    assert_eq!(body["data"]["descompositions"], 2);
+    assert_eq!(body["data"]["adjusted"], 2);
+    assert_eq!(body["data"]["recomputed"], 1);
+    let cloned = body["data"]["version"]["id"].as_i64().unwrap() as i32;
+    assert_eq!(prices(&pool, cloned, &p).await, vec![
+        ("MAT".to_string(), decimal("11.00")),
+        ("MOOF".to_string(), decimal("21.00")),
+        ("PART".to_string(), decimal("75.00")),
+    ]);
+    // Las descomposiciones apuntan a los precios de la nueva versión
+    let part = Price::read_by_codes(&pool, cloned, &[format!("{}PART", p)]).await.unwrap().remove(0);
+    let breakdown = pricing::breakdown(&pool, part.id).await.unwrap().unwrap();
+    assert_eq!(breakdown.unit_price, decimal("75.00"));
+    assert_eq!(breakdown.components[1].quantity, decimal("3"));
+    let components = Descomposition::read_by_parents(&pool, &[part.id]).await.unwrap();
+    let ids: Vec<i32> = components.iter().map(|d| d.component_price_id).collect();
+    assert!(Price::read_by_ids(&pool, &ids).await.unwrap().iter().all(|price| price.version_id == cloned));
+
+    // La versión de origen no cambia
+    assert_eq!(prices(&pool, version.id, &p).await[2], ("PART".to_string(), decimal("99.00")));
+
+    // Sin porcentajes se copian los precios base y se recalculan los descompuestos
+    let (status, body) = clone(&app, version.id, json!({"name": format!("{}copy", p)})).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["adjusted"], 0);
+    let cloned = body["data"]["version"]["id"].as_i64().unwrap() as i32;
+    assert_eq!(prices(&pool, cloned, &p).await, vec![
+        ("MAT".to_string(), decimal("10.00")),
+        ("MOOF".to_string(), decimal("20.00")),
+        ("PART".to_string(), decimal("70.00")),
+    ]);
+}
+
+#[tokio::test]
+async fn test_clone_version_errors() {
+    let (pool, version, p) = setup().await;
+    let app = app(&pool);
+    let (status, body) = clone(&app, -1, json!({"name": "2026.Q2"})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["message"], "Version not found");
+    let (status, body) = clone(&app, version.id, json!({"name": " "})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "Version name is required");
+    let (status, _) = clone(&app, version.id, json!({"name": "2026.Q2", "percentage": -100})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let body = json!({"name": "2026.Q2", "adjustments": [{"prefix": format!("{}MO", p), "percentage": 1_000_000_000}]});
+    let (status, body) = clone(&app, version.id, body).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], format!("Price {}MOOF is out of range after the adjustment", p));
+}
+
+#[tokio::test]
+async fn test_clone_version_snapshot() {
+    let (pool, version, p) = setup().await;
+    let part = Price::read_by_codes(&pool, version.id, &[format!("{}PART", p)]).await.unwrap().remove(0);
+    let edges = Descomposition::read_by_parents(&pool, &[part.id]).await.unwrap();
+
+    // La copia espera a poder crear la versión; mientras, PART pierde a MAT
+    let mut lock = pool.begin().await.unwrap();
+    sqlx::query("LOCK TABLE versions IN EXCLUSIVE MODE").execute(&mut *lock).await.unwrap();
+    let pending = tokio::spawn({
+        let (app, body) = (app(&common::setup_pool().await), json!({"name": format!("{}2026.Q2", p)}));
+        async move { clone(&app, version.id, body).await }
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert!(!pending.is_finished());
+    Descomposition::delete(&common::setup_pool().await, edges[1].id).await.unwrap();
+    lock.commit().await.unwrap();
+
+    // Se copia la versión como estaba al empezar: PART con sus dos componentes
+    let (status, body) = pending.await.unwrap();
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["data"]["descompositions"], 2);
+    let cloned = body["data"]["version"]["id"].as_i64().unwrap() as i32;
+    let part = Price::read_by_codes(&pool, cloned, &[format!("{}PART", p)]).await.unwrap().remove(0);
+    let breakdown = pricing::breakdown(&pool, part.id).await.unwrap().unwrap();
+    assert_eq!(part.base_price, decimal("70.00"));
+    assert_eq!(breakdown.unit_price, part.base_price);
+}
@@ -0,0 +1,220 @@
+use std::{str::FromStr, sync::Arc};
+use axum::{http::StatusCode, Router};
+use backend::mailer::LogMailer;
+use backend::{
+    http,
+    models::{
+        budget::{Budget, BudgetRates, BudgetStatus, NewBudget},
+        element::{Element, ElementType, NewElement},
+        measurement::{Measurement, NewMeasurement},
+        price::{NewPrice, Price, PriceType},
+        project::{NewProject, Project},
+        unit::{NewUnit, Unit},
+        version::{NewVersion, Version},
+        AppState,
+    },
+    version_migration::{self, MigrationError},
+};
+use serde_json::{json, Value};
+use sqlx::{types::BigDecimal, PgPool};
+use uuid::Uuid;
+
+#[path = "common.rs"]
+mod common;
+
+use common::send_json;
+
+fn decimal(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).unwrap()
+}
+
+/// Presupuesto sobre la versión `old` con la línea 01.01, que mide 2 m de
+/// SOL (10) y 3 m de PIN (5). La versión `new` solo tiene SOL, a 12.
+struct Fixture {
+    pool: PgPool,
+    budget: Budget,
+    old: Version,
+    new: Version,
+    unit: Unit,
+    p: String,
+}
+
+async fn setup() -> Fixture {
+    let _ = &common::TRACING;
+    let pool = common::setup_pool().await;
+    let p = format!("{}-", Uuid::new_v4().to_string().chars().take(8).collect::<String>());
+    let project = Project::create(&pool, NewProject {
+        code: format!("{}PRJ", p),
+        title: Some("Migration".to_string()),
+        member_scope: None,
+    })
+    .await
+    .unwrap();
+    let budget = Budget::create(&pool, NewBudget {
+        project_id: project.id,
+        code: format!("{}OBRA", p),
+        version_number: 1,
+        name: "Migration".to_string(),
+        status: BudgetStatus::Draft,
+        rates: BudgetRates::default(),
+    })
+    .await
+    .unwrap();
+    let old = Version::create(&pool, NewVersion { name: format!("{}2025", p) }).await.unwrap();
+    let new = Version::create(&pool, NewVersion { name: format!("{}2026.Q1", p) }).await.unwrap();
+    let unit = Unit::create(&pool, NewUnit {
+        name: format!("{}m", p),
+        symbol: "m".to_string(),
+        description: None,
+        formula: "a".to_string(),
+    })
+    .await
+    .unwrap();
+    let fixture = Fixture { pool, budget, old, new, unit, p };
+    let sol = fixture.price(&fixture.old, "SOL", "10").await;
+    let pin = fixture.price(&fixture.old, "PIN", "5").await;
+    fixture.price(&fixture.new, "SOL", "12").await;
+
+    let element = |parent_id, element_type, budget_code: &str| NewElement {
+        budget_id: fixture.budget.id,
+        parent_id,
+        version_id: fixture.old.id,
+        element_type,
+        code: format!("{}{}", fixture.p, budget_code),
+        budget_code: budget_code.to_string(),
+        description: None,
+    };
+    let chapter = Element::create(&fixture.pool, element(None, ElementType::Chapter, "01")).await.unwrap();
+    let line = Element::create(&fixture.pool, element(Some(chapter.id), ElementType::Line, "01.01")).await.unwrap();
+    for (price, a) in [(&sol, "2"), (&pin, "3")] {
+        Measurement::create(&fixture.pool, NewMeasurement {
+            element_id: line.id,
+            price_id: price.id,
+            params_json: json!({"a": a}),
+            measurement_text: None,
+            measured_quantity: BigDecimal::from(0),
+        })
+        .await
+        .unwrap();
+    }
+    fixture
+}
+
+impl Fixture {
+    async fn price(&self, version: &Version, code: &str, base_price: &str) -> Price {
+        Price::create(&self.pool, NewPrice {
+            version_id: version.id,
+            code: code.to_string(),
+            description: format!("Price {}", code),
+            base_price: decimal(base_price),
+            unit_id: self.unit.id,
+            price_type: PriceType::Base,
+        })
+        .await
+        .unwrap()
+    }
+
+    fn app(&self) -> Router {
+        http::budgets::router().with_state(Arc::new(AppState {
+            pool: self.pool.clone(),
+            secret: "test_secret".to_string(),
+            static_dir: "".to_string(),
+            mailer: Arc::new(LogMailer),
+        }))
+    }
+}
+
+async fn migrate(app: &Router, budget_id: i32, version_id: i32) -> (StatusCode, Value) {
+    send_json(app, "POST", &format!("/{}/migrate-version", budget_id), None, json!({"version_id": version_id})).await
+}
+
+#[tokio::test]
+async fn test_version_migration() {
+    let fixture = setup().await;
+    let app = fixture.app();
+    let budget_id = fixture.budget.id;
+
+    // PIN no existe en la nueva versión: se informa y no se migra nada
+    let uri = format!("/{}/migrate-version?version_id={}", budget_id, fixture.new.id);
+    let (status, body) = send_json(&app, "GET", &uri, None, Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["unmatched"][0]["code"], "PIN");
+    assert_eq!(body["data"]["unmatched"][0]["budget_codes"], json!(["01.01"]));
+    assert_eq!(body["data"]["from_version_ids"], json!([fixture.old.id]));
+    let (status, body) = migrate(&app, budget_id, fixture.new.id).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["message"], "Some prices cannot be migrated: PIN");
+    assert_eq!(body["data"]["unmatched"].as_array().unwrap().len(), 1);
+    let measurements = Measurement::read_by_budget(&fixture.pool, budget_id).await.unwrap();
+    let old_prices: Vec<i32> = measurements.iter().map(|m| m.price_id).collect();
+
+    // Con PIN (a 4) en la nueva versión: 2 × 12 + 3 × 4 = 36 frente a 35
+    let pin = fixture.price(&fixture.new, "PIN", "4").await;
+    let migration = version_migration::preview(&fixture.pool, budget_id, fixture.new.id).await.unwrap().unwrap();
+    assert!(migration.unmatched.is_empty());
+    assert_eq!(migration.measurements, 2);
+    assert_eq!(migration.impact.pem.from, Some(decimal("35.00")));
+    assert_eq!(migration.impact.pem.delta, decimal("1.00"));
+    assert_eq!(migration.impact.lines[0].items.len(), 2);
+    // La previsualización no cambia nada
+    let measurements = Measurement::read_by_budget(&fixture.pool, budget_id).await.unwrap();
+    assert_eq!(measurements.iter().map(|m| m.price_id).collect::<Vec<_>>(), old_prices);
+
+    let (status, body) = migrate(&app, budget_id, fixture.new.id).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["data"]["elements"], 2);
+    let measurements = Measurement::read_by_budget(&fixture.pool, budget_id).await.unwrap();
+    assert_eq!(measurements[1].price_id, pin.id);
+    assert_eq!(measurements[1].measured_quantity, decimal("3"));
+    let elements = Element::read_by_budget(&fixture.pool, budget_id).await.unwrap();
+    assert!(elements.iter().all(|e| e.version_id == fixture.new.id));
+
+    // Migrar de nuevo a la misma versión no cambia ninguna medición
+    let migration = version_migration::preview(&fixture.pool, budget_id, fixture.new.id).await.unwrap().unwrap();
+    assert_eq!(migration.measurements, 0);
+    assert!(migration.impact.lines.is_empty());
+}
+
+#[tokio::test]
+async fn test_version_migration_errors() {
+    let fixture = setup().await;
+    let app = fixture.app();
+    let (status, body) = migrate(&app, fixture.budget.id, -1).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["message"], "Version not found");
+    let (status, body) = migrate(&app, -1, fixture.new.id).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["message"], "Budget not found");
+
+    // La unidad del nuevo precio no admite los parámetros medidos
+    let unit = Unit::create(&fixture.pool, NewUnit {
+        name: format!("{}m2", fixture.p),
+        symbol: "m2".to_string(),
+        description: None,
+        formula: "b * c".to_string(),
+    })
+    .await
+    .unwrap();
+    let other = Version::create(&fixture.pool, NewVersion { name: format!("{}OTHER", fixture.p) }).await.unwrap();
+    Price::create(&fixture.pool, NewPrice {
+        version_id: other.id,
+        code: "SOL".to_string(),
+        description: "SOL".to_string(),
+        base_price: decimal("1"),
+        unit_id: unit.id,
+        price_type: PriceType::Base,
+    })
+    .await
+    .unwrap();
+    let migration = version_migration::preview(&fixture.pool, fixture.budget.id, other.id).await.unwrap().unwrap();
+    let reasons: Vec<(&str, &str)> = migration.unmatched.iter().map(|u| (u.code.as_str(), u.reason.as_str())).collect();
+    assert_eq!(reasons[0].0, "PIN");
+    assert_eq!(reasons[0].1, "Price code not found in the target version");
+    assert_eq!(reasons[1].0, "SOL");
+    assert!(reasons[1].1.starts_with("Unexpected params for unit 'm2'"));
+
+    // Un presupuesto enviado no se puede migrar
+    Budget::transition(&fixture.pool, fixture.budget.id, BudgetStatus::Submitted, None, None).await.unwrap();
+    let result = version_migration::apply(&fixture.pool, fixture.budget.id, fixture.new.id).await;
+    assert!(matches!(result, Err(MigrationError::Locked(BudgetStatus::Submitted))));
+}
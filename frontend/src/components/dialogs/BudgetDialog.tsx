@@ -163,7 +163,7 @@ const BudgetDialog: React.FC<Props> = ({ dialogOpen, handleClose, dialogMode, bu
                         />
                     </Form.Item>
 
-                    {/* Estado del Presupuesto (Enum) */}
+                    {/* Estado del Presupuesto (Enum): solo cambia con las acciones del flujo de aprobación */}
                     <Form.Item
                         label={t("Estado")}
                         name="status"
@@ -174,7 +174,7 @@ const BudgetDialog: React.FC<Props> = ({ dialogOpen, handleClose, dialogMode, bu
                                 label: t(status.charAt(0).toUpperCase() + status.slice(1)),
                                 value: status
                             }))}
-                            disabled={disabled}
+                            disabled
                         />
                     </Form.Item>
                 </div>
@@ -15,7 +15,7 @@ interface Props {
 const getInitialUser = (): Partial<User> => ({
     username: "",
     email: "",
-    hashed_password: "",
+    password: "",
     role_id: 1,
     is_active: true,
 });
@@ -56,7 +56,11 @@ const UserDialog: React.FC<Props> = ({ dialogOpen, handleClose, dialogMode, user
 
     const onOk = async () => {
         try {
-            const values = await form.validateFields();
+            const { password, ...values } = await form.validateFields();
+            // Sin contraseña nueva el servidor conserva la actual
+            if (password) {
+                values.password = password;
+            }
             let result: User | undefined;
 
             if (dialogMode === DialogModes.CREATE) {
@@ -117,6 +121,19 @@ const UserDialog: React.FC<Props> = ({ dialogOpen, handleClose, dialogMode, user
                 </Form.Item>
 
                 {/* Role Select - Reemplaza al InputNumber */}
+                <Form.Item
+                    label={t("Contraseña")}
+                    name="password"
+                    extra={dialogMode === DialogModes.UPDATE ? t("Déjala en blanco para no cambiarla") : undefined}
+                    rules={[
+                        { required: dialogMode === DialogModes.CREATE, message: t("La contraseña es obligatoria") },
+                        { min: 8, message: t("La contraseña debe tener al menos 8 caracteres") },
+                        { pattern: /(?=.*\p{L})(?=.*\d)/u, message: t("La contraseña debe tener letras y números") },
+                    ]}
+                >
+                    <Input.Password autoComplete="new-password" />
+                </Form.Item>
+
                 <Form.Item
                     label={t("Rol")}
                     name="role_id"
@@ -8,7 +8,7 @@ describe('User Model', () => {
             id: -1,
             username: 'testuser',
             email: 'test@example.com',
-            hashed_password: 'password',
+            password: 'password1',
             role_id: 1,
             is_active: true,
         };
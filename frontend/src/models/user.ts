@@ -3,9 +3,11 @@ export interface User {
   id: number;
   username: string;
   email: string;
-  hashed_password: string;
+  // Solo se envía: el servidor guarda su hash y nunca lo devuelve
+  password?: string;
   role_id: number;
   is_active: boolean;
+  email_verified_at?: Date;
   created_at?: Date;
   updated_at?: Date;
 }
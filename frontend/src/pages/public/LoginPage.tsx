@@ -19,7 +19,7 @@ const LoginPage: React.FC = () => {
     const [showMessage, setShowMessage] = useState(false);
     const [messageText, setMessageText] = useState<string>('');
     const [messageType, setMessageType] = useState<'success' | 'error' | 'info' | 'warning'>('info');
-    const [usersCount, setUsersCount] = useState<number | null>(null);
+    const [setupRequired, setSetupRequired] = useState<boolean | null>(null);
     const [loading, setLoading] = useState(false);
 
     const hideMessage = useCallback(debounce(() => {
@@ -28,23 +28,24 @@ const LoginPage: React.FC = () => {
 
 
     useEffect(() => {
-        const fetchUsersCount = async () => {
+        const fetchSetup = async () => {
             setLoading(true);
             try {
-                const response = await fetch(`${BASE_URL}/api/v1/stats/users`);
+                // Mientras no haya usuarios hay que crear el primero
+                const response = await fetch(`${BASE_URL}/api/v1/auth/setup`);
                 if (response.ok) {
                     const result = await response.json();
-                    setUsersCount(result.data || 0);
+                    setSetupRequired(result.data?.required === true);
                 } else {
-                    setUsersCount(0);
+                    setSetupRequired(false);
                 }
             } catch (error) {
-                setUsersCount(0);
+                setSetupRequired(false);
             } finally {
                 setLoading(false);
             }
         };
-        fetchUsersCount();
+        fetchSetup();
     }, []);
 
     const displayMessage = (text: string, type: 'success' | 'error' | 'info' | 'warning') => {
@@ -84,9 +85,9 @@ const LoginPage: React.FC = () => {
 
     if (isLoggedIn) {
         content = <Navigate to={role === "admin" ? "/admin/" : "/"} replace />;
-    } else if (loading || usersCount === null) {
+    } else if (loading || setupRequired === null) {
         content = <Spin fullscreen />;
-    } else if (usersCount === 0) {
+    } else if (setupRequired) {
         content = <Navigate to="/register" replace />;
     } else {
         content = (
@@ -3,7 +3,6 @@ import { Navigate } from "react-router";
 import { Flex, Card, Input, Button, Form, Alert, Typography, Spin } from 'antd';
 import { MailOutlined, LockOutlined, UserOutlined } from '@ant-design/icons';
 import { useTranslation } from "react-i18next";
-import { hashSync } from 'bcrypt-ts';
 
 import AuthContext from '@/components/AuthContext';
 import { BASE_URL } from '@/constants';
@@ -20,28 +19,29 @@ const RegisterPage: React.FC = () => {
     const [showMessage, setShowMessage] = useState(false);
     const [messageText, setMessageText] = useState<string>('');
     const [messageType, setMessageType] = useState<'success' | 'error' | 'info' | 'warning'>('info');
-    const [usersCount, setUsersCount] = useState<number | null>(null);
+    const [setupRequired, setSetupRequired] = useState<boolean | null>(null);
     const [loading, setLoading] = useState(false);
     const [submitting, setSubmitting] = useState(false);
 
     useEffect(() => {
-        const fetchUsersCount = async () => {
+        const fetchSetup = async () => {
             setLoading(true);
             try {
-                const response = await fetch(`${BASE_URL}/api/v1/stats/users`);
+                // Solo se registra aquí el primer usuario
+                const response = await fetch(`${BASE_URL}/api/v1/auth/setup`);
                 if (response.ok) {
                     const result = await response.json();
-                    setUsersCount(result.users || 0);
+                    setSetupRequired(result.data?.required === true);
                 } else {
-                    setUsersCount(1); // Fallback to prevent registration if stats are down
+                    setSetupRequired(false); // Fallback to prevent registration if the server is down
                 }
             } catch (error) {
-                setUsersCount(1); // Fallback to prevent registration
+                setSetupRequired(false); // Fallback to prevent registration
             } finally {
                 setLoading(false);
             }
         };
-        fetchUsersCount();
+        fetchSetup();
     }, []);
 
     if (isLoggedIn) {
@@ -66,16 +66,14 @@ const RegisterPage: React.FC = () => {
         }
         setSubmitting(true);
         try {
-            const hashedPassword = hashSync(values.password, 10);
             const data = {
                 username: values.username,
                 email: values.email,
-                hashed_password: hashedPassword,
-                role_id: 1, // First user will be admin
-                is_active: true,
+                password: values.password,
             };
 
-            const response = await fetch(`${BASE_URL}/api/v1/auth/register`, {
+            // El primer usuario se crea como administrador
+            const response = await fetch(`${BASE_URL}/api/v1/auth/setup`, {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
                 body: JSON.stringify(data),
@@ -95,11 +93,11 @@ const RegisterPage: React.FC = () => {
         }
     };
 
-    if (loading || usersCount === null) {
+    if (loading || setupRequired === null) {
         return <Spin fullscreen />;
     }
 
-    if (usersCount > 0) {
+    if (!setupRequired) {
         return <Navigate to="/login" replace />;
     }
 
@@ -161,7 +159,11 @@ const RegisterPage: React.FC = () => {
                     </Form.Item>
                     <Form.Item
                         name="password"
-                        rules={[{ required: true, message: t('Por favor introduce tu contraseña') }]}
+                        rules={[
+                            { required: true, message: t('Por favor introduce tu contraseña') },
+                            { min: 8, message: t('La contraseña debe tener al menos 8 caracteres') },
+                            { pattern: /(?=.*\p{L})(?=.*\d)/u, message: t('La contraseña debe tener letras y números') },
+                        ]}
                     >
                         <Input.Password
                             prefix={<LockOutlined style={{ color: 'rgba(0,0,0,0.25)' }} />}